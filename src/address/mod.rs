@@ -0,0 +1,292 @@
+use std::fmt;
+use std::ops::Range;
+
+#[cfg(test)]
+mod test;
+
+/// A single recipient: an optional display name and an `addr-spec`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub email: String,
+}
+
+impl fmt::Display for Mailbox {
+    /// `Name <email>`, quoting the name when it contains RFC 5322 specials.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) if name.chars().any(is_special) => {
+                let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{escaped}\" <{}>", self.email)
+            }
+            Some(name) => write!(f, "{name} <{}>", self.email),
+            None => f.write_str(&self.email),
+        }
+    }
+}
+
+/// One entry of an address list: a mailbox or a named group of mailboxes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    Mailbox(Mailbox),
+    Group { name: String, members: Vec<Mailbox> },
+}
+
+impl Address {
+    pub fn mailboxes(&self) -> &[Mailbox] {
+        match self {
+            Address::Mailbox(mailbox) => std::slice::from_ref(mailbox),
+            Address::Group { members, .. } => members,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum AddressError {
+    #[error("missing @ in {0:?}")]
+    MissingAt(String),
+    #[error("invalid local part {0:?}")]
+    InvalidLocalPart(String),
+    #[error("invalid domain {0:?}")]
+    InvalidDomain(String),
+    #[error("missing closing {0:?}")]
+    Unterminated(char),
+    #[error("unexpected {0:?} after the address")]
+    TrailingText(String),
+}
+
+/// An entry of a parsed list and where it sits in the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Byte range of the entry, without surrounding whitespace.
+    pub range: Range<usize>,
+    pub parsed: Result<Address, AddressError>,
+}
+
+/// Byte ranges between the top-level commas of an address list. Commas in
+/// quoted names, angle brackets, comments and groups do not split.
+pub fn segments(input: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut scanner = Scanner::default();
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        if scanner.at_top_level() && c == ',' {
+            ranges.push(start..i);
+            start = i + 1;
+        } else {
+            scanner.step(c);
+        }
+    }
+    ranges.push(start..input.len());
+    ranges
+}
+
+/// Parse an RFC 5322 `address-list`, skipping empty entries.
+pub fn parse_list(input: &str) -> Vec<Entry> {
+    segments(input)
+        .into_iter()
+        .filter_map(|range| {
+            let text = &input[range.clone()];
+            let trimmed = text.trim();
+            if trimmed.is_empty() {
+                return None;
+            }
+            let start = range.start + (text.len() - text.trim_start().len());
+            Some(Entry {
+                range: start..start + trimmed.len(),
+                parsed: parse_address(trimmed),
+            })
+        })
+        .collect()
+}
+
+/// Parse one entry: a group `Name: a@x, b@y;` or a single mailbox.
+pub fn parse_address(text: &str) -> Result<Address, AddressError> {
+    if let Some(colon) = find_top_level(text, ':') {
+        let name = unquote_phrase(&text[..colon])?;
+        let rest = &text[colon + 1..];
+        let semicolon = find_top_level(rest, ';').ok_or(AddressError::Unterminated(';'))?;
+        let trailing = rest[semicolon + 1..].trim();
+        if !trailing.is_empty() {
+            return Err(AddressError::TrailingText(trailing.to_string()));
+        }
+        let mut members = Vec::new();
+        for entry in parse_list(&rest[..semicolon]) {
+            members.extend_from_slice(entry.parsed?.mailboxes());
+        }
+        return Ok(Address::Group { name, members });
+    }
+    parse_mailbox(text).map(Address::Mailbox)
+}
+
+/// Parse `Name <local@domain>` or a bare `local@domain`.
+pub fn parse_mailbox(text: &str) -> Result<Mailbox, AddressError> {
+    let text = strip_comments(text)?;
+    let text = text.trim();
+
+    let Some(open) = find_top_level(text, '<') else {
+        if text.contains(char::is_whitespace) {
+            return Err(if text.contains('@') {
+                AddressError::InvalidLocalPart(text.to_string())
+            } else {
+                AddressError::MissingAt(text.to_string())
+            });
+        }
+        check_addr_spec(text)?;
+        return Ok(Mailbox {
+            name: None,
+            email: text.to_string(),
+        });
+    };
+
+    let close = text[open..]
+        .find('>')
+        .map(|i| open + i)
+        .ok_or(AddressError::Unterminated('>'))?;
+    let trailing = text[close + 1..].trim();
+    if !trailing.is_empty() {
+        return Err(AddressError::TrailingText(trailing.to_string()));
+    }
+    let email = text[open + 1..close].trim();
+    check_addr_spec(email)?;
+    let name = unquote_phrase(&text[..open])?;
+    Ok(Mailbox {
+        name: (!name.is_empty()).then_some(name),
+        email: email.to_string(),
+    })
+}
+
+fn check_addr_spec(spec: &str) -> Result<(), AddressError> {
+    let Some((local, domain)) = spec.rsplit_once('@') else {
+        return Err(AddressError::MissingAt(spec.to_string()));
+    };
+
+    let quoted = local.len() >= 2 && local.starts_with('"') && local.ends_with('"');
+    if !quoted && !is_dot_atom(local) {
+        return Err(AddressError::InvalidLocalPart(local.to_string()));
+    }
+
+    let literal = domain.starts_with('[') && domain.ends_with(']');
+    let valid_domain = literal
+        || (!domain.is_empty()
+            && domain.split('.').all(|label| {
+                !label.is_empty()
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || !c.is_ascii())
+            }));
+    if !valid_domain {
+        return Err(AddressError::InvalidDomain(domain.to_string()));
+    }
+    Ok(())
+}
+
+fn is_dot_atom(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || !c.is_ascii() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+fn is_special(c: char) -> bool {
+    "()<>[]:;@\\,.\"".contains(c)
+}
+
+/// Turn a display name phrase into plain text, joining quoted strings and
+/// bare words.
+fn unquote_phrase(phrase: &str) -> Result<String, AddressError> {
+    let mut out = String::new();
+    let mut chars = phrase.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => out.extend(chars.next()),
+                    Some(c) => out.push(c),
+                    None => return Err(AddressError::Unterminated('"')),
+                }
+            },
+            c => out.push(c),
+        }
+    }
+    Ok(out.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Remove `(comments)` outside quoted strings.
+fn strip_comments(text: &str) -> Result<String, AddressError> {
+    let mut out = String::with_capacity(text.len());
+    let mut scanner = Scanner::default();
+    for c in text.chars() {
+        let in_comment = scanner.comment > 0;
+        scanner.step(c);
+        if !in_comment && scanner.comment == 0 {
+            out.push(c);
+        } else if scanner.comment == 0 {
+            // The closing parenthesis of a comment separates words
+            out.push(' ');
+        }
+    }
+    if scanner.comment > 0 {
+        return Err(AddressError::Unterminated(')'));
+    }
+    if scanner.quoted {
+        return Err(AddressError::Unterminated('"'));
+    }
+    Ok(out)
+}
+
+/// Byte offset of the first `target` outside quotes, comments and angle
+/// brackets.
+fn find_top_level(text: &str, target: char) -> Option<usize> {
+    let mut scanner = Scanner::default();
+    for (i, c) in text.char_indices() {
+        if c == target && !scanner.quoted && scanner.comment == 0 && !scanner.angle {
+            return Some(i);
+        }
+        scanner.step(c);
+    }
+    None
+}
+
+/// Tracks the lexical context while walking an address list.
+#[derive(Default)]
+struct Scanner {
+    quoted: bool,
+    escaped: bool,
+    comment: usize,
+    angle: bool,
+    group: bool,
+}
+
+impl Scanner {
+    fn at_top_level(&self) -> bool {
+        !self.quoted && self.comment == 0 && !self.angle && !self.group
+    }
+
+    fn step(&mut self, c: char) {
+        if self.escaped {
+            self.escaped = false;
+            return;
+        }
+        match c {
+            '\\' if self.quoted || self.comment > 0 => self.escaped = true,
+            '"' if self.comment == 0 => self.quoted = !self.quoted,
+            _ if self.quoted => {}
+            '(' => self.comment += 1,
+            ')' if self.comment > 0 => self.comment -= 1,
+            _ if self.comment > 0 => {}
+            '<' => self.angle = true,
+            '>' => self.angle = false,
+            ':' if !self.angle => self.group = true,
+            ';' if self.group => self.group = false,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use super::*;
+
+fn mailbox(name: Option<&str>, email: &str) -> Mailbox {
+    Mailbox {
+        name: name.map(str::to_string),
+        email: email.to_string(),
+    }
+}
+
+fn addresses(input: &str) -> Vec<Result<Address, AddressError>> {
+    parse_list(input).into_iter().map(|e| e.parsed).collect()
+}
+
+#[test]
+fn parses_bare_and_named_mailboxes() {
+    assert_eq!(
+        addresses("alice@example.com, Bob Jones <bob@example.com>"),
+        vec![
+            Ok(Address::Mailbox(mailbox(None, "alice@example.com"))),
+            Ok(Address::Mailbox(mailbox(
+                Some("Bob Jones"),
+                "bob@example.com"
+            ))),
+        ]
+    );
+}
+
+#[test]
+fn commas_in_quoted_names_do_not_split() {
+    let entries = parse_list(r#""Smith, Alice" <alice@example.com>, bob@example.com"#);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].range, 0..34);
+    assert_eq!(
+        entries[0].parsed,
+        Ok(Address::Mailbox(mailbox(
+            Some("Smith, Alice"),
+            "alice@example.com"
+        )))
+    );
+    assert_eq!(entries[1].range, 36..51);
+}
+
+#[test]
+fn comments_and_escapes_are_handled() {
+    assert_eq!(
+        parse_mailbox(r#""Dr. \"Who\"" (the doctor) <who@tardis.example> (time, space)"#),
+        Ok(mailbox(Some(r#"Dr. "Who""#), "who@tardis.example"))
+    );
+    assert_eq!(
+        parse_mailbox("carol@example.com (Carol)"),
+        Ok(mailbox(None, "carol@example.com"))
+    );
+}
+
+#[test]
+fn parses_groups() {
+    assert_eq!(
+        addresses("Team: a@example.com, B <b@example.com>;, c@example.com"),
+        vec![
+            Ok(Address::Group {
+                name: "Team".to_string(),
+                members: vec![
+                    mailbox(None, "a@example.com"),
+                    mailbox(Some("B"), "b@example.com"),
+                ],
+            }),
+            Ok(Address::Mailbox(mailbox(None, "c@example.com"))),
+        ]
+    );
+    assert_eq!(
+        parse_address("undisclosed-recipients:;"),
+        Ok(Address::Group {
+            name: "undisclosed-recipients".to_string(),
+            members: vec![],
+        })
+    );
+}
+
+#[test]
+fn reports_malformed_addresses() {
+    assert_eq!(
+        parse_mailbox("alice"),
+        Err(AddressError::MissingAt("alice".to_string()))
+    );
+    assert_eq!(
+        parse_mailbox("alice..b@example.com"),
+        Err(AddressError::InvalidLocalPart("alice..b".to_string()))
+    );
+    assert_eq!(
+        parse_mailbox("alice@-example.com"),
+        Err(AddressError::InvalidDomain("-example.com".to_string()))
+    );
+    assert_eq!(
+        parse_mailbox("alice@"),
+        Err(AddressError::InvalidDomain(String::new()))
+    );
+    assert_eq!(
+        parse_mailbox("Alice <alice@example.com"),
+        Err(AddressError::Unterminated('>'))
+    );
+    assert_eq!(
+        parse_mailbox("\"Alice <alice@example.com>"),
+        Err(AddressError::Unterminated('"'))
+    );
+    assert_eq!(
+        parse_mailbox("Alice <alice@example.com> extra"),
+        Err(AddressError::TrailingText("extra".to_string()))
+    );
+    assert_eq!(
+        parse_address("Team: a@example.com"),
+        Err(AddressError::Unterminated(';'))
+    );
+}
+
+#[test]
+fn empty_entries_are_skipped() {
+    let entries = parse_list(" a@example.com ,, , ");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].range, 1..14);
+}
+
+#[test]
+fn segments_cover_the_whole_input() {
+    assert_eq!(
+        segments(r#"a@x, "b, c" <b@x>, "#),
+        vec![0..3, 4..17, 18..19]
+    );
+}
+
+#[test]
+fn display_quotes_names_with_specials() {
+    assert_eq!(
+        mailbox(Some("Smith, Alice"), "alice@example.com").to_string(),
+        r#""Smith, Alice" <alice@example.com>"#
+    );
+    assert_eq!(
+        mailbox(Some(r#"Dr "Who""#), "who@example.com").to_string(),
+        r#""Dr \"Who\"" <who@example.com>"#
+    );
+    assert_eq!(
+        mailbox(Some("Alice"), "alice@example.com").to_string(),
+        "Alice <alice@example.com>"
+    );
+}
@@ -1,16 +1,40 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
 
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::DefaultTerminal;
 use ratatui::widgets::TableState;
 
-use crate::imap::{EmailBody, EmailSummary, ImapClient};
-use crate::smtp::{self, SmtpClient};
+use crate::address;
+use crate::config::{HandlerConfig, Posting, SortMode};
+use crate::contacts::AddressBook;
+use crate::external::{self, ExternalAction};
+use crate::imap::{ConnectionState, EmailBody, EmailSummary, ImapClient};
+use crate::outbox::{self, Outbox};
+use crate::schedule::{self, Schedule};
+use crate::smtp::{self, SmtpClient, SmtpError};
 use crate::ui;
+use crate::{links, mime, quote};
 
 #[cfg(test)]
 mod test;
 
+/// Load another page once the selection is this close to the end of the list.
+const LOAD_AHEAD: usize = 20;
+
+/// Conversations closer than this to the selection are never evicted.
+const EVICT_MARGIN: usize = LOAD_AHEAD * 2;
+
+/// Entries shown in the address completion dropdown.
+const SUGGESTION_LIMIT: usize = 5;
+
+/// How often the app wakes up without input, to check the connection and
+/// keep the status bar current.
+const TICK: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ComposeStep {
     Body,
@@ -23,6 +47,8 @@ pub enum ComposeStep {
 pub struct ComposeState {
     pub step: ComposeStep,
     pub is_reply: bool,
+    /// Identity the message is sent as.
+    pub from: String,
     pub body_lines: Vec<String>,
     pub cursor_row: usize,
     pub cursor_col: usize,
@@ -38,12 +64,119 @@ pub struct ComposeState {
     pub references: Vec<String>,
     pub quoted_text: String,
     pub status_message: Option<String>,
+    /// Completions for the address being typed in To, Cc or Bcc.
+    pub suggestions: Vec<String>,
+    pub suggestion_selected: usize,
+    /// Waiting for `y` to send to more than `max_recipients` people.
+    pub confirm_send: bool,
+    /// Send the body as Markdown with an HTML rendition.
+    pub markdown: bool,
+    /// Show the rendered Markdown in place of the editor.
+    pub preview: bool,
+    /// PGP sign and encrypt the message (`pgp` feature).
+    pub sign: bool,
+    pub encrypt: bool,
+    /// Outbox entry being edited; replaced when the message is sent again.
+    pub outbox_id: Option<String>,
+    /// Scheduled message being edited; replaced when the message is sent or
+    /// scheduled again.
+    pub scheduled_id: Option<String>,
+    /// Time typed at the "send later" prompt, while it is open.
+    pub send_at: Option<String>,
+    pub send_at_cursor: usize,
+}
+
+impl ComposeState {
+    /// The text that will be sent, with the quote placed for `posting`.
+    pub fn body(&self, posting: Posting) -> String {
+        let text = self.body_lines.join("\n");
+        match posting {
+            _ if self.quoted_text.is_empty() => text,
+            Posting::Top => format!("{text}\n\n{}", self.quoted_text),
+            Posting::Bottom => format!("{}\n\n{text}", self.quoted_text),
+        }
+    }
+
+    /// The message as it will be sent.
+    pub fn email(&self, posting: Posting) -> smtp::Email {
+        smtp::Email {
+            from: self.from.clone(),
+            to: recipients(&self.to),
+            cc: recipients(&self.cc),
+            bcc: recipients(&self.bcc),
+            subject: self.subject.clone(),
+            body: self.body(posting),
+            in_reply_to: self.in_reply_to.clone(),
+            references: self.references.clone(),
+            markdown: self.markdown,
+            sign: self.sign,
+            encrypt: self.encrypt,
+        }
+    }
+
+    /// A composer holding `draft` again, as queued or scheduled earlier.
+    fn reopen(draft: smtp::Email) -> Self {
+        let to = draft.to.join(", ");
+        let cc = draft.cc.join(", ");
+        let bcc = draft.bcc.join(", ");
+        Self {
+            step: ComposeStep::Body,
+            is_reply: draft.in_reply_to.is_some(),
+            from: draft.from,
+            body_lines: draft.body.split('\n').map(str::to_string).collect(),
+            cursor_row: 0,
+            cursor_col: 0,
+            to_cursor: to.len(),
+            to,
+            cc_cursor: cc.len(),
+            cc,
+            bcc_cursor: bcc.len(),
+            bcc,
+            subject_cursor: draft.subject.len(),
+            subject: draft.subject,
+            in_reply_to: draft.in_reply_to,
+            references: draft.references,
+            quoted_text: String::new(),
+            status_message: None,
+            suggestions: Vec::new(),
+            suggestion_selected: 0,
+            confirm_send: false,
+            markdown: draft.markdown,
+            preview: false,
+            sign: draft.sign,
+            encrypt: draft.encrypt,
+            outbox_id: None,
+            scheduled_id: None,
+            send_at: None,
+            send_at_cursor: 0,
+        }
+    }
 }
 
 pub enum View {
     Inbox,
     Detail(DetailState),
     Compose(Box<ComposeState>),
+    Outbox(OutboxState),
+    Scheduled(ScheduledState),
+}
+
+/// Messages waiting in the outbox.
+pub struct OutboxState {
+    pub entries: Vec<outbox::Entry>,
+    pub table_state: TableState,
+    pub status_message: Option<String>,
+    /// Waiting for `y` to cancel the selected message.
+    pub confirm_cancel: bool,
+}
+
+/// Messages waiting to be sent later.
+pub struct ScheduledState {
+    pub entries: Vec<schedule::Scheduled>,
+    pub table_state: TableState,
+    pub status_message: Option<String>,
+    /// Waiting for `y` to cancel the selected message.
+    pub confirm_cancel: bool,
 }
 
 pub struct DetailState {
@@ -51,25 +184,197 @@ pub struct DetailState {
     pub active_index: usize,
     pub scroll_offset: u16,
     pub status_message: Option<String>,
+    pub link_picker: Option<LinkPicker>,
+    pub attachment_picker: Option<AttachmentPicker>,
+}
+
+/// Numbered list of the links in the active message.
+pub struct LinkPicker {
+    pub links: Vec<String>,
+    pub selected: usize,
+}
+
+/// A part of the active message that can be opened in an external viewer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Choice between the viewable parts of the active message.
+pub struct AttachmentPicker {
+    pub attachments: Vec<Attachment>,
+    pub selected: usize,
 }
 
 pub struct ThreadMessage {
     pub email_index: usize,
     pub body: Option<EmailBody>,
+    pub display: MessageDisplay,
+    /// Full source, fetched the first time headers or source are shown.
+    pub raw: Option<Vec<u8>>,
+}
+
+/// How a message in the detail view is presented.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MessageDisplay {
+    #[default]
+    Normal,
+    /// Every header of the message above the body.
+    Headers,
+    /// The RFC 5322 source exactly as stored on the server.
+    Source,
+}
+
+impl MessageDisplay {
+    pub fn next(self) -> Self {
+        match self {
+            MessageDisplay::Normal => MessageDisplay::Headers,
+            MessageDisplay::Headers => MessageDisplay::Source,
+            MessageDisplay::Source => MessageDisplay::Normal,
+        }
+    }
+}
+
+/// A due message on its way to the server.
+enum Delivery {
+    /// An outbox entry with the message it was queued with.
+    Queued(outbox::Entry, smtp::Outgoing),
+    /// A scheduled entry, with its message if only that was kept.
+    Scheduled(schedule::Scheduled, Option<smtp::Outgoing>),
+}
+
+impl Delivery {
+    /// Hand the message to `smtp_client`, giving back the message as sent.
+    fn send(&self, smtp_client: &impl SmtpClient) -> Result<Vec<u8>, SmtpError> {
+        match self {
+            Delivery::Queued(_, outgoing) => smtp_client
+                .resend(outgoing)
+                .map(|()| outgoing.message.clone()),
+            Delivery::Scheduled(_, Some(outgoing)) => smtp_client
+                .resend(outgoing)
+                .map(|()| outgoing.message.clone())
+                .map_err(|error| SmtpError::Undelivered {
+                    outgoing: Box::new(outgoing.clone()),
+                    error: Box::new(error),
+                }),
+            Delivery::Scheduled(entry, None) => smtp_client.send(&entry.draft),
+        }
+    }
+
+    fn pending(&self) -> Pending {
+        match self {
+            Delivery::Queued(entry, _) => Pending::Queued(entry.id.clone()),
+            Delivery::Scheduled(entry, _) => Pending::Scheduled(entry.id.clone()),
+        }
+    }
+}
+
+/// The entry a message with the background sender came from.
+#[derive(Debug, PartialEq)]
+enum Pending {
+    Queued(String),
+    Scheduled(String),
+}
+
+/// Due messages handed to a background thread, and what came of them.
+struct Sending {
+    results: Receiver<(Delivery, Result<Vec<u8>, SmtpError>)>,
+    /// Entries not done yet, which are left alone until they are.
+    pending: Vec<Pending>,
+    /// Queued messages the server took.
+    sent_queued: usize,
+    /// Scheduled messages the server took.
+    sent_scheduled: usize,
+    /// Why the last scheduled message that was not sent was not.
+    failure: Option<String>,
+}
+
+impl Sending {
+    fn done(&mut self, pending: &Pending, outcome: Result<(), String>) {
+        self.pending.retain(|p| p != pending);
+        match (pending, outcome) {
+            (Pending::Queued(_), Ok(())) => self.sent_queued += 1,
+            (Pending::Queued(_), Err(_)) => {}
+            (Pending::Scheduled(_), Ok(())) => self.sent_scheduled += 1,
+            (Pending::Scheduled(_), Err(error)) => self.failure = Some(error),
+        }
+    }
 }
 
 pub struct App<I: ImapClient, S: SmtpClient> {
     pub should_quit: bool,
     pub emails: Vec<EmailSummary>,
     pub table_state: TableState,
+    /// Table state for the inbox when group headers are interleaved with the
+    /// conversation rows. `table_state` always indexes `threads`.
+    pub grouped_table_state: TableState,
     pub view: View,
     pub threads: Vec<Vec<usize>>,
+    pub sort_mode: SortMode,
+    pub group_by_date: bool,
+    /// Configured folders, in the order the filter cycles through them.
+    pub folders: Vec<String>,
+    /// When set, only conversations with a message in this folder are listed.
+    pub folder_filter: Option<String>,
+    pub show_folder_column: bool,
+    /// Messages requested per folder when paging.
+    pub page_size: u32,
+    /// Summaries kept in memory before far-away conversations are evicted.
+    pub max_loaded: usize,
+    /// Loaded UID range `(lowest, highest)` per folder.
+    page_windows: HashMap<String, (u32, u32)>,
+    /// Folders with no messages left below their window.
+    older_exhausted: HashSet<String>,
+    /// Folders whose newest messages were evicted and can be paged back in.
+    newer_available: HashSet<String>,
     pub pending_prefix: bool,
     pub status_message: Option<String>,
+    /// IMAP connection state as of the last check.
+    pub connection: ConnectionState,
     pub imap_client: I,
-    pub smtp_client: S,
+    pub smtp_client: Arc<S>,
     pub sender_from: String,
+    /// Aliases that can be chosen instead of `sender_from` when composing.
+    pub identities: Vec<String>,
     pub sent_folder: Option<String>,
+    /// Scheduled messages are also saved here until they are sent.
+    pub drafts_folder: Option<String>,
+    /// Command used to open links, e.g. `xdg-open`.
+    pub opener: String,
+    /// Viewers for message parts by MIME type; parts without a handler
+    /// are passed to `opener`.
+    pub handlers: Vec<HandlerConfig>,
+    /// Action to carry out once the current key press has been handled.
+    pub pending_action: Option<ExternalAction>,
+    /// Known correspondents, used to complete recipient addresses.
+    pub contacts: AddressBook,
+    /// Sending to more recipients than this asks for confirmation first.
+    pub max_recipients: usize,
+    /// Appended below the `-- ` delimiter in every new message.
+    pub signature: Option<String>,
+    /// Used instead of `signature` for the identities that have their own,
+    /// keyed by their From line.
+    pub identity_signatures: HashMap<String, String>,
+    pub posting: Posting,
+    /// Put quoted text in the editor instead of below it.
+    pub inline_quote: bool,
+    /// Start messages in Markdown mode.
+    pub markdown: bool,
+    /// Sign and encrypt new messages by default.
+    pub sign: bool,
+    pub encrypt: bool,
+    /// Where undelivered messages wait for another attempt.
+    pub outbox: Option<Outbox>,
+    /// Messages in the outbox as of the last check.
+    pub queued: usize,
+    /// Due messages being sent in the background, if any.
+    sending: Option<Sending>,
+    /// Where messages to be sent later wait for their time.
+    pub schedule: Option<Schedule>,
+    /// Scheduled messages as of the last check.
+    pub scheduled: usize,
 }
 
 impl<I: ImapClient, S: SmtpClient> App<I, S> {
@@ -81,23 +386,65 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         sent_folder: Option<String>,
     ) -> Self {
         emails.reverse();
-        let threads = build_threads(&emails);
+        sort_newest_first(&mut emails);
+        let sort_mode = SortMode::default();
+        let mut threads = build_threads(&emails);
+        sort_threads(&mut threads, &emails, sort_mode);
         let mut table_state = TableState::default();
         if !threads.is_empty() {
             table_state.select(Some(0));
         }
+        let mut page_windows: HashMap<String, (u32, u32)> = HashMap::new();
+        for email in &emails {
+            let window = page_windows
+                .entry(email.folder.clone())
+                .or_insert((email.uid, email.uid));
+            window.0 = window.0.min(email.uid);
+            window.1 = window.1.max(email.uid);
+        }
         Self {
             should_quit: false,
             emails,
             table_state,
+            grouped_table_state: TableState::default(),
             view: View::Inbox,
             threads,
+            sort_mode,
+            group_by_date: true,
+            folders: Vec::new(),
+            folder_filter: None,
+            show_folder_column: false,
+            page_size: 200,
+            max_loaded: 2000,
+            page_windows,
+            older_exhausted: HashSet::new(),
+            newer_available: HashSet::new(),
             pending_prefix: false,
             status_message: None,
+            connection: ConnectionState::Connected,
             imap_client,
-            smtp_client,
+            smtp_client: Arc::new(smtp_client),
             sender_from,
+            identities: Vec::new(),
             sent_folder,
+            drafts_folder: None,
+            opener: "xdg-open".to_string(),
+            handlers: Vec::new(),
+            pending_action: None,
+            contacts: AddressBook::default(),
+            max_recipients: 10,
+            signature: None,
+            identity_signatures: HashMap::new(),
+            posting: Posting::default(),
+            inline_quote: false,
+            markdown: false,
+            sign: false,
+            encrypt: false,
+            outbox: None,
+            queued: 0,
+            sending: None,
+            schedule: None,
+            scheduled: 0,
         }
     }
 
@@ -114,13 +461,89 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             terminal.draw(|frame| ui::render(frame, self))?;
             #[cfg(feature = "tracing")]
             tracing::trace!("frame drawn, waiting for event");
-            self.handle_event()?;
+            if event::poll(TICK)? {
+                self.handle_event()?;
+            }
+            if let Some(action) = self.pending_action.take() {
+                self.perform_action(action, terminal);
+            }
+            self.tick();
+        }
+        // Messages already handed to the server are seen through.
+        if self.sending.is_some() {
+            self.set_status("Waiting for messages being sent...".to_string());
+            terminal.draw(|frame| ui::render(frame, self))?;
+            self.finish_deliveries();
         }
         #[cfg(feature = "tracing")]
         tracing::trace!("main loop exited");
         Ok(())
     }
 
+    /// Periodic work between key presses.
+    fn tick(&mut self) {
+        let connection = self.imap_client.check_connection();
+        if self.connection != ConnectionState::Connected && connection == ConnectionState::Connected
+        {
+            self.set_status("Reconnected to the IMAP server".to_string());
+        }
+        self.connection = connection;
+        self.collect_deliveries();
+        let now = chrono::Utc::now().timestamp();
+        self.flush_schedule(now);
+        self.flush_outbox(now);
+    }
+
+    fn perform_action(&mut self, action: ExternalAction, terminal: &mut DefaultTerminal) {
+        let result = match action {
+            ExternalAction::Open(target) => external::open(&self.opener, &target),
+            ExternalAction::Copy(text) => external::copy_to_clipboard(&text),
+            ExternalAction::View {
+                filename,
+                content_type,
+                data,
+            } => self.view_externally(&filename, &content_type, &data, terminal),
+        };
+        if let Err(e) = result {
+            self.set_status(format!("Error: {e}"));
+        }
+    }
+
+    fn view_externally(
+        &self,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+        terminal: &mut DefaultTerminal,
+    ) -> Result<(), external::ExternalError> {
+        let path = external::write_temp(filename, data)?;
+        let target = path.to_string_lossy();
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?path, content_type, "viewing part externally");
+
+        match external::find_handler(&self.handlers, content_type) {
+            Some(handler) if handler.blocking => {
+                let result = external::run_suspended(&handler.command, &path);
+                terminal.clear()?;
+                result
+            }
+            Some(handler) => external::open(&handler.command, &target),
+            None => external::open(&self.opener, &target),
+        }
+    }
+
+    /// Show a message in the status bar of the current view.
+    fn set_status(&mut self, message: String) {
+        match self.view {
+            View::Detail(ref mut state) => state.status_message = Some(message),
+            View::Compose(ref mut state) => state.status_message = Some(message),
+            View::Outbox(ref mut state) => state.status_message = Some(message),
+            View::Scheduled(ref mut state) => state.status_message = Some(message),
+            View::Inbox => self.status_message = Some(message),
+        }
+    }
+
     #[cfg_attr(
         feature = "tracing",
         tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
@@ -157,6 +580,8 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             View::Inbox => self.handle_inbox_key(key),
             View::Detail(_) => self.handle_detail_key(key, modifiers),
             View::Compose(_) => self.handle_compose_key(key, modifiers),
+            View::Outbox(_) => self.handle_outbox_key(key),
+            View::Scheduled(_) => self.handle_scheduled_key(key),
         }
     }
 
@@ -191,6 +616,22 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             KeyCode::Enter => self.open_email(),
             KeyCode::Char('r') => self.start_reply(),
             KeyCode::Char('c') => self.start_new_email(),
+            KeyCode::Char('s') => {
+                self.set_sort_mode(self.sort_mode.next());
+                self.status_message = Some(format!("Sorted by {}", self.sort_mode.label()));
+            }
+            KeyCode::Char('S') => {
+                self.group_by_date = !self.group_by_date;
+                let msg = if self.group_by_date {
+                    "Grouping enabled"
+                } else {
+                    "Grouping disabled"
+                };
+                self.status_message = Some(msg.to_string());
+            }
+            KeyCode::Char('f') => self.cycle_folder_filter(),
+            KeyCode::Char('o') => self.open_outbox(),
+            KeyCode::Char('l') => self.open_scheduled(),
             KeyCode::Char('m') => {
                 #[cfg(feature = "tracing")]
                 tracing::trace!("prefix key pressed");
@@ -200,7 +641,295 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         }
     }
 
+    /// Change the inbox ordering, keeping the selected conversation selected.
+    pub fn set_sort_mode(&mut self, mode: SortMode) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?mode, "changing sort mode");
+
+        let selected = self.selected_key();
+        self.sort_mode = mode;
+        sort_threads(&mut self.threads, &self.emails, mode);
+        self.select_key(selected);
+    }
+
+    /// `(uid, folder)` of the newest message in the selected conversation, used
+    /// to keep the selection stable while the email list changes underneath it.
+    fn selected_key(&self) -> Option<(u32, String)> {
+        let thread = self.threads.get(self.table_state.selected()?)?;
+        let email = &self.emails[*thread.last()?];
+        Some((email.uid, email.folder.clone()))
+    }
+
+    fn select_key(&mut self, key: Option<(u32, String)>) {
+        let Some((uid, folder)) = key else {
+            return;
+        };
+        let position = self.threads.iter().position(|t| {
+            t.iter()
+                .any(|&i| self.emails[i].uid == uid && self.emails[i].folder == folder)
+        });
+        if position.is_some() {
+            self.table_state.select(position);
+        }
+    }
+
+    /// Page messages in or out around the selection: load older (or
+    /// previously evicted newer) pages as the selection nears either end, and
+    /// evict conversations far out of view once `max_loaded` is exceeded.
+    fn update_window(&mut self) {
+        let Some(selected) = self.table_state.selected() else {
+            return;
+        };
+        if selected + LOAD_AHEAD >= self.threads.len() {
+            self.load_older();
+        } else if selected < LOAD_AHEAD && !self.newer_available.is_empty() {
+            self.load_newer();
+        }
+        self.evict_out_of_view();
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self))
+    )]
+    fn load_older(&mut self) {
+        let mut folders: Vec<String> = self
+            .page_windows
+            .keys()
+            .filter(|f| !self.older_exhausted.contains(*f))
+            .cloned()
+            .collect();
+        folders.sort();
+
+        let mut loaded = Vec::new();
+        for folder in folders {
+            let low = self.page_windows[&folder].0;
+            match self.imap_client.fetch_older(&folder, low, self.page_size) {
+                Ok(page) => {
+                    if page.len() < self.page_size as usize {
+                        self.older_exhausted.insert(folder.clone());
+                    }
+                    if let Some(min) = page.iter().map(|e| e.uid).min() {
+                        let window = self.page_windows.get_mut(&folder).unwrap();
+                        window.0 = window.0.min(min);
+                    }
+                    loaded.extend(page);
+                }
+                // Keep the pages of the other folders, whose windows
+                // already moved past them
+                Err(e) => self.status_message = Some(format!("Failed to load messages: {e}")),
+            }
+        }
+        self.merge_emails(loaded);
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self))
+    )]
+    fn load_newer(&mut self) {
+        let mut folders: Vec<String> = self.newer_available.iter().cloned().collect();
+        folders.sort();
+
+        let mut loaded = Vec::new();
+        for folder in folders {
+            let high = self.page_windows[&folder].1;
+            match self.imap_client.fetch_newer(&folder, high, self.page_size) {
+                Ok(page) => {
+                    if page.len() < self.page_size as usize {
+                        self.newer_available.remove(&folder);
+                    }
+                    if let Some(max) = page.iter().map(|e| e.uid).max() {
+                        let window = self.page_windows.get_mut(&folder).unwrap();
+                        window.1 = window.1.max(max);
+                    }
+                    loaded.extend(page);
+                }
+                // Keep the pages of the other folders, whose windows
+                // already moved past them
+                Err(e) => self.status_message = Some(format!("Failed to load messages: {e}")),
+            }
+        }
+        self.merge_emails(loaded);
+    }
+
+    /// Add newly paged-in summaries, skipping any already loaded.
+    fn merge_emails(&mut self, loaded: Vec<EmailSummary>) {
+        let mut known: HashSet<(u32, String)> = self
+            .emails
+            .iter()
+            .map(|e| (e.uid, e.folder.clone()))
+            .collect();
+        let mut known_ids: HashSet<String> = self
+            .emails
+            .iter()
+            .filter_map(|e| e.message_id.clone())
+            .collect();
+
+        let before = self.emails.len();
+        for email in loaded {
+            if !known.insert((email.uid, email.folder.clone())) {
+                continue;
+            }
+            if let Some(ref mid) = email.message_id
+                && !known_ids.insert(mid.clone())
+            {
+                continue;
+            }
+            self.emails.push(email);
+        }
+        if self.emails.len() == before {
+            return;
+        }
+        self.contacts.harvest(&self.emails[before..]);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(added = self.emails.len() - before, "merged page");
+
+        let selected = self.selected_key();
+        sort_newest_first(&mut self.emails);
+        self.rebuild_threads();
+        self.select_key(selected);
+    }
+
+    /// Drop whole conversations from whichever end of the list is furthest
+    /// from the selection until at most `max_loaded` summaries remain. Only
+    /// applies when sorted by date, where the ends correspond to the newest
+    /// and oldest pages of each folder.
+    fn evict_out_of_view(&mut self) {
+        if self.sort_mode != SortMode::Date || self.emails.len() <= self.max_loaded {
+            return;
+        }
+        let Some(selected) = self.table_state.selected() else {
+            return;
+        };
+
+        let mut remaining = self.emails.len();
+        let (mut head, mut tail) = (0, self.threads.len());
+        let mut evicted_head: Vec<usize> = Vec::new();
+        let mut evicted_tail: Vec<usize> = Vec::new();
+        while remaining > self.max_loaded && head < tail {
+            let room_above = selected.saturating_sub(head);
+            let room_below = (tail - 1).saturating_sub(selected);
+            if room_below >= room_above && room_below > EVICT_MARGIN {
+                tail -= 1;
+                remaining -= self.threads[tail].len();
+                evicted_tail.extend(&self.threads[tail]);
+            } else if room_above > EVICT_MARGIN {
+                remaining -= self.threads[head].len();
+                evicted_head.extend(&self.threads[head]);
+                head += 1;
+            } else {
+                break;
+            }
+        }
+        if evicted_head.is_empty() && evicted_tail.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            head = evicted_head.len(),
+            tail = evicted_tail.len(),
+            "evicting summaries"
+        );
+
+        // Move each folder's window so the evicted messages are fetched again
+        // when the selection comes back towards them.
+        for &idx in &evicted_tail {
+            let email = &self.emails[idx];
+            if let Some(window) = self.page_windows.get_mut(&email.folder) {
+                window.0 = window.0.max(email.uid + 1);
+            }
+            self.older_exhausted.remove(&email.folder);
+        }
+        for &idx in &evicted_head {
+            let email = &self.emails[idx];
+            if let Some(window) = self.page_windows.get_mut(&email.folder) {
+                window.1 = window.1.min(email.uid.saturating_sub(1));
+            }
+            self.newer_available.insert(email.folder.clone());
+        }
+
+        let evicted: HashSet<usize> = evicted_head.into_iter().chain(evicted_tail).collect();
+        let selected = self.selected_key();
+        let mut index = 0;
+        self.emails.retain(|_| {
+            let keep = !evicted.contains(&index);
+            index += 1;
+            keep
+        });
+        self.rebuild_threads();
+        self.select_key(selected);
+    }
+
+    fn rebuild_threads(&mut self) {
+        self.threads = build_threads(&self.emails);
+        if let Some(ref folder) = self.folder_filter {
+            let emails = &self.emails;
+            self.threads
+                .retain(|t| t.iter().any(|&i| emails[i].folder == *folder));
+        }
+        sort_threads(&mut self.threads, &self.emails, self.sort_mode);
+    }
+
+    /// Step the folder filter from "all folders" through each configured folder.
+    fn cycle_folder_filter(&mut self) {
+        let folders = if self.folders.is_empty() {
+            let mut found: Vec<String> = Vec::new();
+            for email in &self.emails {
+                if !found.contains(&email.folder) {
+                    found.push(email.folder.clone());
+                }
+            }
+            found
+        } else {
+            self.folders.clone()
+        };
+
+        self.folder_filter = match self.folder_filter {
+            None => folders.first().cloned(),
+            Some(ref current) => folders
+                .iter()
+                .position(|f| f == current)
+                .and_then(|i| folders.get(i + 1))
+                .cloned(),
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(filter = ?self.folder_filter, "folder filter changed");
+
+        let selected = self.selected_key();
+        self.rebuild_threads();
+        self.table_state.select(if self.threads.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+        self.select_key(selected);
+        self.status_message = Some(format!("Showing {}", self.folder_scope()));
+    }
+
+    /// Human-readable description of the current folder filter.
+    pub fn folder_scope(&self) -> String {
+        match self.folder_filter {
+            Some(ref folder) => folder.clone(),
+            None => "all folders".to_string(),
+        }
+    }
+
     fn handle_detail_key(&mut self, key: KeyCode, _modifiers: KeyModifiers) {
+        if let View::Detail(ref state) = self.view {
+            if state.link_picker.is_some() {
+                self.handle_link_picker_key(key);
+                return;
+            }
+            if state.attachment_picker.is_some() {
+                self.handle_attachment_picker_key(key);
+                return;
+            }
+        }
+
         match key {
             KeyCode::Esc => {
                 #[cfg(feature = "tracing")]
@@ -214,6 +943,9 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             }
             KeyCode::Char('r') => self.start_reply(),
             KeyCode::Char('c') => self.start_new_email(),
+            KeyCode::Char('l') => self.open_link_picker(),
+            KeyCode::Char('v') => self.view_message_parts(),
+            KeyCode::Char('H') => self.cycle_message_display(),
             KeyCode::Char('m') => {
                 #[cfg(feature = "tracing")]
                 tracing::trace!("prefix key pressed");
@@ -256,68 +988,237 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         }
     }
 
-    #[cfg_attr(
-        feature = "tracing",
-        tracing::instrument(level = tracing::Level::TRACE, skip(self))
-    )]
-    fn open_email(&mut self) {
-        let thread_indices = self.selected_email_indices();
-        if thread_indices.is_empty() {
+    fn open_link_picker(&mut self) {
+        let View::Detail(ref mut state) = self.view else {
             return;
-        }
+        };
+        let links = state.thread[state.active_index]
+            .body
+            .as_ref()
+            .map(links::message_links)
+            .unwrap_or_default();
 
         #[cfg(feature = "tracing")]
-        tracing::trace!(?thread_indices, "opening email thread");
-
-        // The most recent message is the last one in the thread (oldest-first order)
-        let most_recent_idx = thread_indices.len() - 1;
-        let most_recent_email_idx = thread_indices[most_recent_idx];
-        let uid = self.emails[most_recent_email_idx].uid;
-        let folder = self.emails[most_recent_email_idx].folder.clone();
-
-        // Mark as seen
-        let _ = self.imap_client.mark_seen(uid, &folder);
-        self.emails[most_recent_email_idx].seen = true;
-
-        // Fetch full body for the most recent message
-        let body = self.imap_client.fetch_email(uid, &folder).ok();
-
-        let thread: Vec<ThreadMessage> = thread_indices
-            .iter()
-            .enumerate()
-            .map(|(i, &email_index)| ThreadMessage {
-                email_index,
-                body: if i == most_recent_idx {
-                    body.clone()
-                } else {
-                    None
-                },
-            })
-            .collect();
+        tracing::trace!(count = links.len(), "opening link picker");
 
-        self.view = View::Detail(DetailState {
-            thread,
-            active_index: most_recent_idx,
-            scroll_offset: 0,
-            status_message: None,
-        });
+        if links.is_empty() {
+            state.status_message = Some("No links in this message".to_string());
+        } else {
+            state.link_picker = Some(LinkPicker { links, selected: 0 });
+        }
     }
 
-    /// Get the email indices for the current selection.
-    /// In inbox view, returns all indices in the selected thread.
-    /// In detail view, returns just the active message's index.
-    fn selected_email_indices(&self) -> Vec<usize> {
-        match &self.view {
-            View::Detail(state) => {
-                vec![state.thread[state.active_index].email_index]
+    fn handle_link_picker_key(&mut self, key: KeyCode) {
+        let View::Detail(ref mut state) = self.view else {
+            return;
+        };
+        let Some(ref mut picker) = state.link_picker else {
+            return;
+        };
+
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('l') => state.link_picker = None,
+            KeyCode::Char('j') | KeyCode::Down => {
+                picker.selected = (picker.selected + 1).min(picker.links.len() - 1);
             }
-            View::Inbox => {
-                let Some(selected) = self.table_state.selected() else {
-                    return vec![];
-                };
-                self.threads.get(selected).cloned().unwrap_or_default()
+            KeyCode::Char('k') | KeyCode::Up => {
+                picker.selected = picker.selected.saturating_sub(1);
             }
-            View::Compose(_) => vec![],
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < picker.links.len() {
+                    picker.selected = index;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('o') => {
+                let link = picker.links[picker.selected].clone();
+                state.status_message = Some(format!("Opening {link}"));
+                state.link_picker = None;
+                self.pending_action = Some(ExternalAction::Open(link));
+            }
+            KeyCode::Char('y') => {
+                let link = picker.links[picker.selected].clone();
+                state.status_message = Some(format!("Copied {link}"));
+                state.link_picker = None;
+                self.pending_action = Some(ExternalAction::Copy(link));
+            }
+            _ => {}
+        }
+    }
+
+    /// Step the active message through normal, full headers and raw source.
+    fn cycle_message_display(&mut self) {
+        let View::Detail(ref mut state) = self.view else {
+            return;
+        };
+        let message = &mut state.thread[state.active_index];
+        let next = message.display.next();
+
+        if next != MessageDisplay::Normal && message.raw.is_none() {
+            let email = &self.emails[message.email_index];
+            match self.imap_client.fetch_raw(email.uid, &email.folder) {
+                Ok(raw) => message.raw = Some(raw),
+                Err(e) => {
+                    state.status_message = Some(format!("Error: {e}"));
+                    return;
+                }
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?next, "message display changed");
+
+        message.display = next;
+        state.status_message = match next {
+            MessageDisplay::Normal => None,
+            MessageDisplay::Headers => Some("Showing all headers".to_string()),
+            MessageDisplay::Source => Some("Showing raw source".to_string()),
+        };
+    }
+
+    /// Offer the HTML part and attachments of the active message for
+    /// viewing outside the terminal.
+    fn view_message_parts(&mut self) {
+        let View::Detail(ref mut state) = self.view else {
+            return;
+        };
+        let email = &self.emails[state.thread[state.active_index].email_index];
+        let raw = match self.imap_client.fetch_raw(email.uid, &email.folder) {
+            Ok(raw) => raw,
+            Err(e) => {
+                state.status_message = Some(format!("Error: {e}"));
+                return;
+            }
+        };
+        let mut attachments = viewable_parts(&raw, email.uid);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(count = attachments.len(), "viewable parts found");
+
+        match attachments.len() {
+            0 => state.status_message = Some("Nothing to open in this message".to_string()),
+            1 => {
+                let attachment = attachments.remove(0);
+                state.status_message = Some(format!("Opening {}", attachment.filename));
+                self.pending_action = Some(ExternalAction::View {
+                    filename: attachment.filename,
+                    content_type: attachment.content_type,
+                    data: attachment.data,
+                });
+            }
+            _ => {
+                state.attachment_picker = Some(AttachmentPicker {
+                    attachments,
+                    selected: 0,
+                })
+            }
+        }
+    }
+
+    fn handle_attachment_picker_key(&mut self, key: KeyCode) {
+        let View::Detail(ref mut state) = self.view else {
+            return;
+        };
+        let Some(ref mut picker) = state.attachment_picker else {
+            return;
+        };
+
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('v') => {
+                state.attachment_picker = None
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                picker.selected = (picker.selected + 1).min(picker.attachments.len() - 1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                picker.selected = picker.selected.saturating_sub(1);
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < picker.attachments.len() {
+                    picker.selected = index;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('o') => {
+                let attachment = picker.attachments.remove(picker.selected);
+                state.status_message = Some(format!("Opening {}", attachment.filename));
+                state.attachment_picker = None;
+                self.pending_action = Some(ExternalAction::View {
+                    filename: attachment.filename,
+                    content_type: attachment.content_type,
+                    data: attachment.data,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self))
+    )]
+    fn open_email(&mut self) {
+        let thread_indices = self.selected_email_indices();
+        if thread_indices.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?thread_indices, "opening email thread");
+
+        // The most recent message is the last one in the thread (oldest-first order)
+        let most_recent_idx = thread_indices.len() - 1;
+        let most_recent_email_idx = thread_indices[most_recent_idx];
+        let uid = self.emails[most_recent_email_idx].uid;
+        let folder = self.emails[most_recent_email_idx].folder.clone();
+
+        // Mark as seen
+        let _ = self.imap_client.mark_seen(uid, &folder);
+        self.emails[most_recent_email_idx].seen = true;
+
+        // Fetch full body for the most recent message
+        let body = self.imap_client.fetch_email(uid, &folder).ok();
+
+        let thread: Vec<ThreadMessage> = thread_indices
+            .iter()
+            .enumerate()
+            .map(|(i, &email_index)| ThreadMessage {
+                email_index,
+                body: if i == most_recent_idx {
+                    body.clone()
+                } else {
+                    None
+                },
+                display: MessageDisplay::Normal,
+                raw: None,
+            })
+            .collect();
+
+        self.view = View::Detail(DetailState {
+            thread,
+            active_index: most_recent_idx,
+            scroll_offset: 0,
+            status_message: None,
+            link_picker: None,
+            attachment_picker: None,
+        });
+    }
+
+    /// Get the email indices for the current selection.
+    /// In inbox view, returns all indices in the selected thread.
+    /// In detail view, returns just the active message's index.
+    fn selected_email_indices(&self) -> Vec<usize> {
+        match &self.view {
+            View::Detail(state) => {
+                vec![state.thread[state.active_index].email_index]
+            }
+            View::Inbox => {
+                let Some(selected) = self.table_state.selected() else {
+                    return vec![];
+                };
+                self.threads.get(selected).cloned().unwrap_or_default()
+            }
+            View::Compose(_) | View::Outbox(_) | View::Scheduled(_) => vec![],
         }
     }
 
@@ -348,7 +1249,7 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             let _ = self.imap_client.delete_email(*uid, folder);
         }
         self.emails.retain(|e| !uids.contains(&e.uid));
-        self.threads = build_threads(&self.emails);
+        self.rebuild_threads();
         self.fix_selection();
         self.view = View::Inbox;
     }
@@ -371,7 +1272,7 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             let _ = self.imap_client.archive_email(*uid, folder);
         }
         self.emails.retain(|e| !uids.contains(&e.uid));
-        self.threads = build_threads(&self.emails);
+        self.rebuild_threads();
         self.fix_selection();
         self.view = View::Inbox;
     }
@@ -417,6 +1318,7 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         let current = self.table_state.selected().unwrap_or(0);
         let next = (current + 1).min(self.threads.len() - 1);
         self.table_state.select(Some(next));
+        self.update_window();
     }
 
     fn select_previous(&mut self) {
@@ -426,17 +1328,20 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         let current = self.table_state.selected().unwrap_or(0);
         let prev = current.saturating_sub(1);
         self.table_state.select(Some(prev));
+        self.update_window();
     }
 
     fn select_first(&mut self) {
         if !self.threads.is_empty() {
             self.table_state.select(Some(0));
+            self.update_window();
         }
     }
 
     fn select_last(&mut self) {
         if !self.threads.is_empty() {
             self.table_state.select(Some(self.threads.len() - 1));
+            self.update_window();
         }
     }
 
@@ -454,7 +1359,7 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
                 self.threads.get(selected).cloned().unwrap_or_default()
             }
             View::Detail(state) => state.thread.iter().map(|m| m.email_index).collect(),
-            View::Compose(_) => return,
+            View::Compose(_) | View::Outbox(_) | View::Scheduled(_) => return,
         };
 
         if thread_indices.is_empty() {
@@ -484,13 +1389,33 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         }
 
         // If we sent the most recent message, reply to the original recipients
-        // instead of replying to ourselves
-        let to =
-            if extract_email_address(&reply_to.from) == extract_email_address(&self.sender_from) {
-                extract_email_address(&reply_to.to)
-            } else {
-                extract_email_address(&reply_to.from)
-            };
+        // instead of replying to ourselves; otherwise honour Reply-To. Reply
+        // as whichever of our identities the message was sent from or to.
+        let sent_as = reply_to
+            .from
+            .iter()
+            .find_map(|from| self.identity_for(from));
+        let addressed_to = reply_to
+            .to
+            .iter()
+            .chain(&reply_to.cc)
+            .find_map(|addr| self.identity_for(addr));
+        let from = sent_as
+            .or(addressed_to)
+            .unwrap_or(&self.sender_from)
+            .to_string();
+        let recipients = if sent_as.is_some() {
+            &reply_to.to
+        } else if !reply_to.reply_to.is_empty() {
+            &reply_to.reply_to
+        } else {
+            &reply_to.from
+        };
+        let to = recipients
+            .iter()
+            .map(|addr| extract_email_address(addr))
+            .collect::<Vec<_>>()
+            .join(", ");
 
         // Build quoted text by fetching bodies
         let mut quoted_parts = Vec::new();
@@ -498,27 +1423,52 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             let uid = self.emails[idx].uid;
             let folder = self.emails[idx].folder.clone();
             let date = self.emails[idx].date.clone();
-            let from = self.emails[idx].from.clone();
+            let from = self.emails[idx].author().to_string();
             if let Ok(body) = self.imap_client.fetch_email(uid, &folder) {
                 quoted_parts.push(format!(
                     "On {}, {} wrote:\n{}",
                     date,
                     from,
-                    body.body_text
-                        .lines()
-                        .map(|l| format!("> {l}"))
-                        .collect::<Vec<_>>()
-                        .join("\n")
+                    quote::quote(&body.body_text, quote::WIDTH).join("\n")
                 ));
             }
         }
         let quoted_text = quoted_parts.join("\n\n");
 
+        // Inline quoting moves the quote into the editor, keeping the new
+        // text and signature on the side chosen by the posting style
+        let mut body_lines = self.initial_body(&from);
+        let mut cursor_row = 0;
+        let quoted_text = if self.inline_quote && !quoted_text.is_empty() {
+            let quote_lines = quoted_text.lines().map(str::to_string);
+            match self.posting {
+                Posting::Top => {
+                    // The quote goes between the new text and the
+                    // signature, which must stay last
+                    let signature = body_lines.split_off(1);
+                    body_lines.push(String::new());
+                    body_lines.extend(quote_lines);
+                    body_lines.extend(signature);
+                }
+                Posting::Bottom => {
+                    let mut lines: Vec<String> = quote_lines.collect();
+                    lines.push(String::new());
+                    cursor_row = lines.len();
+                    lines.append(&mut body_lines);
+                    body_lines = lines;
+                }
+            }
+            String::new()
+        } else {
+            quoted_text
+        };
+
         self.view = View::Compose(Box::new(ComposeState {
             step: ComposeStep::Body,
             is_reply: true,
-            body_lines: vec![String::new()],
-            cursor_row: 0,
+            from,
+            body_lines,
+            cursor_row,
             cursor_col: 0,
             to: to.clone(),
             to_cursor: to.len(),
@@ -532,9 +1482,79 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             references,
             quoted_text,
             status_message: None,
+            suggestions: Vec::new(),
+            suggestion_selected: 0,
+            confirm_send: false,
+            markdown: self.markdown,
+            preview: false,
+            sign: self.sign,
+            encrypt: self.encrypt,
+            outbox_id: None,
+            scheduled_id: None,
+            send_at: None,
+            send_at_cursor: 0,
         }));
     }
 
+    /// Every identity we send as, the configured sender first.
+    fn all_identities(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.sender_from.as_str()).chain(self.identities.iter().map(String::as_str))
+    }
+
+    /// Our identity with the same address as `address`, if any.
+    fn identity_for(&self, address: &str) -> Option<&str> {
+        let address = extract_email_address(address).to_lowercase();
+        self.all_identities()
+            .find(|identity| extract_email_address(identity).to_lowercase() == address)
+    }
+
+    /// Switch the message being composed to the next identity, along with
+    /// its signature unless that has been edited.
+    fn cycle_identity(&mut self) {
+        let identities: Vec<String> = self.all_identities().map(str::to_string).collect();
+        let View::Compose(ref state) = self.view else {
+            return;
+        };
+        let current = identities.iter().position(|i| *i == state.from);
+        let next = current.map_or(0, |i| (i + 1) % identities.len());
+        let old = signature_block(self.signature_for(&state.from));
+        let new = signature_block(self.signature_for(&identities[next]));
+
+        let View::Compose(ref mut state) = self.view else {
+            return;
+        };
+        state.from = identities[next].clone();
+        if old != new && state.body_lines.ends_with(&old) {
+            state
+                .body_lines
+                .truncate(state.body_lines.len() - old.len());
+            state.body_lines.extend(new);
+            if state.body_lines.is_empty() {
+                state.body_lines.push(String::new());
+            }
+            state.cursor_row = state.cursor_row.min(state.body_lines.len() - 1);
+            state.cursor_col = state
+                .cursor_col
+                .min(state.body_lines[state.cursor_row].len());
+        }
+    }
+
+    /// The signature to use when sending as `from`.
+    fn signature_for(&self, from: &str) -> Option<&str> {
+        self.identity_signatures
+            .get(from)
+            .or(self.signature.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Editor lines for a new message from `from`: an empty line to type
+    /// on, then its signature if one is configured.
+    fn initial_body(&self, from: &str) -> Vec<String> {
+        let mut lines = vec![String::new()];
+        lines.extend(signature_block(self.signature_for(from)));
+        lines
+    }
+
     fn start_new_email(&mut self) {
         if matches!(self.view, View::Compose(_)) {
             return;
@@ -543,7 +1563,8 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         self.view = View::Compose(Box::new(ComposeState {
             step: ComposeStep::Body,
             is_reply: false,
-            body_lines: vec![String::new()],
+            from: self.sender_from.clone(),
+            body_lines: self.initial_body(&self.sender_from),
             cursor_row: 0,
             cursor_col: 0,
             to: String::new(),
@@ -558,6 +1579,17 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             references: vec![],
             quoted_text: String::new(),
             status_message: None,
+            suggestions: Vec::new(),
+            suggestion_selected: 0,
+            confirm_send: false,
+            markdown: self.markdown,
+            preview: false,
+            sign: self.sign,
+            encrypt: self.encrypt,
+            outbox_id: None,
+            scheduled_id: None,
+            send_at: None,
+            send_at_cursor: 0,
         }));
     }
 
@@ -566,6 +1598,47 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         tracing::instrument(level = tracing::Level::TRACE, skip(self))
     )]
     fn handle_compose_key(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+        if let View::Compose(ref mut state) = self.view
+            && state.confirm_send
+        {
+            state.confirm_send = false;
+            let scheduling = state.send_at.is_some();
+            if key != KeyCode::Char('y') {
+                state.send_at = None;
+                state.status_message = Some(
+                    if scheduling {
+                        "Scheduling cancelled"
+                    } else {
+                        "Send cancelled"
+                    }
+                    .to_string(),
+                );
+            } else if scheduling {
+                self.schedule_email(true);
+            } else {
+                self.send_email();
+            }
+            return;
+        }
+
+        if let View::Compose(ref mut state) = self.view
+            && let Some(ref mut text) = state.send_at
+        {
+            match key {
+                KeyCode::Esc => state.send_at = None,
+                KeyCode::Enter => self.schedule_email(false),
+                _ => handle_line_input(text, &mut state.send_at_cursor, key),
+            }
+            return;
+        }
+
+        if let View::Compose(ref mut state) = self.view
+            && !state.suggestions.is_empty()
+            && handle_suggestion_key(state, key)
+        {
+            return;
+        }
+
         match key {
             KeyCode::Esc => {
                 #[cfg(feature = "tracing")]
@@ -575,19 +1648,66 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             KeyCode::Char('s') if modifiers.contains(KeyModifiers::ALT) => {
                 self.advance_compose_step();
             }
+            KeyCode::Char('f') if modifiers.contains(KeyModifiers::ALT) => {
+                self.cycle_identity();
+            }
+            KeyCode::Char('l') if modifiers.contains(KeyModifiers::ALT) => {
+                if let View::Compose(ref mut state) = self.view
+                    && state.step == ComposeStep::Bcc
+                {
+                    state.send_at = Some(String::new());
+                    state.send_at_cursor = 0;
+                    state.status_message = None;
+                }
+            }
+            KeyCode::Char('m') if modifiers.contains(KeyModifiers::ALT) => {
+                if let View::Compose(ref mut state) = self.view {
+                    state.markdown = !state.markdown;
+                    state.preview = false;
+                }
+            }
+            KeyCode::Char('p') if modifiers.contains(KeyModifiers::ALT) => {
+                if let View::Compose(ref mut state) = self.view
+                    && state.markdown
+                {
+                    state.preview = !state.preview;
+                }
+            }
+            #[cfg(feature = "pgp")]
+            KeyCode::Char('g') if modifiers.contains(KeyModifiers::ALT) => {
+                if let View::Compose(ref mut state) = self.view {
+                    state.sign = !state.sign;
+                }
+            }
+            #[cfg(feature = "pgp")]
+            KeyCode::Char('e') if modifiers.contains(KeyModifiers::ALT) => {
+                if let View::Compose(ref mut state) = self.view {
+                    state.encrypt = !state.encrypt;
+                }
+            }
+            KeyCode::Char('q') if modifiers.contains(KeyModifiers::ALT) => {
+                if let View::Compose(ref mut state) = self.view
+                    && state.step == ComposeStep::Body
+                {
+                    reflow_paragraph(state);
+                }
+            }
             _ => {
                 let View::Compose(ref mut state) = self.view else {
                     return;
                 };
                 match state.step {
+                    // The preview is read-only
+                    ComposeStep::Body if state.preview => {}
                     ComposeStep::Body => handle_body_input(state, key),
                     ComposeStep::Subject => {
                         handle_line_input(&mut state.subject, &mut state.subject_cursor, key);
                     }
-                    ComposeStep::To => handle_line_input(&mut state.to, &mut state.to_cursor, key),
-                    ComposeStep::Cc => handle_line_input(&mut state.cc, &mut state.cc_cursor, key),
-                    ComposeStep::Bcc => {
-                        handle_line_input(&mut state.bcc, &mut state.bcc_cursor, key);
+                    ComposeStep::To | ComposeStep::Cc | ComposeStep::Bcc => {
+                        if let Some((field, cursor)) = address_field(state) {
+                            handle_line_input(field, cursor, key);
+                        }
+                        refresh_suggestions(state, &self.contacts);
                     }
                 }
             }
@@ -602,6 +1722,7 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         let View::Compose(ref mut state) = self.view else {
             return;
         };
+        state.suggestions.clear();
 
         match state.step {
             ComposeStep::Body => {
@@ -625,14 +1746,32 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
                     state.status_message = Some("To field cannot be empty".to_string());
                     return;
                 }
+                if let Some(error) = address_error(&state.to) {
+                    state.status_message = Some(error);
+                    return;
+                }
                 state.step = ComposeStep::Cc;
                 state.status_message = None;
             }
             ComposeStep::Cc => {
+                if let Some(error) = address_error(&state.cc) {
+                    state.status_message = Some(error);
+                    return;
+                }
                 state.step = ComposeStep::Bcc;
                 state.status_message = None;
             }
             ComposeStep::Bcc => {
+                if let Some(error) = address_error(&state.bcc) {
+                    state.status_message = Some(error);
+                    return;
+                }
+                let count = recipient_count(state);
+                if count > self.max_recipients {
+                    state.confirm_send = true;
+                    state.status_message = Some(format!("Send to {count} recipients? (y/n)"));
+                    return;
+                }
                 self.send_email();
             }
         }
@@ -648,44 +1787,9 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
         };
 
         let is_reply = state.is_reply;
-
-        let mut body = state.body_lines.join("\n");
-        if !state.quoted_text.is_empty() {
-            body.push_str("\n\n");
-            body.push_str(&state.quoted_text);
-        }
-
-        let to: Vec<String> = state
-            .to
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-
-        let cc: Vec<String> = state
-            .cc
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-
-        let bcc: Vec<String> = state
-            .bcc
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-
-        let email = smtp::Email {
-            from: self.sender_from.clone(),
-            to,
-            cc,
-            bcc,
-            subject: state.subject.clone(),
-            body,
-            in_reply_to: state.in_reply_to.clone(),
-            references: state.references.clone(),
-        };
+        let outbox_id = state.outbox_id.clone();
+        let scheduled_id = state.scheduled_id.clone();
+        let email = state.email(self.posting);
 
         #[cfg(feature = "tracing")]
         tracing::trace!(
@@ -703,12 +1807,9 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
                 #[cfg(feature = "tracing")]
                 tracing::trace!("email sent successfully");
 
-                if let Some(ref folder) = self.sent_folder
-                    && let Err(_e) = self.imap_client.append(folder, &bytes)
-                {
-                    #[cfg(feature = "tracing")]
-                    tracing::warn!(%_e, folder, "failed to append to sent folder");
-                }
+                self.delivered(&email, &bytes);
+                self.unqueue(outbox_id.as_deref());
+                self.unschedule(scheduled_id.as_deref());
 
                 let msg = if is_reply {
                     "Reply sent!"
@@ -718,6 +1819,24 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
                 self.status_message = Some(msg.to_string());
                 self.view = View::Inbox;
             }
+            Err(SmtpError::Undelivered { outgoing, error })
+                if !error.is_permanent() && self.outbox.is_some() =>
+            {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(%error, "email send failed, queueing");
+                match self.enqueue(&outgoing, &email, &error) {
+                    Ok(()) => {
+                        self.unqueue(outbox_id.as_deref());
+                        self.unschedule(scheduled_id.as_deref());
+                        self.status_message =
+                            Some(format!("Not sent ({error}); queued in the outbox"));
+                        self.view = View::Inbox;
+                    }
+                    Err(e) => {
+                        self.set_status(format!("Send failed: {error}; could not queue it: {e}"))
+                    }
+                }
+            }
             Err(e) => {
                 #[cfg(feature = "tracing")]
                 tracing::trace!(%e, "email send failed");
@@ -727,87 +1846,1021 @@ impl<I: ImapClient, S: SmtpClient> App<I, S> {
             }
         }
     }
-}
 
-fn handle_body_input(state: &mut ComposeState, key: KeyCode) {
-    match key {
-        KeyCode::Char(c) => {
-            state.body_lines[state.cursor_row].insert(state.cursor_col, c);
-            state.cursor_col += c.len_utf8();
+    /// Record the recipients and file a copy of a message the server took.
+    fn delivered(&mut self, email: &smtp::Email, bytes: &[u8]) {
+        let now = chrono::Utc::now().timestamp();
+        for address in email.to.iter().chain(&email.cc).chain(&email.bcc) {
+            self.contacts.record(address, now);
         }
-        KeyCode::Enter => {
-            let rest = state.body_lines[state.cursor_row].split_off(state.cursor_col);
-            state.cursor_row += 1;
-            state.body_lines.insert(state.cursor_row, rest);
-            state.cursor_col = 0;
+
+        if let Some(ref folder) = self.sent_folder
+            && let Err(_e) = self.imap_client.append(folder, bytes)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%_e, folder, "failed to append to sent folder");
         }
-        KeyCode::Backspace => {
-            if state.cursor_col > 0 {
-                state.cursor_col -= 1;
-                state.body_lines[state.cursor_row].remove(state.cursor_col);
-            } else if state.cursor_row > 0 {
-                let line = state.body_lines.remove(state.cursor_row);
-                state.cursor_row -= 1;
-                state.cursor_col = state.body_lines[state.cursor_row].len();
-                state.body_lines[state.cursor_row].push_str(&line);
-            }
+    }
+
+    /// Put a message the server could not take right now in the outbox.
+    fn enqueue(
+        &mut self,
+        outgoing: &smtp::Outgoing,
+        email: &smtp::Email,
+        error: &SmtpError,
+    ) -> Result<(), outbox::OutboxError> {
+        if let Some(ref outbox) = self.outbox {
+            let now = chrono::Utc::now().timestamp();
+            outbox.queue(outgoing, email, &error.to_string(), false, now)?;
         }
-        KeyCode::Left => {
-            if state.cursor_col > 0 {
-                state.cursor_col -= 1;
-            }
+        self.refresh_outbox();
+        Ok(())
+    }
+
+    /// Take entry `id`, if any, out of the outbox.
+    fn unqueue(&mut self, id: Option<&str>) {
+        if let (Some(outbox), Some(id)) = (&self.outbox, id)
+            && let Err(_e) = outbox.remove(id)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%_e, id, "failed to remove outbox entry");
         }
-        KeyCode::Right => {
-            if state.cursor_col < state.body_lines[state.cursor_row].len() {
-                state.cursor_col += 1;
-            }
+        self.refresh_outbox();
+    }
+
+    /// Re-read the outbox, keeping the outbox view's selection in range.
+    fn refresh_outbox(&mut self) {
+        let entries = match self.outbox {
+            Some(ref outbox) => outbox.entries().unwrap_or_else(|_e| {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%_e, "failed to read outbox");
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        self.queued = entries.len();
+        if let View::Outbox(ref mut state) = self.view {
+            let selected = state.table_state.selected().unwrap_or(0);
+            let last = entries.len().checked_sub(1);
+            state
+                .table_state
+                .select(last.map(|last| selected.min(last)));
+            state.entries = entries;
         }
-        KeyCode::Up => {
-            if state.cursor_row > 0 {
-                state.cursor_row -= 1;
-                state.cursor_col = state
-                    .cursor_col
-                    .min(state.body_lines[state.cursor_row].len());
+    }
+
+    /// The message open in the composer, if any.
+    fn composing(&self) -> Option<&ComposeState> {
+        match self.view {
+            View::Compose(ref state) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Hand the queued messages that are due at unix time `now` to a
+    /// background sender, unless one is still busy.
+    fn flush_outbox(&mut self, now: i64) {
+        if self.sending.is_some() {
+            return;
+        }
+        let Some(ref outbox) = self.outbox else {
+            return;
+        };
+        let entries = match outbox.entries() {
+            Ok(entries) => entries,
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%_e, "failed to read outbox");
+                return;
             }
+        };
+        self.queued = entries.len();
+        // A message open in the composer goes out when the user sends it.
+        let editing = self.composing().and_then(|state| state.outbox_id.clone());
+        let due: Vec<_> = entries
+            .into_iter()
+            .filter(|e| e.is_due(now) && editing.as_ref() != Some(&e.id))
+            .collect();
+        if due.is_empty() {
+            return;
         }
-        KeyCode::Down => {
-            if state.cursor_row + 1 < state.body_lines.len() {
-                state.cursor_row += 1;
-                state.cursor_col = state
-                    .cursor_col
-                    .min(state.body_lines[state.cursor_row].len());
+        let deliveries = due
+            .into_iter()
+            .filter_map(|entry| self.load_queued(entry, now).ok())
+            .collect();
+        self.send_in_background(deliveries, None);
+    }
+
+    /// Try to deliver `entry` now, taking it out of the outbox once the
+    /// server accepts it.
+    fn deliver_queued(&mut self, entry: outbox::Entry, now: i64) -> Result<(), String> {
+        let delivery = self.load_queued(entry, now)?;
+        let result = delivery.send(&*self.smtp_client);
+        self.delivery_done(delivery, result, now)
+    }
+
+    /// `entry` with the message it was queued with. Without that message
+    /// the entry can only be edited or cancelled.
+    fn load_queued(&self, entry: outbox::Entry, now: i64) -> Result<Delivery, String> {
+        let outgoing = match self.outbox {
+            Some(ref outbox) => outbox.outgoing(&entry).map_err(|e| e.to_string()),
+            None => Err("no outbox".to_string()),
+        };
+        match outgoing {
+            Ok(outgoing) => Ok(Delivery::Queued(entry, outgoing)),
+            Err(error) => Err(self.queued_failed(entry, error, true, now)),
+        }
+    }
+
+    /// Take `entry` out of the outbox once the server accepted its message,
+    /// or note why it did not.
+    fn queued_done(
+        &mut self,
+        entry: outbox::Entry,
+        result: Result<Vec<u8>, SmtpError>,
+        now: i64,
+    ) -> Result<(), String> {
+        match result {
+            Ok(message) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(id = %entry.id, "queued email sent");
+                self.delivered(&entry.draft, &message);
+                match self.outbox {
+                    Some(ref outbox) => outbox.remove(&entry.id).map_err(|e| e.to_string()),
+                    None => Ok(()),
+                }
+            }
+            Err(e) => Err(self.queued_failed(entry, e.to_string(), e.is_permanent(), now)),
+        }
+    }
+
+    /// Record on `entry` why it was not delivered, giving back the error.
+    fn queued_failed(
+        &self,
+        mut entry: outbox::Entry,
+        error: String,
+        permanent: bool,
+        now: i64,
+    ) -> String {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(id = %entry.id, %error, "queued email not sent");
+        if let Some(ref outbox) = self.outbox
+            && let Err(_e) = outbox.failed(&mut entry, &error, permanent, now)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%_e, id = %entry.id, "failed to update outbox entry");
+        }
+        error
+    }
+
+    /// Send `deliveries` one after another on a thread of their own, so a
+    /// slow or unreachable server does not hold up the interface.
+    /// `failure` is why a scheduled message could not even be handed over.
+    fn send_in_background(&mut self, deliveries: Vec<Delivery>, failure: Option<String>) {
+        let (sender, results) = mpsc::channel();
+        let sending = Sending {
+            results,
+            pending: deliveries.iter().map(Delivery::pending).collect(),
+            sent_queued: 0,
+            sent_scheduled: 0,
+            failure,
+        };
+        if deliveries.is_empty() {
+            return self.report_sending(sending);
+        }
+        let smtp_client = Arc::clone(&self.smtp_client);
+        std::thread::spawn(move || {
+            for delivery in deliveries {
+                let result = delivery.send(&*smtp_client);
+                if sender.send((delivery, result)).is_err() {
+                    return;
+                }
+            }
+        });
+        self.sending = Some(sending);
+    }
+
+    /// Take in the results the background sender has so far.
+    fn collect_deliveries(&mut self) {
+        self.receive_deliveries(false);
+    }
+
+    /// Wait for the background sender to get through all its messages.
+    fn finish_deliveries(&mut self) {
+        self.receive_deliveries(true);
+    }
+
+    fn receive_deliveries(&mut self, wait: bool) {
+        let now = chrono::Utc::now().timestamp();
+        loop {
+            let Some(ref sending) = self.sending else {
+                return;
+            };
+            let received = if wait {
+                sending
+                    .results
+                    .recv()
+                    .map_err(|_| TryRecvError::Disconnected)
+            } else {
+                sending.results.try_recv()
+            };
+            match received {
+                Ok((delivery, result)) => {
+                    let pending = delivery.pending();
+                    let outcome = self.delivery_done(delivery, result, now);
+                    if let Some(ref mut sending) = self.sending {
+                        sending.done(&pending, outcome);
+                    }
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    if let Some(sending) = self.sending.take() {
+                        self.report_sending(sending);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Record how sending `delivery` went.
+    fn delivery_done(
+        &mut self,
+        delivery: Delivery,
+        result: Result<Vec<u8>, SmtpError>,
+        now: i64,
+    ) -> Result<(), String> {
+        match delivery {
+            Delivery::Queued(entry, _) => self.queued_done(entry, result, now),
+            Delivery::Scheduled(entry, _) => self.scheduled_done(entry, result),
+        }
+    }
+
+    /// Tell how the messages the background sender took went.
+    fn report_sending(&mut self, sending: Sending) {
+        self.refresh_schedule();
+        self.refresh_outbox();
+        if let Some(error) = sending.failure {
+            self.set_status(format!("Scheduled message not sent: {error}"));
+        } else if sending.sent_scheduled > 0 {
+            let sent = sending.sent_scheduled;
+            let plural = if sent == 1 { "" } else { "s" };
+            self.set_status(format!("Sent {sent} scheduled message{plural}"));
+        }
+        let sent = sending.sent_queued;
+        if sent > 0 {
+            let plural = if sent == 1 { "" } else { "s" };
+            self.set_status(format!("Sent {sent} queued message{plural}"));
+        }
+    }
+
+    /// Whether the entry is with the background sender, saying so if it is.
+    fn being_sent(&mut self, pending: Pending) -> bool {
+        let sending = self
+            .sending
+            .as_ref()
+            .is_some_and(|sending| sending.pending.contains(&pending));
+        if sending {
+            self.set_status("This message is being sent".to_string());
+        }
+        sending
+    }
+
+    /// Show the messages waiting in the outbox.
+    fn open_outbox(&mut self) {
+        if self.outbox.is_none() {
+            self.status_message = Some("No outbox: data directory unavailable".to_string());
+            return;
+        }
+        self.view = View::Outbox(OutboxState {
+            entries: Vec::new(),
+            table_state: TableState::default(),
+            status_message: None,
+            confirm_cancel: false,
+        });
+        self.refresh_outbox();
+    }
+
+    fn handle_outbox_key(&mut self, key: KeyCode) {
+        let View::Outbox(ref mut state) = self.view else {
+            return;
+        };
+        if state.confirm_cancel {
+            state.confirm_cancel = false;
+            if key == KeyCode::Char('y') {
+                self.cancel_queued();
+            } else {
+                state.status_message = Some("Kept in the outbox".to_string());
+            }
+            return;
+        }
+
+        let selected = state.table_state.selected();
+        let last = state.entries.len().saturating_sub(1);
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.view = View::Inbox,
+            KeyCode::Char('j') | KeyCode::Down => {
+                state
+                    .table_state
+                    .select(selected.map(|i| (i + 1).min(last)));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                state
+                    .table_state
+                    .select(selected.map(|i| i.saturating_sub(1)));
+            }
+            KeyCode::Enter | KeyCode::Char('e') => self.edit_queued(),
+            KeyCode::Char('d') if selected.is_some() => {
+                state.confirm_cancel = true;
+                state.status_message = Some("Cancel this message? (y/n)".to_string());
+            }
+            KeyCode::Char('r') => self.retry_queued(),
+            _ => {}
+        }
+    }
+
+    fn selected_queued(&self) -> Option<outbox::Entry> {
+        let View::Outbox(ref state) = self.view else {
+            return None;
+        };
+        let selected = state.table_state.selected()?;
+        state.entries.get(selected).cloned()
+    }
+
+    /// Open the selected queued message in the composer; sending it again
+    /// replaces the queued copy.
+    fn edit_queued(&mut self) {
+        let Some(entry) = self.selected_queued() else {
+            return;
+        };
+        if self.being_sent(Pending::Queued(entry.id.clone())) {
+            return;
+        }
+        // The outbox only keeps the encrypted copy of such a message.
+        if entry.draft.encrypt {
+            self.set_status("Encrypted messages cannot be edited in the outbox".to_string());
+            return;
+        }
+        let mut state = ComposeState::reopen(entry.draft);
+        state.outbox_id = Some(entry.id);
+        self.view = View::Compose(Box::new(state));
+    }
+
+    fn cancel_queued(&mut self) {
+        let Some(entry) = self.selected_queued() else {
+            return;
+        };
+        if self.being_sent(Pending::Queued(entry.id.clone())) {
+            return;
+        }
+        self.unqueue(Some(&entry.id));
+        self.set_status("Message cancelled".to_string());
+    }
+
+    /// Deliver the selected queued message now instead of waiting.
+    fn retry_queued(&mut self) {
+        let Some(entry) = self.selected_queued() else {
+            return;
+        };
+        if self.being_sent(Pending::Queued(entry.id.clone())) {
+            return;
+        }
+        let result = self.deliver_queued(entry, chrono::Utc::now().timestamp());
+        self.refresh_outbox();
+        match result {
+            Ok(()) => self.set_status("Message sent".to_string()),
+            Err(e) => self.set_status(format!("Send failed: {e}")),
+        }
+    }
+
+    /// Put the message being composed on the schedule for the time typed
+    /// at the "send later" prompt, once sending to more than
+    /// `max_recipients` people is `confirmed`.
+    fn schedule_email(&mut self, confirmed: bool) {
+        let View::Compose(ref mut state) = self.view else {
+            return;
+        };
+        let text = state.send_at.clone().unwrap_or_default();
+        let now = chrono::Local::now();
+        let send_at = schedule::parse_time(&text, now.naive_local())
+            .and_then(|at| at.and_local_timezone(chrono::Local).earliest());
+        let Some(send_at) = send_at else {
+            state.status_message = Some(format!(
+                "Unrecognised time {text:?}: use HH:MM, tomorrow HH:MM, YYYY-MM-DD HH:MM or +2h"
+            ));
+            return;
+        };
+        if send_at <= now {
+            state.status_message =
+                Some(format!("{} has already passed", send_at.format("%F %H:%M")));
+            return;
+        }
+        if let Some(error) = address_error(&state.bcc) {
+            state.send_at = None;
+            state.status_message = Some(error);
+            return;
+        }
+        let count = recipient_count(state);
+        if count > self.max_recipients && !confirmed {
+            state.confirm_send = true;
+            state.status_message = Some(format!("Schedule to {count} recipients? (y/n)"));
+            return;
+        }
+        let Some(ref schedule) = self.schedule else {
+            state.status_message = Some("No schedule: data directory unavailable".to_string());
+            return;
+        };
+
+        let email = state.email(self.posting);
+        // An encrypted message is only kept encrypted
+        let built = if email.encrypt {
+            match self.smtp_client.build(&email) {
+                Ok(built) => Some(built),
+                Err(e) => {
+                    state.status_message = Some(format!("Could not schedule: {e}"));
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        match schedule.add(&email, built.as_ref(), send_at.timestamp()) {
+            Ok(entry) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(%send_at, "email scheduled");
+                let outbox_id = state.outbox_id.clone();
+                let scheduled_id = state.scheduled_id.clone();
+                let saved = self.save_draft(entry, built);
+                self.unqueue(outbox_id.as_deref());
+                self.unschedule(scheduled_id.as_deref());
+                let mut status = format!("Scheduled for {}", send_at.format("%a %b %d %H:%M"));
+                if let Err(e) = saved {
+                    status.push_str(&format!("; not saved to drafts: {e}"));
+                }
+                self.status_message = Some(status);
+                self.view = View::Inbox;
+            }
+            Err(e) => state.status_message = Some(format!("Could not schedule: {e}")),
+        }
+    }
+
+    /// Keep a copy of the just scheduled `entry` in the drafts folder, if
+    /// one is configured. Encrypted messages are saved as `built`.
+    fn save_draft(
+        &mut self,
+        mut entry: schedule::Scheduled,
+        built: Option<smtp::Outgoing>,
+    ) -> Result<(), String> {
+        let (Some(folder), Some(schedule)) = (self.drafts_folder.clone(), self.schedule.clone())
+        else {
+            return Ok(());
+        };
+        let message = match built {
+            Some(built) => built.message,
+            None => {
+                self.smtp_client
+                    .build(&entry.draft)
+                    .map_err(|e| e.to_string())?
+                    .message
+            }
+        };
+        self.imap_client
+            .append_draft(&folder, &message)
+            .map_err(|e| e.to_string())?;
+        entry.draft_copy = mime::parse(&message)
+            .header("Message-ID")
+            .map(str::to_string);
+        schedule.save(&entry).map_err(|e| e.to_string())
+    }
+
+    /// Take `entry` off the schedule, along with its copy in the drafts
+    /// folder.
+    fn forget_scheduled(&mut self, entry: &schedule::Scheduled) -> Result<(), String> {
+        if let (Some(folder), Some(message_id)) = (&self.drafts_folder, &entry.draft_copy)
+            && let Err(_e) = self.imap_client.remove_message(folder, message_id)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%_e, folder, message_id, "failed to remove draft");
+        }
+        match self.schedule {
+            Some(ref schedule) => schedule.remove(&entry.id).map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /// Take entry `id`, if any, off the schedule.
+    fn unschedule(&mut self, id: Option<&str>) {
+        let entry = match (&self.schedule, id) {
+            (Some(schedule), Some(id)) => schedule.get(id).ok(),
+            _ => None,
+        };
+        if let Some(entry) = entry
+            && let Err(_e) = self.forget_scheduled(&entry)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%_e, id = %entry.id, "failed to remove scheduled message");
+        }
+        self.refresh_schedule();
+    }
+
+    /// Re-read the schedule, keeping the scheduled view's selection in range.
+    fn refresh_schedule(&mut self) {
+        let entries = match self.schedule {
+            Some(ref schedule) => schedule.entries().unwrap_or_else(|_e| {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%_e, "failed to read schedule");
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        self.scheduled = entries.len();
+        if let View::Scheduled(ref mut state) = self.view {
+            let selected = state.table_state.selected().unwrap_or(0);
+            let last = entries.len().checked_sub(1);
+            state
+                .table_state
+                .select(last.map(|last| selected.min(last)));
+            state.entries = entries;
+        }
+    }
+
+    /// Hand the scheduled messages that are due at unix time `now` to a
+    /// background sender, unless one is still busy.
+    fn flush_schedule(&mut self, now: i64) {
+        if self.sending.is_some() {
+            return;
+        }
+        let Some(ref schedule) = self.schedule else {
+            return;
+        };
+        let entries = match schedule.entries() {
+            Ok(entries) => entries,
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%_e, "failed to read schedule");
+                return;
+            }
+        };
+        self.scheduled = entries.len();
+        // A message open in the composer goes out when the user sends it.
+        let editing = self
+            .composing()
+            .and_then(|state| state.scheduled_id.clone());
+        let due: Vec<_> = entries
+            .into_iter()
+            .filter(|e| e.is_due(now) && editing.as_ref() != Some(&e.id))
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+        let mut deliveries = Vec::new();
+        let mut failure = None;
+        for entry in due {
+            match self.load_scheduled(entry) {
+                Ok(delivery) => deliveries.push(delivery),
+                Err(e) => failure = Some(e),
+            }
+        }
+        self.send_in_background(deliveries, failure);
+    }
+
+    /// Send `entry` now, taking it off the schedule once the server accepts
+    /// it or the outbox takes over retrying it.
+    fn dispatch_scheduled(&mut self, entry: schedule::Scheduled) -> Result<(), String> {
+        let delivery = self.load_scheduled(entry)?;
+        let result = delivery.send(&*self.smtp_client);
+        let now = chrono::Utc::now().timestamp();
+        self.delivery_done(delivery, result, now)
+    }
+
+    /// `entry` ready to be sent. Only the encrypted message was kept of an
+    /// encrypted one, and without it the entry can only be cancelled.
+    fn load_scheduled(&self, entry: schedule::Scheduled) -> Result<Delivery, String> {
+        if !entry.draft.encrypt {
+            return Ok(Delivery::Scheduled(entry, None));
+        }
+        let outgoing = match self.schedule {
+            Some(ref schedule) => schedule.outgoing(&entry).map_err(|e| e.to_string()),
+            None => Err("no schedule".to_string()),
+        };
+        match outgoing {
+            Ok(outgoing) => Ok(Delivery::Scheduled(entry, Some(outgoing))),
+            Err(error) => Err(self.scheduled_failed(entry, error)),
+        }
+    }
+
+    /// Take `entry` off the schedule once the server accepted its message
+    /// or the outbox took over retrying it, or note why neither happened.
+    fn scheduled_done(
+        &mut self,
+        entry: schedule::Scheduled,
+        result: Result<Vec<u8>, SmtpError>,
+    ) -> Result<(), String> {
+        let error = match result {
+            Ok(bytes) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(id = %entry.id, "scheduled email sent");
+                self.delivered(&entry.draft, &bytes);
+                return self.forget_scheduled(&entry);
+            }
+            Err(SmtpError::Undelivered { outgoing, error })
+                if !error.is_permanent() && self.outbox.is_some() =>
+            {
+                match self.enqueue(&outgoing, &entry.draft, &error) {
+                    Ok(()) => {
+                        self.forget_scheduled(&entry)?;
+                        return Err(format!("{error}; queued in the outbox"));
+                    }
+                    Err(e) => format!("{error}; could not queue it: {e}"),
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+        Err(self.scheduled_failed(entry, error))
+    }
+
+    /// Record on `entry` why it was not sent, giving back the error.
+    fn scheduled_failed(&self, mut entry: schedule::Scheduled, error: String) -> String {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(id = %entry.id, %error, "scheduled email not sent");
+        if let Some(ref schedule) = self.schedule
+            && let Err(_e) = schedule.failed(&mut entry, &error)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%_e, id = %entry.id, "failed to update scheduled message");
+        }
+        error
+    }
+
+    /// Show the messages waiting to be sent later.
+    fn open_scheduled(&mut self) {
+        if self.schedule.is_none() {
+            self.status_message = Some("No schedule: data directory unavailable".to_string());
+            return;
+        }
+        self.view = View::Scheduled(ScheduledState {
+            entries: Vec::new(),
+            table_state: TableState::default(),
+            status_message: None,
+            confirm_cancel: false,
+        });
+        self.refresh_schedule();
+    }
+
+    fn handle_scheduled_key(&mut self, key: KeyCode) {
+        let View::Scheduled(ref mut state) = self.view else {
+            return;
+        };
+        if state.confirm_cancel {
+            state.confirm_cancel = false;
+            if key == KeyCode::Char('y') {
+                self.cancel_scheduled();
+            } else {
+                state.status_message = Some("Kept on the schedule".to_string());
+            }
+            return;
+        }
+
+        let selected = state.table_state.selected();
+        let last = state.entries.len().saturating_sub(1);
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.view = View::Inbox,
+            KeyCode::Char('j') | KeyCode::Down => {
+                state
+                    .table_state
+                    .select(selected.map(|i| (i + 1).min(last)));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                state
+                    .table_state
+                    .select(selected.map(|i| i.saturating_sub(1)));
+            }
+            KeyCode::Enter | KeyCode::Char('e') => self.edit_scheduled(),
+            KeyCode::Char('d') if selected.is_some() => {
+                state.confirm_cancel = true;
+                state.status_message = Some("Cancel this message? (y/n)".to_string());
             }
+            KeyCode::Char('s') => self.send_scheduled_now(),
+            _ => {}
+        }
+    }
+
+    fn selected_scheduled(&self) -> Option<schedule::Scheduled> {
+        let View::Scheduled(ref state) = self.view else {
+            return None;
+        };
+        let selected = state.table_state.selected()?;
+        state.entries.get(selected).cloned()
+    }
+
+    /// Open the selected scheduled message in the composer; sending or
+    /// scheduling it again replaces the scheduled copy.
+    fn edit_scheduled(&mut self) {
+        let Some(entry) = self.selected_scheduled() else {
+            return;
+        };
+        if self.being_sent(Pending::Scheduled(entry.id.clone())) {
+            return;
+        }
+        // The schedule only keeps the encrypted copy of such a message.
+        if entry.draft.encrypt {
+            self.set_status("Encrypted messages cannot be edited once scheduled".to_string());
+            return;
+        }
+        let mut state = ComposeState::reopen(entry.draft);
+        state.scheduled_id = Some(entry.id);
+        self.view = View::Compose(Box::new(state));
+    }
+
+    fn cancel_scheduled(&mut self) {
+        let Some(entry) = self.selected_scheduled() else {
+            return;
+        };
+        if self.being_sent(Pending::Scheduled(entry.id.clone())) {
+            return;
+        }
+        self.unschedule(Some(&entry.id));
+        self.set_status("Message cancelled".to_string());
+    }
+
+    /// Send the selected scheduled message without waiting for its time.
+    fn send_scheduled_now(&mut self) {
+        let Some(entry) = self.selected_scheduled() else {
+            return;
+        };
+        if self.being_sent(Pending::Scheduled(entry.id.clone())) {
+            return;
+        }
+        let result = self.dispatch_scheduled(entry);
+        self.refresh_schedule();
+        match result {
+            Ok(()) => self.set_status("Message sent".to_string()),
+            Err(e) => self.set_status(format!("Send failed: {e}")),
+        }
+    }
+}
+
+/// The parts of a raw message worth opening externally: the HTML body and
+/// anything that is an attachment or cannot be shown as text.
+fn viewable_parts(raw: &[u8], uid: u32) -> Vec<Attachment> {
+    let root = mime::parse(raw);
+    root.leaves()
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, part)| {
+            let fallback = || format!("part-{}.{}", i + 1, mime::extension_for(&part.content_type));
+            let filename = if part.is_attachment() {
+                part.filename().unwrap_or_else(fallback)
+            } else if part.content_type == "text/html" {
+                format!("message-{uid}.html")
+            } else if !part.content_type.starts_with("text/") {
+                fallback()
+            } else {
+                return None;
+            };
+            Some(Attachment {
+                filename,
+                content_type: part.content_type.clone(),
+                data: part.body.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The lines `signature` adds to the end of a message: a blank line, the
+/// `-- ` delimiter and the signature itself.
+fn signature_block(signature: Option<&str>) -> Vec<String> {
+    let Some(signature) = signature else {
+        return Vec::new();
+    };
+    let mut lines = vec![String::new()];
+    if !signature.starts_with("-- \n") {
+        lines.push("-- ".to_string());
+    }
+    lines.extend(signature.lines().map(str::to_string));
+    lines
+}
+
+fn handle_body_input(state: &mut ComposeState, key: KeyCode) {
+    match key {
+        KeyCode::Char(c) => {
+            state.body_lines[state.cursor_row].insert(state.cursor_col, c);
+            state.cursor_col += c.len_utf8();
+        }
+        KeyCode::Enter => {
+            let rest = state.body_lines[state.cursor_row].split_off(state.cursor_col);
+            state.cursor_row += 1;
+            state.body_lines.insert(state.cursor_row, rest);
+            state.cursor_col = 0;
+        }
+        KeyCode::Backspace => {
+            if state.cursor_col > 0 {
+                state.cursor_col -= 1;
+                state.body_lines[state.cursor_row].remove(state.cursor_col);
+            } else if state.cursor_row > 0 {
+                let line = state.body_lines.remove(state.cursor_row);
+                state.cursor_row -= 1;
+                state.cursor_col = state.body_lines[state.cursor_row].len();
+                state.body_lines[state.cursor_row].push_str(&line);
+            }
+        }
+        KeyCode::Left if state.cursor_col > 0 => {
+            state.cursor_col -= 1;
+        }
+        KeyCode::Right if state.cursor_col < state.body_lines[state.cursor_row].len() => {
+            state.cursor_col += 1;
+        }
+        KeyCode::Up if state.cursor_row > 0 => {
+            state.cursor_row -= 1;
+            state.cursor_col = state
+                .cursor_col
+                .min(state.body_lines[state.cursor_row].len());
+        }
+        KeyCode::Down if state.cursor_row + 1 < state.body_lines.len() => {
+            state.cursor_row += 1;
+            state.cursor_col = state
+                .cursor_col
+                .min(state.body_lines[state.cursor_row].len());
         }
         _ => {}
     }
 }
 
+/// Rewrap the paragraph under the cursor at its quote depth, leaving the
+/// signature alone.
+fn reflow_paragraph(state: &mut ComposeState) {
+    let lines = &state.body_lines;
+    let row = state.cursor_row;
+    if lines[..=row].iter().any(|line| line == "-- ") {
+        return;
+    }
+    let (level, _) = quote::depth(&lines[row]);
+    let in_paragraph = |line: &String| {
+        let (l, content) = quote::depth(line);
+        l == level && !content.trim().is_empty() && line != "-- "
+    };
+    if !in_paragraph(&lines[row]) {
+        return;
+    }
+
+    let mut start = row;
+    while start > 0 && in_paragraph(&lines[start - 1]) {
+        start -= 1;
+    }
+    let mut end = row + 1;
+    while end < lines.len() && in_paragraph(&lines[end]) {
+        end += 1;
+    }
+
+    let reflowed = quote::reflow(&lines[start..end], quote::WIDTH);
+    let last = start + reflowed.len() - 1;
+    state.body_lines.splice(start..end, reflowed);
+    state.cursor_row = last;
+    state.cursor_col = state.body_lines[last].len();
+}
+
 fn handle_line_input(field: &mut String, cursor: &mut usize, key: KeyCode) {
     match key {
         KeyCode::Char(c) => {
             field.insert(*cursor, c);
             *cursor += c.len_utf8();
         }
-        KeyCode::Backspace => {
-            if *cursor > 0 {
-                *cursor -= 1;
-                field.remove(*cursor);
-            }
+        KeyCode::Backspace if *cursor > 0 => {
+            *cursor -= 1;
+            field.remove(*cursor);
         }
-        KeyCode::Left => {
-            if *cursor > 0 {
-                *cursor -= 1;
-            }
+        KeyCode::Left if *cursor > 0 => {
+            *cursor -= 1;
         }
-        KeyCode::Right => {
-            if *cursor < field.len() {
-                *cursor += 1;
-            }
+        KeyCode::Right if *cursor < field.len() => {
+            *cursor += 1;
         }
         _ => {}
     }
 }
 
+/// The address field being edited and its cursor.
+fn address_field(state: &mut ComposeState) -> Option<(&mut String, &mut usize)> {
+    match state.step {
+        ComposeStep::To => Some((&mut state.to, &mut state.to_cursor)),
+        ComposeStep::Cc => Some((&mut state.cc, &mut state.cc_cursor)),
+        ComposeStep::Bcc => Some((&mut state.bcc, &mut state.bcc_cursor)),
+        ComposeStep::Body | ComposeStep::Subject => None,
+    }
+}
+
+/// Byte range of the address list entry around `cursor`, without the
+/// whitespace after its comma.
+fn address_token(field: &str, cursor: usize) -> (usize, usize) {
+    let segment = address::segments(field)
+        .into_iter()
+        .find(|segment| segment.contains(&cursor) || segment.end == cursor)
+        .unwrap_or(0..field.len());
+    let text = &field[segment.start..cursor];
+    (
+        segment.start + (text.len() - text.trim_start().len()),
+        segment.end,
+    )
+}
+
+/// How many people the message being composed goes to.
+fn recipient_count(state: &ComposeState) -> usize {
+    [&state.to, &state.cc, &state.bcc]
+        .into_iter()
+        .map(|field| recipients(field).len())
+        .sum()
+}
+
+/// A status message for the first malformed entry of an address field.
+fn address_error(field: &str) -> Option<String> {
+    address::parse_list(field).into_iter().find_map(|entry| {
+        let error = entry.parsed.err()?;
+        Some(format!(
+            "Invalid address {:?}: {error}",
+            &field[entry.range]
+        ))
+    })
+}
+
+/// Every mailbox in an address field, with groups expanded.
+fn recipients(field: &str) -> Vec<String> {
+    address::parse_list(field)
+        .into_iter()
+        .filter_map(|entry| entry.parsed.ok())
+        .flat_map(|address| {
+            address
+                .mailboxes()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Look up completions for the address under the cursor.
+fn refresh_suggestions(state: &mut ComposeState, contacts: &AddressBook) {
+    let Some((field, cursor)) = address_field(state) else {
+        return;
+    };
+    let (start, _) = address_token(field, *cursor);
+    let query = &field[start..*cursor];
+    let taken = field.to_lowercase();
+    let now = chrono::Utc::now().timestamp();
+    let suggestions: Vec<String> = contacts
+        .suggest(query, now, SUGGESTION_LIMIT + 1)
+        .into_iter()
+        .filter(|c| !taken.contains(&format!("<{}>", c.email.to_lowercase())))
+        .map(|c| c.formatted())
+        .take(SUGGESTION_LIMIT)
+        .collect();
+    state.suggestions = suggestions;
+    state.suggestion_selected = 0;
+}
+
+/// Keys that drive the completion dropdown. Returns false for keys that
+/// should reach the field instead.
+fn handle_suggestion_key(state: &mut ComposeState, key: KeyCode) -> bool {
+    match key {
+        KeyCode::Down => {
+            state.suggestion_selected = (state.suggestion_selected + 1) % state.suggestions.len();
+        }
+        KeyCode::Up => {
+            state.suggestion_selected = state
+                .suggestion_selected
+                .checked_sub(1)
+                .unwrap_or(state.suggestions.len() - 1);
+        }
+        KeyCode::Tab => {
+            let address = state.suggestions[state.suggestion_selected].clone();
+            state.suggestions.clear();
+            if let Some((field, cursor)) = address_field(state) {
+                complete_address(field, cursor, &address);
+            }
+        }
+        KeyCode::Esc => state.suggestions.clear(),
+        _ => return false,
+    }
+    true
+}
+
+/// Replace the entry under the cursor with `address` and start the next one.
+fn complete_address(field: &mut String, cursor: &mut usize, address: &str) {
+    let (start, end) = address_token(field, *cursor);
+    let prefix = if start > 0 && !field[..start].ends_with(' ') {
+        " "
+    } else {
+        ""
+    };
+    let rest = field[end..].to_string();
+    let replacement = if rest.is_empty() {
+        format!("{prefix}{address}, ")
+    } else {
+        format!("{prefix}{address}")
+    };
+    field.replace_range(start..end, &replacement);
+    *cursor = start + replacement.len();
+}
+
 fn extract_email_address(from: &str) -> String {
     if let Some(start) = from.find('<')
         && let Some(end) = from.find('>')
@@ -890,7 +2943,16 @@ fn build_threads(emails: &[EmailSummary]) -> Vec<Vec<usize>> {
     let mut root_of: Vec<usize> = (0..emails.len()).collect();
     for (i, root) in root_of.iter_mut().enumerate() {
         let mut current = i;
+        // Subject matching can link a reply and its original both ways when
+        // the reply is dated earlier (clock skew). Every member of such a
+        // cycle resolves to its oldest message so they share a root.
+        let mut path = vec![i];
         while let Some(p) = parent[current] {
+            if let Some(start) = path.iter().position(|&v| v == p) {
+                current = path[start..].iter().copied().max().unwrap_or(p);
+                break;
+            }
+            path.push(p);
             current = p;
         }
         *root = current;
@@ -916,3 +2978,43 @@ fn build_threads(emails: &[EmailSummary]) -> Vec<Vec<usize>> {
 
     threads
 }
+
+/// Stable sort of emails by date, newest first. Messages whose date cannot be
+/// determined keep their relative order and sink to the end.
+fn sort_newest_first(emails: &mut [EmailSummary]) {
+    emails.sort_by_key(|e| Reverse(e.timestamp()));
+}
+
+/// Order threads for display. Emails are stored newest-first, so the lowest
+/// index in a thread is its most recent message and is used as the tie-breaker.
+fn sort_threads(threads: &mut [Vec<usize>], emails: &[EmailSummary], mode: SortMode) {
+    let newest = |t: &[usize]| t.iter().copied().min().unwrap_or(0);
+
+    threads.sort_by(|a, b| {
+        let recency = newest(a).cmp(&newest(b));
+        match mode {
+            SortMode::Date => recency,
+            SortMode::Sender => {
+                let sender =
+                    |t: &[usize]| extract_display_name(emails[newest(t)].author()).to_lowercase();
+                sender(a).cmp(&sender(b)).then(recency)
+            }
+            SortMode::Subject => {
+                let subject = |t: &[usize]| normalize_subject(&emails[newest(t)].subject);
+                subject(a).cmp(&subject(b)).then(recency)
+            }
+            SortMode::UnreadFirst => {
+                let unread = |t: &[usize]| t.iter().any(|&i| !emails[i].seen);
+                unread(b).cmp(&unread(a)).then(recency)
+            }
+        }
+    });
+}
+
+/// The display name of an address if present, otherwise the bare address.
+fn extract_display_name(from: &str) -> &str {
+    match from.find('<') {
+        Some(start) if start > 0 => from[..start].trim().trim_matches('"'),
+        _ => from.trim().trim_start_matches('<').trim_end_matches('>'),
+    }
+}
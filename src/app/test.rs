@@ -12,16 +12,23 @@ fn mock_clients() -> (MockImapClient, MockSmtpClient) {
         Ok(EmailBody {
             uid,
             subject: "Test".to_string(),
-            from: "test@example.com".to_string(),
+            from: vec!["test@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
             to: vec!["me@example.com".to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             body_text: "Test body".to_string(),
+            body_html: None,
+            security: None,
         })
     });
     imap.expect_mark_seen().returning(|_, _| Ok(()));
     imap.expect_delete_email().returning(|_, _| Ok(()));
     imap.expect_archive_email().returning(|_, _| Ok(()));
     imap.expect_append().returning(|_, _| Ok(()));
+    imap.expect_fetch_older().returning(|_, _, _| Ok(vec![]));
+    imap.expect_fetch_newer().returning(|_, _, _| Ok(vec![]));
     (imap, MockSmtpClient::new())
 }
 
@@ -31,40 +38,52 @@ fn sample_emails() -> Vec<EmailSummary> {
             uid: 1,
             folder: "INBOX".to_string(),
             subject: "First".to_string(),
-            from: "alice@example.com".to_string(),
-            to: SENDER.to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec![SENDER.to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             seen: true,
             snippet: "Hello".to_string(),
             message_id: Some("msg1@example.com".to_string()),
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
         EmailSummary {
             uid: 2,
             folder: "INBOX".to_string(),
             subject: "Second".to_string(),
-            from: "bob@example.com".to_string(),
-            to: SENDER.to_string(),
+            from: vec!["bob@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec![SENDER.to_string()],
+            cc: vec![],
             date: "2025-01-02".to_string(),
             seen: false,
             snippet: "World".to_string(),
             message_id: Some("msg2@example.com".to_string()),
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
         EmailSummary {
             uid: 3,
             folder: "INBOX".to_string(),
             subject: "Third".to_string(),
-            from: "carol@example.com".to_string(),
-            to: SENDER.to_string(),
+            from: vec!["carol@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec![SENDER.to_string()],
+            cc: vec![],
             date: "2025-01-03".to_string(),
             seen: false,
             snippet: "Test".to_string(),
             message_id: Some("msg3@example.com".to_string()),
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
     ]
 }
@@ -300,27 +319,35 @@ fn thread_grouping_with_reply() {
             uid: 1,
             folder: "INBOX".to_string(),
             subject: "Original".to_string(),
-            from: "alice@example.com".to_string(),
-            to: SENDER.to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec![SENDER.to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             seen: true,
             snippet: "Hello".to_string(),
             message_id: Some("orig@example.com".to_string()),
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
         EmailSummary {
             uid: 2,
             folder: "INBOX".to_string(),
             subject: "Re: Original".to_string(),
-            from: "bob@example.com".to_string(),
-            to: "alice@example.com".to_string(),
+            from: vec!["bob@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["alice@example.com".to_string()],
+            cc: vec![],
             date: "2025-01-02".to_string(),
             seen: false,
             snippet: "Reply".to_string(),
             message_id: Some("reply@example.com".to_string()),
             in_reply_to: Some("orig@example.com".to_string()),
             references: vec!["orig@example.com".to_string()],
+            internal_date: None,
         },
     ];
     let app = App::new(emails, imap, smtp, SENDER.to_string(), None);
@@ -337,27 +364,35 @@ fn thread_grouping_by_subject() {
             uid: 1,
             folder: "INBOX".to_string(),
             subject: "Hello World".to_string(),
-            from: "alice@example.com".to_string(),
-            to: SENDER.to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec![SENDER.to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             seen: true,
             snippet: "Hi".to_string(),
             message_id: None,
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
         EmailSummary {
             uid: 2,
             folder: "INBOX".to_string(),
             subject: "RE: Hello World".to_string(),
-            from: "bob@example.com".to_string(),
-            to: "alice@example.com".to_string(),
+            from: vec!["bob@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["alice@example.com".to_string()],
+            cc: vec![],
             date: "2025-01-02".to_string(),
             seen: false,
             snippet: "Reply".to_string(),
             message_id: None,
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
     ];
     let app = App::new(emails, imap, smtp, SENDER.to_string(), None);
@@ -616,28 +651,36 @@ fn reply_to_own_sent_message_uses_recipient() {
             uid: 1,
             folder: "INBOX".to_string(),
             subject: "Hello".to_string(),
-            from: "alice@example.com".to_string(),
-            to: SENDER.to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec![SENDER.to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             seen: true,
             snippet: "Hi".to_string(),
             message_id: Some("orig@example.com".to_string()),
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         },
         // Most recent message was sent by us
         EmailSummary {
             uid: 2,
             folder: "Sent".to_string(),
             subject: "Re: Hello".to_string(),
-            from: SENDER.to_string(),
-            to: "alice@example.com".to_string(),
+            from: vec![SENDER.to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["alice@example.com".to_string()],
+            cc: vec![],
             date: "2025-01-02".to_string(),
             seen: true,
             snippet: "Reply".to_string(),
             message_id: Some("reply@example.com".to_string()),
             in_reply_to: Some("orig@example.com".to_string()),
             references: vec!["orig@example.com".to_string()],
+            internal_date: None,
         },
     ];
     let mut app = App::new(emails, imap, smtp, SENDER.to_string(), None);
@@ -649,3 +692,2033 @@ fn reply_to_own_sent_message_uses_recipient() {
         panic!("expected compose view");
     }
 }
+
+fn dated_email(uid: u32, from: &str, subject: &str, date: &str, seen: bool) -> EmailSummary {
+    EmailSummary {
+        uid,
+        folder: "INBOX".to_string(),
+        subject: subject.to_string(),
+        from: vec![from.to_string()],
+        sender: None,
+        reply_to: vec![],
+        to: vec![SENDER.to_string()],
+        cc: vec![],
+        date: date.to_string(),
+        seen,
+        snippet: String::new(),
+        message_id: Some(format!("msg{uid}@example.com")),
+        in_reply_to: None,
+        references: vec![],
+        internal_date: None,
+    }
+}
+
+#[test]
+fn emails_sorted_by_parsed_date_across_folders() {
+    let (imap, smtp) = mock_clients();
+    let mut sent = dated_email(3, SENDER, "Middle", "Wed, 15 Jan 2025 10:00:00 +0000", true);
+    sent.folder = "Sent".to_string();
+    let emails = vec![
+        dated_email(
+            1,
+            "alice@example.com",
+            "Newest",
+            "Mon, 20 Jan 2025 10:00:00 +0000",
+            false,
+        ),
+        dated_email(
+            2,
+            "bob@example.com",
+            "Oldest",
+            "Fri, 10 Jan 2025 10:00:00 +0000",
+            false,
+        ),
+        sent,
+    ];
+    let app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    let subjects: Vec<&str> = app.emails.iter().map(|e| e.subject.as_str()).collect();
+    assert_eq!(subjects, vec!["Newest", "Middle", "Oldest"]);
+}
+
+#[test]
+fn internal_date_used_when_header_unparseable() {
+    let (imap, smtp) = mock_clients();
+    let mut fallback = dated_email(1, "alice@example.com", "Fallback", "garbage", false);
+    fallback.internal_date =
+        chrono::DateTime::parse_from_rfc2822("Sat, 01 Feb 2025 10:00:00 +0000").ok();
+    let emails = vec![
+        fallback,
+        dated_email(
+            2,
+            "bob@example.com",
+            "Header",
+            "Wed, 15 Jan 2025 10:00:00 +0000",
+            false,
+        ),
+    ];
+    let app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    assert_eq!(app.emails[0].subject, "Fallback");
+}
+
+#[test]
+fn s_cycles_sort_mode() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    assert_eq!(app.sort_mode, SortMode::Date);
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::NONE);
+    assert_eq!(app.sort_mode, SortMode::Sender);
+    assert_eq!(app.status_message.as_deref(), Some("Sorted by sender"));
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::NONE);
+    assert_eq!(app.sort_mode, SortMode::Subject);
+}
+
+#[test]
+fn sort_by_sender_orders_alphabetically() {
+    let (imap, smtp) = mock_clients();
+    let emails = vec![
+        dated_email(
+            1,
+            "Zed <zed@example.com>",
+            "A",
+            "Mon, 20 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+        dated_email(
+            2,
+            "alice@example.com",
+            "B",
+            "Fri, 10 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+        dated_email(
+            3,
+            "Mallory <m@example.com>",
+            "C",
+            "Wed, 15 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+    ];
+    let mut app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    app.set_sort_mode(SortMode::Sender);
+    let order: Vec<u32> = app
+        .threads
+        .iter()
+        .map(|t| app.emails[*t.last().unwrap()].uid)
+        .collect();
+    assert_eq!(order, vec![2, 3, 1]);
+}
+
+#[test]
+fn sort_unread_first_then_date() {
+    let (imap, smtp) = mock_clients();
+    let emails = vec![
+        dated_email(
+            1,
+            "a@example.com",
+            "Read new",
+            "Mon, 20 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+        dated_email(
+            2,
+            "b@example.com",
+            "Unread old",
+            "Fri, 10 Jan 2025 10:00:00 +0000",
+            false,
+        ),
+        dated_email(
+            3,
+            "c@example.com",
+            "Unread mid",
+            "Wed, 15 Jan 2025 10:00:00 +0000",
+            false,
+        ),
+    ];
+    let mut app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    app.set_sort_mode(SortMode::UnreadFirst);
+    let order: Vec<u32> = app
+        .threads
+        .iter()
+        .map(|t| app.emails[*t.last().unwrap()].uid)
+        .collect();
+    assert_eq!(order, vec![3, 2, 1]);
+}
+
+#[test]
+fn sort_change_keeps_selected_conversation() {
+    let (imap, smtp) = mock_clients();
+    let emails = vec![
+        dated_email(
+            1,
+            "zed@example.com",
+            "A",
+            "Mon, 20 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+        dated_email(
+            2,
+            "alice@example.com",
+            "B",
+            "Fri, 10 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+    ];
+    let mut app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    // Date order: uid 1 first
+    assert_eq!(app.table_state.selected(), Some(0));
+    app.set_sort_mode(SortMode::Sender);
+    // uid 1 (zed) now sorts last but stays selected
+    assert_eq!(app.table_state.selected(), Some(1));
+}
+
+#[test]
+fn shift_s_toggles_grouping() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    assert!(app.group_by_date);
+    app.handle_key(KeyCode::Char('S'), KeyModifiers::NONE);
+    assert!(!app.group_by_date);
+    app.handle_key(KeyCode::Char('S'), KeyModifiers::NONE);
+    assert!(app.group_by_date);
+}
+
+fn numbered_emails(uids: std::ops::RangeInclusive<u32>) -> Vec<EmailSummary> {
+    uids.map(|uid| {
+        let date = chrono::DateTime::parse_from_rfc3339("2025-01-01T00:00:00+00:00").unwrap()
+            + chrono::Duration::minutes(uid as i64);
+        dated_email(
+            uid,
+            "alice@example.com",
+            &format!("Message {uid}"),
+            &date.to_rfc2822(),
+            true,
+        )
+    })
+    .collect()
+}
+
+#[test]
+fn nearing_end_loads_older_page() {
+    let mut imap = MockImapClient::new();
+    imap.expect_fetch_older()
+        .withf(|folder, before, count| folder == "INBOX" && *before == 101 && *count == 2)
+        .times(1)
+        .returning(|_, _, _| Ok(numbered_emails(99..=100)));
+    imap.expect_fetch_older()
+        .withf(|_, before, _| *before == 99)
+        .returning(|_, _, _| Ok(vec![]));
+    let mut app = App::new(
+        numbered_emails(101..=103),
+        imap,
+        MockSmtpClient::new(),
+        SENDER.to_string(),
+        None,
+    );
+    app.page_size = 2;
+
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    assert_eq!(app.emails.len(), 5);
+    assert_eq!(app.threads.len(), 5);
+    // Still on the second-newest conversation after the merge
+    assert_eq!(app.table_state.selected(), Some(1));
+    assert_eq!(app.emails.last().unwrap().uid, 99);
+
+    // A short page marks the folder as exhausted; no further fetches happen
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    assert_eq!(app.emails.len(), 5);
+}
+
+#[test]
+fn loaded_duplicates_are_skipped() {
+    let mut imap = MockImapClient::new();
+    imap.expect_fetch_older()
+        .returning(|_, _, _| Ok(numbered_emails(1..=2)));
+    let mut app = App::new(
+        numbered_emails(1..=3),
+        imap,
+        MockSmtpClient::new(),
+        SENDER.to_string(),
+        None,
+    );
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    assert_eq!(app.emails.len(), 3);
+}
+
+#[test]
+fn far_conversations_evicted_and_reloaded() {
+    let mut imap = MockImapClient::new();
+    imap.expect_fetch_older().returning(|_, _, _| Ok(vec![]));
+    imap.expect_fetch_newer()
+        .withf(|folder, _, _| folder == "INBOX")
+        .returning(|_, after, count| Ok(numbered_emails(after + 1..=(after + count).min(150))));
+    let mut app = App::new(
+        numbered_emails(1..=150),
+        imap,
+        MockSmtpClient::new(),
+        SENDER.to_string(),
+        None,
+    );
+    app.max_loaded = 100;
+    app.page_size = 10;
+
+    // Jump to the oldest message: the newest conversations are evicted
+    app.handle_key(KeyCode::Char('G'), KeyModifiers::NONE);
+    assert_eq!(app.emails.len(), 100);
+    assert_eq!(app.emails[0].uid, 100);
+    assert_eq!(app.emails.last().unwrap().uid, 1);
+    let selected = app.table_state.selected().unwrap();
+    assert_eq!(app.emails[app.threads[selected][0]].uid, 1);
+
+    // Walk back to the top: the evicted messages page back in
+    for _ in 0..200 {
+        app.handle_key(KeyCode::Char('k'), KeyModifiers::NONE);
+    }
+    assert_eq!(app.table_state.selected(), Some(0));
+    assert_eq!(app.emails[0].uid, 150);
+    assert!(app.emails.len() <= 100 + 10);
+}
+
+fn two_folder_emails() -> Vec<EmailSummary> {
+    let mut sent = dated_email(
+        2,
+        SENDER,
+        "Outgoing",
+        "Wed, 15 Jan 2025 10:00:00 +0000",
+        true,
+    );
+    sent.folder = "Sent".to_string();
+    vec![
+        dated_email(
+            1,
+            "alice@example.com",
+            "Incoming",
+            "Mon, 20 Jan 2025 10:00:00 +0000",
+            false,
+        ),
+        sent,
+    ]
+}
+
+#[test]
+fn f_cycles_folder_filter() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(two_folder_emails(), imap, smtp, SENDER.to_string(), None);
+    app.folders = vec!["INBOX".to_string(), "Sent".to_string()];
+    assert_eq!(app.threads.len(), 2);
+
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::NONE);
+    assert_eq!(app.folder_filter.as_deref(), Some("INBOX"));
+    assert_eq!(app.threads.len(), 1);
+    assert_eq!(app.emails[app.threads[0][0]].subject, "Incoming");
+    assert_eq!(app.status_message.as_deref(), Some("Showing INBOX"));
+
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::NONE);
+    assert_eq!(app.folder_filter.as_deref(), Some("Sent"));
+    assert_eq!(app.threads.len(), 1);
+    assert_eq!(app.emails[app.threads[0][0]].subject, "Outgoing");
+    assert_eq!(app.table_state.selected(), Some(0));
+
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::NONE);
+    assert_eq!(app.folder_filter, None);
+    assert_eq!(app.threads.len(), 2);
+    assert_eq!(app.folder_scope(), "all folders");
+}
+
+#[test]
+fn folder_filter_uses_loaded_folders_when_unconfigured() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(two_folder_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::NONE);
+    assert_eq!(app.folder_filter.as_deref(), Some("INBOX"));
+}
+
+#[test]
+fn folder_filter_keeps_whole_conversations() {
+    let (imap, smtp) = mock_clients();
+    let mut emails = two_folder_emails();
+    emails[1].subject = "Re: Incoming".to_string();
+    emails[1].date = "Tue, 21 Jan 2025 10:00:00 +0000".to_string();
+    emails[1].in_reply_to = Some("msg1@example.com".to_string());
+    let mut app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    app.folders = vec!["INBOX".to_string(), "Sent".to_string()];
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::NONE);
+    assert_eq!(app.folder_filter.as_deref(), Some("Sent"));
+    assert_eq!(app.threads.len(), 1);
+    assert_eq!(app.threads[0].len(), 2);
+}
+
+#[test]
+fn thread_grouping_survives_reply_dated_before_original() {
+    let (imap, smtp) = mock_clients();
+    let mut reply = dated_email(
+        2,
+        "bob@example.com",
+        "Re: Skewed",
+        "Wed, 15 Jan 2025 10:00:00 +0000",
+        false,
+    );
+    reply.in_reply_to = Some("msg1@example.com".to_string());
+    let emails = vec![
+        dated_email(
+            1,
+            "alice@example.com",
+            "Skewed",
+            "Mon, 20 Jan 2025 10:00:00 +0000",
+            true,
+        ),
+        reply,
+    ];
+    let app = App::new(emails, imap, smtp, SENDER.to_string(), None);
+    assert_eq!(app.threads.len(), 1);
+    assert_eq!(app.threads[0].len(), 2);
+}
+
+fn mock_clients_with_body(
+    body_text: &str,
+    body_html: Option<&str>,
+) -> (MockImapClient, MockSmtpClient) {
+    let mut imap = MockImapClient::new();
+    let body_text = body_text.to_string();
+    let body_html = body_html.map(str::to_string);
+    imap.expect_fetch_email().returning(move |uid, _folder| {
+        Ok(EmailBody {
+            uid,
+            subject: "Test".to_string(),
+            from: vec!["test@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: "2025-01-01".to_string(),
+            body_text: body_text.clone(),
+            body_html: body_html.clone(),
+            security: None,
+        })
+    });
+    imap.expect_mark_seen().returning(|_, _| Ok(()));
+    (imap, MockSmtpClient::new())
+}
+
+fn link_picker<I: ImapClient, S: SmtpClient>(app: &App<I, S>) -> Option<&LinkPicker> {
+    match app.view {
+        View::Detail(ref state) => state.link_picker.as_ref(),
+        _ => None,
+    }
+}
+
+#[test]
+fn l_opens_link_picker_and_enter_opens_link() {
+    let (imap, smtp) = mock_clients_with_body(
+        "See https://one.example.com and https://two.example.com.",
+        None,
+    );
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    let picker = link_picker(&app).expect("picker should be open");
+    assert_eq!(
+        picker.links,
+        vec!["https://one.example.com", "https://two.example.com"]
+    );
+
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    assert_eq!(link_picker(&app).unwrap().selected, 1);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+
+    assert!(link_picker(&app).is_none());
+    assert_eq!(
+        app.pending_action,
+        Some(ExternalAction::Open("https://two.example.com".to_string()))
+    );
+}
+
+#[test]
+fn link_picker_number_selects_and_y_copies() {
+    let (imap, smtp) = mock_clients_with_body(
+        "",
+        Some("<a href=\"https://a.example.com\">A</a> <a href=\"https://b.example.com\">B</a>"),
+    );
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('2'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('y'), KeyModifiers::NONE);
+    assert_eq!(
+        app.pending_action,
+        Some(ExternalAction::Copy("https://b.example.com".to_string()))
+    );
+    let View::Detail(ref state) = app.view else {
+        panic!("expected detail view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Copied https://b.example.com")
+    );
+}
+
+#[test]
+fn link_picker_esc_closes_without_leaving_detail() {
+    let (imap, smtp) = mock_clients_with_body("https://example.com", None);
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    assert!(matches!(app.view, View::Detail(_)));
+    assert!(link_picker(&app).is_none());
+    assert_eq!(app.pending_action, None);
+}
+
+#[test]
+fn l_without_links_shows_status() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    let View::Detail(ref state) = app.view else {
+        panic!("expected detail view");
+    };
+    assert!(state.link_picker.is_none());
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("No links in this message")
+    );
+}
+
+const MIXED_MESSAGE: &[u8] = b"Content-Type: multipart/mixed; boundary=b\r\n\
+\r\n\
+--b\r\n\
+Content-Type: multipart/alternative; boundary=a\r\n\
+\r\n\
+--a\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Hi\r\n\
+--a\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>Hi</p>\r\n\
+--a--\r\n\
+--b\r\n\
+Content-Type: image/png\r\n\
+Content-Disposition: attachment; filename=\"chart.png\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+iVBORw0KGgo=\r\n\
+--b--\r\n";
+
+fn app_with_raw(raw: &'static [u8]) -> App<MockImapClient, MockSmtpClient> {
+    let (mut imap, smtp) = mock_clients();
+    imap.expect_fetch_raw()
+        .returning(move |_, _| Ok(raw.to_vec()));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    app
+}
+
+#[test]
+fn viewable_parts_lists_html_and_attachments() {
+    let parts = viewable_parts(MIXED_MESSAGE, 7);
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].filename, "message-7.html");
+    assert_eq!(parts[0].content_type, "text/html");
+    assert_eq!(parts[0].data, b"<p>Hi</p>");
+    assert_eq!(parts[1].filename, "chart.png");
+    assert_eq!(parts[1].data, b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn v_offers_picker_and_enter_views_selected_part() {
+    let mut app = app_with_raw(MIXED_MESSAGE);
+    app.handle_key(KeyCode::Char('v'), KeyModifiers::NONE);
+    let View::Detail(ref state) = app.view else {
+        panic!("expected detail view");
+    };
+    assert_eq!(
+        state.attachment_picker.as_ref().unwrap().attachments.len(),
+        2
+    );
+
+    app.handle_key(KeyCode::Char('2'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    let Some(ExternalAction::View {
+        filename,
+        content_type,
+        ..
+    }) = app.pending_action
+    else {
+        panic!("expected a view action");
+    };
+    assert_eq!(filename, "chart.png");
+    assert_eq!(content_type, "image/png");
+}
+
+#[test]
+fn v_opens_single_html_part_directly() {
+    let mut app = app_with_raw(b"Content-Type: text/html; charset=utf-8\r\n\r\n<p>Newsletter</p>");
+    app.handle_key(KeyCode::Char('v'), KeyModifiers::NONE);
+    assert!(matches!(
+        app.pending_action,
+        Some(ExternalAction::View { ref content_type, .. }) if content_type == "text/html"
+    ));
+}
+
+#[test]
+fn v_on_plain_message_reports_nothing_to_open() {
+    let mut app = app_with_raw(b"Subject: hi\r\n\r\nJust text");
+    app.handle_key(KeyCode::Char('v'), KeyModifiers::NONE);
+    assert_eq!(app.pending_action, None);
+    let View::Detail(ref state) = app.view else {
+        panic!("expected detail view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Nothing to open in this message")
+    );
+}
+
+const RAW_WITH_HEADERS: &[u8] = b"Received: from mx.example.com\r\n\
+\tby mail.example.org; Mon, 1 Jan 2025 10:00:00 +0000\r\n\
+Authentication-Results: mail.example.org; spf=pass; dkim=pass\r\n\
+List-Id: <dev.lists.example.com>\r\n\
+Subject: Hello\r\n\
+\r\n\
+Body\r\n";
+
+fn active_message<I: ImapClient, S: SmtpClient>(app: &App<I, S>) -> &ThreadMessage {
+    let View::Detail(ref state) = app.view else {
+        panic!("expected detail view");
+    };
+    &state.thread[state.active_index]
+}
+
+#[test]
+fn shift_h_cycles_display_and_fetches_source_once() {
+    let (mut imap, smtp) = mock_clients();
+    imap.expect_fetch_raw()
+        .times(1)
+        .returning(|_, _| Ok(RAW_WITH_HEADERS.to_vec()));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(active_message(&app).display, MessageDisplay::Normal);
+
+    app.handle_key(KeyCode::Char('H'), KeyModifiers::SHIFT);
+    assert_eq!(active_message(&app).display, MessageDisplay::Headers);
+    assert_eq!(active_message(&app).raw.as_deref(), Some(RAW_WITH_HEADERS));
+
+    app.handle_key(KeyCode::Char('H'), KeyModifiers::SHIFT);
+    assert_eq!(active_message(&app).display, MessageDisplay::Source);
+
+    app.handle_key(KeyCode::Char('H'), KeyModifiers::SHIFT);
+    assert_eq!(active_message(&app).display, MessageDisplay::Normal);
+
+    // The cached source is reused on the next cycle
+    app.handle_key(KeyCode::Char('H'), KeyModifiers::SHIFT);
+    assert_eq!(active_message(&app).display, MessageDisplay::Headers);
+}
+
+#[test]
+fn shift_h_stays_normal_when_fetch_fails() {
+    let (mut imap, smtp) = mock_clients();
+    imap.expect_fetch_raw().returning(|_, _| {
+        Err(crate::imap::ImapError::Imap(imap::Error::Bad(
+            "gone".to_string(),
+        )))
+    });
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('H'), KeyModifiers::SHIFT);
+    assert_eq!(active_message(&app).display, MessageDisplay::Normal);
+    let View::Detail(ref state) = app.view else {
+        panic!("expected detail view");
+    };
+    assert!(
+        state
+            .status_message
+            .as_deref()
+            .unwrap()
+            .starts_with("Error")
+    );
+}
+
+fn reply_recipients(email: EmailSummary) -> String {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(vec![email], imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    match app.view {
+        View::Compose(ref state) => state.to.clone(),
+        _ => panic!("expected compose view"),
+    }
+}
+
+#[test]
+fn reply_honours_reply_to() {
+    let mut email = dated_email(1, "List <list@example.com>", "Announce", "2025-01-01", true);
+    email.reply_to = vec![
+        "Alice <alice@example.com>".to_string(),
+        "bob@example.com".to_string(),
+    ];
+    assert_eq!(
+        reply_recipients(email),
+        "alice@example.com, bob@example.com"
+    );
+}
+
+#[test]
+fn reply_without_reply_to_goes_to_author() {
+    let email = dated_email(1, "Carol <carol@example.com>", "Hi", "2025-01-01", true);
+    assert_eq!(reply_recipients(email), "carol@example.com");
+}
+
+#[test]
+fn reply_to_own_message_keeps_every_recipient() {
+    let mut email = dated_email(1, SENDER, "Plans", "2025-01-01", true);
+    email.to = vec![
+        "alice@example.com".to_string(),
+        "Bob <bob@example.com>".to_string(),
+    ];
+    email.reply_to = vec![SENDER.to_string()];
+    assert_eq!(
+        reply_recipients(email),
+        "alice@example.com, bob@example.com"
+    );
+}
+
+fn compose_to_field(contacts: &[&str]) -> App<MockImapClient, MockSmtpClient> {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    let now = chrono::Utc::now().timestamp();
+    for contact in contacts {
+        app.contacts.record(contact, now);
+    }
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    if let View::Compose(ref mut state) = app.view {
+        state.step = ComposeStep::To;
+    }
+    app
+}
+
+fn compose_state<I: ImapClient, S: SmtpClient>(app: &App<I, S>) -> &ComposeState {
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    state
+}
+
+fn type_text<I: ImapClient, S: SmtpClient>(app: &mut App<I, S>, text: &str) {
+    for c in text.chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+}
+
+#[test]
+fn typing_an_address_suggests_contacts() {
+    let mut app = compose_to_field(&["Alice Smith <alice@example.com>", "bob@example.com"]);
+    type_text(&mut app, "al");
+    assert_eq!(
+        compose_state(&app).suggestions,
+        vec!["Alice Smith <alice@example.com>"]
+    );
+
+    type_text(&mut app, "x");
+    assert!(compose_state(&app).suggestions.is_empty());
+}
+
+#[test]
+fn tab_completes_the_current_address() {
+    let mut app = compose_to_field(&["Alice Smith <alice@example.com>", "Bob <bob@example.com>"]);
+    type_text(&mut app, "bob@example.com, al");
+    app.handle_key(KeyCode::Tab, KeyModifiers::NONE);
+
+    let state = compose_state(&app);
+    assert_eq!(
+        state.to,
+        "bob@example.com, Alice Smith <alice@example.com>, "
+    );
+    assert_eq!(state.to_cursor, state.to.len());
+    assert!(state.suggestions.is_empty());
+}
+
+#[test]
+fn arrows_choose_between_suggestions() {
+    let mut app = compose_to_field(&["ann@example.com", "Anna <anna@example.com>"]);
+    app.contacts
+        .record("ann@example.com", chrono::Utc::now().timestamp());
+    type_text(&mut app, "ann");
+    assert_eq!(compose_state(&app).suggestions.len(), 2);
+
+    app.handle_key(KeyCode::Down, KeyModifiers::NONE);
+    assert_eq!(compose_state(&app).suggestion_selected, 1);
+    app.handle_key(KeyCode::Down, KeyModifiers::NONE);
+    assert_eq!(compose_state(&app).suggestion_selected, 0);
+    app.handle_key(KeyCode::Up, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Tab, KeyModifiers::NONE);
+    assert_eq!(compose_state(&app).to, "Anna <anna@example.com>, ");
+}
+
+#[test]
+fn esc_dismisses_suggestions_before_cancelling() {
+    let mut app = compose_to_field(&["alice@example.com"]);
+    type_text(&mut app, "a");
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    assert!(compose_state(&app).suggestions.is_empty());
+
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    assert!(matches!(app.view, View::Inbox));
+}
+
+#[test]
+fn sending_records_recipients() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send().returning(|_| Ok(vec![]));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    type_text(&mut app, "Hi");
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    type_text(&mut app, "Zed <zed@example.com>");
+    for _ in 0..3 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+
+    assert!(matches!(app.view, View::Inbox));
+    let zed = &app.contacts.contacts[0];
+    assert_eq!(zed.name.as_deref(), Some("Zed"));
+    assert_eq!(zed.email, "zed@example.com");
+}
+
+#[test]
+fn malformed_address_blocks_next_step() {
+    let mut app = compose_to_field(&[]);
+    type_text(&mut app, "alice@example.com, bob");
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+
+    let state = compose_state(&app);
+    assert_eq!(state.step, ComposeStep::To);
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Invalid address \"bob\": missing @ in \"bob\"")
+    );
+}
+
+#[test]
+fn quoted_names_with_commas_are_one_recipient() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send()
+        .withf(|email| {
+            email.to == vec!["\"Smith, Alice\" <alice@example.com>", "bob@example.com"]
+                && email.cc == vec!["a@example.com", "b@example.com"]
+        })
+        .returning(|_| Ok(vec![]));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    type_text(&mut app, "Hi");
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    type_text(
+        &mut app,
+        "\"Smith, Alice\" <alice@example.com>, bob@example.com",
+    );
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    type_text(&mut app, "Team: a@example.com, b@example.com;");
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+
+    assert!(matches!(app.view, View::Inbox));
+}
+
+#[test]
+fn many_recipients_need_confirmation() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send().times(1).returning(|_| Ok(vec![]));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.max_recipients = 2;
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    if let View::Compose(ref mut state) = app.view {
+        state.subject = "Hi".to_string();
+        state.to = "a@example.com, b@example.com, c@example.com".to_string();
+        state.step = ComposeStep::Bcc;
+    }
+
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    let state = compose_state(&app);
+    assert!(state.confirm_send);
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Send to 3 recipients? (y/n)")
+    );
+
+    app.handle_key(KeyCode::Char('n'), KeyModifiers::NONE);
+    let state = compose_state(&app);
+    assert!(!state.confirm_send);
+    assert_eq!(state.status_message.as_deref(), Some("Send cancelled"));
+
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    app.handle_key(KeyCode::Char('y'), KeyModifiers::NONE);
+    assert!(matches!(app.view, View::Inbox));
+}
+
+#[test]
+fn tab_completion_respects_quoted_commas() {
+    let mut app = compose_to_field(&["Carol <carol@example.com>"]);
+    type_text(&mut app, "\"Smith, Alice\" <alice@example.com>, ca");
+    app.handle_key(KeyCode::Tab, KeyModifiers::NONE);
+    assert_eq!(
+        compose_state(&app).to,
+        "\"Smith, Alice\" <alice@example.com>, Carol <carol@example.com>, "
+    );
+}
+
+#[test]
+fn new_message_starts_with_signature() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.signature = Some("Me\nExample Corp".to_string());
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+
+    let state = compose_state(&app);
+    assert_eq!(state.body_lines, vec!["", "", "-- ", "Me", "Example Corp"]);
+    assert_eq!((state.cursor_row, state.cursor_col), (0, 0));
+}
+
+#[test]
+fn signature_with_its_own_delimiter_is_not_doubled() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.signature = Some("-- \nMe".to_string());
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    assert_eq!(compose_state(&app).body_lines, vec!["", "", "-- ", "Me"]);
+}
+
+fn sent_reply_body(posting: Posting) -> String {
+    let (imap, mut smtp) = mock_clients();
+    let body = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let captured = body.clone();
+    smtp.expect_send().returning(move |email| {
+        *captured.lock().unwrap() = email.body.clone();
+        Ok(vec![])
+    });
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.signature = Some("Me".to_string());
+    app.posting = posting;
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    type_text(&mut app, "Thanks");
+    for _ in 0..4 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    assert!(matches!(app.view, View::Inbox));
+    body.lock().unwrap().clone()
+}
+
+#[test]
+fn top_posting_puts_signature_above_quote() {
+    let body = sent_reply_body(Posting::Top);
+    assert!(body.starts_with("Thanks\n\n-- \nMe\n\nOn "));
+    assert!(body.ends_with("> Test body"));
+}
+
+#[test]
+fn bottom_posting_puts_reply_below_quote() {
+    let body = sent_reply_body(Posting::Bottom);
+    assert!(body.starts_with("On "));
+    assert!(body.ends_with("> Test body\n\nThanks\n\n-- \nMe"));
+}
+
+const ALIAS: &str = "Support <support@example.com>";
+
+fn reply_with_identities(email: EmailSummary) -> (String, String) {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(vec![email], imap, smtp, SENDER.to_string(), None);
+    app.identities = vec![ALIAS.to_string()];
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    let state = compose_state(&app);
+    (state.from.clone(), state.to.clone())
+}
+
+#[test]
+fn reply_uses_identity_the_message_was_addressed_to() {
+    let mut email = dated_email(1, "Carol <carol@example.com>", "Help", "2025-01-01", true);
+    email.to = vec!["team@example.com".to_string()];
+    email.cc = vec!["SUPPORT@example.com".to_string()];
+    assert_eq!(
+        reply_with_identities(email),
+        (ALIAS.to_string(), "carol@example.com".to_string())
+    );
+}
+
+#[test]
+fn reply_to_message_sent_from_alias_goes_to_its_recipients() {
+    let mut email = dated_email(1, "support@example.com", "Re: Help", "2025-01-01", true);
+    email.to = vec!["Carol <carol@example.com>".to_string()];
+    assert_eq!(
+        reply_with_identities(email),
+        (ALIAS.to_string(), "carol@example.com".to_string())
+    );
+}
+
+#[test]
+fn reply_defaults_to_primary_identity() {
+    let email = dated_email(1, "Carol <carol@example.com>", "Hi", "2025-01-01", true);
+    assert_eq!(reply_with_identities(email).0, SENDER);
+}
+
+#[test]
+fn alt_f_cycles_identities_and_sends_as_chosen() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send()
+        .withf(|email| email.from == ALIAS)
+        .returning(|_| Ok(vec![]));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.identities = vec![ALIAS.to_string()];
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    assert_eq!(compose_state(&app).from, SENDER);
+
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::ALT);
+    assert_eq!(compose_state(&app).from, ALIAS);
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::ALT);
+    assert_eq!(compose_state(&app).from, SENDER);
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::ALT);
+
+    if let View::Compose(ref mut state) = app.view {
+        state.subject = "Hi".to_string();
+        state.to = "carol@example.com".to_string();
+        state.step = ComposeStep::Bcc;
+    }
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    assert!(matches!(app.view, View::Inbox));
+}
+
+#[test]
+fn alt_f_swaps_in_the_identity_signature() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.signature = Some("Me".to_string());
+    app.identities = vec![ALIAS.to_string(), "me@personal.example".to_string()];
+    app.identity_signatures = HashMap::from([(ALIAS.to_string(), "Example Support".to_string())]);
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    type_text(&mut app, "Hello");
+
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::ALT);
+    let state = compose_state(&app);
+    assert_eq!(
+        state.body_lines,
+        vec!["Hello", "", "-- ", "Example Support"]
+    );
+    assert_eq!((state.cursor_row, state.cursor_col), (0, 5));
+
+    // Identities without their own signature use the account one
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::ALT);
+    assert_eq!(
+        compose_state(&app).body_lines,
+        vec!["Hello", "", "-- ", "Me"]
+    );
+
+    // An edited signature is left alone
+    if let View::Compose(ref mut state) = app.view {
+        state.body_lines[3] = "Me, personally".to_string();
+    }
+    app.handle_key(KeyCode::Char('f'), KeyModifiers::ALT);
+    assert_eq!(compose_state(&app).from, SENDER);
+    assert_eq!(
+        compose_state(&app).body_lines,
+        vec!["Hello", "", "-- ", "Me, personally"]
+    );
+}
+
+#[test]
+fn reply_starts_with_identity_signature() {
+    let (imap, smtp) = mock_clients();
+    let mut email = dated_email(1, "Carol <carol@example.com>", "Help", "2025-01-01", true);
+    email.to = vec!["support@example.com".to_string()];
+    let mut app = App::new(vec![email], imap, smtp, SENDER.to_string(), None);
+    app.signature = Some("Me".to_string());
+    app.identities = vec![ALIAS.to_string()];
+    app.identity_signatures = HashMap::from([(ALIAS.to_string(), "Example Support".to_string())]);
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    assert_eq!(
+        compose_state(&app).body_lines,
+        vec!["", "", "-- ", "Example Support"]
+    );
+}
+
+fn inline_reply(posting: Posting, body_text: &str) -> App<MockImapClient, MockSmtpClient> {
+    let (imap, smtp) = mock_clients_with_body(body_text, None);
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.signature = Some("Me".to_string());
+    app.posting = posting;
+    app.inline_quote = true;
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    app
+}
+
+#[test]
+fn inline_quote_top_posting_keeps_quote_above_signature() {
+    let app = inline_reply(Posting::Top, "Hello\n> earlier");
+    let state = compose_state(&app);
+    assert!(state.quoted_text.is_empty());
+    assert_eq!(state.body_lines[..2], ["", ""]);
+    assert!(state.body_lines[2].ends_with("wrote:"));
+    assert_eq!(
+        state.body_lines[3..],
+        ["> Hello", ">> earlier", "", "-- ", "Me"]
+    );
+    assert_eq!(state.cursor_row, 0);
+}
+
+#[test]
+fn alt_q_reflows_top_posted_quote() {
+    let long = "word ".repeat(20);
+    let mut app = inline_reply(Posting::Top, &long);
+    if let View::Compose(ref mut state) = app.view {
+        state.cursor_row = 3;
+    }
+    app.handle_key(KeyCode::Char('q'), KeyModifiers::ALT);
+
+    let state = compose_state(&app);
+    let reflowed = quote::reflow(&[format!("> {long}")], quote::WIDTH);
+    assert!(reflowed.len() > 1);
+    assert_eq!(state.body_lines[3..3 + reflowed.len()], reflowed[..]);
+    assert_eq!(state.body_lines[3 + reflowed.len()..], ["", "-- ", "Me"]);
+}
+
+#[test]
+fn inline_quote_bottom_posting_starts_below_quote() {
+    let app = inline_reply(Posting::Bottom, "Hello");
+    let state = compose_state(&app);
+    assert!(state.body_lines[0].ends_with("wrote:"));
+    assert_eq!(state.body_lines[1..], ["> Hello", "", "", "", "-- ", "Me"]);
+    assert_eq!((state.cursor_row, state.cursor_col), (3, 0));
+}
+
+#[test]
+fn alt_q_reflows_quoted_paragraph_under_cursor() {
+    let long = "word ".repeat(20);
+    let mut app = inline_reply(Posting::Bottom, &format!("> {long}\n> more\n\nnext"));
+    if let View::Compose(ref mut state) = app.view {
+        state.cursor_row = 2;
+    }
+    app.handle_key(KeyCode::Char('q'), KeyModifiers::ALT);
+
+    let state = compose_state(&app);
+    let reflowed = quote::reflow(&[format!(">> {long}"), ">> more".to_string()], quote::WIDTH);
+    assert_eq!(state.body_lines[1..3], reflowed[..]);
+    assert_eq!(state.body_lines[3], ">");
+    assert_eq!(state.body_lines[4], "> next");
+    assert_eq!(state.cursor_row, 2);
+}
+
+#[test]
+fn alt_q_leaves_signature_alone() {
+    let mut app = inline_reply(Posting::Bottom, "Hello");
+    let before = compose_state(&app).body_lines.clone();
+    if let View::Compose(ref mut state) = app.view {
+        state.cursor_row = before.len() - 1;
+    }
+    app.handle_key(KeyCode::Char('q'), KeyModifiers::ALT);
+    assert_eq!(compose_state(&app).body_lines, before);
+}
+
+#[test]
+fn alt_m_toggles_markdown_and_alt_p_previews() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send()
+        .withf(|email| email.markdown && email.body == "**hi**")
+        .returning(|_| Ok(vec![]));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    assert!(!compose_state(&app).markdown);
+
+    // Preview needs Markdown mode
+    app.handle_key(KeyCode::Char('p'), KeyModifiers::ALT);
+    assert!(!compose_state(&app).preview);
+
+    app.handle_key(KeyCode::Char('m'), KeyModifiers::ALT);
+    type_text(&mut app, "**hi**");
+    app.handle_key(KeyCode::Char('p'), KeyModifiers::ALT);
+    assert!(compose_state(&app).preview);
+
+    // The preview is read-only
+    type_text(&mut app, "x");
+    assert_eq!(compose_state(&app).body_lines, vec!["**hi**"]);
+
+    if let View::Compose(ref mut state) = app.view {
+        state.subject = "Hi".to_string();
+        state.to = "carol@example.com".to_string();
+        state.step = ComposeStep::Bcc;
+    }
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    assert!(matches!(app.view, View::Inbox));
+}
+
+#[test]
+fn markdown_default_comes_from_app() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.markdown = true;
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    assert!(compose_state(&app).markdown);
+}
+
+#[cfg(feature = "pgp")]
+#[test]
+fn alt_g_and_alt_e_toggle_pgp_for_the_message() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send()
+        .withf(|email| !email.sign && email.encrypt)
+        .returning(|_| Ok(vec![]));
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.sign = true;
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    assert!(compose_state(&app).sign);
+
+    app.handle_key(KeyCode::Char('g'), KeyModifiers::ALT);
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::ALT);
+    let state = compose_state(&app);
+    assert!(!state.sign && state.encrypt);
+
+    if let View::Compose(ref mut state) = app.view {
+        state.subject = "Hi".to_string();
+        state.to = "carol@example.com".to_string();
+        state.step = ComposeStep::Bcc;
+    }
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    assert!(matches!(app.view, View::Inbox));
+}
+
+#[test]
+fn tick_tracks_connection_state() {
+    let (mut imap, smtp) = mock_clients();
+    let retry_at = std::time::Instant::now() + Duration::from_secs(4);
+    let mut states = vec![
+        ConnectionState::Connected,
+        ConnectionState::Disconnected {
+            attempts: 1,
+            retry_at,
+        },
+    ];
+    imap.expect_check_connection()
+        .returning(move || states.pop().unwrap());
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+
+    app.tick();
+    assert!(matches!(
+        app.connection,
+        ConnectionState::Disconnected { attempts: 1, .. }
+    ));
+    assert_eq!(app.status_message, None);
+
+    app.tick();
+    assert_eq!(app.connection, ConnectionState::Connected);
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Reconnected to the IMAP server")
+    );
+}
+
+fn temp_outbox(name: &str) -> Outbox {
+    let dir = std::env::temp_dir().join(format!("thrum_test_app_outbox_{name}"));
+    std::fs::remove_dir_all(&dir).ok();
+    Outbox::new(dir)
+}
+
+fn queued(outbox: &Outbox, subject: &str, now: i64) -> outbox::Entry {
+    let draft = smtp::Email {
+        from: SENDER.to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: subject.to_string(),
+        body: "Hi\nthere".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    };
+    let outgoing = smtp::Outgoing {
+        from: SENDER.to_string(),
+        recipients: draft.to.clone(),
+        message: b"Subject: queued\r\n\r\nHi".to_vec(),
+    };
+    outbox
+        .queue(&outgoing, &draft, "connection refused", false, now)
+        .unwrap()
+}
+
+/// What sending fails with while the server cannot be reached, which is
+/// worth retrying.
+fn connection_refused() -> SmtpError {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    lettre::transport::smtp::client::SmtpConnection::connect(
+        ("127.0.0.1", port),
+        Some(std::time::Duration::from_secs(5)),
+        &lettre::transport::smtp::extension::ClientId::default(),
+        None,
+        None,
+    )
+    .err()
+    .unwrap()
+    .into()
+}
+
+#[test]
+fn undelivered_email_is_queued_in_outbox() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send().returning(|email| {
+        Err(SmtpError::Undelivered {
+            outgoing: Box::new(smtp::Outgoing {
+                from: email.from.clone(),
+                recipients: email.to.clone(),
+                message: b"queued".to_vec(),
+            }),
+            error: Box::new(connection_refused()),
+        })
+    });
+    let outbox = temp_outbox("undelivered");
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox.clone());
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    for _ in 0..4 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+
+    assert!(matches!(app.view, View::Inbox));
+    assert!(
+        app.status_message
+            .as_deref()
+            .unwrap()
+            .ends_with("queued in the outbox")
+    );
+    assert_eq!(app.queued, 1);
+    let entries = outbox.entries().unwrap();
+    assert_eq!(
+        entries[0].draft.in_reply_to.as_deref(),
+        Some("msg3@example.com")
+    );
+    assert_eq!(outbox.outgoing(&entries[0]).unwrap().message, b"queued");
+}
+
+#[test]
+fn rejected_certificate_is_shown_instead_of_queued() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send().returning(|email| {
+        Err(SmtpError::Undelivered {
+            outgoing: Box::new(smtp::Outgoing {
+                from: email.from.clone(),
+                recipients: email.to.clone(),
+                message: b"queued".to_vec(),
+            }),
+            error: Box::new(SmtpError::Certificate(
+                crate::tls::CertificateError::Missing,
+            )),
+        })
+    });
+    let outbox = temp_outbox("certificate");
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox.clone());
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    for _ in 0..4 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Send failed: certificate error: server sent no certificate")
+    );
+    assert!(outbox.entries().unwrap().is_empty());
+}
+
+#[test]
+fn tick_retries_due_outbox_entries() {
+    let (mut imap, mut smtp) = mock_clients();
+    imap.expect_check_connection()
+        .returning(|| ConnectionState::Connected);
+    let mut results = vec![Ok(()), Err(connection_refused())];
+    smtp.expect_resend()
+        .times(2)
+        .returning(move |_| results.pop().unwrap());
+    let outbox = temp_outbox("tick");
+    let now = chrono::Utc::now().timestamp();
+    queued(&outbox, "Due", now - 60);
+    queued(&outbox, "Later", now + 1000);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox.clone());
+
+    // Only the due entry is tried; the failure reschedules it.
+    app.tick();
+    app.finish_deliveries();
+    let entries = outbox.entries().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].attempts, 2);
+    assert!(entries[0].next_attempt.unwrap() > now);
+    assert_eq!(app.queued, 2);
+    assert_eq!(app.status_message, None);
+
+    app.flush_outbox(now + outbox::retry_delay(2));
+    app.finish_deliveries();
+    let entries = outbox.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].draft.subject, "Later");
+    assert_eq!(app.queued, 1);
+    assert_eq!(app.status_message.as_deref(), Some("Sent 1 queued message"));
+}
+
+#[test]
+fn outbox_view_edits_and_cancels_entries() {
+    let (imap, smtp) = mock_clients();
+    let outbox = temp_outbox("view");
+    let entry = queued(&outbox, "First", 100);
+    queued(&outbox, "Second", 200);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox.clone());
+    app.handle_key(KeyCode::Char('o'), KeyModifiers::NONE);
+    let View::Outbox(ref state) = app.view else {
+        panic!("expected outbox view");
+    };
+    assert_eq!(state.entries.len(), 2);
+    assert_eq!(state.table_state.selected(), Some(0));
+
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    assert_eq!(state.outbox_id.as_deref(), Some(entry.id.as_str()));
+    assert_eq!(state.to, "you@example.com");
+    assert_eq!(state.subject, "First");
+    assert_eq!(state.body_lines, vec!["Hi", "there"]);
+
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('o'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('j'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('d'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('n'), KeyModifiers::NONE);
+    assert_eq!(outbox.entries().unwrap().len(), 2);
+
+    app.handle_key(KeyCode::Char('d'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('y'), KeyModifiers::NONE);
+    let View::Outbox(ref state) = app.view else {
+        panic!("expected outbox view");
+    };
+    assert_eq!(state.entries, vec![entry]);
+    assert_eq!(state.table_state.selected(), Some(0));
+    assert_eq!(state.status_message.as_deref(), Some("Message cancelled"));
+    assert_eq!(app.queued, 1);
+
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    assert!(matches!(app.view, View::Inbox));
+}
+
+fn temp_schedule(name: &str) -> Schedule {
+    let dir = std::env::temp_dir().join(format!("thrum_test_app_schedule_{name}"));
+    std::fs::remove_dir_all(&dir).ok();
+    Schedule::new(dir)
+}
+
+fn scheduled(schedule: &Schedule, subject: &str, send_at: i64) -> schedule::Scheduled {
+    let draft = smtp::Email {
+        from: SENDER.to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: subject.to_string(),
+        body: "Good\nmorning".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    };
+    schedule.add(&draft, None, send_at).unwrap()
+}
+
+#[test]
+fn alt_l_schedules_message_for_later() {
+    let (imap, smtp) = mock_clients();
+    let schedule = temp_schedule("compose");
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+
+    // Only offered at the send step
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    assert_eq!(state.send_at, None);
+    for _ in 0..3 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+
+    for c in "soon".chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    assert!(
+        state
+            .status_message
+            .as_deref()
+            .unwrap()
+            .starts_with("Unrecognised time \"soon\"")
+    );
+
+    for _ in 0..4 {
+        app.handle_key(KeyCode::Backspace, KeyModifiers::NONE);
+    }
+    for c in "+2h".chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+
+    assert!(matches!(app.view, View::Inbox));
+    assert!(
+        app.status_message
+            .as_deref()
+            .unwrap()
+            .starts_with("Scheduled for ")
+    );
+    assert_eq!(app.scheduled, 1);
+    let entries = schedule.entries().unwrap();
+    let expected = chrono::Utc::now().timestamp() + 2 * 60 * 60;
+    assert!((expected - 5..=expected).contains(&entries[0].send_at));
+    assert_eq!(
+        entries[0].draft.in_reply_to.as_deref(),
+        Some("msg3@example.com")
+    );
+}
+
+#[test]
+fn scheduling_to_many_recipients_needs_confirmation() {
+    let (imap, smtp) = mock_clients();
+    let schedule = temp_schedule("confirm");
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.max_recipients = 2;
+    app.handle_key(KeyCode::Char('c'), KeyModifiers::NONE);
+    if let View::Compose(ref mut state) = app.view {
+        state.subject = "Hi".to_string();
+        state.to = "a@example.com, b@example.com, c@example.com".to_string();
+        state.step = ComposeStep::Bcc;
+    }
+    let schedule_in_two_hours = |app: &mut App<MockImapClient, MockSmtpClient>| {
+        app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+        for c in "+2h".chars() {
+            app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+    };
+
+    schedule_in_two_hours(&mut app);
+    let state = compose_state(&app);
+    assert!(state.confirm_send);
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Schedule to 3 recipients? (y/n)")
+    );
+
+    app.handle_key(KeyCode::Char('n'), KeyModifiers::NONE);
+    let state = compose_state(&app);
+    assert!(!state.confirm_send);
+    assert_eq!(state.send_at, None);
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Scheduling cancelled")
+    );
+    assert!(schedule.entries().unwrap().is_empty());
+
+    schedule_in_two_hours(&mut app);
+    app.handle_key(KeyCode::Char('y'), KeyModifiers::NONE);
+    assert!(matches!(app.view, View::Inbox));
+    assert_eq!(schedule.entries().unwrap().len(), 1);
+}
+
+#[test]
+fn esc_closes_send_later_prompt() {
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    for _ in 0..3 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    assert_eq!(state.send_at, None);
+    assert_eq!(state.step, ComposeStep::Bcc);
+}
+
+#[test]
+fn tick_sends_due_scheduled_messages() {
+    let (mut imap, mut smtp) = mock_clients();
+    imap.expect_check_connection()
+        .returning(|| ConnectionState::Connected);
+    let mut results = vec![
+        Err(SmtpError::Certificate(
+            crate::tls::CertificateError::Missing,
+        )),
+        Ok(b"sent".to_vec()),
+    ];
+    smtp.expect_send()
+        .times(2)
+        .returning(move |_| results.pop().unwrap());
+    let schedule = temp_schedule("tick");
+    let now = chrono::Utc::now().timestamp();
+    scheduled(&schedule, "Due", now - 10);
+    scheduled(&schedule, "Later", now + 1000);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+
+    app.tick();
+    app.finish_deliveries();
+    let entries = schedule.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].draft.subject, "Later");
+    assert_eq!(app.scheduled, 1);
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Sent 1 scheduled message")
+    );
+
+    // A failure is kept for the user to look at, not retried.
+    app.flush_schedule(now + 1000);
+    app.finish_deliveries();
+    app.flush_schedule(now + 2000);
+    app.finish_deliveries();
+    let entries = schedule.entries().unwrap();
+    assert_eq!(
+        entries[0].error.as_deref(),
+        Some("certificate error: server sent no certificate")
+    );
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Scheduled message not sent: certificate error: server sent no certificate")
+    );
+}
+
+#[test]
+fn scheduled_view_edits_and_sends_now() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_send()
+        .times(1)
+        .returning(|_| Ok(b"sent".to_vec()));
+    let schedule = temp_schedule("view");
+    let now = chrono::Utc::now().timestamp();
+    let entry = scheduled(&schedule, "Morning", now + 1000);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    let View::Scheduled(ref state) = app.view else {
+        panic!("expected scheduled view");
+    };
+    assert_eq!(state.entries, vec![entry.clone()]);
+
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+    let View::Compose(ref state) = app.view else {
+        panic!("expected compose view");
+    };
+    assert_eq!(state.scheduled_id.as_deref(), Some(entry.id.as_str()));
+    assert_eq!(state.subject, "Morning");
+    assert_eq!(state.body_lines, vec!["Good", "morning"]);
+
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('s'), KeyModifiers::NONE);
+    let View::Scheduled(ref state) = app.view else {
+        panic!("expected scheduled view");
+    };
+    assert!(state.entries.is_empty());
+    assert_eq!(state.table_state.selected(), None);
+    assert_eq!(state.status_message.as_deref(), Some("Message sent"));
+    assert_eq!(app.scheduled, 0);
+}
+
+#[test]
+fn scheduling_an_edited_message_replaces_it() {
+    let (imap, smtp) = mock_clients();
+    let schedule = temp_schedule("replace");
+    let now = chrono::Utc::now().timestamp();
+    let entry = scheduled(&schedule, "Morning", now + 1000);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+    // Body -> Subject -> To -> Cc -> Bcc
+    for _ in 0..4 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+    for c in "+1d".chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+
+    let entries = schedule.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_ne!(entries[0].id, entry.id);
+    assert!(entries[0].send_at >= now + 24 * 60 * 60);
+    assert_eq!(entries[0].draft.subject, "Morning");
+}
+
+#[test]
+fn encrypted_queued_message_is_not_edited() {
+    let (imap, smtp) = mock_clients();
+    let outbox = temp_outbox("encrypted");
+    let entry = queued(&outbox, "Secret", 100);
+    let mut draft = entry.draft.clone();
+    draft.encrypt = true;
+    let outgoing = outbox.outgoing(&entry).unwrap();
+    outbox.remove(&entry.id).unwrap();
+    outbox
+        .queue(&outgoing, &draft, "connection refused", false, 100)
+        .unwrap();
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox);
+    app.handle_key(KeyCode::Char('o'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+    let View::Outbox(ref state) = app.view else {
+        panic!("expected outbox view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Encrypted messages cannot be edited in the outbox")
+    );
+}
+
+#[test]
+fn queued_message_being_edited_is_not_retried() {
+    let (mut imap, mut smtp) = mock_clients();
+    imap.expect_check_connection()
+        .returning(|| ConnectionState::Connected);
+    smtp.expect_resend().times(1).returning(|_| Ok(()));
+    let outbox = temp_outbox("editing");
+    let now = chrono::Utc::now().timestamp();
+    queued(&outbox, "Due", now - 60);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox.clone());
+    app.handle_key(KeyCode::Char('o'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+
+    app.tick();
+    assert_eq!(outbox.entries().unwrap().len(), 1);
+    assert!(matches!(app.view, View::Compose(_)));
+
+    // Once the composer is left without sending, retries resume.
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    app.tick();
+    app.finish_deliveries();
+    assert!(outbox.entries().unwrap().is_empty());
+}
+
+#[test]
+fn queued_message_is_retried_in_the_background() {
+    let (imap, mut smtp) = mock_clients();
+    let (release, released) = std::sync::mpsc::channel::<()>();
+    smtp.expect_resend().times(1).returning(move |_| {
+        released.recv().unwrap();
+        Ok(())
+    });
+    let outbox = temp_outbox("background");
+    let now = chrono::Utc::now().timestamp();
+    queued(&outbox, "Due", now - 60);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.outbox = Some(outbox.clone());
+
+    // Returns while the server has yet to answer
+    app.flush_outbox(now);
+    app.handle_key(KeyCode::Char('o'), KeyModifiers::NONE);
+    for key in ['e', 'r'] {
+        app.handle_key(KeyCode::Char(key), KeyModifiers::NONE);
+        let View::Outbox(ref state) = app.view else {
+            panic!("expected outbox view");
+        };
+        assert_eq!(
+            state.status_message.as_deref(),
+            Some("This message is being sent")
+        );
+    }
+    assert_eq!(outbox.entries().unwrap().len(), 1);
+
+    release.send(()).unwrap();
+    app.finish_deliveries();
+    assert!(outbox.entries().unwrap().is_empty());
+    let View::Outbox(ref state) = app.view else {
+        panic!("expected outbox view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Sent 1 queued message")
+    );
+}
+
+#[test]
+fn scheduled_message_being_edited_is_not_sent() {
+    let (mut imap, mut smtp) = mock_clients();
+    imap.expect_check_connection()
+        .returning(|| ConnectionState::Connected);
+    smtp.expect_send()
+        .times(1)
+        .returning(|_| Ok(b"sent".to_vec()));
+    let schedule = temp_schedule("editing");
+    let now = chrono::Utc::now().timestamp();
+    scheduled(&schedule, "Morning", now + 1000);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+
+    // Falls due while open in the composer
+    app.flush_schedule(now + 2000);
+    app.finish_deliveries();
+    assert_eq!(schedule.entries().unwrap().len(), 1);
+    assert!(matches!(app.view, View::Compose(_)));
+
+    app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+    app.flush_schedule(now + 2000);
+    app.finish_deliveries();
+    assert!(schedule.entries().unwrap().is_empty());
+}
+
+#[test]
+fn scheduled_message_is_sent_in_the_background() {
+    let (imap, mut smtp) = mock_clients();
+    let (release, released) = std::sync::mpsc::channel::<()>();
+    smtp.expect_send().times(1).returning(move |_| {
+        released.recv().unwrap();
+        Ok(b"sent".to_vec())
+    });
+    let schedule = temp_schedule("background");
+    let now = chrono::Utc::now().timestamp();
+    scheduled(&schedule, "Morning", now - 10);
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+
+    // Returns while the server has yet to answer
+    app.flush_schedule(now);
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    for key in ['e', 's'] {
+        app.handle_key(KeyCode::Char(key), KeyModifiers::NONE);
+        let View::Scheduled(ref state) = app.view else {
+            panic!("expected scheduled view");
+        };
+        assert_eq!(
+            state.status_message.as_deref(),
+            Some("This message is being sent")
+        );
+    }
+    assert_eq!(schedule.entries().unwrap().len(), 1);
+
+    release.send(()).unwrap();
+    app.finish_deliveries();
+    assert!(schedule.entries().unwrap().is_empty());
+    let View::Scheduled(ref state) = app.view else {
+        panic!("expected scheduled view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Sent 1 scheduled message")
+    );
+}
+
+#[test]
+fn scheduled_message_is_kept_in_drafts_until_sent() {
+    let (mut imap, mut smtp) = mock_clients();
+    smtp.expect_build().returning(|_| {
+        Ok(smtp::Outgoing {
+            from: SENDER.to_string(),
+            recipients: vec!["you@example.com".to_string()],
+            message: b"Message-ID: <draft@example.com>\r\nSubject: Re\r\n\r\nhi".to_vec(),
+        })
+    });
+    smtp.expect_send()
+        .times(1)
+        .returning(|_| Ok(b"sent".to_vec()));
+    imap.expect_append_draft()
+        .withf(|folder, content| folder == "Drafts" && content.starts_with(b"Message-ID"))
+        .times(1)
+        .returning(|_, _| Ok(()));
+    imap.expect_remove_message()
+        .withf(|folder, message_id| folder == "Drafts" && message_id == "<draft@example.com>")
+        .times(1)
+        .returning(|_, _| Ok(()));
+    let schedule = temp_schedule("drafts");
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.drafts_folder = Some("Drafts".to_string());
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    for _ in 0..3 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+    for c in "+1h".chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+
+    let entries = schedule.entries().unwrap();
+    assert_eq!(
+        entries[0].draft_copy.as_deref(),
+        Some("<draft@example.com>")
+    );
+
+    app.flush_schedule(entries[0].send_at);
+    app.finish_deliveries();
+    assert!(schedule.entries().unwrap().is_empty());
+}
+
+#[test]
+fn scheduling_reports_failure_to_save_draft() {
+    let (mut imap, mut smtp) = mock_clients();
+    smtp.expect_build().returning(|_| {
+        Ok(smtp::Outgoing {
+            from: SENDER.to_string(),
+            recipients: vec!["you@example.com".to_string()],
+            message: b"Subject: Re\r\n\r\nhi".to_vec(),
+        })
+    });
+    imap.expect_append_draft()
+        .returning(|_, _| Err(crate::imap::ImapError::Disconnected));
+    let schedule = temp_schedule("drafts_failed");
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.drafts_folder = Some("Drafts".to_string());
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    for _ in 0..3 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+    for c in "+1h".chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+
+    // Still scheduled, without a drafts copy
+    assert!(matches!(app.view, View::Inbox));
+    assert!(
+        app.status_message
+            .as_deref()
+            .unwrap()
+            .contains("not saved to drafts")
+    );
+    assert_eq!(schedule.entries().unwrap()[0].draft_copy, None);
+}
+
+#[test]
+fn encrypted_scheduled_message_is_only_kept_encrypted() {
+    let (imap, mut smtp) = mock_clients();
+    smtp.expect_build().times(1).returning(|_| {
+        Ok(smtp::Outgoing {
+            from: SENDER.to_string(),
+            recipients: vec!["you@example.com".to_string()],
+            message: b"Subject: Re\r\n\r\n-----BEGIN PGP MESSAGE-----".to_vec(),
+        })
+    });
+    smtp.expect_send().never();
+    smtp.expect_resend()
+        .withf(|outgoing| outgoing.message.ends_with(b"-----BEGIN PGP MESSAGE-----"))
+        .times(1)
+        .returning(|_| Ok(()));
+    let schedule = temp_schedule("encrypted");
+
+    let mut app = App::new(sample_emails(), imap, smtp, SENDER.to_string(), None);
+    app.schedule = Some(schedule.clone());
+    app.encrypt = true;
+    app.handle_key(KeyCode::Char('r'), KeyModifiers::NONE);
+    for _ in 0..3 {
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    }
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::ALT);
+    for c in "+1h".chars() {
+        app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+
+    let entries = schedule.entries().unwrap();
+    assert!(entries[0].draft.encrypt);
+    assert!(entries[0].draft.body.is_empty());
+
+    app.handle_key(KeyCode::Char('l'), KeyModifiers::NONE);
+    app.handle_key(KeyCode::Char('e'), KeyModifiers::NONE);
+    let View::Scheduled(ref state) = app.view else {
+        panic!("expected scheduled view");
+    };
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Encrypted messages cannot be edited once scheduled")
+    );
+
+    app.flush_schedule(entries[0].send_at);
+    app.finish_deliveries();
+    assert!(schedule.entries().unwrap().is_empty());
+}
+
+#[test]
+fn failed_folder_keeps_pages_of_other_folders() {
+    let mut imap = MockImapClient::new();
+    imap.expect_fetch_older()
+        .withf(|folder, _, _| folder == "INBOX")
+        .returning(|_, _, _| Ok(numbered_emails(99..=100)));
+    imap.expect_fetch_older()
+        .withf(|folder, _, _| folder == "Sent")
+        .returning(|_, _, _| Err(crate::imap::ImapError::Disconnected));
+    let mut emails = numbered_emails(101..=103);
+    let mut sent = dated_email(
+        50,
+        SENDER,
+        "Outgoing",
+        "Wed, 15 Jan 2025 10:00:00 +0000",
+        true,
+    );
+    sent.folder = "Sent".to_string();
+    emails.push(sent);
+    let mut app = App::new(
+        emails,
+        imap,
+        MockSmtpClient::new(),
+        SENDER.to_string(),
+        None,
+    );
+    app.page_size = 2;
+
+    app.load_older();
+    let inbox: Vec<u32> = app
+        .emails
+        .iter()
+        .filter(|e| e.folder == "INBOX")
+        .map(|e| e.uid)
+        .collect();
+    assert_eq!(inbox.len(), 5);
+    assert!(inbox.contains(&99) && inbox.contains(&100));
+    assert!(
+        app.status_message
+            .as_deref()
+            .unwrap()
+            .starts_with("Failed to load messages")
+    );
+}
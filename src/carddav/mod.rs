@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use base64::Engine;
+use quick_xml::events::Event;
+
+use crate::config::CardDavConfig;
+
+#[cfg(test)]
+mod test;
+
+/// Asks for every card in the collection together with its data.
+const ADDRESSBOOK_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<C:addressbook-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+  <D:prop>
+    <D:getetag/>
+    <C:address-data/>
+  </D:prop>
+</C:addressbook-query>
+"#;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum CardDavError {
+    #[error("CardDAV request failed: {0}")]
+    Request(#[from] Box<ureq::Error>),
+    #[error("failed to read CardDAV response: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid CardDAV response: {0}")]
+    Xml(#[from] quick_xml::Error),
+}
+
+pub struct CardDavClient {
+    agent: ureq::Agent,
+    url: String,
+    authorization: String,
+}
+
+impl CardDavClient {
+    pub fn new(config: &CardDavConfig) -> Self {
+        let credentials = format!("{}:{}", config.user, config.pass);
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            url: config.url.clone(),
+            authorization: format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            ),
+        }
+    }
+
+    /// Download every vCard in the configured collection.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), fields(url = %self.url), err)
+    )]
+    pub fn fetch_vcards(&self) -> Result<Vec<String>, CardDavError> {
+        let response = self
+            .agent
+            .request("REPORT", &self.url)
+            .set("Authorization", &self.authorization)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(ADDRESSBOOK_QUERY)
+            .map_err(Box::new)?;
+        let body = response.into_string()?;
+
+        let cards = parse_multistatus(&body)?;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(count = cards.len(), "fetched vCards");
+        Ok(cards)
+    }
+}
+
+/// The `address-data` of each response in a WebDAV multistatus body.
+pub fn parse_multistatus(xml: &str) -> Result<Vec<String>, CardDavError> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut cards = Vec::new();
+    let mut current: Option<String> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"address-data" => {
+                current = Some(String::new());
+            }
+            Event::Text(text) => {
+                if let Some(ref mut card) = current {
+                    card.push_str(&text.unescape()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(ref mut card) = current {
+                    card.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"address-data" => {
+                cards.extend(current.take().filter(|card| !card.trim().is_empty()));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(cards)
+}
@@ -0,0 +1,171 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+use super::*;
+
+const MULTISTATUS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+  <d:response>
+    <d:href>/addressbooks/me/team/alice.vcf</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:getetag>"1"</d:getetag>
+        <card:address-data>BEGIN:VCARD&#13;
+VERSION:3.0&#13;
+FN:Alice &amp; Co&#13;
+EMAIL:alice@example.com&#13;
+END:VCARD&#13;
+</card:address-data>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/addressbooks/me/team/bob.vcf</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:getetag>"2"</d:getetag>
+        <card:address-data><![CDATA[BEGIN:VCARD
+VERSION:4.0
+FN:Bob <Builder>
+EMAIL:bob@example.com
+END:VCARD
+]]></card:address-data>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/addressbooks/me/team/</d:href>
+    <d:propstat>
+      <d:prop><card:address-data/></d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>
+"#;
+
+/// A request as seen by the stand-in server.
+struct Request {
+    line: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Serve one HTTP request on a local port with the given status and body.
+fn serve_once(status: &'static str, body: &'static str) -> (String, JoinHandle<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/addressbooks/me/team/",
+        listener.local_addr().unwrap()
+    );
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut headers = Vec::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let (name, value) = header.split_once(':').unwrap();
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+        let request = Request {
+            line: line.trim_end().to_string(),
+            headers,
+            body: String::new(),
+        };
+        let length: usize = request
+            .header("content-length")
+            .map_or(0, |l| l.parse().unwrap());
+        let mut received = vec![0; length];
+        reader.read_exact(&mut received).unwrap();
+
+        let mut stream = reader.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: application/xml; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+
+        Request {
+            body: String::from_utf8(received).unwrap(),
+            ..request
+        }
+    });
+    (url, handle)
+}
+
+fn client(url: String) -> CardDavClient {
+    CardDavClient::new(&CardDavConfig {
+        url,
+        user: "me".to_string(),
+        pass: "hunter2".to_string(),
+    })
+}
+
+#[test]
+fn parse_multistatus_collects_address_data() {
+    let cards = parse_multistatus(MULTISTATUS).unwrap();
+    assert_eq!(cards.len(), 2);
+    assert!(cards[0].contains("FN:Alice & Co\r\n"));
+    assert!(cards[1].contains("FN:Bob <Builder>\n"));
+}
+
+#[test]
+fn parse_multistatus_rejects_malformed_xml() {
+    assert!(matches!(
+        parse_multistatus("<d:multistatus><d:response></d:multistatus>"),
+        Err(CardDavError::Xml(_))
+    ));
+}
+
+#[test]
+fn fetch_vcards_sends_addressbook_query() {
+    let (url, server) = serve_once("207 Multi-Status", MULTISTATUS);
+    let cards = client(url).fetch_vcards().unwrap();
+    let request = server.join().unwrap();
+
+    assert_eq!(request.line, "REPORT /addressbooks/me/team/ HTTP/1.1");
+    assert_eq!(request.header("depth"), Some("1"));
+    // base64("me:hunter2")
+    assert_eq!(
+        request.header("authorization"),
+        Some("Basic bWU6aHVudGVyMg==")
+    );
+    assert!(request.body.contains("<C:addressbook-query"));
+    assert!(request.body.contains("<C:address-data/>"));
+
+    assert_eq!(cards.len(), 2);
+    assert!(cards[1].contains("EMAIL:bob@example.com"));
+}
+
+#[test]
+fn fetch_vcards_reports_http_errors() {
+    let (url, server) = serve_once("401 Unauthorized", "");
+    let result = client(url).fetch_vcards();
+    server.join().unwrap();
+
+    match result {
+        Err(CardDavError::Request(e)) => assert!(matches!(*e, ureq::Error::Status(401, _))),
+        other => panic!("expected 401, got {other:?}"),
+    }
+}
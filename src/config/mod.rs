@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use serde::Deserialize;
@@ -11,22 +11,186 @@ pub struct Config {
     pub imap: ImapConfig,
     pub smtp: SmtpConfig,
     pub sender: SenderConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub compose: ComposeConfig,
+    /// Viewers for message parts, tried in order.
+    #[serde(default)]
+    pub handlers: Vec<HandlerConfig>,
+    /// Remote address book synced into the local contacts at startup.
+    pub carddav: Option<CardDavConfig>,
+    #[serde(default)]
+    pub pgp: PgpConfig,
+    #[serde(default)]
+    pub smime: SmimeConfig,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct ImapConfig {
     pub host: String,
     pub port: u16,
+    /// Defaults to STARTTLS on port 143 and implicit TLS elsewhere.
+    pub tls: Option<TlsMode>,
+    /// PEM file with CA certificates to trust besides the system store.
+    pub ca_file: Option<PathBuf>,
+    /// Accept any certificate, leaving the connection open to interception.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// SHA-256 fingerprint of the server certificate; when set, only that
+    /// certificate is accepted.
+    pub fingerprint: Option<String>,
     pub user: String,
+    /// Password; not needed when `auth` is `oauth2`.
+    #[serde(default)]
     pub pass: String,
+    #[serde(default)]
+    pub auth: AuthMode,
+    pub oauth2: Option<OAuth2Config>,
     pub folders: Vec<String>,
     pub sent_folder: Option<String>,
+    /// Scheduled messages are also saved here until they are sent.
+    pub drafts_folder: Option<String>,
+    /// Number of messages fetched per folder at startup and per page afterwards.
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+fn default_page_size() -> u32 {
+    200
+}
+
+impl ImapConfig {
+    pub fn tls_mode(&self) -> TlsMode {
+        self.tls.unwrap_or(match self.port {
+            143 => TlsMode::Starttls,
+            _ => TlsMode::Implicit,
+        })
+    }
+}
+
+/// An external viewer for a MIME type, in the spirit of a mailcap entry.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct HandlerConfig {
+    /// Type this handler applies to; `image/*` or `*/*` match any subtype.
+    pub mime: String,
+    /// Command to run; `%s` is replaced by the file path, otherwise the path
+    /// is appended.
+    pub command: String,
+    /// Run in the terminal, suspending the UI until the command exits.
+    #[serde(default)]
+    pub blocking: bool,
+}
+
+impl HandlerConfig {
+    pub fn matches(&self, content_type: &str) -> bool {
+        let (want_type, want_sub) = self.mime.split_once('/').unwrap_or((&self.mime, "*"));
+        let (have_type, have_sub) = content_type.split_once('/').unwrap_or((content_type, ""));
+        (want_type == "*" || want_type.eq_ignore_ascii_case(have_type))
+            && (want_sub == "*" || want_sub.eq_ignore_ascii_case(have_sub))
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct SmtpConfig {
     pub host: String,
     pub port: u16,
+    /// Defaults to implicit TLS on port 465 and STARTTLS elsewhere.
+    pub tls: Option<TlsMode>,
+    /// PEM file with CA certificates to trust besides the system store.
+    pub ca_file: Option<PathBuf>,
+    /// Accept any certificate, leaving the connection open to interception.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// SHA-256 fingerprint of the server certificate; when set, only that
+    /// certificate is accepted.
+    pub fingerprint: Option<String>,
+    pub user: String,
+    /// Password; not needed when `auth` is `oauth2`.
+    #[serde(default)]
+    pub pass: String,
+    #[serde(default)]
+    pub auth: AuthMode,
+    pub oauth2: Option<OAuth2Config>,
+}
+
+impl SmtpConfig {
+    pub fn tls_mode(&self) -> TlsMode {
+        self.tls.unwrap_or(match self.port {
+            465 => TlsMode::Implicit,
+            _ => TlsMode::Starttls,
+        })
+    }
+}
+
+/// How the connection to a server is encrypted.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    /// TLS from the first byte (IMAP port 993, SMTP port 465).
+    Implicit,
+    /// Plain text upgraded with STARTTLS before logging in (IMAP port 143,
+    /// SMTP submission port 587).
+    Starttls,
+    /// No encryption; only allowed for servers on this machine.
+    None,
+}
+
+/// Whether `host` names this machine, the only place plain text is sent to.
+pub fn is_local(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Whether a password or token may be sent to `url`: over `https`, or over
+/// plain `http` to this machine.
+fn is_secure_url(url: &str) -> bool {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return false;
+    };
+    if scheme.eq_ignore_ascii_case("https") {
+        return true;
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    scheme.eq_ignore_ascii_case("http") && is_local(host)
+}
+
+/// How to log in to a server.
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    #[default]
+    Password,
+    /// SASL XOAUTH2 with an access token from `oauth2`.
+    Oauth2,
+}
+
+/// Where OAuth2 access tokens come from: a command that prints one, or a
+/// refresh token exchanged at the provider's token endpoint.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct OAuth2Config {
+    /// Shell command printing a current access token, run on every login.
+    pub token_command: Option<String>,
+    /// Token endpoint, e.g. `https://oauth2.googleapis.com/token`.
+    pub token_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct CardDavConfig {
+    /// URL of the address book collection, e.g.
+    /// `https://dav.example.com/addressbooks/me/team/`.
+    pub url: String,
     pub user: String,
     pub pass: String,
 }
@@ -35,13 +199,189 @@ pub struct SmtpConfig {
 pub struct SenderConfig {
     pub from: String,
     pub name: Option<String>,
+    /// Signature text, or a command in backticks whose output is used.
+    pub signature: Option<String>,
+    /// File holding the signature; takes precedence over `signature`.
+    pub signature_file: Option<PathBuf>,
+    /// Other addresses (aliases) that mail can be sent from.
+    #[serde(default)]
+    pub identities: Vec<IdentityConfig>,
 }
 
 impl SenderConfig {
     pub fn formatted_from(&self) -> String {
-        match &self.name {
-            Some(name) => format!("{name} <{}>", self.from),
-            None => self.from.clone(),
+        format_from(self.name.as_deref(), &self.from)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct IdentityConfig {
+    pub from: String,
+    pub name: Option<String>,
+    /// Used instead of the `[sender]` signature when sending as this
+    /// identity, in the same forms.
+    pub signature: Option<String>,
+    pub signature_file: Option<PathBuf>,
+}
+
+impl IdentityConfig {
+    pub fn formatted_from(&self) -> String {
+        format_from(self.name.as_deref(), &self.from)
+    }
+}
+
+fn format_from(name: Option<&str>, from: &str) -> String {
+    match name {
+        Some(name) => format!("{name} <{from}>"),
+        None => from.to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UiConfig {
+    pub sort: SortMode,
+    pub group_by_date: bool,
+    /// Show which folder each conversation's newest message came from.
+    pub show_folder: bool,
+    /// Upper bound on message summaries kept in memory before conversations
+    /// far from the selection are evicted.
+    pub max_loaded: usize,
+    /// Command used to open links; `%s` is replaced by the link, otherwise
+    /// the link is appended.
+    pub opener: String,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            sort: SortMode::Date,
+            group_by_date: true,
+            show_folder: false,
+            max_loaded: 2000,
+            opener: "xdg-open".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ComposeConfig {
+    /// Ask before sending to more recipients than this.
+    pub max_recipients: usize,
+    /// Where a reply goes relative to the quoted message.
+    pub posting: Posting,
+    /// Put the quoted message into the editor so replies can be
+    /// interleaved with it.
+    pub inline_quote: bool,
+    /// Start messages in Markdown mode, sending an HTML rendition too.
+    pub markdown: bool,
+}
+
+impl Default for ComposeConfig {
+    fn default() -> Self {
+        Self {
+            max_recipients: 10,
+            posting: Posting::Top,
+            inline_quote: false,
+            markdown: false,
+        }
+    }
+}
+
+/// Placement of the new text in a reply.
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Posting {
+    /// New text and signature above the quote.
+    #[default]
+    Top,
+    /// Quote first, new text and signature below it.
+    Bottom,
+}
+
+/// PGP/MIME settings, used when built with the `pgp` feature.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+#[cfg_attr(not(feature = "pgp"), allow(dead_code))]
+pub struct PgpConfig {
+    /// The `gpg` binary to run.
+    pub gpg: String,
+    /// Keyring directory, instead of gpg's default.
+    pub homedir: Option<PathBuf>,
+    /// Sign new messages unless turned off in compose.
+    pub sign: bool,
+    /// Encrypt new messages unless turned off in compose.
+    pub encrypt: bool,
+}
+
+impl Default for PgpConfig {
+    fn default() -> Self {
+        Self {
+            gpg: "gpg".to_string(),
+            homedir: None,
+            sign: false,
+            encrypt: false,
+        }
+    }
+}
+
+/// S/MIME settings, used when built with the `smime` feature.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+#[cfg_attr(not(feature = "smime"), allow(dead_code))]
+pub struct SmimeConfig {
+    /// The `openssl` binary to run.
+    pub openssl: String,
+    /// PEM certificate that mail is encrypted to and signed with.
+    pub cert: Option<PathBuf>,
+    /// PEM private key belonging to `cert`.
+    pub key: Option<PathBuf>,
+    /// PEM bundle of trusted CAs, instead of the system store.
+    pub ca_file: Option<PathBuf>,
+    /// Sign outgoing messages with `cert`.
+    pub sign: bool,
+}
+
+impl Default for SmimeConfig {
+    fn default() -> Self {
+        Self {
+            openssl: "openssl".to_string(),
+            cert: None,
+            key: None,
+            ca_file: None,
+            sign: false,
+        }
+    }
+}
+
+/// Ordering applied to conversations in the inbox.
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    #[default]
+    Date,
+    Sender,
+    Subject,
+    UnreadFirst,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Date => SortMode::Sender,
+            SortMode::Sender => SortMode::Subject,
+            SortMode::Subject => SortMode::UnreadFirst,
+            SortMode::UnreadFirst => SortMode::Date,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Date => "date",
+            SortMode::Sender => "sender",
+            SortMode::Subject => "subject",
+            SortMode::UnreadFirst => "unread first",
         }
     }
 }
@@ -54,8 +394,21 @@ pub enum ConfigError {
     Parse(#[from] toml::de::Error),
     #[error("failed to execute password command: {0}")]
     Command(String),
+    #[error("failed to read signature file {path}: {source}")]
+    Signature {
+        path: PathBuf,
+        source: std::io::Error,
+    },
     #[error("could not determine config directory")]
     NoConfigDir,
+    #[error("tls = \"none\" is only allowed for localhost, not {0}")]
+    Plaintext(String),
+    #[error("page_size must be at least 1")]
+    PageSize,
+    #[error("CardDAV url must use https unless the server is on localhost, not {0}")]
+    InsecureUrl(String),
+    #[error("OAuth2 token_url must use https unless the server is on localhost, not {0}")]
+    InsecureTokenUrl(String),
 }
 
 fn default_config_path() -> Result<PathBuf, ConfigError> {
@@ -95,6 +448,26 @@ fn expand_command(value: &str) -> Result<String, ConfigError> {
     }
 }
 
+/// The signature text from `signature_file` or `signature`, running it if
+/// it is a command. Trailing blank lines are dropped.
+fn load_signature(
+    signature: Option<&str>,
+    signature_file: Option<&Path>,
+) -> Result<Option<String>, ConfigError> {
+    let text = match (signature_file, signature) {
+        (Some(path), _) => {
+            std::fs::read_to_string(path).map_err(|source| ConfigError::Signature {
+                path: path.to_path_buf(),
+                source,
+            })?
+        }
+        (None, Some(signature)) => expand_command(signature)?,
+        (None, None) => return Ok(None),
+    };
+    let text = text.trim_end();
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
+
 #[cfg_attr(
     feature = "tracing",
     tracing::instrument(level = tracing::Level::TRACE, err)
@@ -115,6 +488,37 @@ pub fn load(path: Option<PathBuf>) -> Result<Config, ConfigError> {
     #[cfg(feature = "tracing")]
     tracing::trace!("config parsed");
 
+    for (host, tls) in [
+        (&config.imap.host, config.imap.tls_mode()),
+        (&config.smtp.host, config.smtp.tls_mode()),
+    ] {
+        if tls == TlsMode::None && !is_local(host) {
+            return Err(ConfigError::Plaintext(host.clone()));
+        }
+    }
+
+    if config.imap.page_size == 0 {
+        return Err(ConfigError::PageSize);
+    }
+
+    if let Some(ref carddav) = config.carddav
+        && !is_secure_url(&carddav.url)
+    {
+        return Err(ConfigError::InsecureUrl(carddav.url.clone()));
+    }
+
+    // The token endpoint is sent the refresh token and client secret
+    for oauth2 in [&config.imap.oauth2, &config.smtp.oauth2]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(ref url) = oauth2.token_url
+            && !is_secure_url(url)
+        {
+            return Err(ConfigError::InsecureTokenUrl(url.clone()));
+        }
+    }
+
     #[cfg(feature = "tracing")]
     tracing::trace!("expanding IMAP password");
     config.imap.pass = expand_command(&config.imap.pass)?;
@@ -127,5 +531,34 @@ pub fn load(path: Option<PathBuf>) -> Result<Config, ConfigError> {
     #[cfg(feature = "tracing")]
     tracing::trace!("SMTP password expanded");
 
+    for oauth2 in [&mut config.imap.oauth2, &mut config.smtp.oauth2]
+        .into_iter()
+        .flatten()
+    {
+        for secret in [&mut oauth2.client_secret, &mut oauth2.refresh_token]
+            .into_iter()
+            .flatten()
+        {
+            *secret = expand_command(secret)?;
+        }
+    }
+
+    config.sender.signature = load_signature(
+        config.sender.signature.as_deref(),
+        config.sender.signature_file.as_deref(),
+    )?;
+    for identity in &mut config.sender.identities {
+        identity.signature = load_signature(
+            identity.signature.as_deref(),
+            identity.signature_file.as_deref(),
+        )?;
+    }
+
+    if let Some(ref mut carddav) = config.carddav {
+        carddav.pass = expand_command(&carddav.pass)?;
+        #[cfg(feature = "tracing")]
+        tracing::trace!("CardDAV password expanded");
+    }
+
     Ok(config)
 }
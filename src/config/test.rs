@@ -28,6 +28,7 @@ name = "Me"
     assert_eq!(config.imap.pass, "hunter2");
     assert_eq!(config.imap.folders, vec!["INBOX".to_string()]);
     assert_eq!(config.imap.sent_folder, None);
+    assert_eq!(config.imap.page_size, 200);
     assert_eq!(config.smtp.host, "smtp.example.com");
     assert_eq!(config.smtp.port, 587);
     assert_eq!(config.smtp.user, "me@example.com");
@@ -35,6 +36,13 @@ name = "Me"
     assert_eq!(config.sender.from, "me@example.com");
     assert_eq!(config.sender.name.as_deref(), Some("Me"));
     assert_eq!(config.sender.formatted_from(), "Me <me@example.com>");
+    assert_eq!(config.ui, UiConfig::default());
+    assert_eq!(config.compose.max_recipients, 10);
+    assert!(config.handlers.is_empty());
+    assert_eq!(config.imap.auth, AuthMode::Password);
+    assert_eq!(config.smtp.oauth2, None);
+    assert_eq!(config.imap.ca_file, None);
+    assert!(!config.smtp.accept_invalid_certs);
 }
 
 #[test]
@@ -62,6 +70,131 @@ from = "me@example.com"
     assert_eq!(config.imap.sent_folder.as_deref(), Some("Sent"));
 }
 
+#[test]
+fn parse_config_with_page_size() {
+    let toml = r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "me@example.com"
+pass = "hunter2"
+folders = ["INBOX"]
+page_size = 50
+
+[smtp]
+host = "smtp.example.com"
+port = 587
+user = "me@example.com"
+pass = "hunter2"
+
+[sender]
+from = "me@example.com"
+
+[ui]
+max_loaded = 500
+"#;
+
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(config.imap.page_size, 50);
+    assert_eq!(config.ui.max_loaded, 500);
+    assert_eq!(config.ui.sort, SortMode::Date);
+}
+
+#[test]
+fn parse_config_with_ui_section() {
+    let toml = r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "me@example.com"
+pass = "hunter2"
+folders = ["INBOX"]
+
+[smtp]
+host = "smtp.example.com"
+port = 587
+user = "me@example.com"
+pass = "hunter2"
+
+[sender]
+from = "me@example.com"
+
+[ui]
+sort = "unread_first"
+group_by_date = false
+opener = "open"
+
+[compose]
+max_recipients = 25
+"#;
+
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(config.ui.sort, SortMode::UnreadFirst);
+    assert!(!config.ui.group_by_date);
+    assert_eq!(config.ui.opener, "open");
+    assert_eq!(config.compose.max_recipients, 25);
+}
+
+#[test]
+fn parse_config_with_oauth2() {
+    let toml = r#"
+[imap]
+host = "imap.gmail.com"
+port = 993
+user = "me@gmail.com"
+folders = ["INBOX"]
+auth = "oauth2"
+oauth2 = { token_command = "oama access me@gmail.com" }
+
+[smtp]
+host = "smtp.gmail.com"
+port = 587
+user = "me@gmail.com"
+auth = "oauth2"
+
+[smtp.oauth2]
+token_url = "https://oauth2.googleapis.com/token"
+client_id = "id"
+client_secret = "secret"
+refresh_token = "refresh"
+
+[sender]
+from = "me@gmail.com"
+"#;
+
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(config.imap.auth, AuthMode::Oauth2);
+    assert_eq!(config.imap.pass, "");
+    assert_eq!(
+        config.imap.oauth2.unwrap().token_command.as_deref(),
+        Some("oama access me@gmail.com")
+    );
+    let oauth2 = config.smtp.oauth2.unwrap();
+    assert_eq!(oauth2.token_command, None);
+    assert_eq!(oauth2.refresh_token.as_deref(), Some("refresh"));
+    assert_eq!(config.smtp.auth, AuthMode::Oauth2);
+}
+
+#[test]
+fn sort_mode_cycles_through_all_modes() {
+    let mut mode = SortMode::Date;
+    let mut seen = vec![mode];
+    for _ in 0..3 {
+        mode = mode.next();
+        seen.push(mode);
+    }
+    assert_eq!(
+        seen,
+        vec![
+            SortMode::Date,
+            SortMode::Sender,
+            SortMode::Subject,
+            SortMode::UnreadFirst
+        ]
+    );
+    assert_eq!(mode.next(), SortMode::Date);
+}
+
 #[test]
 fn expand_plain_value() {
     let result = expand_command("plaintext").unwrap();
@@ -128,3 +261,401 @@ from = "u@localhost"
 
     std::fs::remove_dir_all(&dir).ok();
 }
+
+#[test]
+fn tls_mode_defaults_follow_port() {
+    let imap = |port: u16, tls: Option<TlsMode>| ImapConfig {
+        host: "imap.example.com".to_string(),
+        port,
+        tls,
+        ca_file: None,
+        accept_invalid_certs: false,
+        fingerprint: None,
+        user: "u".to_string(),
+        pass: String::new(),
+        auth: AuthMode::Password,
+        oauth2: None,
+        folders: vec![],
+        sent_folder: None,
+        drafts_folder: None,
+        page_size: 200,
+    };
+    assert_eq!(imap(993, None).tls_mode(), TlsMode::Implicit);
+    assert_eq!(imap(143, None).tls_mode(), TlsMode::Starttls);
+    assert_eq!(imap(3143, Some(TlsMode::None)).tls_mode(), TlsMode::None);
+
+    let smtp = |port: u16| -> SmtpConfig {
+        toml::from_str(&format!(
+            "host = \"smtp.example.com\"\nport = {port}\nuser = \"u\""
+        ))
+        .unwrap()
+    };
+    assert_eq!(smtp(465).tls_mode(), TlsMode::Implicit);
+    assert_eq!(smtp(587).tls_mode(), TlsMode::Starttls);
+    let explicit: SmtpConfig =
+        toml::from_str("host = \"h\"\nport = 587\nuser = \"u\"\ntls = \"implicit\"").unwrap();
+    assert_eq!(explicit.tls_mode(), TlsMode::Implicit);
+}
+
+#[test]
+fn plaintext_only_to_localhost() {
+    assert!(is_local("localhost"));
+    assert!(is_local("127.0.0.1"));
+    assert!(is_local("::1"));
+    assert!(is_local("[::1]"));
+    assert!(!is_local("mail.example.com"));
+    assert!(!is_local("192.168.1.10"));
+
+    let dir = std::env::temp_dir().join("thrum_test_plaintext");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("thrum.toml");
+    let config = |smtp_host: &str| {
+        format!(
+            r#"
+[imap]
+host = "localhost"
+port = 3143
+tls = "none"
+user = "u"
+pass = "p"
+folders = ["INBOX"]
+
+[smtp]
+host = "{smtp_host}"
+port = 3025
+tls = "none"
+user = "u"
+pass = "p"
+
+[sender]
+from = "u@localhost"
+"#
+        )
+    };
+    std::fs::write(&path, config("127.0.0.1")).unwrap();
+    assert_eq!(
+        load(Some(path.clone())).unwrap().imap.tls,
+        Some(TlsMode::None)
+    );
+
+    std::fs::write(&path, config("smtp.example.com")).unwrap();
+    match load(Some(path)) {
+        Err(ConfigError::Plaintext(host)) => assert_eq!(host, "smtp.example.com"),
+        other => panic!("expected a plaintext error, got {other:?}"),
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn page_size_must_be_positive() {
+    let dir = std::env::temp_dir().join("thrum_test_page_size");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("thrum.toml");
+    std::fs::write(
+        &path,
+        r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "u"
+pass = "p"
+folders = ["INBOX"]
+page_size = 0
+
+[smtp]
+host = "smtp.example.com"
+port = 465
+user = "u"
+pass = "p"
+
+[sender]
+from = "u@example.com"
+"#,
+    )
+    .unwrap();
+    assert!(matches!(load(Some(path)), Err(ConfigError::PageSize)));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn parse_config_with_handlers() {
+    let toml = r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "me@example.com"
+pass = "hunter2"
+folders = ["INBOX"]
+
+[smtp]
+host = "smtp.example.com"
+port = 587
+user = "me@example.com"
+pass = "hunter2"
+
+[sender]
+from = "me@example.com"
+
+[[handlers]]
+mime = "text/html"
+command = "w3m -T text/html %s"
+blocking = true
+
+[[handlers]]
+mime = "image/*"
+command = "imv"
+"#;
+
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(config.handlers.len(), 2);
+    assert!(config.handlers[0].blocking);
+    assert!(!config.handlers[1].blocking);
+    assert_eq!(config.handlers[1].command, "imv");
+}
+
+#[test]
+fn handler_matches_wildcards() {
+    let handler = |mime: &str| HandlerConfig {
+        mime: mime.to_string(),
+        command: "view".to_string(),
+        blocking: false,
+    };
+    assert!(handler("image/*").matches("image/png"));
+    assert!(!handler("image/*").matches("text/html"));
+    assert!(handler("*/*").matches("application/pdf"));
+    assert!(handler("TEXT/HTML").matches("text/html"));
+    assert!(!handler("text/html").matches("text/plain"));
+    assert!(handler("image").matches("image/gif"));
+}
+
+#[test]
+fn parse_config_with_carddav() {
+    let toml = r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "me@example.com"
+pass = "hunter2"
+folders = ["INBOX"]
+
+[smtp]
+host = "smtp.example.com"
+port = 587
+user = "me@example.com"
+pass = "hunter2"
+
+[sender]
+from = "me@example.com"
+
+[carddav]
+url = "https://dav.example.com/addressbooks/me/team/"
+user = "me"
+pass = "hunter2"
+"#;
+
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(
+        config.carddav,
+        Some(CardDavConfig {
+            url: "https://dav.example.com/addressbooks/me/team/".to_string(),
+            user: "me".to_string(),
+            pass: "hunter2".to_string(),
+        })
+    );
+}
+
+#[test]
+fn carddav_password_only_over_https_or_to_localhost() {
+    assert!(is_secure_url("https://dav.example.com/addressbooks/me/"));
+    assert!(is_secure_url("HTTPS://dav.example.com"));
+    assert!(is_secure_url("http://localhost:5232/me/contacts/"));
+    assert!(is_secure_url("http://me@127.0.0.1/contacts"));
+    assert!(is_secure_url("http://[::1]:5232/"));
+    assert!(!is_secure_url("http://dav.example.com/addressbooks/me/"));
+    assert!(!is_secure_url("http://localhost.example.com/"));
+    assert!(!is_secure_url("ftp://localhost/"));
+    assert!(!is_secure_url("dav.example.com"));
+
+    let dir = std::env::temp_dir().join("thrum_test_carddav_url");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("thrum.toml");
+    std::fs::write(
+        &path,
+        r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "u"
+pass = "p"
+folders = ["INBOX"]
+
+[smtp]
+host = "smtp.example.com"
+port = 465
+user = "u"
+pass = "p"
+
+[sender]
+from = "u@example.com"
+
+[carddav]
+url = "http://dav.example.com/addressbooks/u/"
+user = "u"
+pass = "p"
+"#,
+    )
+    .unwrap();
+    match load(Some(path)) {
+        Err(ConfigError::InsecureUrl(url)) => {
+            assert_eq!(url, "http://dav.example.com/addressbooks/u/")
+        }
+        other => panic!("expected an insecure URL error, got {other:?}"),
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn oauth2_token_url_only_over_https_or_to_localhost() {
+    let dir = std::env::temp_dir().join("thrum_test_token_url");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("thrum.toml");
+    let config = |token_url: &str| {
+        format!(
+            r#"
+[imap]
+host = "imap.example.com"
+port = 993
+user = "u"
+pass = "p"
+folders = ["INBOX"]
+
+[smtp]
+host = "smtp.example.com"
+port = 465
+user = "u"
+auth = "oauth2"
+
+[smtp.oauth2]
+token_url = "{token_url}"
+client_id = "id"
+client_secret = "secret"
+refresh_token = "refresh"
+
+[sender]
+from = "u@example.com"
+"#
+        )
+    };
+
+    std::fs::write(&path, config("http://oauth2.example.com/token")).unwrap();
+    match load(Some(path.clone())) {
+        Err(ConfigError::InsecureTokenUrl(url)) => {
+            assert_eq!(url, "http://oauth2.example.com/token")
+        }
+        other => panic!("expected an insecure token URL error, got {other:?}"),
+    }
+
+    std::fs::write(&path, config("http://localhost:8080/token")).unwrap();
+    assert!(load(Some(path.clone())).is_ok());
+    std::fs::write(&path, config("https://oauth2.example.com/token")).unwrap();
+    assert!(load(Some(path)).is_ok());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn signature_inline_or_command() {
+    assert_eq!(
+        load_signature(Some("Me\nExample Corp\n\n"), None).unwrap(),
+        Some("Me\nExample Corp".to_string())
+    );
+    assert_eq!(
+        load_signature(Some("`printf 'Me\\nvia cmd'`"), None).unwrap(),
+        Some("Me\nvia cmd".to_string())
+    );
+    assert_eq!(load_signature(None, None).unwrap(), None);
+    assert_eq!(load_signature(Some("  \n"), None).unwrap(), None);
+}
+
+#[test]
+fn signature_file_takes_precedence() {
+    let dir = std::env::temp_dir().join("thrum_test_signature");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("signature");
+    std::fs::write(&path, "From the file\n").unwrap();
+
+    assert_eq!(
+        load_signature(Some("inline"), Some(&path)).unwrap(),
+        Some("From the file".to_string())
+    );
+    assert!(matches!(
+        load_signature(None, Some(&dir.join("missing"))),
+        Err(ConfigError::Signature { .. })
+    ));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn parse_posting_style() {
+    let compose: ComposeConfig = toml::from_str("posting = \"bottom\"").unwrap();
+    assert_eq!(compose.posting, Posting::Bottom);
+    assert_eq!(compose.max_recipients, 10);
+    assert!(!compose.inline_quote);
+    assert!(!compose.markdown);
+    assert_eq!(ComposeConfig::default().posting, Posting::Top);
+}
+
+#[test]
+fn parse_pgp_settings() {
+    let pgp: PgpConfig = toml::from_str("homedir = \"/tmp/keys\"\nsign = true").unwrap();
+    assert_eq!(pgp.gpg, "gpg");
+    assert_eq!(pgp.homedir, Some(PathBuf::from("/tmp/keys")));
+    assert!(pgp.sign);
+    assert!(!pgp.encrypt);
+}
+
+#[test]
+fn parse_smime_settings() {
+    let smime: SmimeConfig =
+        toml::from_str("cert = \"/keys/me.pem\"\nkey = \"/keys/me.key\"\nsign = true").unwrap();
+    assert_eq!(smime.openssl, "openssl");
+    assert_eq!(smime.cert, Some(PathBuf::from("/keys/me.pem")));
+    assert_eq!(smime.key, Some(PathBuf::from("/keys/me.key")));
+    assert_eq!(smime.ca_file, None);
+    assert!(smime.sign);
+}
+
+#[test]
+fn parse_sender_identities() {
+    let sender: SenderConfig = toml::from_str(
+        r#"
+from = "me@example.com"
+name = "Me"
+
+[[identities]]
+from = "support@example.com"
+name = "Example Support"
+signature = "Example Support"
+
+[[identities]]
+from = "me@personal.example"
+"#,
+    )
+    .unwrap();
+    let identities: Vec<String> = sender
+        .identities
+        .iter()
+        .map(IdentityConfig::formatted_from)
+        .collect();
+    assert_eq!(
+        identities,
+        vec![
+            "Example Support <support@example.com>",
+            "me@personal.example"
+        ]
+    );
+    assert_eq!(
+        sender.identities[0].signature.as_deref(),
+        Some("Example Support")
+    );
+    assert_eq!(sender.identities[1].signature, None);
+}
@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::address::Mailbox;
+use crate::imap::EmailSummary;
+
+#[cfg(test)]
+mod test;
+
+/// Seconds in a day, for recency weighting.
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    pub name: Option<String>,
+    pub email: String,
+    /// Number of harvested messages this address appeared on.
+    #[serde(default)]
+    pub count: u32,
+    /// Unix time of the newest message this address appeared on.
+    #[serde(default)]
+    pub last_seen: i64,
+    /// Came from the CardDAV server and goes away when removed there.
+    #[serde(default)]
+    pub remote: bool,
+}
+
+impl Contact {
+    /// `Name <email>` when a name is known, otherwise the bare address.
+    pub fn formatted(&self) -> String {
+        Mailbox {
+            name: self.name.clone(),
+            email: self.email.clone(),
+        }
+        .to_string()
+    }
+
+    /// Frequency weighted by how recently the address was used.
+    fn score(&self, now: i64) -> f64 {
+        let age_days = (now - self.last_seen).max(0) / DAY;
+        let recency = match age_days {
+            0..7 => 4.0,
+            7..30 => 2.0,
+            30..90 => 1.0,
+            _ => 0.5,
+        };
+        f64::from(self.count) * recency
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let email = self.email.to_lowercase();
+        if email.starts_with(query) {
+            return true;
+        }
+        self.name.as_deref().is_some_and(|name| {
+            let name = name.to_lowercase();
+            name.starts_with(query) || name.split_whitespace().any(|word| word.starts_with(query))
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContactsError {
+    #[error("failed to access address book: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse address book: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("failed to write address book: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// Addresses seen in mail or imported from vCards, persisted between runs.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AddressBook {
+    #[serde(default)]
+    pub contacts: Vec<Contact>,
+    /// UID range `(lowest, highest)` harvested per folder, so restarts and
+    /// re-fetched pages do not count the same messages twice.
+    #[serde(default)]
+    harvested: BTreeMap<String, (u32, u32)>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// Default location of the address book: `~/.local/share/thrum/contacts.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("thrum").join("contacts.toml"))
+}
+
+impl AddressBook {
+    /// Load the address book at `path`, starting empty if it does not exist yet.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, err)
+    )]
+    pub fn load(path: &Path) -> Result<Self, ContactsError> {
+        let mut book: AddressBook = match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => AddressBook::default(),
+            Err(e) => return Err(e.into()),
+        };
+        book.path = Some(path.to_path_buf());
+        Ok(book)
+    }
+
+    /// Write the address book back to where it was loaded from. Books that
+    /// were never loaded from disk are kept in memory only.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
+    )]
+    pub fn save(&self) -> Result<(), ContactsError> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Count one use of `address` (`Name <email>` or a bare address) at
+    /// unix time `when`.
+    pub fn record(&mut self, address: &str, when: i64) {
+        let (name, email) = split_address(address);
+        if !email.contains('@') {
+            return;
+        }
+        let key = email.to_lowercase();
+        match self
+            .contacts
+            .iter_mut()
+            .find(|c| c.email.to_lowercase() == key)
+        {
+            Some(contact) => {
+                contact.count += 1;
+                contact.last_seen = contact.last_seen.max(when);
+                if contact.name.is_none() {
+                    contact.name = name;
+                }
+            }
+            None => self.contacts.push(Contact {
+                name,
+                email: email.to_string(),
+                count: 1,
+                last_seen: when,
+                remote: false,
+            }),
+        }
+    }
+
+    /// Record the From, To and Cc addresses of messages outside the range
+    /// already harvested from their folder. Pages are contiguous, so the
+    /// range only ever grows at either end.
+    pub fn harvest(&mut self, emails: &[EmailSummary]) {
+        let known = self.harvested.clone();
+        for email in emails {
+            if known
+                .get(&email.folder)
+                .is_some_and(|&(low, high)| (low..=high).contains(&email.uid))
+            {
+                continue;
+            }
+            let when = email.timestamp().map(|t| t.timestamp()).unwrap_or(0);
+            for address in email.from.iter().chain(&email.to).chain(&email.cc) {
+                self.record(address, when);
+            }
+            let range = self
+                .harvested
+                .entry(email.folder.clone())
+                .or_insert((email.uid, email.uid));
+            range.0 = range.0.min(email.uid);
+            range.1 = range.1.max(email.uid);
+        }
+    }
+
+    /// Add the contacts from vCard data, returning how many were new.
+    pub fn import_vcard(&mut self, data: &str) -> usize {
+        let mut added = 0;
+        for card in parse_vcards(data) {
+            for email in card.emails {
+                let key = email.to_lowercase();
+                match self
+                    .contacts
+                    .iter_mut()
+                    .find(|c| c.email.to_lowercase() == key)
+                {
+                    Some(contact) => {
+                        if contact.name.is_none() {
+                            contact.name = card.name.clone();
+                        }
+                    }
+                    None => {
+                        self.contacts.push(Contact {
+                            name: card.name.clone(),
+                            email,
+                            count: 0,
+                            last_seen: 0,
+                            remote: false,
+                        });
+                        added += 1;
+                    }
+                }
+            }
+        }
+        added
+    }
+
+    /// Replace the contacts from the CardDAV server with `cards`, returning
+    /// how many were added and removed. Removed cards that were also
+    /// harvested from mail are kept as ordinary contacts.
+    pub fn sync_remote(&mut self, cards: &[String]) -> (usize, usize) {
+        let mut current: Vec<(Option<String>, String)> = Vec::new();
+        for card in cards.iter().flat_map(|data| parse_vcards(data)) {
+            for email in card.emails {
+                current.push((card.name.clone(), email));
+            }
+        }
+
+        let before = self.contacts.len();
+        self.contacts.retain_mut(|contact| {
+            let key = contact.email.to_lowercase();
+            if !contact.remote || current.iter().any(|(_, e)| e.to_lowercase() == key) {
+                return true;
+            }
+            contact.remote = false;
+            contact.count > 0
+        });
+        let removed = before - self.contacts.len();
+
+        let mut added = 0;
+        for (name, email) in current {
+            let key = email.to_lowercase();
+            match self
+                .contacts
+                .iter_mut()
+                .find(|c| c.email.to_lowercase() == key)
+            {
+                Some(contact) => {
+                    contact.remote = true;
+                    if name.is_some() {
+                        contact.name = name;
+                    }
+                }
+                None => {
+                    self.contacts.push(Contact {
+                        name,
+                        email,
+                        count: 0,
+                        last_seen: 0,
+                        remote: true,
+                    });
+                    added += 1;
+                }
+            }
+        }
+        (added, removed)
+    }
+
+    /// Contacts whose address, name or any word of their name starts with
+    /// `query`, best first.
+    pub fn suggest(&self, query: &str, now: i64, limit: usize) -> Vec<&Contact> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut matches: Vec<&Contact> =
+            self.contacts.iter().filter(|c| c.matches(&query)).collect();
+        matches.sort_by(|a, b| {
+            b.score(now)
+                .total_cmp(&a.score(now))
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then_with(|| a.email.cmp(&b.email))
+        });
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Split `Name <email>` into its parts; a bare address has no name.
+fn split_address(address: &str) -> (Option<String>, &str) {
+    let address = address.trim();
+    match (address.rfind('<'), address.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            let name = address[..start].trim().trim_matches('"').trim();
+            let name = (!name.is_empty()).then(|| name.to_string());
+            (name, address[start + 1..end].trim())
+        }
+        _ => (None, address),
+    }
+}
+
+struct VCard {
+    name: Option<String>,
+    emails: Vec<String>,
+}
+
+/// Pull the display name and addresses out of vCard 3.0/4.0 data.
+fn parse_vcards(data: &str) -> Vec<VCard> {
+    // Unfold continuation lines first
+    let mut lines: Vec<String> = Vec::new();
+    for line in data.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => {
+                if let Some(last) = lines.last_mut() {
+                    last.push_str(rest);
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut cards = Vec::new();
+    let mut current: Option<VCard> = None;
+    for line in &lines {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop parameters and any `item1.` group prefix
+        let name = property.split(';').next().unwrap_or("");
+        let name = name.rsplit('.').next().unwrap_or(name).to_ascii_uppercase();
+        match name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(VCard {
+                    name: None,
+                    emails: Vec::new(),
+                })
+            }
+            "END" if value.eq_ignore_ascii_case("VCARD") => cards.extend(current.take()),
+            "FN" => {
+                if let Some(ref mut card) = current {
+                    let value = unescape_vcard(value);
+                    card.name = (!value.is_empty()).then_some(value);
+                }
+            }
+            "EMAIL" => {
+                if let Some(ref mut card) = current {
+                    let email = value.trim().trim_start_matches("mailto:").to_string();
+                    if email.contains('@') {
+                        card.emails.push(email);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    cards
+}
+
+fn unescape_vcard(value: &str) -> String {
+    value
+        .trim()
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\n", " ")
+        .replace("\\\\", "\\")
+}
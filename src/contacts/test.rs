@@ -0,0 +1,225 @@
+use super::*;
+
+const NOW: i64 = 1_736_935_200; // Wed, 15 Jan 2025 10:00:00 +0000
+
+fn email(uid: u32, from: &str, to: &[&str], date: &str) -> EmailSummary {
+    EmailSummary {
+        uid,
+        folder: "INBOX".to_string(),
+        subject: String::new(),
+        from: vec![from.to_string()],
+        sender: None,
+        reply_to: vec![],
+        to: to.iter().map(|s| s.to_string()).collect(),
+        cc: vec![],
+        date: date.to_string(),
+        seen: true,
+        snippet: String::new(),
+        message_id: None,
+        in_reply_to: None,
+        references: vec![],
+        internal_date: None,
+    }
+}
+
+fn emails(suggestions: Vec<&Contact>) -> Vec<&str> {
+    suggestions.iter().map(|c| c.email.as_str()).collect()
+}
+
+#[test]
+fn record_merges_addresses_case_insensitively() {
+    let mut book = AddressBook::default();
+    book.record("alice@example.com", 100);
+    book.record("Alice Smith <Alice@Example.com>", 50);
+    book.record("not an address", 100);
+
+    assert_eq!(
+        book.contacts,
+        vec![Contact {
+            name: Some("Alice Smith".to_string()),
+            email: "alice@example.com".to_string(),
+            count: 2,
+            last_seen: 100,
+            remote: false,
+        }]
+    );
+}
+
+#[test]
+fn harvest_skips_messages_already_seen() {
+    let mut book = AddressBook::default();
+    let batch = vec![
+        email(
+            5,
+            "Bob <bob@example.com>",
+            &["me@example.com"],
+            "Mon, 13 Jan 2025 09:00:00 +0000",
+        ),
+        email(
+            6,
+            "bob@example.com",
+            &["me@example.com", "Carol <carol@example.com>"],
+            "Tue, 14 Jan 2025 09:00:00 +0000",
+        ),
+    ];
+    book.harvest(&batch);
+    book.harvest(&batch);
+
+    let bob = &book.contacts[0];
+    assert_eq!(bob.name.as_deref(), Some("Bob"));
+    assert_eq!(bob.count, 2);
+    assert_eq!(bob.last_seen, NOW - 25 * 60 * 60);
+    assert_eq!(book.contacts.len(), 3);
+
+    // An older page is still new to the book
+    book.harvest(&[email(
+        3,
+        "bob@example.com",
+        &[],
+        "Fri, 10 Jan 2025 09:00:00 +0000",
+    )]);
+    assert_eq!(book.contacts[0].count, 3);
+    assert_eq!(book.contacts[0].last_seen, NOW - 25 * 60 * 60);
+}
+
+#[test]
+fn suggest_matches_address_name_and_name_words() {
+    let mut book = AddressBook::default();
+    book.record("Alice Smith <alice@example.com>", NOW);
+    book.record("Bob Jones <robert@example.com>", NOW);
+
+    assert_eq!(
+        emails(book.suggest("ali", NOW, 5)),
+        vec!["alice@example.com"]
+    );
+    assert_eq!(
+        emails(book.suggest("JON", NOW, 5)),
+        vec!["robert@example.com"]
+    );
+    assert_eq!(
+        emails(book.suggest("rob", NOW, 5)),
+        vec!["robert@example.com"]
+    );
+    assert!(book.suggest("", NOW, 5).is_empty());
+    assert!(book.suggest("example", NOW, 5).is_empty());
+}
+
+#[test]
+fn suggest_ranks_by_frequency_and_recency() {
+    let mut book = AddressBook::default();
+    // Frequent but stale
+    for _ in 0..3 {
+        book.record("ann.old@example.com", NOW - 200 * DAY);
+    }
+    // Used twice this week
+    book.record("ann.new@example.com", NOW - DAY);
+    book.record("ann.new@example.com", NOW - DAY);
+    // Once, a month ago
+    book.record("anna@example.com", NOW - 40 * DAY);
+
+    assert_eq!(
+        emails(book.suggest("ann", NOW, 5)),
+        vec![
+            "ann.new@example.com",
+            "ann.old@example.com",
+            "anna@example.com"
+        ]
+    );
+    assert_eq!(book.suggest("ann", NOW, 2).len(), 2);
+}
+
+#[test]
+fn formatted_quotes_names_with_specials() {
+    let contact = Contact {
+        name: Some("Smith, Alice".to_string()),
+        email: "alice@example.com".to_string(),
+        count: 1,
+        last_seen: 0,
+        remote: false,
+    };
+    assert_eq!(contact.formatted(), "\"Smith, Alice\" <alice@example.com>");
+}
+
+#[test]
+fn import_vcard_reads_names_and_addresses() {
+    let data = "BEGIN:VCARD\r\n\
+                VERSION:3.0\r\n\
+                FN:Dana Scully\r\n\
+                EMAIL;TYPE=work:dana@fbi.example\r\n\
+                item1.EMAIL:dana.home@example.com\r\n\
+                END:VCARD\r\n\
+                BEGIN:VCARD\r\n\
+                VERSION:4.0\r\n\
+                FN:Mulder\\, Fox\r\n\
+                EMAIL:fox@fbi.ex\r\n ample\r\n\
+                END:VCARD\r\n";
+    let mut book = AddressBook::default();
+    book.record("dana@fbi.example", NOW);
+
+    assert_eq!(book.import_vcard(data), 2);
+    let names: Vec<(Option<&str>, &str)> = book
+        .contacts
+        .iter()
+        .map(|c| (c.name.as_deref(), c.email.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            (Some("Dana Scully"), "dana@fbi.example"),
+            (Some("Dana Scully"), "dana.home@example.com"),
+            (Some("Mulder, Fox"), "fox@fbi.example"),
+        ]
+    );
+}
+
+#[test]
+fn load_and_save_round_trip() {
+    let path = std::env::temp_dir()
+        .join(format!("thrum-contacts-{}", std::process::id()))
+        .join("contacts.toml");
+    let mut book = AddressBook::load(&path).unwrap();
+    assert!(book.contacts.is_empty());
+
+    book.harvest(&[email(
+        7,
+        "Eve <eve@example.com>",
+        &[],
+        "Wed, 15 Jan 2025 10:00:00 +0000",
+    )]);
+    book.save().unwrap();
+
+    let loaded = AddressBook::load(&path).unwrap();
+    assert_eq!(loaded, book);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn sync_remote_follows_the_server() {
+    let card = |name: &str, email: &str| {
+        format!("BEGIN:VCARD\r\nVERSION:3.0\r\nFN:{name}\r\nEMAIL:{email}\r\nEND:VCARD\r\n")
+    };
+    let mut book = AddressBook::default();
+    book.record("frank@example.com", NOW);
+
+    let first = vec![
+        card("Frank Team", "frank@example.com"),
+        card("Grace", "grace@example.com"),
+        card("Heidi", "heidi@example.com"),
+    ];
+    assert_eq!(book.sync_remote(&first), (2, 0));
+    assert_eq!(book.contacts[0].name.as_deref(), Some("Frank Team"));
+    assert!(book.contacts.iter().all(|c| c.remote));
+
+    // Grace left the team; Frank was also harvested from mail, so he stays
+    let second = vec![card("Heidi", "heidi@example.com")];
+    assert_eq!(book.sync_remote(&second), (0, 1));
+    let remaining: Vec<(&str, bool)> = book
+        .contacts
+        .iter()
+        .map(|c| (c.email.as_str(), c.remote))
+        .collect();
+    assert_eq!(
+        remaining,
+        vec![("frank@example.com", false), ("heidi@example.com", true)]
+    );
+}
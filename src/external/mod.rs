@@ -0,0 +1,275 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+
+use base64::Engine;
+
+use crate::config::HandlerConfig;
+use crate::private;
+
+#[cfg(test)]
+mod test;
+
+/// Side effects requested by a key press that have to run outside the
+/// event handler, where the terminal is available.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternalAction {
+    /// Open a link with the configured opener.
+    Open(String),
+    /// Put text on the system clipboard via the terminal.
+    Copy(String),
+    /// Save a message part to a temporary file and open it with the
+    /// handler for its type.
+    View {
+        filename: String,
+        content_type: String,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExternalError {
+    #[error("opener command is empty")]
+    EmptyCommand,
+    #[error("failed to run {command}: {source}")]
+    Spawn {
+        command: String,
+        source: std::io::Error,
+    },
+    #[error("failed to write to terminal: {0}")]
+    Terminal(#[from] std::io::Error),
+    #[error("failed to save {path}: {source}")]
+    TempFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{command} exited with {status}")]
+    Failed {
+        command: String,
+        status: std::process::ExitStatus,
+    },
+}
+
+/// Build the command that opens `target`: `%s` in the opener is replaced by
+/// the target, otherwise the target is appended as the last argument.
+pub fn opener_command(opener: &str, target: &str) -> Result<Command, ExternalError> {
+    let mut words = opener.split_whitespace();
+    let program = words.next().ok_or(ExternalError::EmptyCommand)?;
+    let mut args: Vec<String> = words.map(str::to_string).collect();
+    if args.iter().any(|a| a.contains("%s")) {
+        for arg in &mut args {
+            *arg = arg.replace("%s", target);
+        }
+    } else {
+        args.push(target.to_string());
+    }
+
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+/// Launch the opener in the background, detached from the terminal.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = tracing::Level::TRACE, err)
+)]
+pub fn open(opener: &str, target: &str) -> Result<(), ExternalError> {
+    let mut child = opener_command(opener, target)?
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|source| ExternalError::Spawn {
+            command: opener.to_string(),
+            source,
+        })?;
+    // Reap the opener once it exits so it does not linger as a zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The OSC 52 escape sequence asking the terminal to set the clipboard.
+pub fn osc52_sequence(text: &str) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    format!("\x1b]52;c;{encoded}\x07")
+}
+
+/// Copy `text` to the clipboard of the terminal we are running in, which
+/// also works over SSH.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = tracing::Level::TRACE, skip(text), err)
+)]
+pub fn copy_to_clipboard(text: &str) -> Result<(), ExternalError> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(osc52_sequence(text).as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The first handler configured for `content_type`.
+pub fn find_handler<'a>(
+    handlers: &'a [HandlerConfig],
+    content_type: &str,
+) -> Option<&'a HandlerConfig> {
+    handlers.iter().find(|h| h.matches(content_type))
+}
+
+/// Reduce a file name from a message to something safe to create in the
+/// temp directory.
+pub fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = base
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Run `command` with `input` on stdin and collect its output, whatever its
+/// exit status.
+#[cfg(any(feature = "pgp", feature = "smime"))]
+pub fn piped_output(mut command: Command, input: &[u8]) -> std::io::Result<std::process::Output> {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(?command, "running command");
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Feed stdin from another thread so a full stdout pipe cannot stall it
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    // The command may stop reading early, e.g. on malformed input
+    match writer.join().expect("stdin writer panicked") {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(output),
+    }
+}
+
+/// Directory holding the files we hand to other programs, created on first
+/// use and removed by [`remove_private_dir`].
+static PRIVATE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// A fresh directory in the system temp dir that only we can read. Its name
+/// is random and it must not exist yet, so nobody else can have planted
+/// files or links in it.
+pub fn private_dir() -> std::io::Result<PathBuf> {
+    let mut dir = PRIVATE_DIR.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(dir) = dir.as_ref() {
+        return Ok(dir.clone());
+    }
+    loop {
+        let path = std::env::temp_dir().join(format!("thrum-{:016x}", random()));
+        match private::create_dir(&path) {
+            Ok(()) => return Ok(dir.insert(path).clone()),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Delete the directory from [`private_dir`] and everything in it.
+pub fn remove_private_dir() {
+    let dir = PRIVATE_DIR
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(dir) = dir
+        && let Err(_e) = std::fs::remove_dir_all(&dir)
+    {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%_e, ?dir, "failed to remove temp dir");
+    }
+}
+
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// `name` with `-n` before its extension, so saving the same part twice
+/// does not clobber a file a handler may still have open.
+fn numbered(name: &str, n: usize) -> String {
+    if n == 1 {
+        return name.to_string();
+    }
+    match name.rfind('.') {
+        Some(dot) => format!("{}-{n}{}", &name[..dot], &name[dot..]),
+        None => format!("{name}-{n}"),
+    }
+}
+
+/// Write `data` to a new file in [`private_dir`].
+pub fn write_temp(filename: &str, data: &[u8]) -> Result<PathBuf, ExternalError> {
+    let name = sanitize_filename(filename);
+    let mut path = PathBuf::from(&name);
+    private_dir()
+        .and_then(|dir| {
+            let mut n = 1;
+            loop {
+                path = dir.join(numbered(&name, n));
+                match private::create_new(&path) {
+                    Ok(mut file) => return file.write_all(data),
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                    Err(e) => return Err(e),
+                }
+            }
+        })
+        .map_err(|source| ExternalError::TempFile {
+            path: path.clone(),
+            source,
+        })?;
+    Ok(path)
+}
+
+/// Run `command` with the terminal handed over to it, restoring the TUI
+/// once it exits. The caller must redraw the whole screen afterwards.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = tracing::Level::TRACE, err)
+)]
+pub fn run_suspended(command: &str, path: &Path) -> Result<(), ExternalError> {
+    let target = path.to_string_lossy();
+    let mut cmd = opener_command(command, &target)?;
+
+    ratatui::restore();
+    let status = cmd.status();
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+
+    let status = status.map_err(|source| ExternalError::Spawn {
+        command: command.to_string(),
+        source,
+    })?;
+    if !status.success() {
+        return Err(ExternalError::Failed {
+            command: command.to_string(),
+            status,
+        });
+    }
+    Ok(())
+}
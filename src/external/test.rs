@@ -0,0 +1,117 @@
+use super::*;
+
+fn argv(command: &Command) -> Vec<String> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|s| s.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn opener_appends_target() {
+    let command = opener_command("xdg-open", "https://example.com").unwrap();
+    assert_eq!(argv(&command), vec!["xdg-open", "https://example.com"]);
+}
+
+#[test]
+fn opener_substitutes_placeholder() {
+    let command = opener_command("firefox --new-tab %s", "https://example.com").unwrap();
+    assert_eq!(
+        argv(&command),
+        vec!["firefox", "--new-tab", "https://example.com"]
+    );
+}
+
+#[test]
+fn opener_must_not_be_empty() {
+    assert!(matches!(
+        opener_command("  ", "https://example.com"),
+        Err(ExternalError::EmptyCommand)
+    ));
+}
+
+#[test]
+fn osc52_encodes_text_as_base64() {
+    assert_eq!(
+        osc52_sequence("https://example.com"),
+        "\x1b]52;c;aHR0cHM6Ly9leGFtcGxlLmNvbQ==\x07"
+    );
+}
+
+#[test]
+fn find_handler_takes_first_match() {
+    let handlers = vec![
+        HandlerConfig {
+            mime: "image/png".to_string(),
+            command: "png-viewer".to_string(),
+            blocking: false,
+        },
+        HandlerConfig {
+            mime: "image/*".to_string(),
+            command: "imv".to_string(),
+            blocking: false,
+        },
+    ];
+    assert_eq!(
+        find_handler(&handlers, "image/png").map(|h| h.command.as_str()),
+        Some("png-viewer")
+    );
+    assert_eq!(
+        find_handler(&handlers, "image/gif").map(|h| h.command.as_str()),
+        Some("imv")
+    );
+    assert!(find_handler(&handlers, "text/html").is_none());
+}
+
+#[test]
+fn sanitize_filename_strips_paths_and_odd_characters() {
+    assert_eq!(sanitize_filename("report.pdf"), "report.pdf");
+    assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+    assert_eq!(sanitize_filename("C:\\Users\\me\\a b.txt"), "a_b.txt");
+    assert_eq!(sanitize_filename(".hidden"), "hidden");
+    assert_eq!(sanitize_filename(""), "attachment");
+}
+
+#[test]
+fn write_temp_saves_data() {
+    let path = write_temp("thrum-test-write.txt", b"hello").unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+    assert_eq!(path.parent().unwrap(), private_dir().unwrap());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn write_temp_does_not_clobber_existing_files() {
+    let first = write_temp("thrum-test-twice.txt", b"one").unwrap();
+    let second = write_temp("thrum-test-twice.txt", b"two").unwrap();
+    assert_eq!(second.file_name().unwrap(), "thrum-test-twice-2.txt");
+    assert_eq!(std::fs::read(&first).unwrap(), b"one");
+    assert_eq!(std::fs::read(&second).unwrap(), b"two");
+    std::fs::remove_file(first).unwrap();
+    std::fs::remove_file(second).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn temp_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = write_temp("thrum-test-private.txt", b"secret").unwrap();
+    let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(path.parent().unwrap()), 0o700);
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(
+        crate::private::create_new(&path).unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(all(unix, any(feature = "pgp", feature = "smime")))]
+#[test]
+fn piped_output_does_not_stall_on_large_input() {
+    let input = vec![b'x'; 1 << 20];
+    let output = piped_output(Command::new("cat"), &input).unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, input);
+}
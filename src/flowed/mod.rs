@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+use crate::quote;
+
+#[cfg(test)]
+mod test;
+
+/// Column at which outgoing lines are soft-wrapped.
+pub const WIDTH: usize = 72;
+
+/// Byte ranges of `line` split at spaces into rows of at most `width`
+/// characters. Every row but the last keeps its trailing space, which marks
+/// a soft break; words longer than `width` get a row of their own.
+pub fn split(line: &str, width: usize) -> Vec<Range<usize>> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for token in line.split_inclusive(' ') {
+        let token_end = end + token.len();
+        let visible = line[start..token_end].trim_end_matches(' ').chars().count();
+        if visible > width && end > start {
+            rows.push(start..end);
+            start = end;
+        }
+        end = token_end;
+    }
+    rows.push(start..line.len());
+    rows
+}
+
+/// Encode `text` as `format=flowed`: long lines are soft-wrapped at
+/// `width`, hard line ends lose their trailing spaces, and lines that would
+/// be misread are space-stuffed. The `-- ` signature delimiter is kept.
+pub fn encode(text: &str, width: usize) -> String {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        if line == "-- " {
+            out.push(line.to_string());
+            continue;
+        }
+        let (level, content) = quote::depth(line);
+        let content = content.trim_end_matches(' ');
+        let markers = ">".repeat(level);
+        let available = width.saturating_sub(level + 1);
+        for range in split(content, available) {
+            let row = &content[range];
+            let stuffed =
+                !row.is_empty() && (level > 0 || row.starts_with(' ') || row.starts_with("From "));
+            out.push(format!("{markers}{}{row}", if stuffed { " " } else { "" }));
+        }
+    }
+    out.join("\n")
+}
+
+/// Decode `format=flowed` text, joining soft-broken lines into paragraphs.
+/// With `delsp`, the space before each soft break is removed as well.
+pub fn decode(text: &str, delsp: bool) -> String {
+    let mut out: Vec<String> = Vec::new();
+    // Depth of the paragraph still flowing into the last line
+    let mut open = None;
+    for line in text.lines() {
+        let level = line.chars().take_while(|&c| c == '>').count();
+        let rest = &line[level..];
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+
+        let signature = rest == "-- ";
+        let flowed = rest.ends_with(' ') && !signature;
+        let rest = if flowed && delsp {
+            &rest[..rest.len() - 1]
+        } else {
+            rest
+        };
+
+        match out.last_mut() {
+            Some(last) if open == Some(level) && !signature => last.push_str(rest),
+            _ => out.push(quote::line_at(level, rest)),
+        }
+        open = flowed.then_some(level);
+    }
+    out.join("\n")
+}
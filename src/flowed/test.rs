@@ -0,0 +1,65 @@
+use super::*;
+
+#[test]
+fn split_keeps_trailing_space_on_soft_rows() {
+    let line = "the quick brown fox jumps";
+    let rows: Vec<&str> = split(line, 10).into_iter().map(|r| &line[r]).collect();
+    assert_eq!(rows, vec!["the quick ", "brown fox ", "jumps"]);
+    assert_eq!(split("", 10), vec![0..0]);
+}
+
+#[test]
+fn split_gives_long_words_their_own_row() {
+    let line = "see https://example.com/a/long/path ok";
+    let rows: Vec<&str> = split(line, 10).into_iter().map(|r| &line[r]).collect();
+    assert_eq!(rows, vec!["see ", "https://example.com/a/long/path ", "ok"]);
+}
+
+#[test]
+fn encode_soft_wraps_and_trims_hard_breaks() {
+    assert_eq!(
+        encode("the quick brown fox jumps\nshort   \n\nend", 10),
+        "the quick \nbrown fox \njumps\nshort\n\nend"
+    );
+}
+
+#[test]
+fn encode_space_stuffs_and_keeps_signature() {
+    assert_eq!(
+        encode(" indented\nFrom here\n-- \nMe", 72),
+        "  indented\n From here\n-- \nMe"
+    );
+}
+
+#[test]
+fn encode_wraps_quotes_at_their_depth() {
+    assert_eq!(
+        encode(">> one two three four\n>\n> five", 12),
+        ">> one two \n>> three \n>> four\n>\n> five"
+    );
+}
+
+#[test]
+fn decode_joins_soft_breaks_per_depth() {
+    let text = "Hello \nthere\n> quoted \n> text\n>> deeper \n> shallower\n \nend";
+    assert_eq!(
+        decode(text, false),
+        "Hello there\n> quoted text\n>> deeper \n> shallower\n\nend"
+    );
+}
+
+#[test]
+fn decode_delsp_removes_break_spaces() {
+    assert_eq!(decode("Sch\u{f6}n \nheit", true), "Sch\u{f6}nheit");
+}
+
+#[test]
+fn decode_does_not_flow_into_signature() {
+    assert_eq!(decode("Thanks \n-- \nMe", false), "Thanks \n-- \nMe");
+}
+
+#[test]
+fn encode_then_decode_round_trips() {
+    let text = "a paragraph that is long enough to need more than one line when wrapped\n\n> quoted\n-- \nMe";
+    assert_eq!(decode(&encode(text, 20), false), text);
+}
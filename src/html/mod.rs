@@ -0,0 +1,731 @@
+use html_escape::decode_html_entities;
+
+#[cfg(test)]
+mod test;
+
+/// Narrowest width the renderer will wrap to.
+const MIN_WIDTH: usize = 20;
+
+/// Gap between columns of a rendered table.
+const COLUMN_GAP: &str = "  ";
+
+/// HTML rendered as plain terminal text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub text: String,
+    /// Link targets in footnote order: `[n]` in the text refers to `links[n - 1]`.
+    pub links: Vec<String>,
+}
+
+/// Render an HTML document as text wrapped to `width` columns, keeping
+/// paragraphs, headings, lists, quotes and simple tables, and collecting
+/// link targets as numbered footnotes.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = tracing::Level::TRACE, skip(html))
+)]
+pub fn render(html: &str, width: usize) -> Rendered {
+    let mut renderer = Renderer::new(width.max(MIN_WIDTH));
+    for token in tokenize(html) {
+        renderer.token(token);
+    }
+    renderer.finish()
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    End(String),
+}
+
+/// Elements whose content is never displayed.
+const HIDDEN_ELEMENTS: &[&str] = &["script", "style", "title", "template"];
+
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < html.len() {
+        let rest = &html[i..];
+        if !rest.starts_with('<') {
+            let next = rest.find('<').map(|n| i + n).unwrap_or(html.len());
+            tokens.push(Token::Text(&html[i..next]));
+            i = next;
+            continue;
+        }
+
+        if rest.starts_with("<!--") {
+            i = rest.find("-->").map(|e| i + e + 3).unwrap_or(html.len());
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            i = rest.find('>').map(|e| i + e + 1).unwrap_or(html.len());
+            continue;
+        }
+
+        let Some(end) = find_tag_end(rest) else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        let inner = &rest[1..end];
+        i += end + 1;
+
+        if let Some(name) = inner.strip_prefix('/') {
+            tokens.push(Token::End(tag_name(name)));
+        } else if inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let name = tag_name(inner);
+            let attrs = parse_attributes(&inner[name.len()..]);
+            if HIDDEN_ELEMENTS.contains(&name.as_str()) {
+                let close = format!("</{name}");
+                i = html[i..]
+                    .to_ascii_lowercase()
+                    .find(&close)
+                    .and_then(|start| html[i + start..].find('>').map(|e| i + start + e + 1))
+                    .unwrap_or(html.len());
+                continue;
+            }
+            tokens.push(Token::Start { name, attrs });
+        } else {
+            // A stray '<' that does not open a tag is literal text
+            tokens.push(Token::Text(&rest[..end + 1]));
+        }
+    }
+
+    tokens
+}
+
+/// Offset of the `>` closing the tag at the start of `s`, skipping quoted
+/// attribute values.
+fn find_tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(s: &str) -> String {
+    s.trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+fn parse_attributes(s: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut chars = s.trim_end_matches('/').chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == '/').is_some() {}
+        let name: String =
+            std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != '=' && *c != '/'))
+                .collect();
+        if name.is_empty() {
+            break;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(q) => value.extend(std::iter::from_fn(|| chars.next_if(|c| *c != q))),
+                None => value.extend(std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace()))),
+            }
+            chars.next();
+        }
+        attrs.push((
+            name.to_ascii_lowercase(),
+            decode_html_entities(&value).into_owned(),
+        ));
+    }
+
+    attrs
+}
+
+struct List {
+    ordered: bool,
+    counter: usize,
+    /// Width of the current item's marker; continuation lines hang by this much.
+    hang: usize,
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    /// Nested tables are flattened into the cells of the outermost one.
+    depth: usize,
+    in_cell: bool,
+}
+
+struct Renderer {
+    width: usize,
+    lines: Vec<String>,
+    /// Inline text of the block being built, with whitespace collapsed.
+    inline: String,
+    pending_space: bool,
+    quote_depth: usize,
+    lists: Vec<List>,
+    marker: Option<String>,
+    pre_depth: usize,
+    table: Option<Table>,
+    links: Vec<String>,
+    /// Target and inline offset of the `<a>` currently open.
+    open_link: Option<(String, usize)>,
+    heading_start: Option<(usize, char)>,
+}
+
+impl Renderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width,
+            lines: Vec::new(),
+            inline: String::new(),
+            pending_space: false,
+            quote_depth: 0,
+            lists: Vec::new(),
+            marker: None,
+            pre_depth: 0,
+            table: None,
+            links: Vec::new(),
+            open_link: None,
+            heading_start: None,
+        }
+    }
+
+    fn token(&mut self, token: Token) {
+        match token {
+            Token::Text(text) => self.text(&decode_html_entities(text)),
+            Token::Start { name, attrs } => self.start(&name, &attrs),
+            Token::End(name) => self.end(&name),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.pre_depth > 0 {
+            self.inline.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                self.pending_space = !self.inline.is_empty() && !self.inline.ends_with('\n');
+            } else {
+                if self.pending_space {
+                    self.inline.push(' ');
+                    self.pending_space = false;
+                }
+                self.inline.push(c);
+            }
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &[(String, String)]) {
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+
+        if let Some(ref mut table) = self.table {
+            match name {
+                "table" => table.depth += 1,
+                "tr" if table.depth == 0 => {
+                    table.rows.push(Vec::new());
+                    table.in_cell = false;
+                }
+                "td" | "th" if table.depth == 0 => {
+                    let cell = finish_cell(&mut self.inline);
+                    self.pending_space = false;
+                    if table.in_cell
+                        && let Some(row) = table.rows.last_mut()
+                    {
+                        row.push(cell);
+                    }
+                    if table.rows.is_empty() {
+                        table.rows.push(Vec::new());
+                    }
+                    table.in_cell = true;
+                }
+                "tr" => self.cell_break(),
+                "td" | "th" => self.pending_space = !self.inline.is_empty(),
+                _ => {}
+            }
+        }
+
+        match name {
+            "br" => self.line_break(),
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.paragraph_break();
+                if let Some(underline) = match name {
+                    "h1" => Some('='),
+                    "h2" => Some('-'),
+                    _ => None,
+                } {
+                    self.heading_start = Some((self.lines.len(), underline));
+                }
+            }
+            "div" | "section" | "article" | "header" | "footer" | "center" | "address"
+            | "figure" | "dl" | "dt" | "dd" => self.block_break(),
+            "blockquote" => {
+                self.paragraph_break();
+                self.quote_depth += 1;
+            }
+            "pre" => {
+                self.paragraph_break();
+                self.pre_depth += 1;
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.paragraph_break();
+                } else {
+                    self.block_break();
+                }
+                let counter = attr("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+                self.lists.push(List {
+                    ordered: name == "ol",
+                    counter,
+                    hang: 0,
+                });
+            }
+            "li" if self.table.is_some() => {
+                self.cell_break();
+                self.text("• ");
+            }
+            "li" => {
+                self.block_break();
+                if let Some(list) = self.lists.last_mut() {
+                    let marker = if list.ordered {
+                        list.counter += 1;
+                        format!("{}. ", list.counter - 1)
+                    } else {
+                        "• ".to_string()
+                    };
+                    list.hang = marker.chars().count();
+                    self.marker = Some(marker);
+                }
+            }
+            "hr" => {
+                self.block_break();
+                if self.table.is_none() {
+                    let rule = "─".repeat(self.available_width());
+                    self.lines.push(format!("{}{rule}", self.prefix()));
+                }
+            }
+            "table" if self.table.is_none() => {
+                self.paragraph_break();
+                self.table = Some(Table::default());
+            }
+            "a" => {
+                if let Some(href) = attr("href").map(str::trim).filter(|h| is_followable(h)) {
+                    self.open_link = Some((href.to_string(), self.inline.len()));
+                }
+            }
+            "img" => {
+                if let Some(alt) = attr("alt").map(str::trim).filter(|a| !a.is_empty()) {
+                    self.text(&format!("[{alt}]"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if let Some(ref mut table) = self.table {
+            match name {
+                "table" if table.depth > 0 => {
+                    table.depth -= 1;
+                    self.cell_break();
+                    return;
+                }
+                "table" => {
+                    let mut table = self.table.take().unwrap_or_default();
+                    if table.in_cell {
+                        let cell = finish_cell(&mut self.inline);
+                        if let Some(row) = table.rows.last_mut() {
+                            row.push(cell);
+                        }
+                    }
+                    self.inline.clear();
+                    self.pending_space = false;
+                    self.render_table(table.rows);
+                    self.paragraph_break();
+                    return;
+                }
+                "tr" if table.depth == 0 => {
+                    if table.in_cell {
+                        let cell = finish_cell(&mut self.inline);
+                        if let Some(row) = table.rows.last_mut() {
+                            row.push(cell);
+                        }
+                    }
+                    table.in_cell = false;
+                    self.pending_space = false;
+                }
+                _ => {}
+            }
+        }
+
+        match name {
+            "p" | "h3" | "h4" | "h5" | "h6" | "table" => self.paragraph_break(),
+            "h1" | "h2" => {
+                self.paragraph_break();
+                if let Some((start, underline)) = self.heading_start.take() {
+                    let len = self.lines[start..]
+                        .iter()
+                        .map(|l| l.chars().count())
+                        .max()
+                        .unwrap_or(0);
+                    if len > 0 {
+                        // Replace the blank separator with the underline
+                        self.lines.pop();
+                        self.lines.push(underline.to_string().repeat(len));
+                        self.lines.push(String::new());
+                    }
+                }
+            }
+            "div" | "section" | "article" | "header" | "footer" | "center" | "address"
+            | "figure" | "dl" | "dt" | "dd" | "li" => self.block_break(),
+            "blockquote" => {
+                self.paragraph_break();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            "pre" => {
+                self.flush_pre();
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+                self.paragraph_break();
+            }
+            "ul" | "ol" => {
+                self.block_break();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.paragraph_break();
+                }
+            }
+            "a" => self.close_link(),
+            _ => {}
+        }
+    }
+
+    fn close_link(&mut self) {
+        let Some((href, start)) = self.open_link.take() else {
+            return;
+        };
+        let label = self.inline.get(start..).unwrap_or("").trim();
+        let index = match self.links.iter().position(|l| *l == href) {
+            Some(i) => i + 1,
+            None => {
+                self.links.push(href.clone());
+                self.links.len()
+            }
+        };
+        // A link whose text is its own address needs no footnote marker
+        if label != href && label != href.trim_start_matches("mailto:") {
+            self.inline.push_str(&format!("[{index}]"));
+        }
+    }
+
+    /// Inside a table cell, block boundaries become line breaks in the cell.
+    fn cell_break(&mut self) {
+        if !self.inline.is_empty() && !self.inline.ends_with('\n') {
+            self.inline.push('\n');
+        }
+        self.pending_space = false;
+    }
+
+    fn line_break(&mut self) {
+        if self.table.is_some() {
+            self.inline.push('\n');
+            self.pending_space = false;
+        } else if self.pre_depth > 0 {
+            self.inline.push('\n');
+        } else if self.inline.is_empty() {
+            let prefix = self.prefix();
+            self.lines.push(prefix.trim_end().to_string());
+        } else {
+            self.flush_inline();
+        }
+    }
+
+    /// End the current line of inline text.
+    fn block_break(&mut self) {
+        if self.table.is_some() {
+            self.cell_break();
+        } else if self.pre_depth == 0 {
+            self.flush_inline();
+        }
+    }
+
+    /// End the current block and leave a blank line before the next one.
+    fn paragraph_break(&mut self) {
+        if self.table.is_some() {
+            self.cell_break();
+            return;
+        }
+        if self.pre_depth > 0 {
+            return;
+        }
+        self.flush_inline();
+        if self.lines.last().is_some_and(|l| !is_blank(l)) {
+            let prefix = self.prefix();
+            self.lines.push(prefix.trim_end().to_string());
+        }
+    }
+
+    /// Quote markers and list indentation for continuation lines.
+    fn prefix(&self) -> String {
+        let hang: usize = self.lists.iter().map(|l| l.hang).sum();
+        format!("{}{}", "> ".repeat(self.quote_depth), " ".repeat(hang))
+    }
+
+    fn available_width(&self) -> usize {
+        self.width
+            .saturating_sub(self.prefix().chars().count())
+            .max(MIN_WIDTH / 2)
+    }
+
+    fn flush_inline(&mut self) {
+        let text = std::mem::take(&mut self.inline);
+        self.pending_space = false;
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+
+        let prefix = self.prefix();
+        let first_prefix = match self.marker.take() {
+            Some(marker) => {
+                let current_hang = self.lists.last().map(|l| l.hang).unwrap_or(0);
+                let outer = prefix.chars().count() - current_hang;
+                format!("{}{marker}", prefix.chars().take(outer).collect::<String>())
+            }
+            None => prefix.clone(),
+        };
+
+        let width = self.available_width();
+        for (i, line) in text
+            .split('\n')
+            .flat_map(|l| wrap(l.trim(), width))
+            .enumerate()
+        {
+            let p = if i == 0 { &first_prefix } else { &prefix };
+            self.lines.push(format!("{p}{line}"));
+        }
+    }
+
+    fn flush_pre(&mut self) {
+        let text = std::mem::take(&mut self.inline);
+        let prefix = self.prefix();
+        let text = text.strip_prefix('\n').unwrap_or(&text);
+        for line in text.trim_end().lines() {
+            self.lines.push(format!("{prefix}{}", line.trim_end()));
+        }
+    }
+
+    fn render_table(&mut self, rows: Vec<Vec<String>>) {
+        let mut rows: Vec<Vec<String>> = rows
+            .into_iter()
+            .filter(|row| row.iter().any(|c| !c.trim().is_empty()))
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+        // Drop columns that are empty in every row (spacer cells)
+        let empty: Vec<usize> = (0..columns)
+            .filter(|&c| {
+                rows.iter()
+                    .all(|r| r.get(c).is_none_or(|cell| cell.trim().is_empty()))
+            })
+            .collect();
+        for row in &mut rows {
+            let mut c = 0;
+            row.retain(|_| {
+                let keep = !empty.contains(&c);
+                c += 1;
+                keep
+            });
+        }
+        let columns = columns - empty.len();
+        if columns == 0 {
+            return;
+        }
+
+        let prefix = self.prefix();
+        let available = self.available_width();
+
+        // Layout tables with a single column are just a sequence of blocks
+        if columns == 1 {
+            for row in &rows {
+                for line in row[0].lines().flat_map(|l| wrap(l.trim(), available)) {
+                    self.lines.push(format!("{prefix}{line}"));
+                }
+            }
+            return;
+        }
+
+        let gap = COLUMN_GAP.chars().count();
+        let mut widths: Vec<usize> = (0..columns)
+            .map(|c| {
+                rows.iter()
+                    .filter_map(|r| r.get(c))
+                    .flat_map(|cell| cell.lines())
+                    .map(|l| l.trim().chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let budget = available.saturating_sub(gap * (columns - 1));
+        while widths.iter().sum::<usize>() > budget {
+            let Some((widest, &w)) = widths.iter().enumerate().max_by_key(|(_, w)| **w) else {
+                break;
+            };
+            if w <= 4 {
+                break;
+            }
+            widths[widest] -= 1;
+        }
+
+        for row in &rows {
+            let cells: Vec<Vec<String>> = (0..columns)
+                .map(|c| {
+                    row.get(c)
+                        .map(|cell| {
+                            cell.lines()
+                                .flat_map(|l| wrap(l.trim(), widths[c]))
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                })
+                .collect();
+            let height = cells.iter().map(Vec::len).max().unwrap_or(0);
+            for i in 0..height {
+                let line = cells
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, &w)| {
+                        let text = cell.get(i).map(String::as_str).unwrap_or("");
+                        format!(
+                            "{text}{}",
+                            " ".repeat(w.saturating_sub(text.chars().count()))
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(COLUMN_GAP);
+                self.lines.push(format!("{prefix}{}", line.trim_end()));
+            }
+        }
+    }
+
+    fn finish(mut self) -> Rendered {
+        self.close_link();
+        if self.pre_depth > 0 {
+            self.flush_pre();
+        }
+        self.flush_inline();
+
+        if !self.links.is_empty() {
+            self.lines.push(String::new());
+            for (i, link) in self.links.iter().enumerate() {
+                self.lines.push(format!("[{}] {link}", i + 1));
+            }
+        }
+
+        // Collapse runs of blank lines to one separator, keeping quote
+        // markers only between lines of the same quote
+        let mut text = String::new();
+        let mut separator: Option<String> = None;
+        for line in &self.lines {
+            let line = line.replace('\u{a0}', " ");
+            let line = line.trim_end();
+            if is_blank(line) {
+                separator = Some(line.to_string());
+                continue;
+            }
+            if !text.is_empty() {
+                text.push('\n');
+                if let Some(sep) = separator.take() {
+                    if line.starts_with(&sep) {
+                        text.push_str(&sep);
+                    }
+                    text.push('\n');
+                }
+            }
+            separator = None;
+            text.push_str(line);
+        }
+
+        Rendered {
+            text,
+            links: self.links,
+        }
+    }
+}
+
+/// Whether a line holds nothing but quote markers.
+fn is_blank(line: &str) -> bool {
+    line.chars().all(|c| c == '>' || c.is_whitespace())
+}
+
+/// Collapse a table cell's collected text, keeping its explicit line breaks.
+fn finish_cell(inline: &mut String) -> String {
+    let text = std::mem::take(inline);
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn is_followable(href: &str) -> bool {
+    let lower = href.to_ascii_lowercase();
+    ["http://", "https://", "mailto:", "ftp://"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+}
+
+/// Greedy word wrap; words longer than `width` are split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for word in text.split(' ').filter(|w| !w.is_empty()) {
+        let mut word = word.to_string();
+        let mut word_len = word.chars().count();
+        if current_len > 0 && current_len + 1 + word_len > width {
+            lines.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        while word_len > width {
+            let split: String = word.chars().take(width).collect();
+            word = word.chars().skip(width).collect();
+            word_len -= width;
+            if current_len > 0 {
+                lines.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            lines.push(split);
+        }
+        if current_len > 0 {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.push_str(&word);
+        current_len += word_len;
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
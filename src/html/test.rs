@@ -0,0 +1,181 @@
+use super::*;
+
+#[test]
+fn paragraphs_are_separated_by_blank_lines() {
+    let out = render("<p>First   paragraph.</p><p>Second\nparagraph.</p>", 80);
+    assert_eq!(out.text, "First paragraph.\n\nSecond paragraph.");
+}
+
+#[test]
+fn long_lines_wrap_to_width() {
+    let out = render(
+        "<p>one two three four five six seven eight nine ten eleven twelve</p>",
+        20,
+    );
+    for line in out.text.lines() {
+        assert!(line.chars().count() <= 20, "line too long: {line:?}");
+    }
+    assert_eq!(
+        out.text,
+        "one two three four\nfive six seven eight\nnine ten eleven\ntwelve"
+    );
+}
+
+#[test]
+fn line_breaks_are_kept() {
+    let out = render("Line one<br>Line two<br><br>Line four", 80);
+    assert_eq!(out.text, "Line one\nLine two\n\nLine four");
+}
+
+#[test]
+fn headings_are_underlined() {
+    let out = render("<h1>Title</h1><h2>Sub</h2><h3>Minor</h3><p>Body</p>", 80);
+    assert_eq!(out.text, "Title\n=====\n\nSub\n---\n\nMinor\n\nBody");
+}
+
+#[test]
+fn unordered_and_ordered_lists() {
+    let out = render(
+        "<ul><li>Apples</li><li>Pears</li></ul><ol><li>First</li><li>Second</li></ol>",
+        80,
+    );
+    assert_eq!(out.text, "• Apples\n• Pears\n\n1. First\n2. Second");
+}
+
+#[test]
+fn nested_lists_indent() {
+    let out = render(
+        "<ul><li>Fruit<ul><li>Apple</li><li>Pear</li></ul></li><li>Veg</li></ul>",
+        80,
+    );
+    assert_eq!(out.text, "• Fruit\n  • Apple\n  • Pear\n• Veg");
+}
+
+#[test]
+fn list_items_hang_when_wrapped() {
+    let out = render("<ol><li>alpha beta gamma delta epsilon zeta</li></ol>", 20);
+    assert_eq!(out.text, "1. alpha beta gamma\n   delta epsilon\n   zeta");
+}
+
+#[test]
+fn blockquotes_are_prefixed() {
+    let out = render(
+        "<p>Reply</p><blockquote><p>Quoted text</p></blockquote>",
+        80,
+    );
+    assert_eq!(out.text, "Reply\n\n> Quoted text");
+}
+
+#[test]
+fn entities_are_decoded() {
+    let out = render(
+        "<p>Fish &amp; chips &mdash; &lt;tasty&gt;&nbsp;&#169; &eacute;</p>",
+        80,
+    );
+    assert_eq!(out.text, "Fish & chips — <tasty> © é");
+}
+
+#[test]
+fn scripts_styles_and_comments_are_hidden() {
+    let html = "<html><head><title>Ignore</title><style>p { color: red; }</style></head>\
+                <body><!-- hidden --><script>alert('x')</script><p>Visible</p></body></html>";
+    assert_eq!(render(html, 80).text, "Visible");
+}
+
+#[test]
+fn links_become_numbered_footnotes() {
+    let out = render(
+        "<p>Read <a href=\"https://example.com/a\">the post</a> or \
+         <a href='https://example.com/b'>this</a>, again <a href=\"https://example.com/a\">here</a>.</p>",
+        80,
+    );
+    assert_eq!(
+        out.links,
+        vec!["https://example.com/a", "https://example.com/b"]
+    );
+    assert_eq!(
+        out.text,
+        "Read the post[1] or this[2], again here[1].\n\n\
+         [1] https://example.com/a\n[2] https://example.com/b"
+    );
+}
+
+#[test]
+fn bare_links_and_anchors_get_no_marker() {
+    let out = render(
+        "<a href=\"https://example.com\">https://example.com</a> \
+         <a href=\"#top\">top</a> <a href=\"mailto:a@b.com\">a@b.com</a>",
+        80,
+    );
+    assert_eq!(out.links, vec!["https://example.com", "mailto:a@b.com"]);
+    assert!(out.text.starts_with("https://example.com top a@b.com\n\n"));
+}
+
+#[test]
+fn simple_table_is_laid_out_in_columns() {
+    let html = "<table><tr><th>Item</th><th>Qty</th></tr>\
+                <tr><td>Widget</td><td>2</td></tr>\
+                <tr><td>Gadget deluxe</td><td>10</td></tr></table>";
+    assert_eq!(
+        render(html, 80).text,
+        "Item           Qty\nWidget         2\nGadget deluxe  10"
+    );
+}
+
+#[test]
+fn single_column_layout_tables_render_as_blocks() {
+    let html = "<table><tr><td></td><td><p>Hello there</p></td><td></td></tr>\
+                <tr><td></td><td>Second row</td><td> </td></tr></table><p>After</p>";
+    assert_eq!(render(html, 80).text, "Hello there\nSecond row\n\nAfter");
+}
+
+#[test]
+fn wide_tables_wrap_cells() {
+    let html = "<table><tr><td>short</td>\
+                <td>a much longer cell that must wrap inside its column</td></tr></table>";
+    let out = render(html, 30);
+    for line in out.text.lines() {
+        assert!(line.chars().count() <= 30, "line too long: {line:?}");
+    }
+    assert!(out.text.starts_with("short  a much longer cell"));
+}
+
+#[test]
+fn preformatted_text_is_kept() {
+    let out = render("<pre>fn main() {\n    run();\n}</pre><p>done</p>", 80);
+    assert_eq!(out.text, "fn main() {\n    run();\n}\n\ndone");
+}
+
+#[test]
+fn images_show_alt_text() {
+    let out = render("<p><img src=\"x.png\" alt=\"Logo\"> News</p>", 80);
+    assert_eq!(out.text, "[Logo] News");
+}
+
+#[test]
+fn attributes_with_angle_brackets_do_not_end_tags() {
+    let out = render("<p title=\"a > b\">Text</p>", 80);
+    assert_eq!(out.text, "Text");
+}
+
+#[test]
+fn stray_angle_bracket_is_text() {
+    assert_eq!(render("1 < 2 and 3 > 2", 80).text, "1 < 2 and 3 > 2");
+}
+
+#[test]
+fn overlong_words_are_split() {
+    assert_eq!(
+        wrap("aaaaaaaaaaaaaaaaaaaaaaaaa b", 10),
+        vec!["aaaaaaaaaa", "aaaaaaaaaa", "aaaaa b"]
+    );
+}
+
+#[test]
+fn quoted_paragraphs_keep_quote_marker_between_them() {
+    let out = render(
+        "<blockquote><p>One</p><p>Two</p></blockquote><p>Three</p>",
+        80,
+    );
+    assert_eq!(out.text, "> One\n>\n> Two\n\nThree");
+}
@@ -1,7 +1,15 @@
+use chrono::{DateTime, FixedOffset};
 use imap::types::Flag;
-use native_tls::TlsConnector;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
 
-use crate::config::ImapConfig;
+use native_tls::{HandshakeError, TlsStream};
+
+use crate::config::{AuthMode, ImapConfig, TlsMode};
+use crate::flowed;
+use crate::oauth2::{self, OAuth2Error, TokenSource, XOAuth2};
+use crate::tls::{self, CertificateError, Trust};
 
 #[cfg(test)]
 mod test;
@@ -11,47 +19,306 @@ pub struct EmailSummary {
     pub uid: u32,
     pub folder: String,
     pub subject: String,
-    pub from: String,
-    pub to: String,
+    pub from: Vec<String>,
+    pub sender: Option<String>,
+    pub reply_to: Vec<String>,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
     pub date: String,
     pub seen: bool,
     pub snippet: String,
     pub message_id: Option<String>,
     pub in_reply_to: Option<String>,
     pub references: Vec<String>,
+    pub internal_date: Option<DateTime<FixedOffset>>,
+}
+
+impl EmailSummary {
+    /// The message date, parsed from the envelope `Date` header and falling
+    /// back to the server's INTERNALDATE when the header is missing or malformed.
+    pub fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        parse_date(&self.date).or(self.internal_date)
+    }
+
+    /// The first From address, which is what the inbox shows as the author.
+    pub fn author(&self) -> &str {
+        self.from.first().map(String::as_str).unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct EmailBody {
     pub uid: u32,
     pub subject: String,
-    pub from: String,
+    pub from: Vec<String>,
+    pub sender: Option<String>,
+    pub reply_to: Vec<String>,
     pub to: Vec<String>,
+    pub cc: Vec<String>,
     pub date: String,
     pub body_text: String,
+    /// Decoded HTML source when the message has no plain-text rendition.
+    pub body_html: Option<String>,
+    /// PGP/MIME protection found on the message, once checked.
+    pub security: Option<Security>,
+}
+
+/// How a message was protected, as found when opening it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Security {
+    pub scheme: Scheme,
+    pub encrypted: bool,
+    pub signature: Option<SignatureStatus>,
+}
+
+/// The kind of protection on a message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(all(feature = "pgp", feature = "smime")), allow(dead_code))]
+pub enum Scheme {
+    Pgp,
+    Smime,
+}
+
+/// A signed or encrypted message with its protection taken off.
+#[derive(Debug)]
+#[cfg_attr(not(any(feature = "pgp", feature = "smime")), allow(dead_code))]
+pub struct Opened {
+    /// The inner MIME entity, headers included.
+    pub entity: Vec<u8>,
+    pub security: Security,
+}
+
+/// Outcome of checking a signature.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(any(feature = "pgp", feature = "smime")), allow(dead_code))]
+pub enum SignatureStatus {
+    /// Good signature; holds the signer's user ID or certificate address.
+    Valid(String),
+    /// Signed by a PGP key that is not in the keyring, holding its key ID,
+    /// or by an S/MIME certificate that is not trusted, holding its address.
+    UnknownKey(String),
+    /// The signature does not match, or its key expired or was revoked.
+    Bad(String),
+    /// The signature could not be checked at all.
+    Error(String),
+}
+
+/// The connection under an IMAP session.
+pub enum Stream {
+    Tls(TlsStream<TcpStream>),
+    /// Unencrypted, for test servers on this machine.
+    Plain(TcpStream),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tls(stream) => stream.read(buf),
+            Stream::Plain(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tls(stream) => stream.write(buf),
+            Stream::Plain(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tls(stream) => stream.flush(),
+            Stream::Plain(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Start TLS on `tcp`, checking the certificate against `host`.
+fn secure(tcp: TcpStream, host: &str, trust: &Trust) -> Result<TlsStream<TcpStream>, ImapError> {
+    let stream = trust.connector()?.connect(host, tcp).map_err(|e| match e {
+        HandshakeError::Failure(ref failure) => match tls::untrusted(&failure.to_string()) {
+            Some(untrusted) => ImapError::Certificate(untrusted),
+            None => imap::Error::TlsHandshake(e).into(),
+        },
+        e => imap::Error::TlsHandshake(e).into(),
+    })?;
+    let certificate = stream.peer_certificate()?.map(|c| c.to_der()).transpose()?;
+    trust.check(certificate.as_deref())?;
+    Ok(stream)
+}
+
+/// Open a TCP connection to `host`, trying each of its addresses in turn.
+fn dial(host: &str, port: u16) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(tcp) => {
+                tcp.set_read_timeout(Some(TIMEOUT))?;
+                tcp.set_write_timeout(Some(TIMEOUT))?;
+                return Ok(tcp);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "host has no addresses")
+    }))
 }
 
+/// Read the greeting and ask the server to start TLS (RFC 3501 section
+/// 6.2.1), leaving `stream` ready for the handshake.
+fn starttls<S: Read + Write>(stream: &mut S) -> Result<(), ImapError> {
+    let greeting = read_line(stream)?;
+    if !greeting.starts_with("* OK") {
+        return Err(ImapError::Starttls(greeting));
+    }
+    stream
+        .write_all(b"a0 STARTTLS\r\n")
+        .map_err(imap::Error::Io)?;
+    loop {
+        let line = read_line(stream)?;
+        if let Some(status) = line.strip_prefix("a0 ") {
+            return match status.get(..2) {
+                Some(ok) if ok.eq_ignore_ascii_case("OK") => Ok(()),
+                _ => Err(ImapError::Starttls(status.to_string())),
+            };
+        }
+    }
+}
+
+/// One response line, read a byte at a time so nothing meant for the TLS
+/// handshake is consumed.
+fn read_line<S: Read>(stream: &mut S) -> Result<String, ImapError> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+    while !line.ends_with(b"\r\n") {
+        if stream.read(&mut byte).map_err(imap::Error::Io)? == 0 {
+            return Err(ImapError::Starttls("connection closed".to_string()));
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+/// Fetch items for a full message: the envelope, the top-level headers
+/// needed to decode a single-part body, and the body itself.
+const BODY_QUERY: &str = "(UID ENVELOPE BODY.PEEK[HEADER.FIELDS (Content-Type Content-Transfer-Encoding)] BODY.PEEK[TEXT])";
+
+/// Column width used when HTML has to be flattened to text outside the
+/// viewer, e.g. for quoting in replies.
+const TEXT_WIDTH: usize = 72;
+
 #[derive(Debug, thiserror::Error)]
 pub enum ImapError {
     #[error("TLS error: {0}")]
     Tls(#[from] native_tls::Error),
     #[error("IMAP error: {0}")]
     Imap(#[from] imap::Error),
+    #[error("OAuth2 error: {0}")]
+    OAuth2(#[from] OAuth2Error),
+    #[error("STARTTLS failed: {0}")]
+    Starttls(String),
+    #[error("certificate error: {0}")]
+    Certificate(#[from] CertificateError),
+    #[error("not connected to the IMAP server")]
+    Disconnected,
 }
 
 #[cfg_attr(test, mockall::automock)]
 pub trait ImapClient {
     fn fetch_inbox(&mut self) -> Result<Vec<EmailSummary>, ImapError>;
+    /// Fetch up to `count` of the newest messages in `folder` with a UID below `before_uid`.
+    fn fetch_older(
+        &mut self,
+        folder: &str,
+        before_uid: u32,
+        count: u32,
+    ) -> Result<Vec<EmailSummary>, ImapError>;
+    /// Fetch up to `count` of the oldest messages in `folder` with a UID above `after_uid`.
+    fn fetch_newer(
+        &mut self,
+        folder: &str,
+        after_uid: u32,
+        count: u32,
+    ) -> Result<Vec<EmailSummary>, ImapError>;
     fn fetch_email(&mut self, uid: u32, folder: &str) -> Result<EmailBody, ImapError>;
+    /// Fetch the complete RFC 5322 source of a message.
+    fn fetch_raw(&mut self, uid: u32, folder: &str) -> Result<Vec<u8>, ImapError>;
     fn mark_seen(&mut self, uid: u32, folder: &str) -> Result<(), ImapError>;
     fn delete_email(&mut self, uid: u32, folder: &str) -> Result<(), ImapError>;
     fn archive_email(&mut self, uid: u32, folder: &str) -> Result<(), ImapError>;
     fn append(&mut self, folder: &str, content: &[u8]) -> Result<(), ImapError>;
+    /// Save `content` to `folder` flagged as a draft.
+    fn append_draft(&mut self, folder: &str, content: &[u8]) -> Result<(), ImapError>;
+    /// Permanently remove the messages in `folder` with this `Message-ID`.
+    fn remove_message(&mut self, folder: &str, message_id: &str) -> Result<(), ImapError>;
+    /// Make sure the connection is still up, reconnecting once a retry is
+    /// due, and report its state. Called regularly while the app is idle.
+    fn check_connection(&mut self) -> ConnectionState;
+}
+
+/// `text` as an IMAP quoted string.
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Whether the server can be reached, as shown in the status bars.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    #[default]
+    Connected,
+    /// The connection dropped and logging in again failed `attempts` times;
+    /// the next attempt is made at `retry_at`.
+    Disconnected { attempts: u32, retry_at: Instant },
+}
+
+/// How long a connection may sit unused before it is checked with `NOOP`.
+/// Servers drop idle clients after 30 minutes at the earliest.
+const KEEPALIVE: Duration = Duration::from_secs(120);
+
+/// Limit on connecting and on waiting for a response, so a dead connection
+/// is noticed instead of hanging the app.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait between reconnect attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Wait after the `attempts`th failed reconnect: 1s, 2s, 4s, ... up to
+/// [`MAX_BACKOFF`].
+fn backoff(attempts: u32) -> Duration {
+    Duration::from_secs(1 << attempts.saturating_sub(1).min(16)).min(MAX_BACKOFF)
+}
+
+/// Whether `error` means the connection is gone rather than the command
+/// being refused.
+fn is_disconnect(error: &ImapError) -> bool {
+    matches!(
+        error,
+        ImapError::Disconnected
+            | ImapError::Imap(
+                imap::Error::Io(_) | imap::Error::Tls(_) | imap::Error::ConnectionLost
+            )
+    )
 }
 
+/// Items fetched for every message shown in the inbox list.
+const SUMMARY_QUERY: &str = "(UID ENVELOPE FLAGS INTERNALDATE BODY.PEEK[TEXT]<0.200> BODY.PEEK[HEADER.FIELDS (References)])";
+
 pub struct NativeImapClient {
-    session: imap::Session<native_tls::TlsStream<std::net::TcpStream>>,
+    /// Kept after the connection drops until a new login replaces it.
+    session: imap::Session<Stream>,
+    /// Settings to log in again with after the connection drops.
+    config: ImapConfig,
+    state: ConnectionState,
+    last_used: Instant,
     folders: Vec<String>,
+    page_size: u32,
+    #[cfg(feature = "pgp")]
+    gpg: crate::pgp::Gpg,
+    #[cfg(feature = "smime")]
+    smime: crate::smime::Smime,
 }
 
 impl NativeImapClient {
@@ -60,36 +327,184 @@ impl NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(config), err)
     )]
     pub fn connect(config: &ImapConfig) -> Result<Self, ImapError> {
-        #[cfg(feature = "tracing")]
-        tracing::trace!("building TLS connector");
-        let tls = TlsConnector::builder().build()?;
-        #[cfg(feature = "tracing")]
-        tracing::trace!("TLS connector built");
-
-        let addr = (config.host.as_str(), config.port);
+        Ok(Self {
+            session: Self::login(config)?,
+            config: config.clone(),
+            state: ConnectionState::Connected,
+            last_used: Instant::now(),
+            folders: config.folders.clone(),
+            page_size: config.page_size,
+            #[cfg(feature = "pgp")]
+            gpg: crate::pgp::Gpg::default(),
+            #[cfg(feature = "smime")]
+            smime: crate::smime::Smime::default(),
+        })
+    }
 
+    /// Connect to the server and log in.
+    fn login(config: &ImapConfig) -> Result<imap::Session<Stream>, ImapError> {
+        let tls = config.tls_mode();
         #[cfg(feature = "tracing")]
         tracing::trace!(
             host = config.host,
             port = config.port,
+            ?tls,
             "connecting to IMAP server"
         );
-        let client = imap::connect(addr, &config.host, &tls)?;
+        let trust = Trust::new(
+            config.ca_file.as_deref(),
+            config.accept_invalid_certs,
+            config.fingerprint.as_deref(),
+        )?;
+        let mut tcp = dial(&config.host, config.port).map_err(imap::Error::Io)?;
+        let stream = match tls {
+            TlsMode::None => Stream::Plain(tcp),
+            TlsMode::Implicit => Stream::Tls(secure(tcp, &config.host, &trust)?),
+            TlsMode::Starttls => {
+                starttls(&mut tcp)?;
+                Stream::Tls(secure(tcp, &config.host, &trust)?)
+            }
+        };
+        let mut client = imap::Client::new(stream);
+        // The greeting came before STARTTLS
+        if tls != TlsMode::Starttls {
+            client.read_greeting()?;
+        }
         #[cfg(feature = "tracing")]
-        tracing::trace!("TLS connection established");
+        tracing::trace!("connection established");
 
         #[cfg(feature = "tracing")]
-        tracing::trace!(user = %config.user, "logging in");
-        let session = client
-            .login(&config.user, &config.pass)
-            .map_err(|(e, _)| e)?;
+        tracing::trace!(user = %config.user, auth = ?config.auth, "logging in");
+        let session = match config.auth {
+            AuthMode::Password => client.login(&config.user, &config.pass),
+            AuthMode::Oauth2 => {
+                let oauth2 = config.oauth2.as_ref().ok_or(OAuth2Error::NotConfigured)?;
+                let tokens =
+                    TokenSource::new(oauth2, oauth2::cache_path(&config.user, &config.host));
+                let authenticator = XOAuth2 {
+                    user: config.user.clone(),
+                    token: tokens.access_token()?,
+                };
+                client.authenticate("XOAUTH2", &authenticator)
+            }
+        }
+        .map_err(|(e, _)| e)?;
         #[cfg(feature = "tracing")]
         tracing::trace!("login successful");
 
-        Ok(Self {
-            session,
-            folders: config.folders.clone(),
-        })
+        Ok(session)
+    }
+
+    /// Run `command` on the session, logging in again first if the
+    /// connection dropped. An `idempotent` command that fails because the
+    /// connection dropped is retried once on a new connection; others are
+    /// not, since the server may have carried them out already.
+    fn run<T>(
+        &mut self,
+        idempotent: bool,
+        mut command: impl FnMut(&mut Self) -> Result<T, ImapError>,
+    ) -> Result<T, ImapError> {
+        if self.check_connection() != ConnectionState::Connected {
+            return Err(ImapError::Disconnected);
+        }
+        let result = command(self);
+        self.last_used = Instant::now();
+        match result {
+            Err(e) if is_disconnect(&e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%e, "IMAP connection lost");
+                self.reconnect();
+                if idempotent && self.state == ConnectionState::Connected {
+                    let result = command(self);
+                    self.last_used = Instant::now();
+                    result
+                } else {
+                    Err(e)
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Log in again, giving up until the backoff has passed if that fails.
+    fn reconnect(&mut self) {
+        let attempts = match self.state {
+            ConnectionState::Connected => 0,
+            ConnectionState::Disconnected { attempts, .. } => attempts,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::trace!(attempts, "reconnecting to IMAP server");
+        match Self::login(&self.config) {
+            Ok(session) => {
+                // Replacing the dead session drops it without a LOGOUT
+                self.session = session;
+                self.state = ConnectionState::Connected;
+                self.last_used = Instant::now();
+            }
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%_e, attempts = attempts + 1, "reconnect failed");
+                self.state = ConnectionState::Disconnected {
+                    attempts: attempts + 1,
+                    retry_at: Instant::now() + backoff(attempts + 1),
+                };
+            }
+        }
+    }
+
+    /// Use `gpg` to verify and decrypt PGP/MIME messages.
+    #[cfg(feature = "pgp")]
+    pub fn with_gpg(mut self, gpg: crate::pgp::Gpg) -> Self {
+        self.gpg = gpg;
+        self
+    }
+
+    /// Use `smime` to verify and decrypt S/MIME messages.
+    #[cfg(feature = "smime")]
+    pub fn with_smime(mut self, smime: crate::smime::Smime) -> Self {
+        self.smime = smime;
+        self
+    }
+
+    /// Take PGP/MIME or S/MIME protection off a message with the given
+    /// top-level headers. Unprotected messages, and schemes not built in,
+    /// give `None`.
+    #[cfg_attr(not(any(feature = "pgp", feature = "smime")), allow(unused_variables))]
+    fn open(&self, header: &str, text: &[u8]) -> Option<Result<Opened, (Scheme, String)>> {
+        #[cfg(feature = "pgp")]
+        {
+            let content_type = header_value(header, "content-type").unwrap_or_default();
+            if let Some(result) = self.gpg.open(&content_type, text) {
+                return Some(result.map_err(|e| (Scheme::Pgp, e.to_string())));
+            }
+        }
+        #[cfg(feature = "smime")]
+        if let Some(result) = self.smime.open(header, text) {
+            return Some(result.map_err(|e| (Scheme::Smime, e.to_string())));
+        }
+        None
+    }
+
+    /// Fetch summaries for the given UIDs in the currently selected folder.
+    fn fetch_uids(&mut self, folder: &str, uids: &[u32]) -> Result<Vec<EmailSummary>, ImapError> {
+        if uids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let uid_set = uids
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        #[cfg(feature = "tracing")]
+        tracing::trace!(folder, count = uids.len(), "fetching page of messages");
+        let messages = self.session.uid_fetch(uid_set, SUMMARY_QUERY)?;
+
+        Ok(parse_summaries(
+            &messages,
+            folder,
+            &mut std::collections::HashSet::new(),
+        ))
     }
 }
 
@@ -99,96 +514,97 @@ impl ImapClient for NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
     )]
     fn fetch_inbox(&mut self) -> Result<Vec<EmailSummary>, ImapError> {
-        let mut emails = Vec::new();
-        let mut seen_message_ids: std::collections::HashSet<String> =
-            std::collections::HashSet::new();
+        self.run(true, |client| {
+            let mut emails = Vec::new();
+            let mut seen_message_ids: std::collections::HashSet<String> =
+                std::collections::HashSet::new();
+
+            for folder in &client.folders.clone() {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(folder = %folder, "selecting folder");
+                let mailbox = client.session.select(folder)?;
+                #[cfg(feature = "tracing")]
+                tracing::trace!(exists = mailbox.exists, "folder selected");
+
+                if mailbox.exists == 0 {
+                    continue;
+                }
 
-        for folder in &self.folders.clone() {
-            #[cfg(feature = "tracing")]
-            tracing::trace!(folder = %folder, "selecting folder");
-            self.session.select(folder)?;
-            #[cfg(feature = "tracing")]
-            tracing::trace!("folder selected");
+                // Only the newest page of each folder is loaded up front; older
+                // messages are paged in by UID with `fetch_older`.
+                let start = mailbox.exists.saturating_sub(client.page_size) + 1;
+
+                #[cfg(feature = "tracing")]
+                tracing::trace!(start, end = mailbox.exists, "fetching messages");
+                let messages = client
+                    .session
+                    .fetch(format!("{start}:{}", mailbox.exists), SUMMARY_QUERY)?;
+                #[cfg(feature = "tracing")]
+                tracing::trace!(raw_count = messages.len(), "messages fetched from server");
+
+                emails.extend(parse_summaries(&messages, folder, &mut seen_message_ids));
+            }
 
             #[cfg(feature = "tracing")]
-            tracing::trace!("fetching messages");
-            let messages = self.session.fetch(
-                "1:*",
-                "(UID ENVELOPE FLAGS BODY.PEEK[TEXT]<0.200> BODY.PEEK[HEADER.FIELDS (References)])",
-            )?;
-            #[cfg(feature = "tracing")]
-            tracing::trace!(raw_count = messages.len(), "messages fetched from server");
-
-            for fetch in messages.iter() {
-                let uid = fetch.uid.unwrap_or(0);
-
-                let seen = fetch.flags().iter().any(|f| matches!(f, Flag::Seen));
-
-                let snippet = fetch.text().map(extract_snippet).unwrap_or_default();
-
-                let references = fetch.header().map(parse_references).unwrap_or_default();
-
-                if let Some(envelope) = fetch.envelope() {
-                    let subject = envelope
-                        .subject
-                        .map(|s| String::from_utf8_lossy(s).into_owned())
-                        .unwrap_or_default();
-
-                    let from = envelope
-                        .from
-                        .as_ref()
-                        .and_then(|addrs| addrs.first())
-                        .map(format_address)
-                        .unwrap_or_default();
-
-                    let to = envelope
-                        .to
-                        .as_ref()
-                        .and_then(|addrs| addrs.first())
-                        .map(format_address)
-                        .unwrap_or_default();
-
-                    let date = envelope
-                        .date
-                        .map(|d| String::from_utf8_lossy(d).into_owned())
-                        .unwrap_or_default();
-
-                    let message_id = envelope
-                        .message_id
-                        .map(|m| String::from_utf8_lossy(m).into_owned());
-
-                    let in_reply_to = envelope
-                        .in_reply_to
-                        .map(|r| String::from_utf8_lossy(r).into_owned());
-
-                    // Deduplicate by message_id (keep first seen)
-                    if let Some(ref mid) = message_id
-                        && !seen_message_ids.insert(mid.clone())
-                    {
-                        continue;
-                    }
+            tracing::trace!(count = emails.len(), "emails parsed");
 
-                    emails.push(EmailSummary {
-                        uid,
-                        folder: folder.clone(),
-                        subject,
-                        from,
-                        to,
-                        date,
-                        seen,
-                        snippet,
-                        message_id,
-                        in_reply_to,
-                        references,
-                    });
-                }
+            Ok(emails)
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
+    )]
+    fn fetch_older(
+        &mut self,
+        folder: &str,
+        before_uid: u32,
+        count: u32,
+    ) -> Result<Vec<EmailSummary>, ImapError> {
+        self.run(true, |client| {
+            if before_uid <= 1 {
+                return Ok(Vec::new());
             }
-        }
 
-        #[cfg(feature = "tracing")]
-        tracing::trace!(count = emails.len(), "emails parsed");
+            client.session.select(folder)?;
+            let mut uids: Vec<u32> = client
+                .session
+                .uid_search(format!("UID 1:{}", before_uid - 1))?
+                .into_iter()
+                .filter(|&uid| uid < before_uid)
+                .collect();
+            uids.sort_unstable();
+            let page = &uids[uids.len().saturating_sub(count as usize)..];
+
+            client.fetch_uids(folder, page)
+        })
+    }
 
-        Ok(emails)
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
+    )]
+    fn fetch_newer(
+        &mut self,
+        folder: &str,
+        after_uid: u32,
+        count: u32,
+    ) -> Result<Vec<EmailSummary>, ImapError> {
+        self.run(true, |client| {
+            client.session.select(folder)?;
+            // `n:*` always includes the highest UID, even when it is below n
+            let mut uids: Vec<u32> = client
+                .session
+                .uid_search(format!("UID {}:*", after_uid + 1))?
+                .into_iter()
+                .filter(|&uid| uid > after_uid)
+                .collect();
+            uids.sort_unstable();
+            uids.truncate(count as usize);
+
+            client.fetch_uids(folder, &uids)
+        })
     }
 
     #[cfg_attr(
@@ -196,53 +612,96 @@ impl ImapClient for NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
     )]
     fn fetch_email(&mut self, uid: u32, folder: &str) -> Result<EmailBody, ImapError> {
-        #[cfg(feature = "tracing")]
-        tracing::trace!(uid, folder, "fetching email body");
-
-        self.session.select(folder)?;
-        let messages = self
-            .session
-            .uid_fetch(uid.to_string(), "(UID ENVELOPE BODY.PEEK[TEXT])")?;
+        self.run(true, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(uid, folder, "fetching email body");
 
-        let fetch = messages
-            .iter()
-            .next()
-            .ok_or_else(|| imap::Error::Bad("message not found".to_string()))?;
+            client.session.select(folder)?;
+            let messages = client.session.uid_fetch(uid.to_string(), BODY_QUERY)?;
 
-        let body_text = fetch.text().map(extract_body_text).unwrap_or_default();
+            let fetch = messages
+                .iter()
+                .next()
+                .ok_or_else(|| imap::Error::Bad("message not found".to_string()))?;
 
-        let (subject, from, to, date) = if let Some(envelope) = fetch.envelope() {
-            let subject = envelope
-                .subject
-                .map(|s| String::from_utf8_lossy(s).into_owned())
-                .unwrap_or_default();
-            let from = envelope
-                .from
-                .as_ref()
-                .and_then(|addrs| addrs.first())
-                .map(format_address)
+            let header = fetch
+                .header()
+                .map(String::from_utf8_lossy)
                 .unwrap_or_default();
-            let to = envelope
-                .to
-                .as_ref()
-                .map(|addrs| addrs.iter().map(format_address).collect())
-                .unwrap_or_default();
-            let date = envelope
-                .date
-                .map(|d| String::from_utf8_lossy(d).into_owned())
-                .unwrap_or_default();
-            (subject, from, to, date)
-        } else {
-            (String::new(), String::new(), Vec::new(), String::new())
-        };
+            let text = fetch.text().unwrap_or_default();
+
+            let (body_text, body_html, security) = match client.open(&header, text) {
+                Some(Ok(opened)) => {
+                    let (header, body) = crate::mime::split_header(&opened.entity);
+                    let (body_text, body_html) =
+                        message_text(&String::from_utf8_lossy(header), body);
+                    (body_text, body_html, Some(opened.security))
+                }
+                Some(Err((scheme, e))) => {
+                    let security = Security {
+                        scheme,
+                        encrypted: true,
+                        signature: None,
+                    };
+                    (
+                        format!("Could not decrypt this message: {e}"),
+                        None,
+                        Some(security),
+                    )
+                }
+                None => {
+                    let (body_text, body_html) = message_text(&header, text);
+                    (body_text, body_html, None)
+                }
+            };
 
-        Ok(EmailBody {
-            uid,
-            subject,
-            from,
-            to,
-            date,
-            body_text,
+            let (subject, addresses, date) = if let Some(envelope) = fetch.envelope() {
+                let subject = envelope
+                    .subject
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .unwrap_or_default();
+                let date = envelope
+                    .date
+                    .map(|d| String::from_utf8_lossy(d).into_owned())
+                    .unwrap_or_default();
+                (subject, EnvelopeAddresses::from(envelope), date)
+            } else {
+                (String::new(), EnvelopeAddresses::default(), String::new())
+            };
+
+            Ok(EmailBody {
+                uid,
+                subject,
+                from: addresses.from,
+                sender: addresses.sender,
+                reply_to: addresses.reply_to,
+                to: addresses.to,
+                cc: addresses.cc,
+                date,
+                body_text,
+                body_html,
+                security,
+            })
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
+    )]
+    fn fetch_raw(&mut self, uid: u32, folder: &str) -> Result<Vec<u8>, ImapError> {
+        self.run(true, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(uid, folder, "fetching raw message");
+
+            client.session.select(folder)?;
+            let messages = client.session.uid_fetch(uid.to_string(), "BODY.PEEK[]")?;
+            let raw = messages
+                .iter()
+                .next()
+                .and_then(|fetch| fetch.body())
+                .ok_or_else(|| imap::Error::Bad("message not found".to_string()))?;
+            Ok(raw.to_vec())
         })
     }
 
@@ -251,12 +710,16 @@ impl ImapClient for NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
     )]
     fn mark_seen(&mut self, uid: u32, folder: &str) -> Result<(), ImapError> {
-        #[cfg(feature = "tracing")]
-        tracing::trace!(uid, folder, "marking as seen");
+        self.run(true, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(uid, folder, "marking as seen");
 
-        self.session.select(folder)?;
-        self.session.uid_store(uid.to_string(), "+FLAGS (\\Seen)")?;
-        Ok(())
+            client.session.select(folder)?;
+            client
+                .session
+                .uid_store(uid.to_string(), "+FLAGS (\\Seen)")?;
+            Ok(())
+        })
     }
 
     #[cfg_attr(
@@ -264,12 +727,14 @@ impl ImapClient for NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
     )]
     fn delete_email(&mut self, uid: u32, folder: &str) -> Result<(), ImapError> {
-        #[cfg(feature = "tracing")]
-        tracing::trace!(uid, folder, "moving to Trash");
+        self.run(true, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(uid, folder, "moving to Trash");
 
-        self.session.select(folder)?;
-        self.session.uid_mv(uid.to_string(), "Trash")?;
-        Ok(())
+            client.session.select(folder)?;
+            client.session.uid_mv(uid.to_string(), "Trash")?;
+            Ok(())
+        })
     }
 
     #[cfg_attr(
@@ -277,12 +742,14 @@ impl ImapClient for NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
     )]
     fn archive_email(&mut self, uid: u32, folder: &str) -> Result<(), ImapError> {
-        #[cfg(feature = "tracing")]
-        tracing::trace!(uid, folder, "moving to Archive");
+        self.run(true, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(uid, folder, "moving to Archive");
 
-        self.session.select(folder)?;
-        self.session.uid_mv(uid.to_string(), "Archive")?;
-        Ok(())
+            client.session.select(folder)?;
+            client.session.uid_mv(uid.to_string(), "Archive")?;
+            Ok(())
+        })
     }
 
     #[cfg_attr(
@@ -290,22 +757,194 @@ impl ImapClient for NativeImapClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(self, content), err)
     )]
     fn append(&mut self, folder: &str, content: &[u8]) -> Result<(), ImapError> {
-        #[cfg(feature = "tracing")]
-        tracing::trace!(folder, bytes = content.len(), "appending to folder");
+        self.run(false, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(folder, bytes = content.len(), "appending to folder");
 
-        self.session.append(folder, content)?;
+            client.session.append(folder, content)?;
 
-        #[cfg(feature = "tracing")]
-        tracing::trace!("append successful");
+            #[cfg(feature = "tracing")]
+            tracing::trace!("append successful");
 
-        Ok(())
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, content), err)
+    )]
+    fn append_draft(&mut self, folder: &str, content: &[u8]) -> Result<(), ImapError> {
+        self.run(false, |client| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(folder, bytes = content.len(), "saving draft");
+
+            client
+                .session
+                .append_with_flags(folder, content, &[Flag::Draft])?;
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
+    )]
+    fn remove_message(&mut self, folder: &str, message_id: &str) -> Result<(), ImapError> {
+        self.run(true, |client| {
+            client.session.select(folder)?;
+            let uids = client
+                .session
+                .uid_search(format!("HEADER Message-ID {}", quoted(message_id)))?;
+            if uids.is_empty() {
+                return Ok(());
+            }
+            let uid_set = uids
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!(folder, uid_set, "removing message");
+
+            client.session.uid_store(&uid_set, "+FLAGS (\\Deleted)")?;
+            // Other messages marked deleted in the folder are left alone
+            client.session.uid_expunge(&uid_set)?;
+            Ok(())
+        })
+    }
+
+    fn check_connection(&mut self) -> ConnectionState {
+        match self.state {
+            ConnectionState::Connected if self.last_used.elapsed() >= KEEPALIVE => {
+                self.last_used = Instant::now();
+                if let Err(_e) = self.session.noop() {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(%_e, "IMAP connection lost");
+                    self.reconnect();
+                }
+            }
+            ConnectionState::Disconnected { retry_at, .. } if Instant::now() >= retry_at => {
+                self.reconnect();
+            }
+            _ => {}
+        }
+        self.state
     }
 }
 
 impl Drop for NativeImapClient {
     fn drop(&mut self) {
-        let _ = self.session.logout();
+        if self.state == ConnectionState::Connected {
+            let _ = self.session.logout();
+        }
+    }
+}
+
+fn parse_summaries(
+    messages: &[imap::types::Fetch],
+    folder: &str,
+    seen_message_ids: &mut std::collections::HashSet<String>,
+) -> Vec<EmailSummary> {
+    let mut emails = Vec::new();
+
+    for fetch in messages.iter() {
+        let uid = fetch.uid.unwrap_or(0);
+
+        let seen = fetch.flags().iter().any(|f| matches!(f, Flag::Seen));
+
+        let snippet = fetch.text().map(extract_snippet).unwrap_or_default();
+
+        let references = fetch.header().map(parse_references).unwrap_or_default();
+
+        let internal_date = fetch.internal_date();
+
+        if let Some(envelope) = fetch.envelope() {
+            let subject = envelope
+                .subject
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .unwrap_or_default();
+
+            let addresses = EnvelopeAddresses::from(envelope);
+
+            let date = envelope
+                .date
+                .map(|d| String::from_utf8_lossy(d).into_owned())
+                .unwrap_or_default();
+
+            let message_id = envelope
+                .message_id
+                .map(|m| String::from_utf8_lossy(m).into_owned());
+
+            let in_reply_to = envelope
+                .in_reply_to
+                .map(|r| String::from_utf8_lossy(r).into_owned());
+
+            // Deduplicate by message_id (keep first seen)
+            if let Some(ref mid) = message_id
+                && !seen_message_ids.insert(mid.clone())
+            {
+                continue;
+            }
+
+            emails.push(EmailSummary {
+                uid,
+                folder: folder.to_string(),
+                subject,
+                from: addresses.from,
+                sender: addresses.sender,
+                reply_to: addresses.reply_to,
+                to: addresses.to,
+                cc: addresses.cc,
+                date,
+                seen,
+                snippet,
+                message_id,
+                in_reply_to,
+                references,
+                internal_date,
+            });
+        }
     }
+
+    emails
+}
+
+/// The address fields of an envelope, formatted for display.
+#[derive(Debug, Default, PartialEq)]
+struct EnvelopeAddresses {
+    from: Vec<String>,
+    sender: Option<String>,
+    reply_to: Vec<String>,
+    to: Vec<String>,
+    cc: Vec<String>,
+}
+
+impl From<&imap_proto::Envelope<'_>> for EnvelopeAddresses {
+    fn from(envelope: &imap_proto::Envelope<'_>) -> Self {
+        Self {
+            from: format_addresses(&envelope.from),
+            sender: format_addresses(&envelope.sender).into_iter().next(),
+            reply_to: format_addresses(&envelope.reply_to),
+            to: format_addresses(&envelope.to),
+            cc: format_addresses(&envelope.cc),
+        }
+    }
+}
+
+fn format_addresses(addrs: &Option<Vec<imap_proto::Address>>) -> Vec<String> {
+    addrs
+        .as_ref()
+        .map(|addrs| {
+            addrs
+                .iter()
+                // Group syntax shows up as entries without a host
+                .filter(|a| a.host.is_some())
+                .map(format_address)
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 fn format_address(addr: &imap_proto::Address) -> String {
@@ -334,6 +973,21 @@ fn format_address(addr: &imap_proto::Address) -> String {
     email
 }
 
+/// Parse an RFC 2822 date header, tolerating a trailing comment such as
+/// `(UTC)` which many mailers append.
+pub fn parse_date(raw: &str) -> Option<DateTime<FixedOffset>> {
+    let trimmed = raw.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc2822(trimmed) {
+        return Some(parsed);
+    }
+
+    let without_comment = match trimmed.rfind('(') {
+        Some(start) if trimmed.ends_with(')') => trimmed[..start].trim_end(),
+        _ => return None,
+    };
+    DateTime::parse_from_rfc2822(without_comment).ok()
+}
+
 pub fn parse_references(raw: &[u8]) -> Vec<String> {
     let text = String::from_utf8_lossy(raw);
     let mut refs = Vec::new();
@@ -357,37 +1011,93 @@ pub fn parse_references(raw: &[u8]) -> Vec<String> {
     refs
 }
 
+/// The readable text of a message body, and its HTML when there is no
+/// plain-text rendition, given the top-level headers.
+fn message_text(header: &str, text: &[u8]) -> (String, Option<String>) {
+    let raw = decode_single_part(header, text);
+    let content_type = header_value(header, "content-type")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if content_type.starts_with("text/html") {
+        let html = String::from_utf8_lossy(&raw).into_owned();
+        (html_to_text(&html), Some(html))
+    } else {
+        (
+            unflow(&content_type, extract_body_text(&raw)),
+            extract_body_html(&raw),
+        )
+    }
+}
+
 pub fn extract_body_text(raw: &[u8]) -> String {
     let text = String::from_utf8_lossy(raw);
 
-    if let Some(mime_text) = extract_mime_text(&text) {
-        return mime_text;
+    if let Some((plain, html)) = extract_mime_parts(&text) {
+        // Prefer plaintext, fall back to rendered HTML
+        match (plain, html) {
+            (Some(plain), _) => return plain,
+            (None, Some(html)) => return html_to_text(&html),
+            (None, None) => {}
+        }
     }
 
+    let body = single_part_body(&text);
+    if looks_like_html(&body) {
+        html_to_text(&body)
+    } else {
+        body
+    }
+}
+
+/// The HTML source of a message that has no plain-text rendition, so the
+/// viewer can lay it out at the width it has available.
+pub fn extract_body_html(raw: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(raw);
+
+    if let Some((plain, html)) = extract_mime_parts(&text) {
+        if plain.is_some() {
+            return None;
+        }
+        if html.is_some() {
+            return html;
+        }
+    }
+
+    let body = single_part_body(&text);
+    looks_like_html(&body).then_some(body)
+}
+
+fn html_to_text(html: &str) -> String {
+    crate::html::render(html, TEXT_WIDTH).text
+}
+
+fn single_part_body(text: &str) -> String {
     let trimmed = text.trim_start();
 
     // Raw HTML email (no MIME wrapper)
     if trimmed.starts_with("<!") || trimmed.starts_with("<html") || trimmed.starts_with("<HTML") {
-        return strip_html_tags(&text);
+        return text.to_string();
     }
 
-    let body = if text.contains("Content-Type:") {
+    if text.contains("Content-Type:") {
         text.split_once("\r\n\r\n")
             .or_else(|| text.split_once("\n\n"))
             .map(|(_, body)| body.to_string())
-            .unwrap_or_else(|| text.into_owned())
+            .unwrap_or_else(|| text.to_string())
     } else {
-        text.into_owned()
-    };
-
-    // If it looks like HTML, strip tags
-    if body.contains('<') && body.contains('>') {
-        strip_html_tags(&body)
-    } else {
-        body
+        text.to_string()
     }
 }
 
+fn looks_like_html(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    [
+        "<html", "<body", "<p>", "<p ", "<div", "<br", "<table", "</a>",
+    ]
+    .iter()
+    .any(|tag| lower.contains(tag))
+}
+
 pub fn extract_snippet(raw: &[u8]) -> String {
     let text = String::from_utf8_lossy(raw);
 
@@ -417,6 +1127,12 @@ pub fn extract_snippet(raw: &[u8]) -> String {
 }
 
 fn extract_mime_text(text: &str) -> Option<String> {
+    let (plain, html) = extract_mime_parts(text)?;
+    plain.or_else(|| html.map(|h| strip_html_tags(&h)))
+}
+
+/// The decoded `text/plain` and `text/html` parts of a multipart body.
+fn extract_mime_parts(text: &str) -> Option<(Option<String>, Option<String>)> {
     // Look for a multipart boundary from a Content-Type header in the text
     let boundary = text
         .lines()
@@ -447,19 +1163,18 @@ fn extract_mime_text(text: &str) -> Option<String> {
     for part in &parts {
         let lower = part.to_lowercase();
         if (lower.contains("content-type: text/plain") || lower.contains("content-type:text/plain"))
-            && let Some(body) = extract_part_body(part)
+            && let Some(body) = decode_part(part)
         {
-            plain_text = Some(body.to_string());
+            plain_text = Some(body);
         } else if (lower.contains("content-type: text/html")
             || lower.contains("content-type:text/html"))
-            && let Some(body) = extract_part_body(part)
+            && let Some(body) = decode_part(part)
         {
-            html_text = Some(strip_html_tags(body));
+            html_text = Some(body);
         }
     }
 
-    // Prefer plaintext, fall back to stripped HTML
-    plain_text.or(html_text)
+    Some((plain_text, html_text))
 }
 
 fn extract_part_body(part: &str) -> Option<&str> {
@@ -472,6 +1187,75 @@ fn extract_part_body(part: &str) -> Option<&str> {
     }
 }
 
+/// The body of a MIME part with its Content-Transfer-Encoding undone.
+fn decode_part(part: &str) -> Option<String> {
+    let body = extract_part_body(part)?;
+    let headers = &part[..part.len() - body.len()];
+    let decoded = decode_transfer_encoding(
+        header_value(headers, "content-transfer-encoding").as_deref(),
+        body.as_bytes(),
+    );
+    let text = String::from_utf8_lossy(&decoded).into_owned();
+    Some(match header_value(headers, "content-type") {
+        Some(content_type) => unflow(&content_type, text),
+        None => text,
+    })
+}
+
+/// Join the soft-broken lines of a `format=flowed` text body; other
+/// content types are returned as they are.
+fn unflow(content_type: &str, text: String) -> String {
+    let param = |name| crate::mime::param(content_type, name).map(|v| v.to_ascii_lowercase());
+    if param("format").as_deref() == Some("flowed") {
+        flowed::decode(&text, param("delsp").as_deref() == Some("yes"))
+    } else {
+        text
+    }
+}
+
+/// Undo the top-level transfer encoding of a single-part message body.
+/// Multipart bodies are returned untouched; their parts are decoded one by one.
+fn decode_single_part(header: &str, body: &[u8]) -> Vec<u8> {
+    let is_multipart = header_value(header, "content-type")
+        .is_some_and(|ct| ct.to_ascii_lowercase().starts_with("multipart/"));
+    if is_multipart {
+        return body.to_vec();
+    }
+    decode_transfer_encoding(
+        header_value(header, "content-transfer-encoding").as_deref(),
+        body,
+    )
+}
+
+fn decode_transfer_encoding(encoding: Option<&str>, body: &[u8]) -> Vec<u8> {
+    let encoding = encoding.map(|e| e.trim().to_ascii_lowercase());
+    crate::mime::decode(encoding.as_deref(), body)
+}
+
+/// The value of header `name` in a raw header block, with folded
+/// continuation lines joined.
+fn header_value(headers: &str, name: &str) -> Option<String> {
+    let mut value: Option<String> = None;
+    for line in headers.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some(ref mut v) = value {
+                v.push(' ');
+                v.push_str(line.trim());
+            }
+            continue;
+        }
+        if value.is_some() {
+            break;
+        }
+        if let Some((key, rest)) = line.split_once(':')
+            && key.trim().eq_ignore_ascii_case(name)
+        {
+            value = Some(rest.trim().to_string());
+        }
+    }
+    value
+}
+
 fn strip_html_tags(input: &str) -> String {
     // First pass: remove <style>...</style> and <script>...</script> blocks
     let mut text = input.to_string();
@@ -52,14 +52,18 @@ fn mock_client_returns_emails() {
             uid: 1,
             folder: "INBOX".to_string(),
             subject: "Hello".to_string(),
-            from: "alice@example.com".to_string(),
-            to: "me@example.com".to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             seen: false,
             snippet: "Hey there".to_string(),
             message_id: None,
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         }])
     });
 
@@ -268,3 +272,455 @@ fn strip_html_tags_decodes_entities() {
     let result = strip_html_tags(input);
     assert_eq!(result, "foo bar & baz <ok>");
 }
+
+#[test]
+fn parse_date_rfc2822() {
+    let parsed = parse_date("Mon, 14 Feb 2022 10:00:00 +0000").unwrap();
+    assert_eq!(parsed.to_rfc3339(), "2022-02-14T10:00:00+00:00");
+}
+
+#[test]
+fn parse_date_with_trailing_comment() {
+    let parsed = parse_date("Mon, 14 Feb 2022 10:00:00 +0000 (UTC)").unwrap();
+    assert_eq!(parsed.to_rfc3339(), "2022-02-14T10:00:00+00:00");
+}
+
+#[test]
+fn parse_date_invalid() {
+    assert!(parse_date("not a date").is_none());
+    assert!(parse_date("").is_none());
+}
+
+#[test]
+fn extract_body_text_decodes_quoted_printable_part() {
+    let input = b"--qp\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        Caf=C3=A9 prices are =3D 3 euros and this line is soft=\r\n\
+        ly wrapped\r\n\
+        --qp--";
+    let result = extract_body_text(input);
+    assert!(
+        result.contains("Café prices are = 3 euros and this line is softly wrapped"),
+        "got: {result}"
+    );
+}
+
+#[test]
+fn extract_body_text_decodes_base64_part() {
+    // "Hello from base64\n"
+    let input = b"--b64\r\n\
+        Content-Type: text/plain\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        SGVsbG8gZnJv\r\n\
+        bSBiYXNlNjQK\r\n\
+        --b64--";
+    assert!(extract_body_text(input).contains("Hello from base64"));
+}
+
+#[test]
+fn extract_body_html_only_without_plain_part() {
+    let html_only = b"--h\r\n\
+        Content-Type: text/html\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        <p style=3D\"margin:0\">Hi</p>\r\n\
+        --h--";
+    assert_eq!(
+        extract_body_html(html_only).as_deref().map(str::trim),
+        Some("<p style=\"margin:0\">Hi</p>")
+    );
+
+    let with_plain = b"--a\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        Hi\r\n\
+        --a\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <p>Hi</p>\r\n\
+        --a--";
+    assert_eq!(extract_body_html(with_plain), None);
+    assert_eq!(extract_body_html(b"Plain text <bob@example.com>"), None);
+}
+
+#[test]
+fn extract_body_text_renders_html_structure() {
+    let input = b"--l\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <p>Agenda</p><ol><li>Intro</li><li>Wrap up</li></ol>\r\n\
+        --l--";
+    assert_eq!(extract_body_text(input), "Agenda\n\n1. Intro\n2. Wrap up");
+}
+
+#[test]
+fn decode_single_part_uses_top_level_headers() {
+    let header = "Content-Type: text/html;\r\n charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n";
+    assert_eq!(
+        decode_single_part(header, b"<p>a=3Db</p>"),
+        b"<p>a=b</p>".to_vec()
+    );
+
+    let multipart = "Content-Type: multipart/alternative; boundary=x\r\n\
+                     Content-Transfer-Encoding: quoted-printable\r\n";
+    assert_eq!(decode_single_part(multipart, b"a=3Db"), b"a=3Db".to_vec());
+}
+
+#[test]
+fn header_value_unfolds_continuation_lines() {
+    let headers = "Subject: Hi\r\nContent-Type: text/plain;\r\n\tcharset=utf-8\r\nX-Other: 1\r\n";
+    assert_eq!(
+        header_value(headers, "content-type").as_deref(),
+        Some("text/plain; charset=utf-8")
+    );
+    assert_eq!(header_value(headers, "missing"), None);
+}
+
+fn address<'a>(
+    name: Option<&'a [u8]>,
+    mailbox: &'a [u8],
+    host: Option<&'a [u8]>,
+) -> imap_proto::Address<'a> {
+    imap_proto::Address {
+        name,
+        adl: None,
+        mailbox: Some(mailbox),
+        host,
+    }
+}
+
+#[test]
+fn envelope_addresses_keep_every_recipient() {
+    let envelope = imap_proto::Envelope {
+        date: None,
+        subject: None,
+        from: Some(vec![address(
+            Some(b"Alice"),
+            b"alice",
+            Some(b"example.com"),
+        )]),
+        sender: Some(vec![address(None, b"list", Some(b"example.com"))]),
+        reply_to: Some(vec![address(None, b"team", Some(b"example.com"))]),
+        to: Some(vec![
+            address(None, b"me", Some(b"example.com")),
+            address(Some(b"Bob"), b"bob", Some(b"example.com")),
+        ]),
+        cc: Some(vec![
+            // Group syntax: start and end markers have no host
+            address(None, b"friends", None),
+            address(None, b"carol", Some(b"example.com")),
+            imap_proto::Address {
+                name: None,
+                adl: None,
+                mailbox: None,
+                host: None,
+            },
+        ]),
+        bcc: None,
+        in_reply_to: None,
+        message_id: None,
+    };
+
+    assert_eq!(
+        EnvelopeAddresses::from(&envelope),
+        EnvelopeAddresses {
+            from: vec!["Alice <alice@example.com>".to_string()],
+            sender: Some("list@example.com".to_string()),
+            reply_to: vec!["team@example.com".to_string()],
+            to: vec![
+                "me@example.com".to_string(),
+                "Bob <bob@example.com>".to_string(),
+            ],
+            cc: vec!["carol@example.com".to_string()],
+        }
+    );
+}
+
+#[test]
+fn extract_body_text_decodes_format_flowed_part() {
+    let input = b"--f\r\n\
+        Content-Type: text/plain; charset=utf-8; format=flowed; delsp=yes\r\n\
+        \r\n\
+        A para \r\n\
+        graph.\r\n\
+        > quo \r\n\
+        > ted\r\n\
+        --f--";
+    assert_eq!(extract_body_text(input), "A paragraph.\n> quoted");
+}
+
+#[test]
+fn unflow_only_touches_flowed_text() {
+    let text = "soft \nbreak".to_string();
+    assert_eq!(
+        unflow("text/plain; format=flowed", text.clone()),
+        "soft break"
+    );
+    assert_eq!(unflow("text/plain; charset=utf-8", text.clone()), text);
+}
+
+/// A connection replaying `input` and recording what the client sends.
+struct Script {
+    input: std::io::Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl Script {
+    fn new(input: &str) -> Self {
+        Self {
+            input: std::io::Cursor::new(input.as_bytes().to_vec()),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for Script {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn starttls_negotiation() {
+    let mut stream = Script::new(
+        "* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n\
+         * CAPABILITY IMAP4rev1\r\n\
+         a0 OK Begin TLS negotiation now\r\n\
+         \x16\x03\x01",
+    );
+    starttls(&mut stream).unwrap();
+    assert_eq!(stream.output, b"a0 STARTTLS\r\n");
+    // The handshake bytes are left for the TLS connector
+    assert_eq!(
+        stream.input.position(),
+        stream.input.get_ref().len() as u64 - 3
+    );
+
+    let mut refused = Script::new("* OK ready\r\na0 NO STARTTLS disabled\r\n");
+    assert!(matches!(
+        starttls(&mut refused),
+        Err(ImapError::Starttls(reason)) if reason == "NO STARTTLS disabled"
+    ));
+
+    let mut bye = Script::new("* BYE overloaded\r\n");
+    assert!(matches!(starttls(&mut bye), Err(ImapError::Starttls(_))));
+    assert!(bye.output.is_empty());
+
+    let mut closed = Script::new("* OK ready\r\n");
+    assert!(matches!(
+        starttls(&mut closed),
+        Err(ImapError::Starttls(reason)) if reason == "connection closed"
+    ));
+}
+
+#[test]
+fn backoff_doubles_up_to_limit() {
+    assert_eq!(backoff(1), Duration::from_secs(1));
+    assert_eq!(backoff(2), Duration::from_secs(2));
+    assert_eq!(backoff(5), Duration::from_secs(16));
+    assert_eq!(backoff(9), Duration::from_secs(256));
+    assert_eq!(backoff(10), MAX_BACKOFF);
+    assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+}
+
+#[test]
+fn only_connection_failures_count_as_disconnects() {
+    assert!(is_disconnect(&ImapError::Imap(imap::Error::ConnectionLost)));
+    assert!(is_disconnect(&ImapError::Imap(imap::Error::Io(
+        std::io::ErrorKind::BrokenPipe.into()
+    ))));
+    assert!(is_disconnect(&ImapError::Disconnected));
+    assert!(!is_disconnect(&ImapError::Imap(imap::Error::No(
+        "no such mailbox".to_string()
+    ))));
+}
+
+/// How one connection to [`imap_server`] behaves.
+struct Session {
+    login: bool,
+    /// Hang up on receiving this command instead of answering it.
+    hang_up_on: Option<&'static str>,
+}
+
+/// A plain-text IMAP server on localhost serving one connection per entry
+/// of `sessions`, giving back the commands each received.
+fn imap_server(sessions: Vec<Session>) -> (u16, std::thread::JoinHandle<Vec<Vec<String>>>) {
+    use std::io::{BufRead, BufReader};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for session in sessions {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut commands = Vec::new();
+            stream.write_all(b"* OK ready\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                let (tag, command) = line.trim_end().split_once(' ').unwrap();
+                // UID commands are told apart by the command they wrap
+                let words = if command.starts_with("UID ") { 2 } else { 1 };
+                let name = command
+                    .splitn(words + 1, ' ')
+                    .take(words)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                commands.push(name.clone());
+                if session.hang_up_on == Some(name.as_str()) {
+                    break;
+                }
+                let reply = match name.as_str() {
+                    "LOGIN" if !session.login => format!("{tag} NO bad password\r\n"),
+                    "SELECT" => format!(
+                        "* 1 EXISTS\r\n* 0 RECENT\r\n* OK [UIDVALIDITY 1] ok\r\n\
+                         {tag} OK [READ-WRITE] done\r\n"
+                    ),
+                    "UID SEARCH" => {
+                        format!("* SEARCH 7 9\r\n{tag} OK done\r\n")
+                    }
+                    "LOGOUT" => format!("* BYE\r\n{tag} OK done\r\n"),
+                    _ => format!("{tag} OK done\r\n"),
+                };
+                stream.write_all(reply.as_bytes()).unwrap();
+                if name == "LOGOUT" || (name == "LOGIN" && !session.login) {
+                    break;
+                }
+            }
+            received.push(commands);
+        }
+        received
+    });
+    (port, handle)
+}
+
+fn local_config(port: u16) -> ImapConfig {
+    toml::from_str(&format!(
+        "host = \"127.0.0.1\"\nport = {port}\ntls = \"none\"\nuser = \"me\"\npass = \"p\"\n\
+         folders = [\"INBOX\"]"
+    ))
+    .unwrap()
+}
+
+#[test]
+fn reconnects_and_retries_after_connection_drops() {
+    let (port, server) = imap_server(vec![
+        Session {
+            login: true,
+            hang_up_on: Some("SELECT"),
+        },
+        Session {
+            login: true,
+            hang_up_on: None,
+        },
+    ]);
+    let mut client = NativeImapClient::connect(&local_config(port)).unwrap();
+    client.mark_seen(1, "INBOX").unwrap();
+    assert_eq!(client.check_connection(), ConnectionState::Connected);
+    drop(client);
+
+    let received = server.join().unwrap();
+    assert_eq!(received[0], ["LOGIN", "SELECT"]);
+    // Logged in again, re-selected the folder and finished the command
+    assert_eq!(received[1], ["LOGIN", "SELECT", "UID STORE", "LOGOUT"]);
+}
+
+#[test]
+fn append_is_not_repeated_after_drop() {
+    let (port, server) = imap_server(vec![
+        Session {
+            login: true,
+            hang_up_on: Some("APPEND"),
+        },
+        Session {
+            login: true,
+            hang_up_on: None,
+        },
+    ]);
+    let mut client = NativeImapClient::connect(&local_config(port)).unwrap();
+    assert!(client.append("Sent", b"Subject: hi\r\n\r\nhi\r\n").is_err());
+    // The next command uses the new connection
+    assert_eq!(client.check_connection(), ConnectionState::Connected);
+    drop(client);
+
+    let received = server.join().unwrap();
+    assert_eq!(received[1], ["LOGIN", "LOGOUT"]);
+}
+
+#[test]
+fn failed_reconnect_backs_off() {
+    let (port, server) = imap_server(vec![
+        Session {
+            login: true,
+            hang_up_on: Some("SELECT"),
+        },
+        Session {
+            login: false,
+            hang_up_on: None,
+        },
+    ]);
+    let mut client = NativeImapClient::connect(&local_config(port)).unwrap();
+    assert!(client.mark_seen(1, "INBOX").is_err());
+    let ConnectionState::Disconnected { attempts, retry_at } = client.check_connection() else {
+        panic!("expected to be disconnected");
+    };
+    assert_eq!(attempts, 1);
+    assert!(retry_at > Instant::now());
+
+    // No new connection is tried before the backoff has passed
+    assert!(matches!(
+        client.mark_seen(1, "INBOX"),
+        Err(ImapError::Disconnected)
+    ));
+    drop(client);
+    assert_eq!(server.join().unwrap().len(), 2);
+}
+
+#[test]
+fn quoted_escapes_imap_strings() {
+    assert_eq!(quoted("<a@b>"), "\"<a@b>\"");
+    assert_eq!(quoted("a\"b\\c"), "\"a\\\"b\\\\c\"");
+}
+
+#[test]
+fn remove_message_deletes_and_expunges_matches() {
+    let (port, server) = imap_server(vec![Session {
+        login: true,
+        hang_up_on: None,
+    }]);
+    let mut client = NativeImapClient::connect(&local_config(port)).unwrap();
+    client
+        .remove_message("Drafts", "<draft@example.com>")
+        .unwrap();
+    drop(client);
+
+    let received = server.join().unwrap();
+    // SEARCH, then STORE +FLAGS (\Deleted) and EXPUNGE on just the matches
+    assert_eq!(
+        received[0],
+        [
+            "LOGIN",
+            "SELECT",
+            "UID SEARCH",
+            "UID STORE",
+            "UID EXPUNGE",
+            "LOGOUT"
+        ]
+    );
+}
@@ -0,0 +1,86 @@
+use crate::imap::EmailBody;
+
+#[cfg(test)]
+mod test;
+
+/// Schemes recognised when scanning plain text for links.
+const SCHEMES: &[&str] = &["https://", "http://", "mailto:", "ftp://"];
+
+/// Characters that end a URL found in plain text.
+const URL_TERMINATORS: &[char] = &['<', '>', '"', '\'', '`', '{', '}', '|', '\\', '^'];
+
+/// Punctuation that is usually part of the surrounding sentence rather
+/// than the URL when it appears at the end.
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', '*'];
+
+/// Links in a message, in the order they should be offered: HTML hrefs
+/// first, so their numbers match the footnotes in the rendered body, then
+/// any further URLs written out in the text.
+pub fn message_links(body: &EmailBody) -> Vec<String> {
+    let mut links = body
+        .body_html
+        .as_deref()
+        .map(|html| crate::html::render(html, 80).links)
+        .unwrap_or_default();
+    for url in find_urls(&body.body_text) {
+        if !links.contains(&url) {
+            links.push(url);
+        }
+    }
+    links
+}
+
+/// URLs written out in plain text, deduplicated in order of appearance.
+pub fn find_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    let lower = text.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while let Some((start, scheme)) = SCHEMES
+        .iter()
+        .filter_map(|s| lower[pos..].find(s).map(|i| (pos + i, *s)))
+        .min_by_key(|(i, _)| *i)
+    {
+        let end = text[start..]
+            .find(|c: char| c.is_whitespace() || URL_TERMINATORS.contains(&c))
+            .map(|e| start + e)
+            .unwrap_or(text.len());
+        pos = end.max(start + scheme.len());
+
+        // The scheme must start a word, so "xhttp://" is not a link
+        let at_word_start = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        if !at_word_start {
+            continue;
+        }
+
+        let url = trim_url(&text[start..end]);
+        if url.len() > scheme.len() && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+
+    urls
+}
+
+/// Strip trailing sentence punctuation and closing brackets that have no
+/// matching opener inside the URL.
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().next_back() else {
+            return url;
+        };
+        let unbalanced = match last {
+            ')' => url.matches('(').count() < url.matches(')').count(),
+            ']' => url.matches('[').count() < url.matches(']').count(),
+            _ => TRAILING_PUNCTUATION.contains(&last),
+        };
+        if !unbalanced {
+            return url;
+        }
+        url = &url[..url.len() - last.len_utf8()];
+    }
+}
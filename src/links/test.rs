@@ -0,0 +1,77 @@
+use super::*;
+
+fn body(text: &str, html: Option<&str>) -> EmailBody {
+    EmailBody {
+        uid: 1,
+        subject: String::new(),
+        from: vec![],
+        sender: None,
+        reply_to: vec![],
+        to: vec![],
+        cc: vec![],
+        date: String::new(),
+        body_text: text.to_string(),
+        body_html: html.map(str::to_string),
+        security: None,
+    }
+}
+
+#[test]
+fn find_urls_in_plain_text() {
+    let text = "See https://example.com/a?b=1&c=2 and http://foo.org/path.\n\
+                Mail mailto:bob@example.com or ftp://files.example.com/x";
+    assert_eq!(
+        find_urls(text),
+        vec![
+            "https://example.com/a?b=1&c=2",
+            "http://foo.org/path",
+            "mailto:bob@example.com",
+            "ftp://files.example.com/x",
+        ]
+    );
+}
+
+#[test]
+fn find_urls_trims_surrounding_punctuation() {
+    let text = "(see https://example.com/page), <https://example.com/angle> \
+                \"https://example.com/quoted\" and https://en.wikipedia.org/wiki/Rust_(language)!";
+    assert_eq!(
+        find_urls(text),
+        vec![
+            "https://example.com/page",
+            "https://example.com/angle",
+            "https://example.com/quoted",
+            "https://en.wikipedia.org/wiki/Rust_(language)",
+        ]
+    );
+}
+
+#[test]
+fn find_urls_skips_duplicates_and_bare_schemes() {
+    let text = "https://a.com https://a.com xhttps://b.com https:// HTTPS://C.com";
+    assert_eq!(find_urls(text), vec!["https://a.com", "HTTPS://C.com"]);
+}
+
+#[test]
+fn message_links_puts_html_hrefs_first() {
+    let body = body(
+        "Visit https://plain.example.com and https://b.example.com",
+        Some("<a href=\"https://b.example.com\">B</a> <a href=\"https://a.example.com\">A</a>"),
+    );
+    assert_eq!(
+        message_links(&body),
+        vec![
+            "https://b.example.com",
+            "https://a.example.com",
+            "https://plain.example.com",
+        ]
+    );
+}
+
+#[test]
+fn message_links_plain_only() {
+    assert_eq!(
+        message_links(&body("Nothing to see here", None)),
+        Vec::<String>::new()
+    );
+}
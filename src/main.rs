@@ -1,7 +1,26 @@
+mod address;
 mod app;
+mod carddav;
 mod config;
+mod contacts;
+mod external;
+mod flowed;
+mod html;
 mod imap;
+mod links;
+mod markdown;
+mod mime;
+mod oauth2;
+mod outbox;
+#[cfg(feature = "pgp")]
+mod pgp;
+mod private;
+mod quote;
+mod schedule;
+#[cfg(feature = "smime")]
+mod smime;
 mod smtp;
+mod tls;
 mod ui;
 
 use std::path::PathBuf;
@@ -16,6 +35,9 @@ struct Cli {
     /// Path to config file (default: ~/.config/thrum.toml)
     #[arg(long)]
     config: Option<PathBuf>,
+    /// Add the contacts in a vCard file to the address book and exit
+    #[arg(long, value_name = "FILE")]
+    import_vcard: Option<PathBuf>,
 }
 
 fn main() -> std::io::Result<()> {
@@ -33,16 +55,68 @@ fn main() -> std::io::Result<()> {
     #[cfg(feature = "tracing")]
     tracing::trace!("CLI args parsed");
 
+    let contacts_path =
+        contacts::default_path().ok_or_else(|| std::io::Error::other("no data directory"))?;
+    let mut contacts = contacts::AddressBook::load(&contacts_path)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    if let Some(path) = cli.import_vcard {
+        let data = std::fs::read_to_string(&path)?;
+        let added = contacts.import_vcard(&data);
+        contacts
+            .save()
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        println!("Imported {added} new contacts from {}", path.display());
+        return Ok(());
+    }
+
     #[cfg(feature = "tracing")]
     tracing::trace!("loading config");
     let config = config::load(cli.config).map_err(|e| std::io::Error::other(e.to_string()))?;
     #[cfg(feature = "tracing")]
     tracing::trace!("config loaded");
 
+    // Disabled certificate checks are announced on every start, in the
+    // terminal and in the status bar
+    let mut insecure = Vec::new();
+    for (host, accept_invalid_certs) in [
+        (&config.imap.host, config.imap.accept_invalid_certs),
+        (&config.smtp.host, config.smtp.accept_invalid_certs),
+    ] {
+        if accept_invalid_certs && !insecure.contains(host) {
+            insecure.push(host.clone());
+        }
+    }
+    let insecure_warning = (!insecure.is_empty()).then(|| {
+        format!(
+            "WARNING: certificate checks are off for {} (accept_invalid_certs); anyone on the network can read your mail",
+            insecure.join(", ")
+        )
+    });
+    if let Some(ref warning) = insecure_warning {
+        eprintln!("{warning}");
+        #[cfg(feature = "tracing")]
+        tracing::warn!("{warning}");
+    }
+
+    // Sync the remote address book while the mail servers are contacted
+    let carddav_sync = config.carddav.as_ref().map(|carddav| {
+        let client = carddav::CardDavClient::new(carddav);
+        std::thread::spawn(move || client.fetch_vcards())
+    });
+
     #[cfg(feature = "tracing")]
     tracing::trace!(host = %config.imap.host, port = config.imap.port, "connecting to IMAP server");
     let mut client = imap::NativeImapClient::connect(&config.imap)
         .map_err(|e| std::io::Error::other(e.to_string()))?;
+    #[cfg(feature = "pgp")]
+    {
+        client = client.with_gpg(pgp::Gpg::new(&config.pgp));
+    }
+    #[cfg(feature = "smime")]
+    {
+        client = client.with_smime(smime::Smime::new(&config.smime));
+    }
     #[cfg(feature = "tracing")]
     tracing::trace!("IMAP connected");
 
@@ -50,6 +124,14 @@ fn main() -> std::io::Result<()> {
     tracing::trace!(host = %config.smtp.host, port = config.smtp.port, "connecting to SMTP server");
     let smtp_client = smtp::NativeSmtpClient::connect(&config.smtp)
         .map_err(|e| std::io::Error::other(e.to_string()))?;
+    #[cfg(feature = "pgp")]
+    let smtp_client = smtp_client.with_gpg(pgp::Gpg::new(&config.pgp));
+    #[cfg(feature = "smime")]
+    let smtp_client = if config.smime.sign {
+        smtp_client.with_smime(smime::Smime::new(&config.smime))
+    } else {
+        smtp_client
+    };
     #[cfg(feature = "tracing")]
     tracing::trace!("SMTP connected");
 
@@ -61,6 +143,19 @@ fn main() -> std::io::Result<()> {
     #[cfg(feature = "tracing")]
     tracing::trace!(count = emails.len(), "inbox fetched");
 
+    contacts.harvest(&emails);
+    let sync_error = match carddav_sync.map(|handle| handle.join()) {
+        Some(Ok(Ok(cards))) => {
+            let (_added, _removed) = contacts.sync_remote(&cards);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(_added, _removed, "contacts synced");
+            None
+        }
+        Some(Ok(Err(e))) => Some(format!("Contacts sync failed: {e}")),
+        Some(Err(_)) => Some("Contacts sync failed".to_string()),
+        None => None,
+    };
+
     #[cfg(feature = "tracing")]
     tracing::trace!("initializing terminal");
     let mut terminal = ratatui::init();
@@ -68,14 +163,52 @@ fn main() -> std::io::Result<()> {
     tracing::trace!("terminal initialized, starting app");
 
     let sender_from = config.sender.formatted_from();
-    let sent_folder = config.imap.sent_folder;
-    let result = App::new(emails, client, smtp_client, sender_from, sent_folder).run(&mut terminal);
+    let sent_folder = config.imap.sent_folder.clone();
+    let mut app = App::new(emails, client, smtp_client, sender_from, sent_folder);
+    app.set_sort_mode(config.ui.sort);
+    app.group_by_date = config.ui.group_by_date;
+    app.folders = config.imap.folders.clone();
+    app.show_folder_column = config.ui.show_folder;
+    app.page_size = config.imap.page_size;
+    app.max_loaded = config.ui.max_loaded;
+    app.opener = config.ui.opener.clone();
+    app.handlers = config.handlers.clone();
+    app.contacts = contacts;
+    app.max_recipients = config.compose.max_recipients;
+    app.signature = config.sender.signature.clone();
+    app.identities = config
+        .sender
+        .identities
+        .iter()
+        .map(config::IdentityConfig::formatted_from)
+        .collect();
+    app.identity_signatures = config
+        .sender
+        .identities
+        .iter()
+        .filter_map(|identity| Some((identity.formatted_from(), identity.signature.clone()?)))
+        .collect();
+    app.posting = config.compose.posting;
+    app.inline_quote = config.compose.inline_quote;
+    app.markdown = config.compose.markdown;
+    app.sign = config.pgp.sign;
+    app.encrypt = config.pgp.encrypt;
+    app.outbox = outbox::default_dir().map(outbox::Outbox::new);
+    app.schedule = schedule::default_dir().map(schedule::Schedule::new);
+    app.drafts_folder = config.imap.drafts_folder.clone();
+    app.status_message = insecure_warning.or(sync_error);
+    let result = app.run(&mut terminal);
+    if let Err(_e) = app.contacts.save() {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%_e, "failed to save address book");
+    }
 
     #[cfg(feature = "tracing")]
     tracing::trace!("app exited, restoring terminal");
     ratatui::restore();
     #[cfg(feature = "tracing")]
     tracing::trace!("terminal restored");
+    external::remove_private_dir();
 
     result
 }
@@ -0,0 +1,404 @@
+use html_escape::{encode_double_quoted_attribute, encode_text};
+
+#[cfg(test)]
+mod test;
+
+/// Render Markdown to an HTML fragment. Covers what mail needs: headings,
+/// paragraphs, lists, block quotes, fenced code, rules, emphasis, code
+/// spans, links and images. A `-- ` line starts the signature, which is
+/// kept line for line.
+pub fn to_html(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::new();
+    render_blocks(&lines, &mut out);
+    out
+}
+
+fn render_blocks(lines: &[&str], out: &mut String) {
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            i += 1;
+        } else if line == "-- " {
+            out.push_str("<p>-- ");
+            for signature in &lines[i + 1..] {
+                out.push_str("<br>\n");
+                out.push_str(&encode_text(signature));
+            }
+            out.push_str("</p>\n");
+            return;
+        } else if let Some(fence) = fence(trimmed) {
+            let info = trimmed[fence.len()..].trim();
+            let mut end = i + 1;
+            while end < lines.len() && !lines[end].trim_start().starts_with(fence) {
+                end += 1;
+            }
+            if info.is_empty() {
+                out.push_str("<pre><code>");
+            } else {
+                out.push_str(&format!(
+                    "<pre><code class=\"language-{}\">",
+                    encode_double_quoted_attribute(info)
+                ));
+            }
+            for code in &lines[i + 1..end] {
+                out.push_str(&encode_text(code));
+                out.push('\n');
+            }
+            out.push_str("</code></pre>\n");
+            i = end + 1;
+        } else if let Some((level, title)) = heading(trimmed) {
+            out.push_str(&format!("<h{level}>{}</h{level}>\n", inline(title)));
+            i += 1;
+        } else if is_rule(trimmed) {
+            out.push_str("<hr>\n");
+            i += 1;
+        } else if trimmed.starts_with('>') {
+            let mut quoted = Vec::new();
+            while let Some(rest) = lines.get(i).and_then(|l| l.trim_start().strip_prefix('>')) {
+                quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+                i += 1;
+            }
+            out.push_str("<blockquote>\n");
+            render_blocks(&quoted, out);
+            out.push_str("</blockquote>\n");
+        } else if let Some(marker) = list_marker(line) {
+            i = render_list(lines, i, marker, out);
+        } else {
+            let start = i;
+            i += 1;
+            while i < lines.len() && !lines[i].trim().is_empty() && !starts_block(lines[i]) {
+                i += 1;
+            }
+            out.push_str(&format!("<p>{}</p>\n", paragraph(&lines[start..i])));
+        }
+    }
+}
+
+/// Whether `line` opens a block that interrupts a paragraph.
+fn starts_block(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    line == "-- "
+        || fence(trimmed).is_some()
+        || heading(trimmed).is_some()
+        || is_rule(trimmed)
+        || trimmed.starts_with('>')
+        || list_marker(line).is_some()
+}
+
+fn fence(line: &str) -> Option<&'static str> {
+    ["```", "~~~"].into_iter().find(|f| line.starts_with(f))
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let title = line[level..].strip_prefix(' ')?;
+    (1..=6)
+        .contains(&level)
+        .then(|| (level, title.trim().trim_end_matches('#').trim_end()))
+}
+
+fn is_rule(line: &str) -> bool {
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&m| marks.chars().all(|c| c == m))
+}
+
+/// A list item marker: `-`, `*`, `+` or `1.`/`1)` followed by a space.
+#[derive(Clone, Copy)]
+struct Marker {
+    ordered: bool,
+    number: u32,
+    indent: usize,
+    /// Byte offset where the item's text starts.
+    content: usize,
+}
+
+fn list_marker(line: &str) -> Option<Marker> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let (ordered, width) = if digits > 0 && digits < 10 {
+        if !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        (true, digits + 1)
+    } else if rest.starts_with(['-', '*', '+']) {
+        (false, 1)
+    } else {
+        return None;
+    };
+    if !rest[width..].starts_with(' ') || rest[width..].trim().is_empty() {
+        return None;
+    }
+    Some(Marker {
+        ordered,
+        number: rest[..digits].parse().unwrap_or(1),
+        indent,
+        content: indent + width + 1,
+    })
+}
+
+/// Render the list starting at `start` and return the index after it.
+fn render_list(lines: &[&str], start: usize, first: Marker, out: &mut String) -> usize {
+    let same_list = |line: &str| {
+        list_marker(line).filter(|m| m.ordered == first.ordered && m.indent < first.content)
+    };
+
+    let mut items: Vec<Vec<&str>> = Vec::new();
+    let mut loose = false;
+    let mut content = first.content;
+    let mut i = start;
+    while i < lines.len() {
+        let line = lines[i];
+        let indent = line.len() - line.trim_start().len();
+        if let Some(marker) = same_list(line) {
+            items.push(vec![&line[marker.content..]]);
+            content = marker.content;
+        } else if line.trim().is_empty() {
+            // A blank line only continues the list if more of it follows
+            let next = lines[i + 1..].iter().find(|l| !l.trim().is_empty());
+            let continues = next.is_some_and(|next| {
+                same_list(next).is_some() || next.len() - next.trim_start().len() >= content
+            });
+            if !continues {
+                break;
+            }
+            loose = true;
+            items.last_mut().unwrap().push("");
+        } else if indent >= content {
+            items.last_mut().unwrap().push(&line[content..]);
+        } else if !starts_block(line) && items.last().is_some_and(|i| i.last() != Some(&"")) {
+            // Lazy continuation of the item's paragraph
+            items.last_mut().unwrap().push(line.trim_start());
+        } else {
+            break;
+        }
+        i += 1;
+    }
+
+    let tag = if first.ordered { "ol" } else { "ul" };
+    if first.ordered && first.number != 1 {
+        out.push_str(&format!("<{tag} start=\"{}\">\n", first.number));
+    } else {
+        out.push_str(&format!("<{tag}>\n"));
+    }
+    for item in items {
+        let mut body = String::new();
+        render_blocks(&item, &mut body);
+        if !loose && let Some(rest) = body.strip_prefix("<p>") {
+            // Tight items hold their first paragraph's text directly
+            body = rest.replacen("</p>\n", "\n", 1);
+        }
+        out.push_str(&format!("<li>{}</li>\n", body.trim_end()));
+    }
+    out.push_str(&format!("</{tag}>\n"));
+    i
+}
+
+/// The lines of a paragraph, keeping hard breaks written as two trailing
+/// spaces or a backslash.
+fn paragraph(lines: &[&str]) -> String {
+    let last = lines.len() - 1;
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let line = line.trim_start();
+            if i < last && line.ends_with("  ") {
+                format!("{}<br>", inline(line.trim_end()))
+            } else if i < last && line.ends_with('\\') {
+                format!("{}<br>", inline(&line[..line.len() - 1]))
+            } else {
+                inline(line.trim_end())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render inline Markdown with everything else escaped.
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let prev = text[..text.len() - rest.len()].chars().next_back();
+        let consumed = match c {
+            '\\' => match rest[1..].chars().next() {
+                Some(next) if next.is_ascii_punctuation() => {
+                    out.push_str(&encode_text(&rest[1..2]));
+                    2
+                }
+                _ => {
+                    out.push('\\');
+                    1
+                }
+            },
+            '`' => code_span(rest, &mut out),
+            '*' | '_' => emphasis(rest, prev, &mut out),
+            '!' if rest.starts_with("![") => image(rest, &mut out),
+            '[' => link(rest, &mut out),
+            '<' => autolink(rest, &mut out),
+            'h' if !prev.is_some_and(char::is_alphanumeric)
+                && (rest.starts_with("https://") || rest.starts_with("http://")) =>
+            {
+                bare_url(rest, &mut out)
+            }
+            _ => 0,
+        };
+        if consumed == 0 {
+            out.push_str(&encode_text(&rest[..c.len_utf8()]));
+            rest = &rest[c.len_utf8()..];
+        } else {
+            rest = &rest[consumed..];
+        }
+    }
+    out
+}
+
+fn code_span(rest: &str, out: &mut String) -> usize {
+    let run = rest.chars().take_while(|&c| c == '`').count();
+    let delimiter = &rest[..run];
+    let mut search = run;
+    while let Some(found) = rest[search..].find(delimiter) {
+        let end = search + found;
+        let closing = rest[end..].chars().take_while(|&c| c == '`').count();
+        if closing == run {
+            let code = &rest[run..end];
+            let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                Some(inner) if !inner.trim().is_empty() => inner,
+                _ => code,
+            };
+            out.push_str(&format!("<code>{}</code>", encode_text(code)));
+            return end + run;
+        }
+        search = end + closing;
+    }
+    out.push_str(delimiter);
+    run
+}
+
+fn emphasis(rest: &str, prev: Option<char>, out: &mut String) -> usize {
+    let c = rest.chars().next().unwrap_or('*');
+    let double = rest[1..].starts_with(c);
+    let delimiter = if double { &rest[..2] } else { &rest[..1] };
+    let after = &rest[delimiter.len()..];
+
+    // Underscores inside words are literal
+    let opens = after.chars().next().is_some_and(|n| !n.is_whitespace())
+        && (c == '*' || !prev.is_some_and(char::is_alphanumeric));
+    if !opens {
+        return 0;
+    }
+
+    let mut search = 0;
+    while let Some(found) = after[search..].find(delimiter) {
+        let end = search + found;
+        let before = after[..end].chars().next_back();
+        let next = after[end + delimiter.len()..].chars().next();
+        let closes = end > 0
+            && !before.is_some_and(char::is_whitespace)
+            && (double || (before != Some(c) && next != Some(c)))
+            && (c == '*' || !next.is_some_and(char::is_alphanumeric));
+        if closes {
+            let tag = if double { "strong" } else { "em" };
+            out.push_str(&format!("<{tag}>{}</{tag}>", inline(&after[..end])));
+            return delimiter.len() + end + delimiter.len();
+        }
+        search = end + after[end..].chars().take_while(|&d| d == c).count();
+    }
+    0
+}
+
+/// Split `[text](target)` at the start of `rest` into its text, target and
+/// length.
+fn link_parts(rest: &str) -> Option<(&str, &str, usize)> {
+    let mut depth = 0;
+    let close = rest.char_indices().find_map(|(i, c)| {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        None
+    })?;
+    let target = rest[close + 1..].strip_prefix('(')?;
+    let end = target.find(')')?;
+    // Drop an optional "title"
+    let url = target[..end].split_whitespace().next().unwrap_or("");
+    let url = url.trim_start_matches('<').trim_end_matches('>');
+    Some((&rest[1..close], url, close + 2 + end + 1))
+}
+
+fn link(rest: &str, out: &mut String) -> usize {
+    let Some((text, url, len)) = link_parts(rest) else {
+        return 0;
+    };
+    out.push_str(&format!(
+        "<a href=\"{}\">{}</a>",
+        encode_double_quoted_attribute(url),
+        inline(text)
+    ));
+    len
+}
+
+fn image(rest: &str, out: &mut String) -> usize {
+    let Some((alt, url, len)) = link_parts(&rest[1..]) else {
+        return 0;
+    };
+    out.push_str(&format!(
+        "<img src=\"{}\" alt=\"{}\">",
+        encode_double_quoted_attribute(url),
+        encode_double_quoted_attribute(alt)
+    ));
+    len + 1
+}
+
+fn autolink(rest: &str, out: &mut String) -> usize {
+    let Some(end) = rest.find('>') else {
+        return 0;
+    };
+    let target = &rest[1..end];
+    if target.contains(char::is_whitespace) {
+        return 0;
+    }
+    let href = if target.contains("://") || target.starts_with("mailto:") {
+        target.to_string()
+    } else if target.contains('@') {
+        format!("mailto:{target}")
+    } else {
+        return 0;
+    };
+    out.push_str(&format!(
+        "<a href=\"{}\">{}</a>",
+        encode_double_quoted_attribute(&href),
+        encode_text(target)
+    ));
+    end + 1
+}
+
+fn bare_url(rest: &str, out: &mut String) -> usize {
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(rest.len());
+    let url = rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+    out.push_str(&format!(
+        "<a href=\"{}\">{}</a>",
+        encode_double_quoted_attribute(url),
+        encode_text(url)
+    ));
+    url.len()
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+#[test]
+fn renders_paragraphs_and_headings() {
+    assert_eq!(
+        to_html("# Title #\n\nfirst line\nsecond line  \nthird\n\n### Sub"),
+        "<h1>Title</h1>\n<p>first line\nsecond line<br>\nthird</p>\n<h3>Sub</h3>\n"
+    );
+}
+
+#[test]
+fn renders_inline_markup() {
+    assert_eq!(
+        to_html("**bold** and *em* and _also_ in snake_case_name, `a < b` \\*not\\*"),
+        "<p><strong>bold</strong> and <em>em</em> and <em>also</em> in snake_case_name, \
+         <code>a &lt; b</code> *not*</p>\n"
+    );
+    assert_eq!(
+        to_html("*a **b** c*"),
+        "<p><em>a <strong>b</strong> c</em></p>\n"
+    );
+}
+
+#[test]
+fn renders_links_and_images() {
+    assert_eq!(
+        to_html(
+            "[the *docs*](https://example.com/a?b=1&c=2 \"Docs\") ![logo](logo.png) \
+             <me@example.com> see https://example.com/x."
+        ),
+        "<p><a href=\"https://example.com/a?b=1&amp;c=2\">the <em>docs</em></a> \
+         <img src=\"logo.png\" alt=\"logo\"> \
+         <a href=\"mailto:me@example.com\">me@example.com</a> \
+         see <a href=\"https://example.com/x\">https://example.com/x</a>.</p>\n"
+    );
+}
+
+#[test]
+fn renders_fenced_code_verbatim() {
+    assert_eq!(
+        to_html("```rust\nfn main() {\n    println!(\"<hi>\");\n}\n```\nafter"),
+        "<pre><code class=\"language-rust\">fn main() {\n    println!(\"&lt;hi&gt;\");\n}\n\
+         </code></pre>\n<p>after</p>\n"
+    );
+}
+
+#[test]
+fn renders_tight_and_nested_lists() {
+    assert_eq!(
+        to_html("Steps:\n1. one\n2. two\n   - nested\n3. three\n\n- a\n- b"),
+        "<p>Steps:</p>\n<ol>\n<li>one</li>\n<li>two\n<ul>\n<li>nested</li>\n</ul></li>\n\
+         <li>three</li>\n</ol>\n<ul>\n<li>a</li>\n<li>b</li>\n</ul>\n"
+    );
+    assert_eq!(
+        to_html("3) x\n\n4) y"),
+        "<ol start=\"3\">\n<li><p>x</p></li>\n<li><p>y</p></li>\n</ol>\n"
+    );
+}
+
+#[test]
+fn renders_quotes_rules_and_signature() {
+    assert_eq!(
+        to_html(
+            "On Monday, Bob wrote:\n> hi\n>> older\n\n---\nThanks\n-- \nMe & Co\nhttps://example.com"
+        ),
+        "<p>On Monday, Bob wrote:</p>\n<blockquote>\n<p>hi</p>\n<blockquote>\n<p>older</p>\n\
+         </blockquote>\n</blockquote>\n<hr>\n<p>Thanks</p>\n\
+         <p>-- <br>\nMe &amp; Co<br>\nhttps://example.com</p>\n"
+    );
+}
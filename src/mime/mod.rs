@@ -0,0 +1,290 @@
+use base64::Engine;
+
+#[cfg(test)]
+mod test;
+
+/// One node of a parsed MIME message. Multipart nodes have `parts` and an
+/// empty body; leaves carry their body with the transfer encoding undone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub headers: Vec<(String, String)>,
+    /// Lowercase `type/subtype`, `text/plain` when the header is missing.
+    pub content_type: String,
+    pub body: Vec<u8>,
+    pub parts: Vec<Part>,
+}
+
+impl Part {
+    /// The first header called `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The file name from Content-Disposition, falling back to the `name`
+    /// parameter of Content-Type.
+    pub fn filename(&self) -> Option<String> {
+        self.header("content-disposition")
+            .and_then(|d| param(d, "filename"))
+            .or_else(|| self.header("content-type").and_then(|ct| param(ct, "name")))
+            .filter(|name| !name.is_empty())
+    }
+
+    pub fn is_attachment(&self) -> bool {
+        self.header("content-disposition").is_some_and(|d| {
+            d.trim_start()
+                .to_ascii_lowercase()
+                .starts_with("attachment")
+        }) || self.filename().is_some()
+    }
+
+    /// All leaf parts, depth first.
+    pub fn leaves(&self) -> Vec<&Part> {
+        if self.parts.is_empty() {
+            vec![self]
+        } else {
+            self.parts.iter().flat_map(Part::leaves).collect()
+        }
+    }
+}
+
+/// Parse a complete message (or a single body part) into its MIME tree.
+pub fn parse(raw: &[u8]) -> Part {
+    let (header_block, body) = split_header(raw);
+    let headers = parse_headers(&String::from_utf8_lossy(header_block));
+
+    let content_type_header = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+        .map(|(_, v)| v.clone());
+    let content_type = content_type_header
+        .as_deref()
+        .and_then(|ct| ct.split(';').next())
+        .map(|ct| ct.trim().to_ascii_lowercase())
+        .filter(|ct| ct.contains('/'))
+        .unwrap_or_else(|| "text/plain".to_string());
+
+    if content_type.starts_with("multipart/")
+        && let Some(boundary) = content_type_header
+            .as_deref()
+            .and_then(|ct| param(ct, "boundary"))
+    {
+        return Part {
+            headers,
+            content_type,
+            body: Vec::new(),
+            parts: split_multipart(body, &boundary)
+                .into_iter()
+                .map(parse)
+                .collect(),
+        };
+    }
+
+    let encoding = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-transfer-encoding"))
+        .map(|(_, v)| v.trim().to_ascii_lowercase());
+    let body = decode(encoding.as_deref(), body);
+
+    Part {
+        headers,
+        content_type,
+        body,
+        parts: Vec::new(),
+    }
+}
+
+/// Split raw bytes at the first empty line into header block and body.
+pub fn split_header(raw: &[u8]) -> (&[u8], &[u8]) {
+    let crlf = find(raw, b"\r\n\r\n").map(|i| (i, 4));
+    let lf = find(raw, b"\n\n").map(|i| (i, 2));
+    let split = match (crlf, lf) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    };
+    match split {
+        Some((i, len)) => (&raw[..i], &raw[i + len..]),
+        // A part that starts with a blank line has no headers
+        None if raw.starts_with(b"\r\n") => (&[], &raw[2..]),
+        None if raw.starts_with(b"\n") => (&[], &raw[1..]),
+        None => (raw, &[]),
+    }
+}
+
+/// Parse a header block, joining folded continuation lines.
+pub fn parse_headers(block: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in block.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+/// A parameter of a structured header such as `boundary` in Content-Type,
+/// including RFC 2231 `name*=charset''percent-encoded` values.
+pub fn param(value: &str, name: &str) -> Option<String> {
+    let mut segments = split_params(value).into_iter().skip(1);
+    segments.find_map(|segment| {
+        let (key, val) = segment.split_once('=')?;
+        let key = key.trim();
+        let val = val.trim();
+        if key.eq_ignore_ascii_case(name) {
+            Some(unquote(val))
+        } else if key.len() == name.len() + 1
+            && key.ends_with('*')
+            && key[..name.len()].eq_ignore_ascii_case(name)
+        {
+            let encoded = unquote(val);
+            let text = encoded.splitn(3, '\'').nth(2).unwrap_or(&encoded);
+            Some(percent_decode(text))
+        } else {
+            None
+        }
+    })
+}
+
+/// Split on `;` outside quoted strings.
+fn split_params(value: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                segments.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&value[start..]);
+    segments
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_string(),
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The bodies between `--boundary` delimiter lines, stopping at the
+/// closing `--boundary--`.
+pub fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut current: Option<usize> = None;
+    let mut pos = 0;
+
+    while pos <= body.len() {
+        let line_end = find(&body[pos..], b"\n")
+            .map(|i| pos + i)
+            .unwrap_or(body.len());
+        let line = trim_line_end(&body[pos..line_end]);
+
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            if let Some(start) = current {
+                // The line break before a delimiter belongs to the delimiter
+                let end = if pos >= 2 && &body[pos - 2..pos] == b"\r\n" {
+                    pos - 2
+                } else {
+                    pos.saturating_sub(1).max(start)
+                };
+                parts.push(&body[start..end.max(start)]);
+            }
+            if rest.starts_with(b"--") {
+                return parts;
+            }
+            current = Some((line_end + 1).min(body.len()));
+        }
+
+        pos = line_end + 1;
+    }
+
+    // Tolerate a missing closing delimiter
+    if let Some(start) = current {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    while end > 0 && matches!(line[end - 1], b'\r' | b' ' | b'\t') {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Undo a Content-Transfer-Encoding; unknown encodings pass through.
+pub fn decode(encoding: Option<&str>, body: &[u8]) -> Vec<u8> {
+    match encoding {
+        Some("quoted-printable") => {
+            quoted_printable::decode(body, quoted_printable::ParseMode::Robust)
+                .unwrap_or_else(|_| body.to_vec())
+        }
+        Some("base64") => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(&compact)
+                .unwrap_or_else(|_| body.to_vec())
+        }
+        _ => body.to_vec(),
+    }
+}
+
+/// A file extension for content of the given type, used when a part has
+/// no file name of its own.
+pub fn extension_for(content_type: &str) -> &str {
+    match content_type {
+        "text/html" => "html",
+        "text/plain" => "txt",
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        "message/rfc822" => "eml",
+        "application/octet-stream" => "bin",
+        other => other
+            .rsplit('/')
+            .next()
+            .filter(|sub| !sub.is_empty() && sub.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("bin"),
+    }
+}
@@ -0,0 +1,111 @@
+use super::*;
+
+const MESSAGE: &[u8] = b"From: Alice <alice@example.com>\r\n\
+Subject: Report\r\n\
+Content-Type: multipart/mixed;\r\n boundary=\"outer\"\r\n\
+\r\n\
+preamble\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Hello\r\n\
+--inner\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<p style=3D\"x\">Hello</p>\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"report.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0x\r\n\
+LjQK\r\n\
+--outer--\r\n\
+epilogue\r\n";
+
+#[test]
+fn parse_nested_multipart() {
+    let root = parse(MESSAGE);
+    assert_eq!(root.content_type, "multipart/mixed");
+    assert_eq!(root.header("subject"), Some("Report"));
+    assert_eq!(root.parts.len(), 2);
+    assert_eq!(root.parts[0].content_type, "multipart/alternative");
+
+    let leaves = root.leaves();
+    let types: Vec<&str> = leaves.iter().map(|p| p.content_type.as_str()).collect();
+    assert_eq!(types, vec!["text/plain", "text/html", "application/pdf"]);
+    assert_eq!(leaves[0].body, b"Hello");
+    assert_eq!(leaves[1].body, b"<p style=\"x\">Hello</p>");
+    assert_eq!(leaves[2].body, b"%PDF-1.4\n");
+}
+
+#[test]
+fn attachments_are_detected_by_disposition_or_name() {
+    let root = parse(MESSAGE);
+    let leaves = root.leaves();
+    assert!(!leaves[0].is_attachment());
+    assert!(!leaves[1].is_attachment());
+    assert!(leaves[2].is_attachment());
+    assert_eq!(leaves[2].filename().as_deref(), Some("report.pdf"));
+
+    let named = parse(b"Content-Type: image/png; name=logo.png\r\n\r\nPNG");
+    assert!(named.is_attachment());
+    assert_eq!(named.filename().as_deref(), Some("logo.png"));
+}
+
+#[test]
+fn single_part_defaults_to_text_plain() {
+    let part = parse(b"Subject: hi\n\nJust text\n");
+    assert_eq!(part.content_type, "text/plain");
+    assert!(part.parts.is_empty());
+    assert_eq!(part.body, b"Just text\n");
+}
+
+#[test]
+fn missing_closing_boundary_keeps_last_part() {
+    let part = parse(
+        b"Content-Type: multipart/mixed; boundary=b\n\n--b\nContent-Type: text/plain\n\none\n--b\n\ntwo\n",
+    );
+    assert_eq!(part.parts.len(), 2);
+    assert_eq!(part.parts[0].body, b"one");
+    assert_eq!(part.parts[1].body, b"two\n");
+}
+
+#[test]
+fn param_handles_quotes_and_rfc2231() {
+    let value = "attachment; filename=\"a; b.txt\"; size=10";
+    assert_eq!(param(value, "filename").as_deref(), Some("a; b.txt"));
+    assert_eq!(param(value, "size").as_deref(), Some("10"));
+    assert_eq!(param(value, "missing"), None);
+
+    let encoded = "attachment; filename*=UTF-8''na%C3%AFve%20plan.txt";
+    assert_eq!(
+        param(encoded, "filename").as_deref(),
+        Some("naïve plan.txt")
+    );
+}
+
+#[test]
+fn parse_headers_unfolds() {
+    let headers = parse_headers("To: a@example.com,\r\n\tb@example.com\r\nX-Empty:\r\n");
+    assert_eq!(
+        headers,
+        vec![
+            ("To".to_string(), "a@example.com, b@example.com".to_string()),
+            ("X-Empty".to_string(), String::new()),
+        ]
+    );
+}
+
+#[test]
+fn extension_for_common_types() {
+    assert_eq!(extension_for("text/html"), "html");
+    assert_eq!(extension_for("image/jpeg"), "jpg");
+    assert_eq!(extension_for("image/png"), "png");
+    assert_eq!(extension_for("application/vnd.ms-excel"), "bin");
+}
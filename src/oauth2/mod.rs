@@ -0,0 +1,245 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::OAuth2Config;
+use crate::private;
+
+#[cfg(test)]
+mod test;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tokens this close to expiry are renewed before use.
+const EXPIRY_MARGIN: i64 = 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OAuth2Error {
+    #[error("auth = \"oauth2\" needs a token_command, or a token_url, client_id and refresh_token")]
+    NotConfigured,
+    #[error("token command failed: {0}")]
+    Command(String),
+    #[error("token request failed: {0}")]
+    Request(#[from] Box<ureq::Error>),
+    #[error("failed to read or cache token: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid token response: {0}")]
+    Response(String),
+}
+
+/// An access token as kept in the on-disk cache.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedToken {
+    pub access_token: String,
+    /// Unix time the access token stops working.
+    pub expires_at: i64,
+    /// Replacement refresh token, for providers that rotate them.
+    pub refresh_token: Option<String>,
+}
+
+/// Hands out access tokens for one account, renewing them when they expire.
+pub struct TokenSource {
+    config: OAuth2Config,
+    cache: Option<PathBuf>,
+}
+
+/// Where tokens for `user` on `host` are cached.
+pub fn cache_path(user: &str, host: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|d| {
+        d.join("thrum")
+            .join("oauth2")
+            .join(format!("{user}@{host}.toml"))
+    })
+}
+
+impl TokenSource {
+    pub fn new(config: &OAuth2Config, cache: Option<PathBuf>) -> Self {
+        Self {
+            config: config.clone(),
+            cache,
+        }
+    }
+
+    /// A current access token, from the token command, the cache or a
+    /// refresh at the token endpoint.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self), err)
+    )]
+    pub fn access_token(&self) -> Result<String, OAuth2Error> {
+        if let Some(ref command) = self.config.token_command {
+            return run_token_command(command);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let cached = self.load();
+        if let Some(ref cached) = cached
+            && cached.expires_at - EXPIRY_MARGIN > now
+        {
+            return Ok(cached.access_token.clone());
+        }
+
+        // A rotated refresh token first, then the configured one in case
+        // it was replaced after the rotated one was revoked
+        let mut candidates: Vec<String> = cached
+            .and_then(|cached| cached.refresh_token)
+            .into_iter()
+            .collect();
+        if let Some(ref configured) = self.config.refresh_token
+            && !candidates.contains(configured)
+        {
+            candidates.push(configured.clone());
+        }
+        let mut result = Err(OAuth2Error::NotConfigured);
+        for refresh_token in candidates {
+            result = self.refresh(&refresh_token, now).map(|mut token| {
+                token.refresh_token.get_or_insert(refresh_token);
+                token
+            });
+            if result.is_ok() {
+                break;
+            }
+        }
+        let token = result?;
+        self.store(&token)?;
+        Ok(token.access_token)
+    }
+
+    /// Exchange `refresh_token` for a new access token.
+    fn refresh(&self, refresh_token: &str, now: i64) -> Result<CachedToken, OAuth2Error> {
+        let (Some(url), Some(client_id)) = (&self.config.token_url, &self.config.client_id) else {
+            return Err(OAuth2Error::NotConfigured);
+        };
+        #[cfg(feature = "tracing")]
+        tracing::trace!(url, "refreshing access token");
+
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id.as_str()),
+            ("refresh_token", refresh_token),
+        ];
+        if let Some(ref secret) = self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let body = match agent.post(url).send_form(&form) {
+            Ok(response) => response.into_string()?,
+            // The provider explains rejected refresh tokens in the body
+            Err(ureq::Error::Status(_, response)) => {
+                let body = response.into_string()?;
+                return Err(match parse_token_response(&body, now) {
+                    Err(e) => e,
+                    Ok(_) => OAuth2Error::Response(body),
+                });
+            }
+            Err(e) => return Err(Box::new(e).into()),
+        };
+        parse_token_response(&body, now)
+    }
+
+    fn load(&self) -> Option<CachedToken> {
+        let text = std::fs::read_to_string(self.cache.as_ref()?).ok()?;
+        toml::from_str(&text).ok()
+    }
+
+    fn store(&self, token: &CachedToken) -> Result<(), OAuth2Error> {
+        let Some(ref path) = self.cache else {
+            return Ok(());
+        };
+        // The cache holds live credentials
+        if let Some(dir) = path.parent() {
+            private::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(token).map_err(|e| OAuth2Error::Response(e.to_string()))?;
+        private::write(path, text)?;
+        Ok(())
+    }
+}
+
+fn run_token_command(command: &str) -> Result<String, OAuth2Error> {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(command, "running token command");
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| OAuth2Error::Command(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(OAuth2Error::Command(format!(
+            "command failed ({}): {}",
+            output.status,
+            stderr.trim()
+        )));
+    }
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if token.is_empty() {
+        return Err(OAuth2Error::Command("command printed no token".to_string()));
+    }
+    Ok(token)
+}
+
+/// Parse a token endpoint response (RFC 6749 section 5) received at unix
+/// time `now`.
+pub fn parse_token_response(body: &str, now: i64) -> Result<CachedToken, OAuth2Error> {
+    #[derive(Deserialize)]
+    struct Response {
+        access_token: Option<String>,
+        expires_in: Option<i64>,
+        refresh_token: Option<String>,
+        error: Option<String>,
+        error_description: Option<String>,
+    }
+
+    let response: Response =
+        serde_json::from_str(body).map_err(|e| OAuth2Error::Response(e.to_string()))?;
+    match response {
+        Response {
+            access_token: Some(access_token),
+            expires_in,
+            refresh_token,
+            ..
+        } => Ok(CachedToken {
+            access_token,
+            // Providers that leave it out issue tokens for an hour
+            expires_at: now + expires_in.unwrap_or(3600),
+            refresh_token,
+        }),
+        Response {
+            error_description: Some(reason),
+            ..
+        }
+        | Response {
+            error: Some(reason),
+            ..
+        } => Err(OAuth2Error::Response(reason)),
+        _ => Err(OAuth2Error::Response("no access_token".to_string())),
+    }
+}
+
+/// The SASL XOAUTH2 initial response for `user` and `token`.
+pub fn xoauth2(user: &str, token: &str) -> String {
+    format!("user={user}\x01auth=Bearer {token}\x01\x01")
+}
+
+/// Answers an IMAP `AUTHENTICATE XOAUTH2` exchange.
+pub struct XOAuth2 {
+    pub user: String,
+    pub token: String,
+}
+
+impl imap::Authenticator for XOAuth2 {
+    type Response = String;
+
+    fn process(&self, challenge: &[u8]) -> String {
+        // A non-empty challenge is the server's error report, which is
+        // acknowledged with an empty response before it fails the command
+        if challenge.is_empty() {
+            xoauth2(&self.user, &self.token)
+        } else {
+            String::new()
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+use imap::Authenticator;
+
+use super::*;
+
+fn config() -> OAuth2Config {
+    OAuth2Config {
+        token_command: None,
+        token_url: None,
+        client_id: Some("thrum".to_string()),
+        client_secret: Some("s3cret".to_string()),
+        refresh_token: Some("configured".to_string()),
+    }
+}
+
+/// A token endpoint on localhost answering each request with the next of
+/// `responses`, and giving back the request bodies it received.
+fn token_endpoint(responses: Vec<(u16, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/token", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut bodies = Vec::new();
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+            bodies.push(String::from_utf8(request).unwrap());
+            write!(
+                stream,
+                "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
+
+fn cache_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("thrum_test_oauth2_{name}"));
+    std::fs::remove_dir_all(&dir).ok();
+    dir.join("token.toml")
+}
+
+#[test]
+fn xoauth2_initial_response() {
+    assert_eq!(
+        xoauth2("me@example.com", "ya29.token"),
+        "user=me@example.com\x01auth=Bearer ya29.token\x01\x01"
+    );
+    let authenticator = XOAuth2 {
+        user: "me@example.com".to_string(),
+        token: "ya29.token".to_string(),
+    };
+    assert_eq!(
+        authenticator.process(b""),
+        xoauth2("me@example.com", "ya29.token")
+    );
+    // An error report is acknowledged with an empty response
+    assert_eq!(
+        authenticator.process(br#"{"status":"401","schemes":"Bearer"}"#),
+        ""
+    );
+}
+
+#[test]
+fn token_from_command() {
+    let source = TokenSource::new(
+        &OAuth2Config {
+            token_command: Some("printf 'ya29.token\\n'".to_string()),
+            ..config()
+        },
+        None,
+    );
+    assert_eq!(source.access_token().unwrap(), "ya29.token");
+
+    let failing = TokenSource::new(
+        &OAuth2Config {
+            token_command: Some("echo expired >&2; false".to_string()),
+            ..config()
+        },
+        None,
+    );
+    match failing.access_token() {
+        Err(OAuth2Error::Command(reason)) => assert!(reason.contains("expired")),
+        other => panic!("expected a command error, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_token_responses() {
+    assert_eq!(
+        parse_token_response(
+            r#"{"access_token":"a","expires_in":3599,"token_type":"Bearer"}"#,
+            1000
+        )
+        .unwrap(),
+        CachedToken {
+            access_token: "a".to_string(),
+            expires_at: 4599,
+            refresh_token: None,
+        }
+    );
+    assert_eq!(
+        parse_token_response(r#"{"access_token":"a","refresh_token":"r"}"#, 0)
+            .unwrap()
+            .expires_at,
+        3600
+    );
+    assert_eq!(
+        parse_token_response(
+            r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#,
+            0
+        )
+        .unwrap_err()
+        .to_string(),
+        "invalid token response: Token has been expired or revoked."
+    );
+    assert!(matches!(
+        parse_token_response(r#"{"error":"invalid_client"}"#, 0),
+        Err(OAuth2Error::Response(reason)) if reason == "invalid_client"
+    ));
+    assert!(parse_token_response("<html>", 0).is_err());
+}
+
+#[test]
+fn refreshes_and_caches_token() {
+    let (url, endpoint) = token_endpoint(vec![(
+        200,
+        r#"{"access_token":"fresh","expires_in":3600,"refresh_token":"rotated"}"#,
+    )]);
+    let cache = cache_file("refresh");
+    let source = TokenSource::new(
+        &OAuth2Config {
+            token_url: Some(url),
+            ..config()
+        },
+        Some(cache.clone()),
+    );
+
+    assert_eq!(source.access_token().unwrap(), "fresh");
+    // Served from the cache; the endpoint only answers once
+    assert_eq!(source.access_token().unwrap(), "fresh");
+
+    let bodies = endpoint.join().unwrap();
+    assert_eq!(
+        bodies,
+        vec![
+            "grant_type=refresh_token&client_id=thrum&refresh_token=configured&client_secret=s3cret"
+        ]
+    );
+    let cached: CachedToken = toml::from_str(&std::fs::read_to_string(&cache).unwrap()).unwrap();
+    assert_eq!(cached.refresh_token.as_deref(), Some("rotated"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(cache.parent().unwrap()), 0o700);
+        assert_eq!(mode(&cache), 0o600);
+    }
+    std::fs::remove_dir_all(cache.parent().unwrap()).ok();
+}
+
+#[test]
+fn falls_back_to_configured_refresh_token() {
+    let (url, endpoint) = token_endpoint(vec![
+        (400, r#"{"error":"invalid_grant"}"#),
+        (200, r#"{"access_token":"fresh","expires_in":3600}"#),
+    ]);
+    let cache = cache_file("fallback");
+    std::fs::create_dir_all(cache.parent().unwrap()).unwrap();
+    std::fs::write(
+        &cache,
+        "access_token = \"stale\"\nexpires_at = 0\nrefresh_token = \"revoked\"\n",
+    )
+    .unwrap();
+    let source = TokenSource::new(
+        &OAuth2Config {
+            token_url: Some(url),
+            ..config()
+        },
+        Some(cache.clone()),
+    );
+
+    assert_eq!(source.access_token().unwrap(), "fresh");
+    let bodies = endpoint.join().unwrap();
+    assert!(bodies[0].contains("refresh_token=revoked"));
+    assert!(bodies[1].contains("refresh_token=configured"));
+
+    // The refresh token that worked is kept for next time
+    let cached: CachedToken = toml::from_str(&std::fs::read_to_string(&cache).unwrap()).unwrap();
+    assert_eq!(cached.refresh_token.as_deref(), Some("configured"));
+    std::fs::remove_dir_all(cache.parent().unwrap()).ok();
+}
+
+#[test]
+fn missing_settings_are_reported() {
+    let source = TokenSource::new(
+        &OAuth2Config {
+            refresh_token: None,
+            ..config()
+        },
+        None,
+    );
+    assert!(matches!(
+        source.access_token(),
+        Err(OAuth2Error::NotConfigured)
+    ));
+}
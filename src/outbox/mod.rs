@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::private;
+use crate::smtp::{Email, Outgoing};
+
+#[cfg(test)]
+mod test;
+
+/// Seconds before the first automatic retry; each failure doubles it.
+const FIRST_RETRY: i64 = 30;
+
+/// Longest wait between automatic retries.
+const MAX_RETRY: i64 = 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutboxError {
+    #[error("failed to access outbox: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse outbox entry: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("failed to write outbox entry: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// A queued message: its envelope, the draft it was built from and how
+/// delivery went so far. The message itself is stored next to it as an
+/// RFC 5322 `.eml` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(skip)]
+    pub id: String,
+    pub from: String,
+    pub recipients: Vec<String>,
+    /// Unix time the message was queued.
+    pub queued_at: i64,
+    pub attempts: u32,
+    /// Unix time of the next automatic attempt; `None` once the server
+    /// refused the message for good.
+    pub next_attempt: Option<i64>,
+    pub last_error: String,
+    /// What was composed, for editing the message before it goes out.
+    pub draft: Email,
+}
+
+impl Entry {
+    pub fn is_due(&self, now: i64) -> bool {
+        self.next_attempt.is_some_and(|at| at <= now)
+    }
+}
+
+/// Wait in seconds after the `attempts`th failed delivery: 30s, 1m, 2m, ...
+/// up to an hour.
+pub fn retry_delay(attempts: u32) -> i64 {
+    FIRST_RETRY
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(MAX_RETRY)
+}
+
+/// Default location of the outbox: `~/.local/share/thrum/outbox/`.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("thrum").join("outbox"))
+}
+
+/// Messages that could not be delivered yet, kept on disk between runs.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    dir: PathBuf,
+}
+
+impl Outbox {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn message_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.eml"))
+    }
+
+    fn entry_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.toml"))
+    }
+
+    /// Queue `outgoing`, built from `draft`, after a delivery attempt at unix
+    /// time `now` failed with `error`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, outgoing, draft), err)
+    )]
+    pub fn queue(
+        &self,
+        outgoing: &Outgoing,
+        draft: &Email,
+        error: &str,
+        permanent: bool,
+        now: i64,
+    ) -> Result<Entry, OutboxError> {
+        private::create_dir_all(&self.dir)?;
+        let mut id = now.to_string();
+        let mut n = 1;
+        while self.entry_path(&id).exists() || self.message_path(&id).exists() {
+            n += 1;
+            id = format!("{now}-{n}");
+        }
+        let mut entry = Entry {
+            id,
+            from: outgoing.from.clone(),
+            recipients: outgoing.recipients.clone(),
+            queued_at: now,
+            attempts: 0,
+            next_attempt: None,
+            last_error: String::new(),
+            draft: draft.clone(),
+        };
+        // An encrypted message is only kept encrypted; the draft still names
+        // the recipients and subject for the outbox view.
+        if draft.encrypt {
+            entry.draft.body.clear();
+        }
+        private::write(&self.message_path(&entry.id), &outgoing.message)?;
+        self.failed(&mut entry, error, permanent, now)?;
+        Ok(entry)
+    }
+
+    /// Every queued message, oldest first. Entries that cannot be read are
+    /// skipped.
+    pub fn entries(&self) -> Result<Vec<Entry>, OutboxError> {
+        let dir = match std::fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries: Vec<Entry> = dir
+            .filter_map(|file| {
+                let path = file.ok()?.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                let id = path.file_stem()?.to_str()?.to_string();
+                self.load(&path, id).ok()
+            })
+            .collect();
+        entries.sort_by(|a, b| (a.queued_at, &a.id).cmp(&(b.queued_at, &b.id)));
+        Ok(entries)
+    }
+
+    fn load(&self, path: &Path, id: String) -> Result<Entry, OutboxError> {
+        let mut entry: Entry = toml::from_str(&std::fs::read_to_string(path)?)?;
+        entry.id = id;
+        Ok(entry)
+    }
+
+    /// The stored message of `entry`, ready to deliver.
+    pub fn outgoing(&self, entry: &Entry) -> Result<Outgoing, OutboxError> {
+        Ok(Outgoing {
+            from: entry.from.clone(),
+            recipients: entry.recipients.clone(),
+            message: std::fs::read(self.message_path(&entry.id))?,
+        })
+    }
+
+    /// Record another failed delivery of `entry` at unix time `now` and
+    /// schedule the next attempt, unless the failure was `permanent`.
+    pub fn failed(
+        &self,
+        entry: &mut Entry,
+        error: &str,
+        permanent: bool,
+        now: i64,
+    ) -> Result<(), OutboxError> {
+        entry.attempts += 1;
+        entry.last_error = error.to_string();
+        entry.next_attempt = (!permanent).then(|| now + retry_delay(entry.attempts));
+        private::write(&self.entry_path(&entry.id), toml::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Take the entry `id` out of the outbox, after delivery or when
+    /// cancelled.
+    pub fn remove(&self, id: &str) -> Result<(), OutboxError> {
+        for path in [self.entry_path(id), self.message_path(id)] {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
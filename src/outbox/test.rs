@@ -0,0 +1,154 @@
+use super::*;
+
+fn outbox(name: &str) -> Outbox {
+    let dir = std::env::temp_dir().join(format!("thrum_test_outbox_{name}"));
+    std::fs::remove_dir_all(&dir).ok();
+    Outbox::new(dir)
+}
+
+fn draft() -> Email {
+    Email {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec!["secret@example.com".to_string()],
+        subject: "Hello".to_string(),
+        body: "Hi there".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    }
+}
+
+fn outgoing() -> Outgoing {
+    Outgoing {
+        from: "me@example.com".to_string(),
+        recipients: vec![
+            "you@example.com".to_string(),
+            "secret@example.com".to_string(),
+        ],
+        message: b"Subject: Hello\r\n\r\nHi there\r\n".to_vec(),
+    }
+}
+
+#[test]
+fn retry_delay_doubles_up_to_an_hour() {
+    assert_eq!(retry_delay(1), 30);
+    assert_eq!(retry_delay(2), 60);
+    assert_eq!(retry_delay(4), 240);
+    assert_eq!(retry_delay(8), 3600);
+    assert_eq!(retry_delay(u32::MAX), 3600);
+}
+
+#[test]
+fn queued_message_survives_reload() {
+    let outbox = outbox("reload");
+    let queued = outbox
+        .queue(&outgoing(), &draft(), "connection refused", false, 1000)
+        .unwrap();
+    assert_eq!(queued.attempts, 1);
+    assert_eq!(queued.next_attempt, Some(1030));
+
+    let dir = outbox.dir.clone();
+    let entries = Outbox::new(dir.clone()).entries().unwrap();
+    assert_eq!(entries, vec![queued.clone()]);
+    assert_eq!(entries[0].draft.bcc, vec!["secret@example.com".to_string()]);
+    assert_eq!(outbox.outgoing(&entries[0]).unwrap(), outgoing());
+    // The message is kept as a plain RFC 5322 file
+    assert_eq!(
+        std::fs::read(dir.join(format!("{}.eml", queued.id))).unwrap(),
+        outgoing().message
+    );
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn encrypted_message_is_not_kept_in_plaintext() {
+    let outbox = outbox("encrypted");
+    let mut draft = draft();
+    draft.encrypt = true;
+    let queued = outbox
+        .queue(&outgoing(), &draft, "connection refused", false, 1000)
+        .unwrap();
+    assert_eq!(queued.draft.body, "");
+    assert_eq!(queued.draft.subject, draft.subject);
+
+    let text = std::fs::read_to_string(outbox.dir.join(format!("{}.toml", queued.id))).unwrap();
+    assert!(!text.contains(&draft.body));
+    std::fs::remove_dir_all(&outbox.dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn outbox_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let outbox = outbox("private");
+    let queued = outbox
+        .queue(&outgoing(), &draft(), "connection refused", false, 1000)
+        .unwrap();
+    let mode = |name: String| {
+        let path = outbox.dir.join(name);
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    };
+    assert_eq!(mode(String::new()), 0o700);
+    assert_eq!(mode(format!("{}.eml", queued.id)), 0o600);
+    assert_eq!(mode(format!("{}.toml", queued.id)), 0o600);
+    std::fs::remove_dir_all(&outbox.dir).ok();
+}
+
+#[test]
+fn failures_back_off_until_permanent() {
+    let outbox = outbox("failures");
+    let mut entry = outbox
+        .queue(&outgoing(), &draft(), "timed out", false, 1000)
+        .unwrap();
+    assert!(!entry.is_due(1029));
+    assert!(entry.is_due(1030));
+
+    outbox.failed(&mut entry, "timed out", false, 1030).unwrap();
+    assert_eq!(entry.attempts, 2);
+    assert_eq!(entry.next_attempt, Some(1090));
+
+    outbox
+        .failed(&mut entry, "550 mailbox unavailable", true, 1090)
+        .unwrap();
+    assert_eq!(entry.next_attempt, None);
+    assert!(!entry.is_due(i64::MAX));
+    let reloaded = outbox.entries().unwrap();
+    assert_eq!(reloaded[0].last_error, "550 mailbox unavailable");
+    std::fs::remove_dir_all(&outbox.dir).ok();
+}
+
+#[test]
+fn entries_are_ordered_and_removable() {
+    let outbox = outbox("order");
+    assert!(outbox.entries().unwrap().is_empty());
+
+    let second = outbox
+        .queue(&outgoing(), &draft(), "offline", false, 2000)
+        .unwrap();
+    let first = outbox
+        .queue(&outgoing(), &draft(), "offline", false, 1000)
+        .unwrap();
+    // Same second, different file
+    let third = outbox
+        .queue(&outgoing(), &draft(), "offline", false, 2000)
+        .unwrap();
+    assert_ne!(second.id, third.id);
+    let ids: Vec<String> = outbox
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|e| e.id)
+        .collect();
+    assert_eq!(ids, [first.id, second.id.clone(), third.id]);
+
+    outbox.remove(&second.id).unwrap();
+    outbox.remove(&second.id).unwrap();
+    assert_eq!(outbox.entries().unwrap().len(), 2);
+    assert!(outbox.outgoing(&second).is_err());
+    std::fs::remove_dir_all(&outbox.dir).ok();
+}
@@ -0,0 +1,294 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::PgpConfig;
+use crate::external;
+use crate::imap::{Opened, Scheme, Security, SignatureStatus};
+use crate::mime;
+use crate::private;
+
+#[cfg(test)]
+mod test;
+#[cfg(test)]
+pub mod testing;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PgpError {
+    #[error("failed to run gpg: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("gpg failed: {0}")]
+    Gpg(String),
+    #[error("no public key for {}", .0.join(", "))]
+    MissingKeys(Vec<String>),
+}
+
+/// The plaintext of an encrypted message and the signature inside it.
+#[derive(Debug)]
+pub struct Decrypted {
+    pub data: Vec<u8>,
+    pub signature: Option<SignatureStatus>,
+}
+
+/// Runs `gpg` in batch mode against the user's keyring, or the one in
+/// `homedir`.
+#[derive(Debug, Clone)]
+pub struct Gpg {
+    program: String,
+    homedir: Option<PathBuf>,
+}
+
+impl Default for Gpg {
+    fn default() -> Self {
+        Self::new(&PgpConfig::default())
+    }
+}
+
+impl Gpg {
+    pub fn new(config: &PgpConfig) -> Self {
+        Self {
+            program: config.gpg.clone(),
+            homedir: config.homedir.clone(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(["--batch", "--no-tty", "--yes"]);
+        if let Some(ref homedir) = self.homedir {
+            command.arg("--homedir").arg(homedir);
+        }
+        command
+    }
+
+    /// Run `command` with `input` on stdin and return its stdout.
+    fn run(&self, command: Command, input: &[u8]) -> Result<Vec<u8>, PgpError> {
+        let output = external::piped_output(command, input)?;
+        if !output.status.success() {
+            return Err(PgpError::Gpg(reason(&output.stderr)));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Whether the keyring holds a valid key that can encrypt to `address`.
+    pub fn can_encrypt_to(&self, address: &str) -> bool {
+        let output = self
+            .command()
+            .args(["--list-keys", "--with-colons", "--"])
+            .arg(format!("<{address}>"))
+            .stderr(Stdio::null())
+            .output();
+        output.is_ok_and(|output| {
+            output.status.success()
+                && String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .any(usable_for_encryption)
+        })
+    }
+
+    /// The addresses among `addresses` with no key to encrypt to.
+    pub fn missing_keys<'a>(&self, addresses: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        addresses
+            .into_iter()
+            .filter(|address| !self.can_encrypt_to(address))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// An armored detached signature over `data` by `signer`'s key.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, data), err)
+    )]
+    pub fn sign(&self, data: &[u8], signer: &str) -> Result<Vec<u8>, PgpError> {
+        let mut command = self.command();
+        command
+            .args(["--armor", "--detach-sign", "--digest-algo", "SHA256"])
+            .arg("--local-user")
+            .arg(format!("<{signer}>"));
+        self.run(command, data)
+    }
+
+    /// Encrypt `data` to `recipients` and, without revealing their key IDs,
+    /// to `hidden`; signed by `signer` when given.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, data), err)
+    )]
+    pub fn encrypt(
+        &self,
+        data: &[u8],
+        recipients: &[String],
+        hidden: &[String],
+        signer: Option<&str>,
+    ) -> Result<Vec<u8>, PgpError> {
+        let missing = self.missing_keys(recipients.iter().chain(hidden).map(String::as_str));
+        if !missing.is_empty() {
+            return Err(PgpError::MissingKeys(missing));
+        }
+
+        let mut command = self.command();
+        command.args(["--armor", "--encrypt"]);
+        if let Some(signer) = signer {
+            command
+                .args(["--sign", "--digest-algo", "SHA256", "--local-user"])
+                .arg(format!("<{signer}>"));
+        }
+        for recipient in recipients {
+            command.arg("--recipient").arg(format!("<{recipient}>"));
+        }
+        for recipient in hidden {
+            command
+                .arg("--hidden-recipient")
+                .arg(format!("<{recipient}>"));
+        }
+        self.run(command, data)
+    }
+
+    /// Check a detached `signature` over `data`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, data, signature), err)
+    )]
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<SignatureStatus, PgpError> {
+        // gpg wants the signature in a file when the data comes on stdin
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = external::private_dir()?.join(format!(
+            "signature-{}.asc",
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        private::create_new(&path)?.write_all(signature)?;
+
+        let mut command = self.command();
+        command
+            .args(["--status-fd", "1", "--verify"])
+            .arg(&path)
+            .arg("-");
+        let output = external::piped_output(command, data);
+        std::fs::remove_file(&path).ok();
+        let output = output?;
+
+        signature_status(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| PgpError::Gpg(reason(&output.stderr)))
+    }
+
+    /// Decrypt `data`, checking the signature of signed-and-encrypted
+    /// messages on the way.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, data), err)
+    )]
+    pub fn decrypt(&self, data: &[u8]) -> Result<Decrypted, PgpError> {
+        let mut command = self.command();
+        command.args(["--status-fd", "2", "--decrypt"]);
+        let output = external::piped_output(command, data)?;
+
+        // gpg also fails when only the signature could not be checked
+        let status = String::from_utf8_lossy(&output.stderr);
+        if !status.contains("[GNUPG:] DECRYPTION_OKAY") {
+            return Err(PgpError::Gpg(reason(&output.stderr)));
+        }
+        Ok(Decrypted {
+            data: output.stdout,
+            signature: signature_status(&status),
+        })
+    }
+
+    /// Take the protection off a `multipart/signed` or `multipart/encrypted`
+    /// PGP/MIME `body` with the given top-level Content-Type. Other bodies
+    /// give `None`.
+    pub fn open(&self, content_type: &str, body: &[u8]) -> Option<Result<Opened, PgpError>> {
+        let kind = content_type.trim_start().to_ascii_lowercase();
+        let protocol = mime::param(content_type, "protocol")?.to_ascii_lowercase();
+        let boundary = mime::param(content_type, "boundary")?;
+        let parts = mime::split_multipart(body, &boundary);
+
+        if kind.starts_with("multipart/signed") && protocol == "application/pgp-signature" {
+            let [signed, signature] = parts[..] else {
+                return None;
+            };
+            let signature = mime::parse(signature).body;
+            let status = self
+                .verify(signed, &signature)
+                .unwrap_or_else(|e| SignatureStatus::Error(e.to_string()));
+            return Some(Ok(Opened {
+                entity: signed.to_vec(),
+                security: Security {
+                    scheme: Scheme::Pgp,
+                    encrypted: false,
+                    signature: Some(status),
+                },
+            }));
+        }
+
+        if kind.starts_with("multipart/encrypted") && protocol == "application/pgp-encrypted" {
+            let encrypted = mime::parse(parts.get(1)?).body;
+            return Some(self.decrypt(&encrypted).map(|decrypted| {
+                // Signed first, then encrypted (RFC 3156 section 6.1)
+                let inner = mime::parse(&decrypted.data)
+                    .header("content-type")
+                    .and_then(|ct| {
+                        let (_, body) = mime::split_header(&decrypted.data);
+                        self.open(ct, body)
+                    });
+                match inner {
+                    Some(Ok(inner)) => Opened {
+                        entity: inner.entity,
+                        security: Security {
+                            scheme: Scheme::Pgp,
+                            encrypted: true,
+                            signature: inner.security.signature,
+                        },
+                    },
+                    _ => Opened {
+                        entity: decrypted.data,
+                        security: Security {
+                            scheme: Scheme::Pgp,
+                            encrypted: true,
+                            signature: decrypted.signature,
+                        },
+                    },
+                }
+            }));
+        }
+        None
+    }
+}
+
+/// The first signature verdict in gpg `--status-fd` output.
+fn signature_status(status: &str) -> Option<SignatureStatus> {
+    status.lines().find_map(|line| {
+        let mut words = line.strip_prefix("[GNUPG:] ")?.splitn(3, ' ');
+        let keyword = words.next()?;
+        let key_id = words.next().unwrap_or("").to_string();
+        let user_id = words.next().unwrap_or(&key_id).to_string();
+        match keyword {
+            "GOODSIG" => Some(SignatureStatus::Valid(user_id)),
+            "BADSIG" | "EXPSIG" | "EXPKEYSIG" | "REVKEYSIG" => Some(SignatureStatus::Bad(user_id)),
+            "NO_PUBKEY" | "ERRSIG" => Some(SignatureStatus::UnknownKey(key_id)),
+            _ => None,
+        }
+    })
+}
+
+/// The last line gpg wrote to stderr that is not a status line.
+fn reason(stderr: &[u8]) -> String {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .rfind(|line| !line.starts_with("[GNUPG:]"))
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+/// A `pub` line of `--with-colons` output for a key that is neither
+/// expired, revoked nor disabled and has an encryption-capable subkey.
+fn usable_for_encryption(line: &str) -> bool {
+    let fields: Vec<&str> = line.split(':').collect();
+    fields.first() == Some(&"pub")
+        && fields
+            .get(1)
+            .is_some_and(|validity| !"eridn".contains(*validity))
+        && fields.get(11).is_some_and(|caps| caps.contains('E'))
+}
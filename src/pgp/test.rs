@@ -0,0 +1,217 @@
+use super::testing::Keyring;
+use super::*;
+
+#[test]
+fn detached_signature_verifies() {
+    let keyring = Keyring::new("sign", &["alice@example.com"]);
+    let data = b"Content-Type: text/plain\r\n\r\nHello\r\n";
+    let signature = keyring.gpg.sign(data, "alice@example.com").unwrap();
+
+    assert!(signature.starts_with(b"-----BEGIN PGP SIGNATURE-----"));
+    assert!(keyring.verify(&signature, data));
+    assert!(!keyring.verify(&signature, b"Content-Type: text/plain\r\n\r\nHullo\r\n"));
+}
+
+#[test]
+fn signing_without_a_secret_key_fails() {
+    let keyring = Keyring::new("nosecret", &["alice@example.com"]);
+    assert!(matches!(
+        keyring.gpg.sign(b"data", "mallory@example.com"),
+        Err(PgpError::Gpg(_))
+    ));
+}
+
+#[test]
+fn encrypts_to_visible_and_hidden_recipients() {
+    let keyring = Keyring::new(
+        "encrypt",
+        &["alice@example.com", "bob@example.com", "carol@example.com"],
+    );
+    let encrypted = keyring
+        .gpg
+        .encrypt(
+            b"secret",
+            &[
+                "alice@example.com".to_string(),
+                "bob@example.com".to_string(),
+            ],
+            &["carol@example.com".to_string()],
+            Some("alice@example.com"),
+        )
+        .unwrap();
+
+    assert!(encrypted.starts_with(b"-----BEGIN PGP MESSAGE-----"));
+    assert_eq!(keyring.decrypt(&encrypted), b"secret");
+}
+
+#[test]
+fn encryption_lists_recipients_without_keys() {
+    let keyring = Keyring::new("missing", &["alice@example.com"]);
+    assert_eq!(
+        keyring
+            .gpg
+            .missing_keys(["alice@example.com", "dave@example.com"]),
+        vec!["dave@example.com"]
+    );
+    match keyring.gpg.encrypt(
+        b"secret",
+        &[
+            "dave@example.com".to_string(),
+            "erin@example.com".to_string(),
+        ],
+        &[],
+        None,
+    ) {
+        Err(e @ PgpError::MissingKeys(_)) => assert_eq!(
+            e.to_string(),
+            "no public key for dave@example.com, erin@example.com"
+        ),
+        other => panic!("expected missing keys, got {other:?}"),
+    }
+}
+
+#[test]
+fn usable_for_encryption_checks_validity_and_capabilities() {
+    assert!(usable_for_encryption(
+        "pub:u:255:22:ABCD:1700000000:::u:::scESC:::+:::23::0:"
+    ));
+    assert!(!usable_for_encryption(
+        "pub:r:255:22:ABCD:1700000000:::u:::scESC:::+:::23::0:"
+    ));
+    assert!(!usable_for_encryption(
+        "pub:u:255:22:ABCD:1700000000:::u:::scSC:::+:::23::0:"
+    ));
+    assert!(!usable_for_encryption(
+        "uid:u::::1700000000::HASH::Test <a@b>::::::::::0:"
+    ));
+}
+
+/// A multipart/signed message over `entity`, returning its Content-Type and
+/// body.
+fn signed_message(entity: &[u8], signature: &[u8]) -> (String, Vec<u8>) {
+    let content_type = "multipart/signed; micalg=pgp-sha256; \
+                        protocol=\"application/pgp-signature\"; boundary=\"sig\"";
+    let mut body = b"--sig\r\n".to_vec();
+    body.extend_from_slice(entity);
+    body.extend_from_slice(b"\r\n--sig\r\nContent-Type: application/pgp-signature\r\n\r\n");
+    body.extend_from_slice(signature);
+    body.extend_from_slice(b"\r\n--sig--\r\n");
+    (content_type.to_string(), body)
+}
+
+const ENTITY: &[u8] = b"Content-Type: text/plain; charset=utf-8\r\n\r\nHello Bob\r\n";
+
+#[test]
+fn opens_signed_message() {
+    let keyring = Keyring::new("open_signed", &["alice@example.com"]);
+    let signature = keyring.gpg.sign(ENTITY, "alice@example.com").unwrap();
+
+    let (content_type, body) = signed_message(ENTITY, &signature);
+    let opened = keyring.gpg.open(&content_type, &body).unwrap().unwrap();
+    assert_eq!(opened.entity, ENTITY);
+    assert!(!opened.security.encrypted);
+    assert_eq!(
+        opened.security.signature,
+        Some(SignatureStatus::Valid(
+            "Test <alice@example.com>".to_string()
+        ))
+    );
+
+    let tampered = String::from_utf8(body).unwrap().replace("Hello", "Hullo");
+    let opened = keyring
+        .gpg
+        .open(&content_type, tampered.as_bytes())
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        opened.security.signature,
+        Some(SignatureStatus::Bad(_))
+    ));
+}
+
+#[test]
+fn signature_by_unknown_key() {
+    let signer = Keyring::new("open_signer", &["alice@example.com"]);
+    let reader = Keyring::new("open_reader", &["bob@example.com"]);
+    let signature = signer.gpg.sign(ENTITY, "alice@example.com").unwrap();
+
+    let (content_type, body) = signed_message(ENTITY, &signature);
+    let opened = reader.gpg.open(&content_type, &body).unwrap().unwrap();
+    assert!(matches!(
+        opened.security.signature,
+        Some(SignatureStatus::UnknownKey(_))
+    ));
+}
+
+#[test]
+fn opens_encrypted_message() {
+    let keyring = Keyring::new("open_encrypted", &["alice@example.com", "bob@example.com"]);
+    let encrypted = keyring
+        .gpg
+        .encrypt(
+            ENTITY,
+            &["bob@example.com".to_string()],
+            &[],
+            Some("alice@example.com"),
+        )
+        .unwrap();
+
+    let content_type = "multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=enc";
+    let mut body = b"--enc\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n\
+                     --enc\r\nContent-Type: application/octet-stream\r\n\r\n"
+        .to_vec();
+    body.extend_from_slice(&encrypted);
+    body.extend_from_slice(b"\r\n--enc--\r\n");
+
+    let opened = keyring.gpg.open(content_type, &body).unwrap().unwrap();
+    assert_eq!(opened.entity, ENTITY);
+    assert!(opened.security.encrypted);
+    assert_eq!(
+        opened.security.signature,
+        Some(SignatureStatus::Valid(
+            "Test <alice@example.com>".to_string()
+        ))
+    );
+
+    let stranger = Keyring::new("open_stranger", &[]);
+    assert!(matches!(
+        stranger.gpg.open(content_type, &body),
+        Some(Err(PgpError::Gpg(_)))
+    ));
+}
+
+#[test]
+fn leaves_other_messages_alone() {
+    let gpg = Gpg::default();
+    assert!(gpg.open("text/plain; charset=utf-8", b"Hello").is_none());
+    assert!(
+        gpg.open("multipart/mixed; boundary=x", b"--x\r\n\r\nHi\r\n--x--")
+            .is_none()
+    );
+}
+
+#[test]
+fn parses_signature_status() {
+    assert_eq!(
+        signature_status(
+            "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 0123456789ABCDEF Alice <alice@example.com>\n"
+        ),
+        Some(SignatureStatus::Valid(
+            "Alice <alice@example.com>".to_string()
+        ))
+    );
+    assert_eq!(
+        signature_status("[GNUPG:] BADSIG 0123456789ABCDEF Alice <alice@example.com>"),
+        Some(SignatureStatus::Bad(
+            "Alice <alice@example.com>".to_string()
+        ))
+    );
+    assert_eq!(
+        signature_status(
+            "[GNUPG:] ERRSIG 0123456789ABCDEF 22 10 00 1700000000 9 -\n\
+             [GNUPG:] NO_PUBKEY 0123456789ABCDEF"
+        ),
+        Some(SignatureStatus::UnknownKey("0123456789ABCDEF".to_string()))
+    );
+    assert_eq!(signature_status("[GNUPG:] DECRYPTION_OKAY"), None);
+}
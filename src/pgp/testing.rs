@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use super::*;
+
+/// A throwaway keyring with a key pair per address, removed on drop.
+pub struct Keyring {
+    pub gpg: Gpg,
+    dir: PathBuf,
+}
+
+impl Keyring {
+    pub fn new(name: &str, addresses: &[&str]) -> Self {
+        let dir = std::env::temp_dir().join(format!("thrum_test_gpg_{name}"));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        }
+
+        let gpg = Gpg::new(&PgpConfig {
+            homedir: Some(dir.clone()),
+            ..PgpConfig::default()
+        });
+        for address in addresses {
+            let status = gpg
+                .command()
+                .args(["--passphrase", "", "--quick-gen-key"])
+                .arg(format!("Test <{address}>"))
+                .args(["future-default", "default", "never"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success(), "key generation failed for {address}");
+        }
+        Self { gpg, dir }
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> PathBuf {
+        let path = self.dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// Whether `signature` is a good signature over `data`.
+    pub fn verify(&self, signature: &[u8], data: &[u8]) -> bool {
+        let signature = self.write("check.asc", signature);
+        let data = self.write("check.dat", data);
+        self.gpg
+            .command()
+            .arg("--verify")
+            .args([&signature, &data])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Vec<u8> {
+        let mut command = self.gpg.command();
+        command.arg("--decrypt");
+        self.gpg.run(command, data).unwrap()
+    }
+}
+
+impl Drop for Keyring {
+    fn drop(&mut self) {
+        Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(&self.dir)
+            .args(["--kill", "gpg-agent"])
+            .status()
+            .ok();
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
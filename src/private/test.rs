@@ -0,0 +1,51 @@
+use super::*;
+
+#[cfg(unix)]
+fn mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn write_replaces_contents() {
+    let dir = std::env::temp_dir().join("thrum_test_private_write");
+    std::fs::remove_dir_all(&dir).ok();
+    create_dir_all(&dir.join("nested")).unwrap();
+    let path = dir.join("nested").join("file");
+    write(&path, "first, longer").unwrap();
+    write(&path, "second").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+    assert_eq!(
+        create_new(&path).unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(
+        create_dir(&dir).unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join("thrum_test_private_modes");
+    std::fs::remove_dir_all(&dir).ok();
+    create_dir_all(&dir.join("nested")).unwrap();
+    assert_eq!(mode(&dir), 0o700);
+    assert_eq!(mode(&dir.join("nested")), 0o700);
+
+    let path = dir.join("new");
+    create_new(&path).unwrap();
+    assert_eq!(mode(&path), 0o600);
+
+    // Written by an older version without the restriction
+    let path = dir.join("old");
+    std::fs::write(&path, "old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    write(&path, "new").unwrap();
+    assert_eq!(mode(&path), 0o600);
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod test;
+
+/// Column at which quoted and reflowed text is wrapped.
+pub const WIDTH: usize = 72;
+
+/// Quote depth of a line and its text after the markers. Accepts `>>`,
+/// `> >` and `>text` alike.
+pub fn depth(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+    while let Some(after) = rest.strip_prefix('>') {
+        depth += 1;
+        rest = after;
+        if let Some(next) = rest.strip_prefix(' ')
+            && next.starts_with('>')
+        {
+            rest = next;
+        }
+    }
+    if depth > 0 {
+        rest = rest.strip_prefix(' ').unwrap_or(rest);
+    }
+    (depth, rest)
+}
+
+/// Markers for a line at `depth`, e.g. `>> ` for 2.
+fn prefix(depth: usize) -> String {
+    if depth == 0 {
+        String::new()
+    } else {
+        format!("{} ", ">".repeat(depth))
+    }
+}
+
+/// A line at `depth`, without trailing space when the text is empty.
+pub fn line_at(depth: usize, text: &str) -> String {
+    if text.is_empty() {
+        ">".repeat(depth)
+    } else {
+        format!("{}{text}", prefix(depth))
+    }
+}
+
+/// Quote `text` one level deeper, wrapping lines that would run past
+/// `width` without joining short ones.
+pub fn quote(text: &str, width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let (level, content) = depth(line);
+        let level = level + 1;
+        let available = width.saturating_sub(prefix(level).len());
+        if content.chars().count() <= available {
+            out.push(line_at(level, content.trim_end()));
+        } else {
+            out.extend(wrap(content, available).iter().map(|l| line_at(level, l)));
+        }
+    }
+    out
+}
+
+/// Join the paragraphs in `lines` and wrap them to `width`, keeping each
+/// paragraph at its quote depth. Blank lines, changes of depth and quoted
+/// signature delimiters end a paragraph; a `-- ` line and everything after
+/// it is kept as is.
+pub fn reflow(lines: &[String], width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut paragraph: Option<(usize, Vec<&str>)> = None;
+
+    let flush = |paragraph: &mut Option<(usize, Vec<&str>)>, out: &mut Vec<String>| {
+        if let Some((level, words)) = paragraph.take() {
+            let available = width.saturating_sub(prefix(level).len());
+            let text = words.join(" ");
+            out.extend(wrap(&text, available).iter().map(|l| line_at(level, l)));
+        }
+    };
+
+    for (i, line) in lines.iter().enumerate() {
+        // Our own signature is left exactly as written
+        if line == "-- " {
+            flush(&mut paragraph, &mut out);
+            out.extend(lines[i..].iter().cloned());
+            return out;
+        }
+        let (level, content) = depth(line);
+        let content = content.trim_end();
+        if content.is_empty() || content == "--" {
+            flush(&mut paragraph, &mut out);
+            out.push(line_at(level, content));
+            continue;
+        }
+        if paragraph.as_ref().is_some_and(|(l, _)| *l != level) {
+            flush(&mut paragraph, &mut out);
+        }
+        paragraph
+            .get_or_insert_with(|| (level, Vec::new()))
+            .1
+            .extend(content.split_whitespace());
+    }
+    flush(&mut paragraph, &mut out);
+    out
+}
+
+/// Greedy word wrap; words longer than `width` get a line of their own.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
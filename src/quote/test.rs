@@ -0,0 +1,77 @@
+use super::*;
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn depth_accepts_common_marker_styles() {
+    assert_eq!(depth("plain"), (0, "plain"));
+    assert_eq!(depth("> one"), (1, "one"));
+    assert_eq!(depth(">> two"), (2, "two"));
+    assert_eq!(depth("> > two"), (2, "two"));
+    assert_eq!(depth(">>>three"), (3, "three"));
+    assert_eq!(depth(">"), (1, ""));
+}
+
+#[test]
+fn quote_adds_a_level_and_normalises_markers() {
+    assert_eq!(
+        quote("Hi\n\n> > earlier\n>> also earlier", 72),
+        vec!["> Hi", ">", ">>> earlier", ">>> also earlier"]
+    );
+}
+
+#[test]
+fn quote_wraps_long_lines_without_joining_short_ones() {
+    let text = "short\nthe quick brown fox jumps over the lazy dog\n> nested words that run long";
+    assert_eq!(
+        quote(text, 20),
+        vec![
+            "> short",
+            "> the quick brown",
+            "> fox jumps over the",
+            "> lazy dog",
+            ">> nested words that",
+            ">> run long",
+        ]
+    );
+}
+
+#[test]
+fn reflow_joins_paragraphs_per_depth() {
+    let input = lines(
+        ">> a b c\n>> d e f g h\n> one two\n> three four five six\n>\n> new paragraph\nmy reply",
+    );
+    assert_eq!(
+        reflow(&input, 14),
+        vec![
+            ">> a b c d e f",
+            ">> g h",
+            "> one two",
+            "> three four",
+            "> five six",
+            ">",
+            "> new",
+            "> paragraph",
+            "my reply",
+        ]
+    );
+}
+
+#[test]
+fn reflow_keeps_signature_verbatim() {
+    let input = lines("some\ntext\n\n-- \nMe\nExample Corp");
+    assert_eq!(
+        reflow(&input, 72),
+        vec!["some text", "", "-- ", "Me", "Example Corp"]
+    );
+}
+
+#[test]
+fn long_words_get_their_own_line() {
+    assert_eq!(
+        wrap("see https://example.com/a/very/long/path ok", 10),
+        vec!["see", "https://example.com/a/very/long/path", "ok"]
+    );
+}
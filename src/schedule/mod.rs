@@ -0,0 +1,211 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::private;
+use crate::smtp::{Email, Outgoing};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("failed to access schedule: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse scheduled message: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("failed to write scheduled message: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// A message waiting for its time to be sent. Encrypted messages are only
+/// kept built, as an RFC 5322 `.eml` file next to the entry, and their
+/// draft has no body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scheduled {
+    #[serde(skip)]
+    pub id: String,
+    /// Unix time the message goes out.
+    pub send_at: i64,
+    /// Envelope of the built message, for encrypted ones.
+    #[serde(default)]
+    pub from: String,
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// Why sending failed; such messages wait to be edited or sent by hand.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// `Message-ID` of the copy saved to the drafts folder.
+    #[serde(default)]
+    pub draft_copy: Option<String>,
+    pub draft: Email,
+}
+
+impl Scheduled {
+    pub fn is_due(&self, now: i64) -> bool {
+        self.error.is_none() && self.send_at <= now
+    }
+}
+
+/// Default location of the schedule: `~/.local/share/thrum/scheduled/`.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("thrum").join("scheduled"))
+}
+
+/// Drafts to be sent later, kept on disk between runs.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    dir: PathBuf,
+}
+
+impl Schedule {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.toml"))
+    }
+
+    fn message_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.eml"))
+    }
+
+    /// Schedule `draft` to be sent at unix time `send_at`. An encrypted
+    /// draft is stored as `built`, the message it was made into.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, draft, built), err)
+    )]
+    pub fn add(
+        &self,
+        draft: &Email,
+        built: Option<&Outgoing>,
+        send_at: i64,
+    ) -> Result<Scheduled, ScheduleError> {
+        private::create_dir_all(&self.dir)?;
+        let mut id = send_at.to_string();
+        let mut n = 1;
+        while self.path(&id).exists() || self.message_path(&id).exists() {
+            n += 1;
+            id = format!("{send_at}-{n}");
+        }
+        let mut entry = Scheduled {
+            id,
+            send_at,
+            from: String::new(),
+            recipients: Vec::new(),
+            error: None,
+            draft_copy: None,
+            draft: draft.clone(),
+        };
+        if let Some(built) = built {
+            entry.from = built.from.clone();
+            entry.recipients = built.recipients.clone();
+            entry.draft.body.clear();
+            private::write(&self.message_path(&entry.id), &built.message)?;
+        }
+        self.save(&entry)?;
+        Ok(entry)
+    }
+
+    /// Write `entry` back after changing it.
+    pub fn save(&self, entry: &Scheduled) -> Result<(), ScheduleError> {
+        private::write(&self.path(&entry.id), toml::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// The stored message of an encrypted `entry`, ready to deliver.
+    pub fn outgoing(&self, entry: &Scheduled) -> Result<Outgoing, ScheduleError> {
+        Ok(Outgoing {
+            from: entry.from.clone(),
+            recipients: entry.recipients.clone(),
+            message: std::fs::read(self.message_path(&entry.id))?,
+        })
+    }
+
+    /// Every scheduled message, soonest first. Entries that cannot be read
+    /// are skipped.
+    pub fn entries(&self) -> Result<Vec<Scheduled>, ScheduleError> {
+        let dir = match std::fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries: Vec<Scheduled> = dir
+            .filter_map(|file| {
+                let path = file.ok()?.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                let id = path.file_stem()?.to_str()?.to_string();
+                self.load(&path, id).ok()
+            })
+            .collect();
+        entries.sort_by(|a, b| (a.send_at, &a.id).cmp(&(b.send_at, &b.id)));
+        Ok(entries)
+    }
+
+    /// The entry `id`.
+    pub fn get(&self, id: &str) -> Result<Scheduled, ScheduleError> {
+        self.load(&self.path(id), id.to_string())
+    }
+
+    fn load(&self, path: &Path, id: String) -> Result<Scheduled, ScheduleError> {
+        let mut entry: Scheduled = toml::from_str(&std::fs::read_to_string(path)?)?;
+        entry.id = id;
+        Ok(entry)
+    }
+
+    /// Record that sending `entry` failed; it is not tried again on its own.
+    pub fn failed(&self, entry: &mut Scheduled, error: &str) -> Result<(), ScheduleError> {
+        entry.error = Some(error.to_string());
+        self.save(entry)
+    }
+
+    /// Take the entry `id` off the schedule, once sent or when cancelled.
+    pub fn remove(&self, id: &str) -> Result<(), ScheduleError> {
+        for path in [self.path(id), self.message_path(id)] {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// When to send, relative to the local time `now`: `HH:MM` (the next time
+/// the clock shows it), `tomorrow HH:MM`, `YYYY-MM-DD HH:MM`, or a delay
+/// such as `+30m`, `+2h` or `+1d`.
+pub fn parse_time(text: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let text = text.trim().to_ascii_lowercase();
+    if let Some(delay) = text.strip_prefix('+') {
+        let (count, unit) = delay.split_at(delay.find(|c: char| !c.is_ascii_digit())?);
+        let count: i64 = count.parse().ok()?;
+        let delay = match unit.trim() {
+            "m" | "min" => Duration::try_minutes(count)?,
+            "h" => Duration::try_hours(count)?,
+            "d" => Duration::try_days(count)?,
+            _ => return None,
+        };
+        return now.checked_add_signed(delay);
+    }
+    if let Some(time) = text.strip_prefix("tomorrow") {
+        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+        return Some(now.date().succ_opt()?.and_time(time));
+    }
+    if let Some((date, time)) = text.split_once(' ') {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+        return Some(date.and_time(time));
+    }
+    let time = NaiveTime::parse_from_str(&text, "%H:%M").ok()?;
+    let today = now.date().and_time(time);
+    if today > now {
+        Some(today)
+    } else {
+        Some(today.date().succ_opt()?.and_time(time))
+    }
+}
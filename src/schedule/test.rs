@@ -0,0 +1,142 @@
+use super::*;
+
+fn schedule(name: &str) -> Schedule {
+    let dir = std::env::temp_dir().join(format!("thrum_test_schedule_{name}"));
+    std::fs::remove_dir_all(&dir).ok();
+    Schedule::new(dir)
+}
+
+fn draft(subject: &str) -> Email {
+    Email {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: subject.to_string(),
+        body: "Good morning".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    }
+}
+
+fn at(text: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+}
+
+#[test]
+fn parses_send_times() {
+    let now = at("2025-03-10 22:15");
+    assert_eq!(parse_time("23:00", now), Some(at("2025-03-10 23:00")));
+    assert_eq!(parse_time("08:30", now), Some(at("2025-03-11 08:30")));
+    assert_eq!(parse_time("22:15", now), Some(at("2025-03-11 22:15")));
+    assert_eq!(
+        parse_time("Tomorrow 9:00", now),
+        Some(at("2025-03-11 09:00"))
+    );
+    assert_eq!(
+        parse_time("2025-04-01 07:45", now),
+        Some(at("2025-04-01 07:45"))
+    );
+    assert_eq!(parse_time("+30m", now), Some(at("2025-03-10 22:45")));
+    assert_eq!(parse_time(" +2h ", now), Some(at("2025-03-11 00:15")));
+    assert_eq!(parse_time("+1d", now), Some(at("2025-03-11 22:15")));
+
+    for text in [
+        "",
+        "soon",
+        "25:00",
+        "+",
+        "+2",
+        "+2y",
+        "tomorrow",
+        "2025-13-01 08:00",
+    ] {
+        assert_eq!(parse_time(text, now), None, "{text:?}");
+    }
+}
+
+#[test]
+fn scheduled_messages_survive_reload() {
+    let schedule = schedule("reload");
+    assert!(schedule.entries().unwrap().is_empty());
+
+    let later = schedule.add(&draft("Later"), None, 2000).unwrap();
+    let sooner = schedule.add(&draft("Sooner"), None, 1000).unwrap();
+    let same_time = schedule.add(&draft("Same time"), None, 2000).unwrap();
+    assert_ne!(later.id, same_time.id);
+
+    let entries = Schedule::new(schedule.dir.clone()).entries().unwrap();
+    assert_eq!(entries, vec![sooner.clone(), later.clone(), same_time]);
+    assert!(entries[0].is_due(1000));
+    assert!(!entries[1].is_due(1999));
+
+    schedule.remove(&later.id).unwrap();
+    schedule.remove(&later.id).unwrap();
+    assert_eq!(schedule.entries().unwrap().len(), 2);
+    std::fs::remove_dir_all(&schedule.dir).ok();
+}
+
+#[test]
+fn failed_messages_are_not_due() {
+    let schedule = schedule("failed");
+    let mut entry = schedule.add(&draft("Hello"), None, 1000).unwrap();
+    schedule
+        .failed(&mut entry, "550 mailbox unavailable")
+        .unwrap();
+    assert!(!entry.is_due(i64::MAX));
+
+    let reloaded = schedule.entries().unwrap();
+    assert_eq!(
+        reloaded[0].error.as_deref(),
+        Some("550 mailbox unavailable")
+    );
+    std::fs::remove_dir_all(&schedule.dir).ok();
+}
+
+#[test]
+fn encrypted_message_is_only_kept_built() {
+    let schedule = schedule("encrypted");
+    let mut secret = draft("Secret");
+    secret.encrypt = true;
+    let built = Outgoing {
+        from: "me@example.com".to_string(),
+        recipients: vec!["you@example.com".to_string()],
+        message: b"Content-Type: multipart/encrypted\r\n\r\n...".to_vec(),
+    };
+    let entry = schedule.add(&secret, Some(&built), 1000).unwrap();
+    assert_eq!(entry.draft.body, "");
+    assert_eq!(entry.draft.subject, "Secret");
+
+    let text = std::fs::read_to_string(schedule.path(&entry.id)).unwrap();
+    assert!(!text.contains("Good morning"));
+    let reloaded = schedule.get(&entry.id).unwrap();
+    assert_eq!(schedule.outgoing(&reloaded).unwrap(), built);
+
+    schedule.remove(&entry.id).unwrap();
+    assert!(!schedule.message_path(&entry.id).exists());
+    std::fs::remove_dir_all(&schedule.dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn schedule_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let schedule = schedule("private");
+    let mut secret = draft("Secret");
+    secret.encrypt = true;
+    let built = Outgoing {
+        from: "me@example.com".to_string(),
+        recipients: vec!["you@example.com".to_string()],
+        message: b"encrypted".to_vec(),
+    };
+    let entry = schedule.add(&secret, Some(&built), 1000).unwrap();
+    let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&schedule.dir), 0o700);
+    assert_eq!(mode(&schedule.path(&entry.id)), 0o600);
+    assert_eq!(mode(&schedule.message_path(&entry.id)), 0o600);
+    std::fs::remove_dir_all(&schedule.dir).ok();
+}
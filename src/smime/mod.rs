@@ -0,0 +1,278 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::SmimeConfig;
+use crate::external;
+use crate::imap::{Opened, Scheme, Security, SignatureStatus};
+use crate::mime;
+use crate::private;
+
+#[cfg(test)]
+mod test;
+#[cfg(test)]
+pub mod testing;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SmimeError {
+    #[error("failed to run openssl: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("openssl failed: {0}")]
+    Openssl(String),
+    #[error("no S/MIME certificate and key configured")]
+    NoCertificate,
+}
+
+/// Runs `openssl cms` with the configured certificate, key and trusted CAs.
+#[derive(Debug, Clone)]
+pub struct Smime {
+    program: String,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    ca_file: Option<PathBuf>,
+}
+
+impl Default for Smime {
+    fn default() -> Self {
+        Self::new(&SmimeConfig::default())
+    }
+}
+
+impl Smime {
+    pub fn new(config: &SmimeConfig) -> Self {
+        Self {
+            program: config.openssl.clone(),
+            cert: config.cert.clone(),
+            key: config.key.clone(),
+            ca_file: config.ca_file.clone(),
+        }
+    }
+
+    fn cms(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.arg("cms");
+        command
+    }
+
+    /// The certificate and key to sign and decrypt with.
+    fn identity(&self) -> Result<(&Path, &Path), SmimeError> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => Ok((cert, key)),
+            _ => Err(SmimeError::NoCertificate),
+        }
+    }
+
+    /// Run `command` with `input` on stdin and return its stdout.
+    fn run(&self, command: Command, input: &[u8]) -> Result<Vec<u8>, SmimeError> {
+        let output = external::piped_output(command, input)?;
+        if !output.status.success() {
+            return Err(SmimeError::Openssl(reason(&output.stderr)));
+        }
+        Ok(output.stdout)
+    }
+
+    /// A detached DER signature over `data` with the configured certificate.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, data), err)
+    )]
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SmimeError> {
+        let (cert, key) = self.identity()?;
+        let mut command = self.cms();
+        command
+            .args(["-sign", "-binary", "-outform", "DER", "-md", "sha256"])
+            .arg("-signer")
+            .arg(cert)
+            .arg("-inkey")
+            .arg(key);
+        self.run(command, data)
+    }
+
+    /// Check the signature on a signed S/MIME `message`, either
+    /// `multipart/signed` or opaque `signed-data`, and return the content
+    /// with the verdict.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, message), err)
+    )]
+    pub fn verify(&self, message: &[u8]) -> Result<(Vec<u8>, SignatureStatus), SmimeError> {
+        // openssl writes the signer's certificate to a file, which we create
+        // first so it is private to us
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let signer = external::private_dir()?.join(format!(
+            "signer-{}.pem",
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        private::create_new(&signer)?;
+        let verify = |options: &[&str]| {
+            let mut command = self.cms();
+            command
+                .args(["-verify", "-inform", "SMIME"])
+                .args(options)
+                .arg("-signer")
+                .arg(&signer);
+            if let Some(ref ca_file) = self.ca_file {
+                command.arg("-CAfile").arg(ca_file);
+            }
+            external::piped_output(command, message)
+        };
+
+        // Trusted, then signed by an untrusted certificate, then broken
+        let result = (|| {
+            let output = verify(&[])?;
+            if output.status.success() {
+                return Ok((output.stdout, SignatureStatus::Valid(self.signer(&signer))));
+            }
+            let output = verify(&["-noverify"])?;
+            if output.status.success() {
+                return Ok((
+                    output.stdout,
+                    SignatureStatus::UnknownKey(self.signer(&signer)),
+                ));
+            }
+            let output = verify(&["-noverify", "-nosigs"])?;
+            if output.status.success() {
+                return Ok((output.stdout, SignatureStatus::Bad(self.signer(&signer))));
+            }
+            Err(SmimeError::Openssl(reason(&output.stderr)))
+        })();
+        std::fs::remove_file(&signer).ok();
+        result
+    }
+
+    /// The address in the certificate at `path`, or its subject.
+    fn signer(&self, path: &Path) -> String {
+        let field = |name: &str| {
+            Command::new(&self.program)
+                .args(["x509", "-noout", name, "-in"])
+                .arg(path)
+                .stderr(Stdio::null())
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| {
+                    let text = String::from_utf8_lossy(&output.stdout);
+                    let line = text.lines().next()?.trim();
+                    let line = line.strip_prefix("subject=").unwrap_or(line);
+                    (!line.is_empty()).then(|| line.to_string())
+                })
+        };
+        field("-email")
+            .or_else(|| field("-subject"))
+            .unwrap_or_else(|| "unknown signer".to_string())
+    }
+
+    /// Decrypt an `enveloped-data` S/MIME `message` with the configured key.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, message), err)
+    )]
+    pub fn decrypt(&self, message: &[u8]) -> Result<Vec<u8>, SmimeError> {
+        let (cert, key) = self.identity()?;
+        let mut command = self.cms();
+        command
+            .args(["-decrypt", "-inform", "SMIME"])
+            .arg("-recip")
+            .arg(cert)
+            .arg("-inkey")
+            .arg(key);
+        self.run(command, message)
+    }
+
+    /// Take the protection off an S/MIME message with the given top-level
+    /// `header` block and `body`. Other messages give `None`.
+    pub fn open(&self, header: &str, body: &[u8]) -> Option<Result<Opened, SmimeError>> {
+        let content_type = mime::parse_headers(header)
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))?
+            .1;
+        let kind = content_type.trim_start().to_ascii_lowercase();
+        let protocol = mime::param(&content_type, "protocol")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let smime_type = mime::param(&content_type, "smime-type")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let signed = kind.starts_with("multipart/signed")
+            && matches!(
+                protocol.as_str(),
+                "application/pkcs7-signature" | "application/x-pkcs7-signature"
+            );
+        let enveloped = kind.starts_with("application/pkcs7-mime")
+            || kind.starts_with("application/x-pkcs7-mime");
+        if !signed && !enveloped {
+            return None;
+        }
+
+        // openssl reads the message with its headers
+        let mut message = header
+            .trim_end()
+            .replace("\r\n", "\n")
+            .replace('\n', "\r\n");
+        message.push_str("\r\n\r\n");
+        let mut message = message.into_bytes();
+        message.extend_from_slice(body);
+
+        if signed || smime_type == "signed-data" {
+            let (entity, status) = match self.verify(&message) {
+                Ok(verified) => verified,
+                Err(e) => (
+                    signed_content(&content_type, body)?,
+                    SignatureStatus::Error(e.to_string()),
+                ),
+            };
+            return Some(Ok(Opened {
+                entity,
+                security: Security {
+                    scheme: Scheme::Smime,
+                    encrypted: false,
+                    signature: Some(status),
+                },
+            }));
+        }
+
+        Some(self.decrypt(&message).map(|entity| {
+            // Usually signed first, then encrypted
+            let (header, body) = mime::split_header(&entity);
+            match self.open(&String::from_utf8_lossy(header), body) {
+                Some(Ok(inner)) => Opened {
+                    entity: inner.entity,
+                    security: Security {
+                        encrypted: true,
+                        ..inner.security
+                    },
+                },
+                _ => Opened {
+                    entity,
+                    security: Security {
+                        scheme: Scheme::Smime,
+                        encrypted: true,
+                        signature: None,
+                    },
+                },
+            }
+        }))
+    }
+}
+
+/// The first part of a `multipart/signed` body, shown as is when the
+/// signature cannot be checked at all.
+fn signed_content(content_type: &str, body: &[u8]) -> Option<Vec<u8>> {
+    let boundary = mime::param(content_type, "boundary")?;
+    mime::split_multipart(body, &boundary)
+        .first()
+        .map(|part| part.to_vec())
+}
+
+/// The last line openssl wrote to stderr, without the library's error code
+/// prefix.
+fn reason(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let line = stderr.lines().next_back().unwrap_or("").trim();
+    // 40F7851C777F0000:error:068000D3:asn1 encoding routines:...:reason:file:line:
+    match line.split(':').collect::<Vec<_>>()[..] {
+        [_, "error", _, _, _, reason, ..] => reason.to_string(),
+        _ => line.to_string(),
+    }
+}
@@ -0,0 +1,128 @@
+use super::testing::Certificate;
+use super::*;
+
+const ENTITY: &[u8] = b"Content-Type: text/plain; charset=utf-8\r\n\r\nHello Bob\r\n";
+
+/// A multipart/signed message over `entity`, returning its header block
+/// and body.
+fn signed_message(entity: &[u8], signature: &[u8]) -> (String, Vec<u8>) {
+    use base64::Engine;
+    let header = "Content-Type: multipart/signed; micalg=sha-256;\r\n \
+                  protocol=\"application/pkcs7-signature\"; boundary=\"signed-boundary\"\r\n\r\n";
+    let mut body = b"--signed-boundary\r\n".to_vec();
+    body.extend_from_slice(entity);
+    body.extend_from_slice(
+        b"\r\n--signed-boundary\r\nContent-Type: application/pkcs7-signature\r\n\
+          Content-Transfer-Encoding: base64\r\n\r\n",
+    );
+    let signature = base64::engine::general_purpose::STANDARD.encode(signature);
+    for line in signature.as_bytes().chunks(76) {
+        body.extend_from_slice(line);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--signed-boundary--\r\n");
+    (header.to_string(), body)
+}
+
+#[test]
+fn opens_signed_message() {
+    let certificate = Certificate::new("signed", "alice@example.com");
+    let signature = certificate.smime.sign(ENTITY).unwrap();
+    let (header, body) = signed_message(ENTITY, &signature);
+
+    let opened = certificate.smime.open(&header, &body).unwrap().unwrap();
+    assert_eq!(opened.entity, ENTITY);
+    assert_eq!(
+        opened.security,
+        Security {
+            scheme: Scheme::Smime,
+            encrypted: false,
+            signature: Some(SignatureStatus::Valid("alice@example.com".to_string())),
+        }
+    );
+
+    let opened = certificate
+        .untrusting
+        .open(&header, &body)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        opened.security.signature,
+        Some(SignatureStatus::UnknownKey("alice@example.com".to_string()))
+    );
+
+    let tampered = String::from_utf8(body).unwrap().replace("Hello", "Hullo");
+    let opened = certificate
+        .smime
+        .open(&header, tampered.as_bytes())
+        .unwrap()
+        .unwrap();
+    assert!(String::from_utf8(opened.entity).unwrap().contains("Hullo"));
+    assert_eq!(
+        opened.security.signature,
+        Some(SignatureStatus::Bad("alice@example.com".to_string()))
+    );
+}
+
+#[test]
+fn opens_encrypted_message() {
+    let certificate = Certificate::new("encrypted", "bob@example.com");
+    let signature = certificate.smime.sign(ENTITY).unwrap();
+    let (header, body) = signed_message(ENTITY, &signature);
+    let mut inner = header.into_bytes();
+    inner.extend_from_slice(&body);
+
+    let encrypted = certificate.encrypt(&inner);
+    let (header, body) = mime::split_header(&encrypted);
+    let header = String::from_utf8_lossy(header);
+    assert!(header.contains("application/pkcs7-mime"));
+
+    let opened = certificate.smime.open(&header, body).unwrap().unwrap();
+    assert_eq!(opened.entity, ENTITY);
+    assert!(opened.security.encrypted);
+    assert_eq!(
+        opened.security.signature,
+        Some(SignatureStatus::Valid("bob@example.com".to_string()))
+    );
+
+    let other = Certificate::new("encrypted_other", "carol@example.com");
+    assert!(matches!(
+        other.smime.open(&header, body),
+        Some(Err(SmimeError::Openssl(_)))
+    ));
+    assert!(matches!(
+        Smime::default().open(&header, body),
+        Some(Err(SmimeError::NoCertificate))
+    ));
+}
+
+#[test]
+fn leaves_other_messages_alone() {
+    let smime = Smime::default();
+    assert!(
+        smime
+            .open("Content-Type: text/plain\r\n\r\n", b"Hello")
+            .is_none()
+    );
+    assert!(
+        smime
+            .open(
+                "Content-Type: multipart/signed; protocol=\"application/pgp-signature\"; \
+                 boundary=x\r\n\r\n",
+                b"--x\r\n\r\nHi\r\n--x--",
+            )
+            .is_none()
+    );
+}
+
+#[test]
+fn reason_drops_error_code_prefix() {
+    assert_eq!(
+        reason(
+            b"Error decrypting CMS structure\n\
+              40F7851C777F0000:error:02000079:rsa routines:ossl_rsa_padding_check:oaep decoding error:crypto/rsa/rsa_oaep.c:341:\n"
+        ),
+        "oaep decoding error"
+    );
+    assert_eq!(reason(b"Verification failure\n"), "Verification failure");
+}
@@ -0,0 +1,72 @@
+use super::*;
+
+/// A throwaway self-signed certificate and key, removed on drop. `smime`
+/// trusts the certificate; `untrusting` uses the same identity without it.
+pub struct Certificate {
+    pub smime: Smime,
+    pub untrusting: Smime,
+    dir: PathBuf,
+}
+
+impl Certificate {
+    pub fn new(name: &str, address: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("thrum_test_smime_{name}"));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+
+        let status = Command::new("openssl")
+            .args([
+                "req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "1",
+            ])
+            .arg("-keyout")
+            .arg(&key)
+            .arg("-out")
+            .arg(&cert)
+            .arg("-subj")
+            .arg(format!("/CN=Test/emailAddress={address}"))
+            .arg("-addext")
+            .arg(format!("subjectAltName=email:{address}"))
+            .args(["-addext", "keyUsage=digitalSignature,keyEncipherment"])
+            .args(["-addext", "extendedKeyUsage=emailProtection"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "certificate generation failed");
+
+        let config = SmimeConfig {
+            cert: Some(cert.clone()),
+            key: Some(key),
+            ca_file: Some(cert),
+            ..SmimeConfig::default()
+        };
+        let smime = Smime::new(&config);
+        // The system store does not know the certificate
+        let untrusting = Smime::new(&SmimeConfig {
+            ca_file: None,
+            ..config
+        });
+        Self {
+            smime,
+            untrusting,
+            dir,
+        }
+    }
+
+    /// `entity` encrypted to this certificate, as an S/MIME message.
+    pub fn encrypt(&self, entity: &[u8]) -> Vec<u8> {
+        let mut command = self.smime.cms();
+        command
+            .args(["-encrypt", "-aes256"])
+            .arg(self.dir.join("cert.pem"));
+        self.smime.run(command, entity).unwrap()
+    }
+}
+
+impl Drop for Certificate {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
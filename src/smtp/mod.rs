@@ -1,12 +1,34 @@
-use lettre::message::Mailbox;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
+use std::time::Duration;
 
-use crate::config::SmtpConfig;
+use lettre::Message;
+use lettre::address::Envelope;
+#[cfg(any(feature = "pgp", feature = "smime"))]
+use lettre::message::MultiPartBuilder;
+#[cfg(feature = "smime")]
+use lettre::message::header::ContentDisposition;
+#[cfg(any(feature = "pgp", feature = "smime"))]
+use lettre::message::header::ContentTransferEncoding;
+use lettre::message::header::{ContentType, ContentTypeErr};
+use lettre::message::{Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::transport::smtp::client::{SmtpConnection, TlsParameters};
+use lettre::transport::smtp::extension::ClientId;
+
+use crate::config::{AuthMode, SmtpConfig, TlsMode};
+use serde::{Deserialize, Serialize};
+
+use crate::oauth2::{self, OAuth2Error, TokenSource};
+#[cfg(feature = "pgp")]
+use crate::pgp::{Gpg, PgpError};
+#[cfg(feature = "smime")]
+use crate::smime::{Smime, SmimeError};
+use crate::tls::{self, CertificateError, Trust};
+use crate::{flowed, markdown};
 
 #[cfg(test)]
 mod test;
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Email {
     pub from: String,
     pub to: Vec<String>,
@@ -16,6 +38,22 @@ pub struct Email {
     pub body: String,
     pub in_reply_to: Option<String>,
     pub references: Vec<String>,
+    /// Treat the body as Markdown and send an HTML rendition alongside it.
+    pub markdown: bool,
+    /// Sign with the sender's PGP key (`pgp` feature).
+    #[cfg_attr(not(feature = "pgp"), allow(dead_code))]
+    pub sign: bool,
+    /// Encrypt to every recipient's PGP key (`pgp` feature).
+    #[cfg_attr(not(feature = "pgp"), allow(dead_code))]
+    pub encrypt: bool,
+}
+
+/// A built message with its envelope, ready to be delivered again as is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outgoing {
+    pub from: String,
+    pub recipients: Vec<String>,
+    pub message: Vec<u8>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -26,16 +64,83 @@ pub enum SmtpError {
     Message(#[from] lettre::error::Error),
     #[error("address parse error: {0}")]
     Address(#[from] lettre::address::AddressError),
+    #[error("content type error: {0}")]
+    ContentType(#[from] ContentTypeErr),
+    #[error("OAuth2 error: {0}")]
+    OAuth2(#[from] OAuth2Error),
+    #[error("certificate error: {0}")]
+    Certificate(#[from] CertificateError),
+    /// The message was built but the server did not take it.
+    #[error("{error}")]
+    Undelivered {
+        outgoing: Box<Outgoing>,
+        error: Box<SmtpError>,
+    },
+    #[cfg(feature = "pgp")]
+    #[error("PGP error: {0}")]
+    Pgp(#[from] PgpError),
+    #[cfg(feature = "smime")]
+    #[error("S/MIME error: {0}")]
+    Smime(#[from] SmimeError),
 }
 
+impl SmtpError {
+    /// Whether sending the message again unchanged cannot succeed: the
+    /// server refused it for good, or its certificate or our login was
+    /// rejected and needs fixing first.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            SmtpError::Smtp(e) => e.is_permanent(),
+            SmtpError::Undelivered { error, .. } => error.is_permanent(),
+            SmtpError::Certificate(_) => true,
+            // Only an unreachable or failing token endpoint may recover
+            SmtpError::OAuth2(OAuth2Error::Request(e)) => match **e {
+                ureq::Error::Status(code, _) => code < 500,
+                ureq::Error::Transport(_) => false,
+            },
+            SmtpError::OAuth2(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Shared with the thread that sends due messages in the background.
 #[cfg_attr(test, mockall::automock)]
-pub trait SmtpClient {
+pub trait SmtpClient: Send + Sync + 'static {
     fn send(&self, email: &Email) -> Result<Vec<u8>, SmtpError>;
+    /// Deliver a message built earlier, e.g. one waiting in the outbox.
+    fn resend(&self, outgoing: &Outgoing) -> Result<(), SmtpError>;
+    /// The message `send` would deliver for `email`, with its envelope,
+    /// without sending it.
+    fn build(&self, email: &Email) -> Result<Outgoing, SmtpError>;
 }
 
+/// How long to wait on the server before giving up, as lettre does.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
 pub struct NativeSmtpClient {
-    #[allow(dead_code)]
-    transport: SmtpTransport,
+    host: String,
+    port: u16,
+    tls: TlsMode,
+    /// Unused for `TlsMode::None`.
+    parameters: TlsParameters,
+    trust: Trust,
+    login: Login,
+    #[cfg(feature = "pgp")]
+    gpg: Gpg,
+    /// Signs outgoing mail when set.
+    #[cfg(feature = "smime")]
+    smime: Option<Smime>,
+}
+
+/// How to authenticate once connected.
+enum Login {
+    Password(Credentials),
+    /// Access tokens expire, so each message logs in with a fresh one.
+    OAuth2 {
+        user: String,
+        tokens: TokenSource,
+    },
 }
 
 impl NativeSmtpClient {
@@ -44,24 +149,109 @@ impl NativeSmtpClient {
         tracing::instrument(level = tracing::Level::TRACE, skip(config), err)
     )]
     pub fn connect(config: &SmtpConfig) -> Result<Self, SmtpError> {
+        let tls = config.tls_mode();
         #[cfg(feature = "tracing")]
-        tracing::trace!(host = %config.host, port = config.port, "connecting to SMTP server");
+        tracing::trace!(host = %config.host, port = config.port, ?tls, "configuring SMTP server");
 
-        let creds = Credentials::new(config.user.clone(), config.pass.clone());
-        #[cfg(feature = "tracing")]
-        tracing::trace!(user = %config.user, "credentials created");
+        let trust = Trust::new(
+            config.ca_file.as_deref(),
+            config.accept_invalid_certs,
+            config.fingerprint.as_deref(),
+        )?;
+        let login = match config.auth {
+            AuthMode::Password => {
+                Login::Password(Credentials::new(config.user.clone(), config.pass.clone()))
+            }
+            AuthMode::Oauth2 => {
+                let oauth2 = config.oauth2.as_ref().ok_or(OAuth2Error::NotConfigured)?;
+                Login::OAuth2 {
+                    user: config.user.clone(),
+                    tokens: TokenSource::new(
+                        oauth2,
+                        oauth2::cache_path(&config.user, &config.host),
+                    ),
+                }
+            }
+        };
 
-        #[cfg(feature = "tracing")]
-        tracing::trace!("building SMTP transport");
-        let transport = SmtpTransport::relay(&config.host)?
-            .port(config.port)
-            .credentials(creds)
-            .build();
+        Ok(Self {
+            host: config.host.clone(),
+            port: config.port,
+            tls,
+            parameters: trust.parameters(&config.host)?,
+            trust,
+            login,
+            #[cfg(feature = "pgp")]
+            gpg: Gpg::default(),
+            #[cfg(feature = "smime")]
+            smime: None,
+        })
+    }
+
+    /// Use `gpg` for signing and encryption instead of the default keyring.
+    #[cfg(feature = "pgp")]
+    pub fn with_gpg(mut self, gpg: Gpg) -> Self {
+        self.gpg = gpg;
+        self
+    }
 
+    /// Sign every message that is not PGP-protected with `smime`.
+    #[cfg(feature = "smime")]
+    pub fn with_smime(mut self, smime: Smime) -> Self {
+        self.smime = Some(smime);
+        self
+    }
+
+    fn deliver(&self, envelope: &Envelope, message: &[u8]) -> Result<(), SmtpError> {
+        let mut connection = self.open()?;
+        connection.send(envelope, message)?;
+        // The message is accepted; a failed goodbye does not matter
+        connection.quit().ok();
+        Ok(())
+    }
+
+    /// Open an encrypted, logged in connection to the server.
+    fn open(&self) -> Result<SmtpConnection, SmtpError> {
+        let hello = ClientId::default();
+        let wrapper = (self.tls == TlsMode::Implicit).then_some(&self.parameters);
+        let mut connection = SmtpConnection::connect(
+            (self.host.as_str(), self.port),
+            Some(TIMEOUT),
+            &hello,
+            wrapper,
+            None,
+        )
+        .map_err(handshake_error)?;
+        if self.tls == TlsMode::Starttls {
+            connection
+                .starttls(&self.parameters, &hello)
+                .map_err(handshake_error)?;
+        }
+        if self.tls != TlsMode::None {
+            let certificate = connection.peer_certificate()?;
+            self.trust.check(Some(&certificate))?;
+        }
         #[cfg(feature = "tracing")]
-        tracing::trace!("SMTP transport built");
+        tracing::trace!(
+            encrypted = connection.is_encrypted(),
+            "connection established"
+        );
 
-        Ok(Self { transport })
+        let (credentials, mechanisms) = match self.login {
+            Login::Password(ref credentials) => (
+                credentials.clone(),
+                vec![Mechanism::Plain, Mechanism::Login],
+            ),
+            Login::OAuth2 {
+                ref user,
+                ref tokens,
+            } => (
+                Credentials::new(user.clone(), tokens.access_token()?),
+                vec![Mechanism::Xoauth2],
+            ),
+        };
+        connection.auth(&mechanisms, &credentials)?;
+        Ok(connection)
     }
 }
 
@@ -74,52 +264,270 @@ impl SmtpClient for NativeSmtpClient {
         #[cfg(feature = "tracing")]
         tracing::trace!(from = %email.from, to = ?email.to, subject = %email.subject, "building message");
 
-        let from: Mailbox = email.from.parse()?;
+        let message = build_message(
+            email,
+            #[cfg(feature = "pgp")]
+            &self.gpg,
+            #[cfg(feature = "smime")]
+            self.smime.as_ref(),
+        )?;
+        let formatted = message.formatted();
+        #[cfg(feature = "tracing")]
+        tracing::trace!("message built");
 
-        let mut builder = Message::builder().from(from).subject(&email.subject);
+        #[cfg(feature = "tracing")]
+        tracing::trace!("sending email");
 
-        for to_addr in &email.to {
-            let mailbox: Mailbox = to_addr.parse()?;
-            builder = builder.to(mailbox);
-        }
+        let envelope = message.envelope();
+        self.deliver(envelope, &formatted)
+            .map_err(|e| SmtpError::Undelivered {
+                outgoing: Box::new(outgoing(&message)),
+                error: Box::new(e),
+            })?;
 
-        for cc_addr in &email.cc {
-            let mailbox: Mailbox = cc_addr.parse()?;
-            builder = builder.cc(mailbox);
-        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!("email sent");
+
+        Ok(formatted)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = tracing::Level::TRACE, skip(self, outgoing), err)
+    )]
+    fn resend(&self, outgoing: &Outgoing) -> Result<(), SmtpError> {
+        let from = match outgoing.from.as_str() {
+            "" => None,
+            from => Some(from.parse()?),
+        };
+        let recipients = outgoing
+            .recipients
+            .iter()
+            .map(|recipient| recipient.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        let envelope = Envelope::new(from, recipients)?;
+        self.deliver(&envelope, &outgoing.message)
+    }
+
+    fn build(&self, email: &Email) -> Result<Outgoing, SmtpError> {
+        let message = build_message(
+            email,
+            #[cfg(feature = "pgp")]
+            &self.gpg,
+            #[cfg(feature = "smime")]
+            self.smime.as_ref(),
+        )?;
+        Ok(outgoing(&message))
+    }
+}
+
+/// `message` with its envelope, to be delivered later.
+fn outgoing(message: &Message) -> Outgoing {
+    let envelope = message.envelope();
+    Outgoing {
+        from: envelope.from().map(ToString::to_string).unwrap_or_default(),
+        recipients: envelope.to().iter().map(ToString::to_string).collect(),
+        message: message.formatted(),
+    }
+}
+
+/// Explain a failed connection or STARTTLS upgrade that came down to the
+/// server's certificate.
+fn handshake_error(e: lettre::transport::smtp::Error) -> SmtpError {
+    let reason = std::error::Error::source(&e)
+        .map(ToString::to_string)
+        .unwrap_or_else(|| e.to_string());
+    match tls::untrusted(&reason) {
+        Some(untrusted) => untrusted.into(),
+        None => e.into(),
+    }
+}
+
+/// Content type of every plain text body sent.
+const FLOWED_TEXT: &str = "text/plain; charset=utf-8; format=flowed";
+
+/// Build the message for `email`, with the body sent as `format=flowed`
+/// text soft-wrapped at [`flowed::WIDTH`]. Markdown bodies become
+/// `multipart/alternative` with the rendered HTML as the second part.
+fn build_message(
+    email: &Email,
+    #[cfg(feature = "pgp")] gpg: &Gpg,
+    #[cfg(feature = "smime")] smime: Option<&Smime>,
+) -> Result<Message, SmtpError> {
+    let from: Mailbox = email.from.parse()?;
+
+    let mut builder = Message::builder().from(from).subject(&email.subject);
+
+    for to_addr in &email.to {
+        let mailbox: Mailbox = to_addr.parse()?;
+        builder = builder.to(mailbox);
+    }
+
+    for cc_addr in &email.cc {
+        let mailbox: Mailbox = cc_addr.parse()?;
+        builder = builder.cc(mailbox);
+    }
+
+    for bcc_addr in &email.bcc {
+        let mailbox: Mailbox = bcc_addr.parse()?;
+        builder = builder.bcc(mailbox);
+    }
+
+    if let Some(ref reply_to) = email.in_reply_to {
+        builder = builder.in_reply_to(reply_to.clone());
+    }
+
+    if !email.references.is_empty() {
+        let refs_str = email
+            .references
+            .iter()
+            .map(|r| format!("<{r}>"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        builder = builder.references(refs_str);
+    }
+
+    let content_type = ContentType::parse(FLOWED_TEXT)?;
+    let text = flowed::encode(&email.body, flowed::WIDTH);
+
+    #[cfg(feature = "pgp")]
+    if email.sign || email.encrypt {
+        return Ok(builder.multipart(pgp_mime(email, text, gpg)?)?);
+    }
+
+    #[cfg(feature = "smime")]
+    if let Some(smime) = smime {
+        return Ok(builder.multipart(smime_signed(email, text, smime)?)?);
+    }
+
+    if !email.markdown {
+        return Ok(builder.header(content_type).body(text)?);
+    }
 
-        for bcc_addr in &email.bcc {
-            let mailbox: Mailbox = bcc_addr.parse()?;
-            builder = builder.bcc(mailbox);
+    let alternative = MultiPart::alternative()
+        .singlepart(SinglePart::builder().header(content_type).body(text))
+        .singlepart(SinglePart::html(markdown::to_html(&email.body)));
+    Ok(builder.multipart(alternative)?)
+}
+
+/// A message body before a PGP/MIME or S/MIME layer is wrapped around it.
+#[cfg(any(feature = "pgp", feature = "smime"))]
+enum Content {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+#[cfg(any(feature = "pgp", feature = "smime"))]
+impl Content {
+    /// The body of `email`, all quoted-printable: signed parts must arrive
+    /// byte for byte, which also protects format=flowed trailing spaces.
+    fn new(email: &Email, text: String) -> Result<Self, SmtpError> {
+        let text = SinglePart::builder()
+            .header(ContentType::parse(FLOWED_TEXT)?)
+            .header(ContentTransferEncoding::QuotedPrintable)
+            .body(text);
+        if !email.markdown {
+            return Ok(Content::Single(text));
         }
+        let html = SinglePart::builder()
+            .header(ContentType::TEXT_HTML)
+            .header(ContentTransferEncoding::QuotedPrintable)
+            .body(markdown::to_html(&email.body));
+        Ok(Content::Multi(
+            MultiPart::alternative().singlepart(text).singlepart(html),
+        ))
+    }
 
-        if let Some(ref reply_to) = email.in_reply_to {
-            builder = builder.in_reply_to(reply_to.clone());
+    fn formatted(&self) -> Vec<u8> {
+        match self {
+            Content::Single(part) => part.formatted(),
+            Content::Multi(part) => part.formatted(),
         }
+    }
 
-        if !email.references.is_empty() {
-            let refs_str = email
-                .references
-                .iter()
-                .map(|r| format!("<{r}>"))
-                .collect::<Vec<_>>()
-                .join(" ");
-            builder = builder.references(refs_str);
+    fn add_to(self, parts: MultiPartBuilder) -> MultiPart {
+        match self {
+            Content::Single(part) => parts.singlepart(part),
+            Content::Multi(part) => parts.multipart(part),
         }
+    }
+}
 
-        let message = builder.body(email.body.clone())?;
-        let formatted = message.formatted();
-        #[cfg(feature = "tracing")]
-        tracing::trace!("message built");
+/// Wrap the body in PGP/MIME (RFC 3156): `multipart/encrypted` when
+/// encrypting, otherwise `multipart/signed` with a detached signature.
+#[cfg(feature = "pgp")]
+fn pgp_mime(email: &Email, text: String, gpg: &Gpg) -> Result<MultiPart, SmtpError> {
+    let content = Content::new(email, text)?;
+    let entity = content.formatted();
+    let signer = email.from.parse::<Mailbox>()?.email.to_string();
 
-        #[cfg(feature = "tracing")]
-        tracing::trace!("sending email");
+    if email.encrypt {
+        let addresses = |list: &[String]| -> Result<Vec<String>, SmtpError> {
+            list.iter()
+                .map(|a| Ok(a.parse::<Mailbox>()?.email.to_string()))
+                .collect()
+        };
+        // Encrypted to ourselves too, so the copy in Sent stays readable
+        let mut recipients = addresses(&email.to)?;
+        recipients.extend(addresses(&email.cc)?);
+        recipients.push(signer.clone());
+        let hidden = addresses(&email.bcc)?;
 
-        self.transport.send(&message)?;
+        let signer = email.sign.then_some(signer.as_str());
+        let encrypted = gpg.encrypt(&entity, &recipients, &hidden, signer)?;
+        return Ok(
+            MultiPart::encrypted("application/pgp-encrypted".to_string())
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::parse("application/pgp-encrypted")?)
+                        .body(String::from("Version: 1")),
+                )
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::parse(
+                            "application/octet-stream; name=\"encrypted.asc\"",
+                        )?)
+                        .body(encrypted),
+                ),
+        );
+    }
 
-        #[cfg(feature = "tracing")]
-        tracing::trace!("email sent");
+    // The CRLF before the next boundary is not part of the signed entity
+    let signed = entity.strip_suffix(b"\r\n").unwrap_or(&entity);
+    let signature = gpg.sign(signed, &signer)?;
+    let parts = MultiPart::signed(
+        "application/pgp-signature".to_string(),
+        "pgp-sha256".to_string(),
+    );
+    Ok(content.add_to(parts).singlepart(
+        SinglePart::builder()
+            .header(ContentType::parse(
+                "application/pgp-signature; name=\"signature.asc\"",
+            )?)
+            .body(signature),
+    ))
+}
 
-        Ok(formatted)
-    }
+/// Wrap the body in `multipart/signed` with a detached S/MIME signature
+/// (RFC 8551).
+#[cfg(feature = "smime")]
+fn smime_signed(email: &Email, text: String, smime: &Smime) -> Result<MultiPart, SmtpError> {
+    let content = Content::new(email, text)?;
+    let entity = content.formatted();
+    // The CRLF before the next boundary is not part of the signed entity
+    let signed = entity.strip_suffix(b"\r\n").unwrap_or(&entity);
+    let signature = smime.sign(signed)?;
+    let parts = MultiPart::signed(
+        "application/pkcs7-signature".to_string(),
+        "sha-256".to_string(),
+    );
+    Ok(content.add_to(parts).singlepart(
+        SinglePart::builder()
+            .header(ContentType::parse(
+                "application/pkcs7-signature; name=\"smime.p7s\"",
+            )?)
+            .header(ContentTransferEncoding::Base64)
+            .header(ContentDisposition::attachment("smime.p7s"))
+            .body(signature),
+    ))
 }
@@ -14,6 +14,9 @@ fn mock_client_sends_email() {
         body: "Hello".to_string(),
         in_reply_to: None,
         references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
     };
 
     assert!(mock.send(&email).is_ok());
@@ -34,12 +37,48 @@ fn mock_client_send_fails() {
         body: "Hello".to_string(),
         in_reply_to: None,
         references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
     };
 
     let result = mock.send(&email);
     assert!(result.is_err());
 }
 
+#[test]
+fn certificate_and_login_failures_are_permanent() {
+    let undelivered = |error: SmtpError| SmtpError::Undelivered {
+        outgoing: Box::new(Outgoing {
+            from: String::new(),
+            recipients: vec![],
+            message: vec![],
+        }),
+        error: Box::new(error),
+    };
+    assert!(undelivered(SmtpError::Certificate(CertificateError::Missing)).is_permanent());
+    assert!(undelivered(SmtpError::OAuth2(OAuth2Error::NotConfigured)).is_permanent());
+    assert!(
+        undelivered(SmtpError::OAuth2(OAuth2Error::Command(
+            "denied".to_string()
+        )))
+        .is_permanent()
+    );
+
+    // The token endpoint could not be reached
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/token", listener.local_addr().unwrap());
+    drop(listener);
+    let unreachable = ureq::post(&url).call().unwrap_err();
+    assert!(
+        !undelivered(SmtpError::OAuth2(OAuth2Error::Request(Box::new(
+            unreachable
+        ))))
+        .is_permanent()
+    );
+    assert!(!SmtpError::Message(lettre::error::Error::MissingTo).is_permanent());
+}
+
 #[test]
 fn email_struct_fields() {
     let email = Email {
@@ -54,6 +93,9 @@ fn email_struct_fields() {
         body: "Hi there".to_string(),
         in_reply_to: Some("msg1@example.com".to_string()),
         references: vec!["msg1@example.com".to_string()],
+        markdown: false,
+        sign: false,
+        encrypt: false,
     };
 
     assert_eq!(email.from, "alice@example.com");
@@ -65,3 +107,303 @@ fn email_struct_fields() {
     assert_eq!(email.in_reply_to.as_deref(), Some("msg1@example.com"));
     assert_eq!(email.references.len(), 1);
 }
+
+/// Build a message that needs no keyring.
+fn build(email: &Email) -> lettre::Message {
+    build_message(
+        email,
+        #[cfg(feature = "pgp")]
+        &crate::pgp::Gpg::default(),
+        #[cfg(feature = "smime")]
+        None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn build_message_sends_format_flowed() {
+    let email = Email {
+        from: "alice@example.com".to_string(),
+        to: vec!["bob@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: "Test".to_string(),
+        body: format!("{}end\n\n-- \nAlice", "word ".repeat(15)),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    };
+    let formatted = String::from_utf8(build(&email).formatted()).unwrap();
+
+    assert!(formatted.contains("Content-Type: text/plain; charset=utf-8; format=flowed"));
+    let body = formatted.split_once("\r\n\r\n").unwrap().1;
+    assert_eq!(
+        body,
+        format!(
+            "{} \r\nword end\r\n\r\n-- \r\nAlice",
+            ["word"; 14].join(" ")
+        )
+    );
+}
+
+#[test]
+fn build_message_sends_markdown_as_alternative() {
+    let email = Email {
+        from: "alice@example.com".to_string(),
+        to: vec!["bob@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: "Test".to_string(),
+        body: "Some **bold** text".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: true,
+        sign: false,
+        encrypt: false,
+    };
+    let formatted = String::from_utf8(build(&email).formatted()).unwrap();
+
+    assert!(formatted.contains("Content-Type: multipart/alternative"));
+    let plain = formatted
+        .find("Content-Type: text/plain; charset=utf-8; format=flowed")
+        .unwrap();
+    let html = formatted.find("Content-Type: text/html").unwrap();
+    assert!(plain < html);
+    assert!(formatted.contains("Some **bold** text"));
+    assert!(formatted.contains("<p>Some <strong>bold</strong> text</p>"));
+}
+
+#[cfg(feature = "pgp")]
+fn pgp_email(sign: bool, encrypt: bool) -> Email {
+    Email {
+        from: "Alice <alice@example.com>".to_string(),
+        to: vec!["bob@example.com".to_string()],
+        cc: vec![],
+        bcc: vec!["carol@example.com".to_string()],
+        subject: "Secret".to_string(),
+        body: "Trailing flowed text that is long enough to be soft wrapped at seventy-two columns"
+            .to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign,
+        encrypt,
+    }
+}
+
+/// The raw parts between the top-level boundaries of a formatted message.
+#[cfg(feature = "pgp")]
+fn top_level_parts(formatted: &str) -> Vec<String> {
+    let (headers, body) = formatted.split_once("\r\n\r\n").unwrap();
+    let headers = headers.replace("\r\n ", " ");
+    let content_type = headers
+        .split("\r\n")
+        .find(|h| h.starts_with("Content-Type:"))
+        .unwrap();
+    let boundary = crate::mime::param(content_type, "boundary").unwrap();
+    body.split(format!("--{boundary}").as_str())
+        .skip(1)
+        .filter(|part| !part.starts_with("--"))
+        .map(|part| {
+            let part = part.strip_prefix("\r\n").unwrap_or(part);
+            part.strip_suffix("\r\n").unwrap_or(part).to_string()
+        })
+        .collect()
+}
+
+#[cfg(feature = "pgp")]
+#[test]
+fn build_message_signs_as_pgp_mime() {
+    let keyring = crate::pgp::testing::Keyring::new("smtp_sign", &["alice@example.com"]);
+    let message = build_message(
+        &pgp_email(true, false),
+        &keyring.gpg,
+        #[cfg(feature = "smime")]
+        None,
+    )
+    .unwrap();
+    let formatted = String::from_utf8(message.formatted()).unwrap();
+
+    assert!(formatted.contains("Content-Type: multipart/signed"));
+    assert!(formatted.contains("protocol=\"application/pgp-signature\""));
+    assert!(formatted.contains("micalg=\"pgp-sha256\""));
+
+    let parts = top_level_parts(&formatted);
+    assert_eq!(parts.len(), 2);
+    assert!(parts[0].contains("Content-Transfer-Encoding: quoted-printable"));
+    assert!(parts[1].starts_with("Content-Type: application/pgp-signature"));
+    let signature = parts[1].split_once("\r\n\r\n").unwrap().1;
+    assert!(keyring.verify(signature.as_bytes(), parts[0].as_bytes()));
+}
+
+#[cfg(feature = "pgp")]
+#[test]
+fn build_message_encrypts_to_every_recipient() {
+    let keyring = crate::pgp::testing::Keyring::new(
+        "smtp_encrypt",
+        &["alice@example.com", "bob@example.com", "carol@example.com"],
+    );
+    let message = build_message(
+        &pgp_email(true, true),
+        &keyring.gpg,
+        #[cfg(feature = "smime")]
+        None,
+    )
+    .unwrap();
+    let formatted = String::from_utf8(message.formatted()).unwrap();
+
+    assert!(formatted.contains("Content-Type: multipart/encrypted"));
+    assert!(!formatted.contains("Trailing flowed"));
+    let parts = top_level_parts(&formatted);
+    assert_eq!(parts.len(), 2);
+    assert!(parts[0].contains("application/pgp-encrypted"));
+    assert!(parts[0].ends_with("Version: 1"));
+
+    let armored = parts[1].split_once("\r\n\r\n").unwrap().1;
+    let inner = String::from_utf8(keyring.decrypt(armored.as_bytes())).unwrap();
+    assert!(inner.starts_with("Content-Type: text/plain; charset=utf-8; format=flowed"));
+    assert!(inner.contains("soft wrapped"));
+}
+
+#[cfg(feature = "pgp")]
+#[test]
+fn build_message_refuses_recipients_without_keys() {
+    let keyring = crate::pgp::testing::Keyring::new("smtp_missing", &["alice@example.com"]);
+    let result = build_message(
+        &pgp_email(false, true),
+        &keyring.gpg,
+        #[cfg(feature = "smime")]
+        None,
+    );
+    match result {
+        Err(SmtpError::Pgp(crate::pgp::PgpError::MissingKeys(missing))) => {
+            assert_eq!(missing, vec!["bob@example.com", "carol@example.com"]);
+        }
+        other => panic!("expected missing keys, got {:?}", other.err()),
+    }
+}
+
+#[cfg(feature = "smime")]
+#[test]
+fn build_message_signs_as_smime() {
+    let certificate = crate::smime::testing::Certificate::new("smtp_sign", "alice@example.com");
+    let email = Email {
+        from: "Alice <alice@example.com>".to_string(),
+        to: vec!["bob@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: "Signed".to_string(),
+        body: "Some **bold** text".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: true,
+        sign: false,
+        encrypt: false,
+    };
+    let message = build_message(
+        &email,
+        #[cfg(feature = "pgp")]
+        &crate::pgp::Gpg::default(),
+        Some(&certificate.smime),
+    )
+    .unwrap();
+    let formatted = message.formatted();
+    let text = String::from_utf8_lossy(&formatted);
+    assert!(text.contains("protocol=\"application/pkcs7-signature\""));
+    assert!(text.contains("filename=\"smime.p7s\""));
+
+    let (header, body) = crate::mime::split_header(&formatted);
+    let opened = certificate
+        .smime
+        .open(&String::from_utf8_lossy(header), body)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        opened.security.signature,
+        Some(crate::imap::SignatureStatus::Valid(
+            "alice@example.com".to_string()
+        ))
+    );
+    assert!(
+        String::from_utf8_lossy(&opened.entity).starts_with("Content-Type: multipart/alternative")
+    );
+}
+
+/// An SMTP server on localhost speaking plain text, giving back every line
+/// the client sent.
+fn smtp_server() -> (u16, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut lines = Vec::new();
+        stream.write_all(b"220 localhost ready\r\n").unwrap();
+        let mut data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            let reply: &[u8] = if data {
+                data = line != ".";
+                if data { b"" } else { b"250 queued\r\n" }
+            } else if line.starts_with("EHLO") {
+                b"250-localhost\r\n250 AUTH PLAIN LOGIN\r\n"
+            } else if line.starts_with("AUTH") {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                lines.push(line);
+                stream.write_all(b"221 bye\r\n").unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            lines.push(line);
+            stream.write_all(reply).unwrap();
+        }
+        lines
+    });
+    (port, handle)
+}
+
+#[test]
+fn native_client_delivers_to_local_server() {
+    let (port, server) = smtp_server();
+    let config: SmtpConfig = toml::from_str(&format!(
+        "host = \"127.0.0.1\"\nport = {port}\ntls = \"none\"\nuser = \"me\"\npass = \"hunter2\""
+    ))
+    .unwrap();
+    let client = NativeSmtpClient::connect(&config).unwrap();
+
+    let email = Email {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: "Test".to_string(),
+        body: "Hello".to_string(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    };
+    client.send(&email).unwrap();
+
+    let lines = server.join().unwrap();
+    // "\0me\0hunter2"
+    assert!(lines.contains(&"AUTH PLAIN AG1lAGh1bnRlcjI=".to_string()));
+    assert!(lines.contains(&"MAIL FROM:<me@example.com>".to_string()));
+    assert!(lines.contains(&"RCPT TO:<you@example.com>".to_string()));
+    assert!(lines.contains(&"Subject: Test".to_string()));
+    assert_eq!(lines.last().map(String::as_str), Some("QUIT"));
+}
@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use lettre::transport::smtp::client::TlsParameters;
+use native_tls::{Certificate, TlsConnector};
+use sha2::{Digest, Sha256};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    #[error("failed to load CA certificates from {0}: {1}")]
+    CaFile(PathBuf, String),
+    #[error("invalid fingerprint {0:?}: expected a SHA-256 digest as 64 hex digits")]
+    Fingerprint(String),
+    #[error("server certificate {actual} does not match the pinned fingerprint {expected}")]
+    Mismatch { expected: String, actual: String },
+    #[error("server sent no certificate")]
+    Missing,
+    #[error(
+        "server certificate not trusted ({0}); set ca_file to the CA that issued it or pin its fingerprint"
+    )]
+    Untrusted(String),
+}
+
+/// Which server certificates to accept.
+#[derive(Debug, Clone, Default)]
+pub struct Trust {
+    /// CA certificates from `ca_file`, DER encoded.
+    roots: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+    fingerprint: Option<[u8; 32]>,
+}
+
+impl Trust {
+    pub fn new(
+        ca_file: Option<&Path>,
+        accept_invalid_certs: bool,
+        fingerprint: Option<&str>,
+    ) -> Result<Self, CertificateError> {
+        let roots = match ca_file {
+            Some(path) => load_roots(path)?,
+            None => Vec::new(),
+        };
+        let fingerprint = fingerprint
+            .map(|text| parse_fingerprint(text).ok_or(CertificateError::Fingerprint(text.into())))
+            .transpose()?;
+        Ok(Self {
+            roots,
+            accept_invalid_certs,
+            fingerprint,
+        })
+    }
+
+    /// Whether the usual certificate checks are skipped, either entirely or
+    /// in favour of the pinned fingerprint.
+    fn skip_verification(&self) -> bool {
+        self.accept_invalid_certs || self.fingerprint.is_some()
+    }
+
+    /// A connector trusting the system store and `ca_file`.
+    pub fn connector(&self) -> Result<TlsConnector, native_tls::Error> {
+        let mut builder = TlsConnector::builder();
+        for der in &self.roots {
+            builder.add_root_certificate(Certificate::from_der(der)?);
+        }
+        builder.danger_accept_invalid_certs(self.skip_verification());
+        builder.build()
+    }
+
+    /// SMTP TLS parameters for `host` trusting the system store and `ca_file`.
+    pub fn parameters(&self, host: &str) -> Result<TlsParameters, lettre::transport::smtp::Error> {
+        let mut builder = TlsParameters::builder(host.to_string());
+        for der in &self.roots {
+            builder = builder.add_root_certificate(
+                lettre::transport::smtp::client::Certificate::from_der(der.clone())?,
+            );
+        }
+        builder
+            .dangerous_accept_invalid_certs(self.skip_verification())
+            .build_native()
+    }
+
+    /// Check the certificate the server presented, DER encoded, against the
+    /// pinned fingerprint.
+    pub fn check(&self, certificate: Option<&[u8]>) -> Result<(), CertificateError> {
+        let Some(expected) = self.fingerprint else {
+            return Ok(());
+        };
+        let certificate = certificate.ok_or(CertificateError::Missing)?;
+        let actual: [u8; 32] = Sha256::digest(certificate).into();
+        if actual != expected {
+            return Err(CertificateError::Mismatch {
+                expected: format_fingerprint(&expected),
+                actual: format_fingerprint(&actual),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The DER certificates in the PEM file at `path`.
+fn load_roots(path: &Path) -> Result<Vec<Vec<u8>>, CertificateError> {
+    let error = |reason: String| CertificateError::CaFile(path.to_path_buf(), reason);
+    let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+    let roots = pem_blocks(&text)
+        .map(|pem| {
+            Certificate::from_pem(pem.as_bytes())
+                .and_then(|certificate| certificate.to_der())
+                .map_err(|e| error(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if roots.is_empty() {
+        return Err(error("no certificates found".to_string()));
+    }
+    Ok(roots)
+}
+
+/// Each `CERTIFICATE` block in `text`, with its armour lines.
+fn pem_blocks(text: &str) -> impl Iterator<Item = &str> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let start = rest.find(BEGIN)?;
+        let end = start + rest[start..].find(END)? + END.len();
+        let block = &rest[start..end];
+        rest = &rest[end..];
+        Some(block)
+    })
+}
+
+/// Parse a SHA-256 fingerprint written as hex digits, optionally separated
+/// by colons or spaces as `openssl x509 -fingerprint` prints them.
+pub fn parse_fingerprint(text: &str) -> Option<[u8; 32]> {
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|b| *b != b':' && !b.is_ascii_whitespace())
+        .collect();
+    if digits.len() != 64 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut fingerprint = [0; 32];
+    for (byte, pair) in fingerprint.iter_mut().zip(digits.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(fingerprint)
+}
+
+/// `AB:CD:...`, the way `openssl x509 -fingerprint` prints a digest.
+pub fn format_fingerprint(fingerprint: &[u8]) -> String {
+    fingerprint
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// What the TLS backends say when they reject the server's certificate:
+/// OpenSSL's `certificate verify failed` and the messages of
+/// Security.framework and SChannel for an untrusted, expired or misnamed
+/// certificate, all lowercase.
+const UNTRUSTED: &[&str] = &[
+    "certificate",
+    "cert chain",
+    "untrusted root",
+    "not trusted",
+    "target principal name is incorrect",
+];
+
+/// The certificate error behind a failed TLS handshake, if that is why it
+/// failed.
+///
+/// native-tls keeps the backend's verify result private, so this goes by
+/// the handshake error's text `reason` instead. Errors that match none of
+/// [`UNTRUSTED`] are taken as network trouble, which is retried rather
+/// than reported, so new backend wording belongs in that list.
+pub fn untrusted(reason: &str) -> Option<CertificateError> {
+    let lowercase = reason.to_lowercase();
+    UNTRUSTED
+        .iter()
+        .any(|marker| lowercase.contains(marker))
+        .then(|| CertificateError::Untrusted(reason.to_string()))
+}
@@ -0,0 +1,215 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+
+use native_tls::{Identity, TlsAcceptor};
+
+use super::*;
+
+/// A throwaway self-signed certificate for `localhost`, removed on drop.
+struct SelfSigned {
+    dir: PathBuf,
+}
+
+impl SelfSigned {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("thrum_test_tls_{name}"));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let status = Command::new("openssl")
+            .args([
+                "req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "1",
+            ])
+            .arg("-keyout")
+            .arg(dir.join("key.pem"))
+            .arg("-out")
+            .arg(dir.join("cert.pem"))
+            .args(["-subj", "/CN=localhost"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "certificate generation failed");
+        Self { dir }
+    }
+
+    fn cert(&self) -> PathBuf {
+        self.dir.join("cert.pem")
+    }
+
+    /// The fingerprint as `openssl x509 -fingerprint -sha256` reports it.
+    fn fingerprint(&self) -> String {
+        let output = Command::new("openssl")
+            .args(["x509", "-noout", "-fingerprint", "-sha256", "-in"])
+            .arg(self.cert())
+            .output()
+            .unwrap();
+        let text = String::from_utf8(output.stdout).unwrap();
+        text.trim().rsplit('=').next().unwrap().to_string()
+    }
+
+    /// Accept one TLS connection on localhost and greet the client.
+    fn serve(&self) -> u16 {
+        let cert = std::fs::read(self.cert()).unwrap();
+        let key = std::fs::read(self.dir.join("key.pem")).unwrap();
+        let acceptor = TlsAcceptor::new(Identity::from_pkcs8(&cert, &key).unwrap()).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            if let Ok(mut stream) = acceptor.accept(tcp) {
+                stream.write_all(b"hello").ok();
+            }
+        });
+        port
+    }
+}
+
+impl Drop for SelfSigned {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+#[test]
+fn parses_fingerprints() {
+    let hex = "00112233445566778899aabbccddeeff00112233445566778899AABBCCDDEEFF";
+    let fingerprint = parse_fingerprint(hex).unwrap();
+    assert_eq!(fingerprint[1], 0x11);
+    assert_eq!(fingerprint[31], 0xff);
+    assert_eq!(
+        parse_fingerprint(&format_fingerprint(&fingerprint)),
+        Some(fingerprint)
+    );
+    assert_eq!(
+        format_fingerprint(&fingerprint[..3]),
+        "00:11:22".to_string()
+    );
+
+    assert_eq!(parse_fingerprint(&hex[2..]), None);
+    assert_eq!(parse_fingerprint(&hex.replace('0', "g")), None);
+    assert!(matches!(
+        Trust::new(None, false, Some("AB:CD")),
+        Err(CertificateError::Fingerprint(text)) if text == "AB:CD"
+    ));
+}
+
+#[test]
+fn loads_every_certificate_in_ca_file() {
+    let first = SelfSigned::new("ca_first");
+    let second = SelfSigned::new("ca_second");
+    let bundle = first.dir.join("bundle.pem");
+    let mut text = std::fs::read_to_string(first.cert()).unwrap();
+    text.push_str("# second\n");
+    text.push_str(&std::fs::read_to_string(second.cert()).unwrap());
+    std::fs::write(&bundle, text).unwrap();
+
+    let trust = Trust::new(Some(&bundle), false, None).unwrap();
+    assert_eq!(trust.roots.len(), 2);
+
+    std::fs::write(&bundle, "not a certificate").unwrap();
+    assert!(matches!(
+        Trust::new(Some(&bundle), false, None),
+        Err(CertificateError::CaFile(path, reason))
+            if path == bundle && reason == "no certificates found"
+    ));
+    assert!(matches!(
+        Trust::new(Some(&first.dir.join("missing.pem")), false, None),
+        Err(CertificateError::CaFile(..))
+    ));
+}
+
+/// Connect to `port` on localhost and read the greeting.
+fn handshake(trust: &Trust, port: u16) -> Result<String, String> {
+    let tcp = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut stream = trust
+        .connector()
+        .unwrap()
+        .connect("localhost", tcp)
+        .map_err(|e| e.to_string())?;
+    let certificate = stream
+        .peer_certificate()
+        .unwrap()
+        .unwrap()
+        .to_der()
+        .unwrap();
+    trust.check(Some(&certificate)).map_err(|e| e.to_string())?;
+    let mut greeting = String::new();
+    stream.read_to_string(&mut greeting).unwrap();
+    Ok(greeting)
+}
+
+#[test]
+fn trusts_ca_file_or_pinned_certificate() {
+    let server = SelfSigned::new("server");
+
+    let error = handshake(&Trust::default(), server.serve()).unwrap_err();
+    assert!(matches!(
+        untrusted(&error),
+        Some(CertificateError::Untrusted(_))
+    ));
+
+    let ca_file = Trust::new(Some(&server.cert()), false, None).unwrap();
+    assert_eq!(handshake(&ca_file, server.serve()).unwrap(), "hello");
+
+    let pinned = Trust::new(None, false, Some(&server.fingerprint())).unwrap();
+    assert_eq!(handshake(&pinned, server.serve()).unwrap(), "hello");
+
+    let other = SelfSigned::new("other");
+    let wrong_pin = Trust::new(None, false, Some(&other.fingerprint())).unwrap();
+    let error = handshake(&wrong_pin, server.serve()).unwrap_err();
+    assert!(error.contains("does not match the pinned fingerprint"));
+    assert!(error.contains(&other.fingerprint()));
+}
+
+#[test]
+fn accept_invalid_certs_skips_checks() {
+    let server = SelfSigned::new("invalid");
+    let trust = Trust::new(None, true, None).unwrap();
+    assert_eq!(handshake(&trust, server.serve()).unwrap(), "hello");
+    assert!(trust.check(None).is_ok());
+}
+
+#[test]
+fn pin_needs_a_certificate() {
+    let hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+    let trust = Trust::new(None, false, Some(hex)).unwrap();
+    assert!(matches!(trust.check(None), Err(CertificateError::Missing)));
+}
+
+#[test]
+fn untrusted_only_for_certificate_failures() {
+    // OpenSSL, as native-tls prints its verify failures
+    for reason in [
+        "error:0A000086:SSL routines:tls_post_process_server_certificate:certificate verify failed:../ssl/statem/statem_clnt.c:1889: (self-signed certificate)",
+        "error:0A000086:SSL routines:tls_post_process_server_certificate:certificate verify failed:../ssl/statem/statem_clnt.c:1889: (hostname mismatch)",
+        "error:1416F086:SSL routines:tls_process_server_certificate:certificate verify failed:ssl/statem/statem_clnt.c:1915: (unable to get local issuer certificate)",
+    ] {
+        assert!(untrusted(reason).is_some(), "{reason}");
+    }
+    // Security.framework
+    for reason in [
+        "The certificate was not trusted.",
+        "invalid certificate chain",
+        "valid cert chain, untrusted root",
+        "SSL certificate expired",
+    ] {
+        assert!(untrusted(reason).is_some(), "{reason}");
+    }
+    // SChannel
+    for reason in [
+        "The certificate chain was issued by an authority that is not trusted. (os error -2146762487)",
+        "The target principal name is incorrect. (os error -2146893022)",
+    ] {
+        assert!(untrusted(reason).is_some(), "{reason}");
+    }
+
+    for reason in [
+        "unexpected EOF",
+        "error:0A000410:SSL routines:ssl3_read_bytes:sslv3 alert handshake failure:../ssl/record/rec_layer_s3.c:1605:SSL alert number 40",
+        "Connection reset by peer (os error 104)",
+        "The operation couldn\u{2019}t be completed. (OSStatus error -9806.)",
+    ] {
+        assert!(untrusted(reason).is_none(), "{reason}");
+    }
+}
@@ -1,12 +1,21 @@
-use chrono::{DateTime, Datelike, Local};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate};
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Layout};
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Cell, Paragraph, Row, Table, Wrap};
+use ratatui::widgets::{
+    Block, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, Wrap,
+};
 
-use crate::app::{App, ComposeStep, View};
-use crate::imap::ImapClient;
+use crate::address::{self, Address};
+use crate::app::{App, ComposeState, ComposeStep, MessageDisplay, View};
+use crate::config::{Posting, SortMode};
+use crate::flowed;
+use crate::imap::{
+    ConnectionState, EmailBody, EmailSummary, ImapClient, Scheme, Security, SignatureStatus,
+};
+use crate::markdown;
+use crate::quote;
 use crate::smtp::SmtpClient;
 
 #[cfg(test)]
@@ -21,6 +30,8 @@ pub fn render<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<I,
         View::Inbox => render_inbox(frame, app),
         View::Detail(_) => render_detail(frame, app),
         View::Compose(_) => render_compose(frame, app),
+        View::Outbox(_) => render_outbox(frame, app),
+        View::Scheduled(_) => render_scheduled(frame, app),
     }
 }
 
@@ -32,17 +43,29 @@ fn render_inbox<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<I
     ])
     .areas(frame.area());
 
-    render_inbox_top_bar(frame, top);
+    render_inbox_top_bar(frame, top, app);
     render_inbox_main(frame, main, app);
     render_inbox_status_bar(frame, status, app);
 }
 
-fn render_inbox_top_bar(frame: &mut Frame, area: ratatui::layout::Rect) {
+fn render_inbox_top_bar<I: ImapClient, S: SmtpClient>(
+    frame: &mut Frame,
+    area: ratatui::layout::Rect,
+    app: &App<I, S>,
+) {
+    let scope = format!(" [{}] ", app.folder_scope());
+    let [hints_area, scope_area] =
+        Layout::horizontal([Constraint::Fill(1), Constraint::Length(scope.len() as u16)])
+            .areas(area);
+
     let bar = Paragraph::new(
-        Line::from(" q=Quit  j/k=Navigate  r=Reply  c=Compose  m-a=Archive  m-r=Read  m-d=Delete  m-l=Labels")
+        Line::from(" q=Quit  j/k=Navigate  r=Reply  c=Compose  o=Outbox  l=Scheduled  f=Folder  s=Sort  S=Group  m-a=Archive  m-r=Read  m-d=Delete  m-l=Labels")
             .style(Style::new().bold()),
     );
-    frame.render_widget(bar, area);
+    frame.render_widget(bar, hints_area);
+
+    let scope = Paragraph::new(Line::from(scope).style(Style::new().fg(Color::Magenta).bold()));
+    frame.render_widget(scope, scope_area);
 }
 
 fn render_inbox_main<I: ImapClient, S: SmtpClient>(
@@ -56,62 +79,121 @@ fn render_inbox_main<I: ImapClient, S: SmtpClient>(
         let content = Paragraph::new("No messages").block(block);
         frame.render_widget(content, area);
     } else {
-        let rows: Vec<Row> = app
-            .threads
-            .iter()
-            .filter_map(|thread| {
-                // Show the newest message (last in thread, oldest-first order)
-                let email_idx = *thread.last()?;
-                let e = app.emails.get(email_idx)?;
-                let thread_count = thread.len();
-
-                let row_style = if e.seen {
-                    Style::new().fg(Color::Gray)
-                } else {
-                    Style::new().bold()
-                };
-
-                let unread_cell = Cell::from(if e.seen {
-                    Span::raw("  ")
-                } else {
-                    Span::styled("● ", Style::new().fg(Color::Blue))
-                });
+        let today = Local::now().date_naive();
+        let grouped =
+            app.group_by_date && matches!(app.sort_mode, SortMode::Date | SortMode::UnreadFirst);
+        let mut current_group = None;
+        let mut selected_row = None;
+        let mut rows: Vec<Row> = Vec::new();
 
-                let from_cell = Cell::from(e.from.as_str());
-
-                let mut subject_parts = vec![Span::raw(&e.subject)];
-                if thread_count > 1 {
-                    subject_parts.push(Span::styled(
-                        format!(" ({thread_count})"),
-                        Style::new().fg(Color::Cyan),
-                    ));
+        for (thread_idx, thread) in app.threads.iter().enumerate() {
+            if grouped && let Some(&newest) = thread.last() {
+                let label = match app.sort_mode {
+                    SortMode::UnreadFirst => {
+                        if thread.iter().any(|&i| !app.emails[i].seen) {
+                            "Unread"
+                        } else {
+                            "Read"
+                        }
+                    }
+                    _ => date_group(app.emails[newest].timestamp(), today),
+                };
+                if current_group != Some(label) {
+                    current_group = Some(label);
+                    // The label goes in the From column, past the folder one
+                    let mut cells = vec![Cell::from(""), Cell::from(label)];
+                    if app.show_folder_column {
+                        cells.insert(1, Cell::from(""));
+                    }
+                    rows.push(Row::new(cells).style(Style::new().fg(Color::Cyan).bold()));
                 }
-                subject_parts.push(Span::raw(" "));
-                subject_parts.push(Span::styled(&e.snippet, Style::new().fg(Color::DarkGray)));
-                let subject_cell = Cell::from(Line::from(subject_parts));
-
-                let date_cell = Cell::from(format_date(&e.date));
-
-                Some(
-                    Row::new(vec![unread_cell, from_cell, subject_cell, date_cell])
-                        .style(row_style),
-                )
-            })
-            .collect();
+            }
+            if app.table_state.selected() == Some(thread_idx) {
+                selected_row = Some(rows.len());
+            }
+            rows.extend(inbox_row(&app.emails, thread, app.show_folder_column));
+        }
 
-        let widths = [
+        let mut widths = vec![
             Constraint::Length(2),
             Constraint::Length(20),
             Constraint::Fill(1),
             Constraint::Length(12),
         ];
+        if app.show_folder_column {
+            let folder_width = app
+                .threads
+                .iter()
+                .filter_map(|t| t.last())
+                .map(|&i| app.emails[i].folder.chars().count())
+                .max()
+                .unwrap_or(0)
+                .min(16);
+            widths.insert(1, Constraint::Length(folder_width as u16));
+        }
 
         let table = Table::new(rows, widths)
             .block(block)
             .row_highlight_style(Style::new().bg(Color::DarkGray).fg(Color::White));
 
-        frame.render_stateful_widget(table, area, &mut app.table_state);
+        if grouped {
+            app.grouped_table_state.select(selected_row);
+            frame.render_stateful_widget(table, area, &mut app.grouped_table_state);
+        } else {
+            frame.render_stateful_widget(table, area, &mut app.table_state);
+        }
+    }
+}
+
+fn inbox_row<'a>(
+    emails: &'a [EmailSummary],
+    thread: &[usize],
+    show_folder: bool,
+) -> Option<Row<'a>> {
+    // Show the newest message (last in thread, oldest-first order)
+    let email_idx = *thread.last()?;
+    let e = emails.get(email_idx)?;
+    let thread_count = thread.len();
+
+    let row_style = if e.seen {
+        Style::new().fg(Color::Gray)
+    } else {
+        Style::new().bold()
+    };
+
+    let unread_cell = Cell::from(if e.seen {
+        Span::raw("  ")
+    } else {
+        Span::styled("● ", Style::new().fg(Color::Blue))
+    });
+
+    let from_cell = Cell::from(e.author());
+
+    let mut subject_parts = vec![Span::raw(&e.subject)];
+    if thread_count > 1 {
+        subject_parts.push(Span::styled(
+            format!(" ({thread_count})"),
+            Style::new().fg(Color::Cyan),
+        ));
+    }
+    subject_parts.push(Span::raw(" "));
+    subject_parts.push(Span::styled(&e.snippet, Style::new().fg(Color::DarkGray)));
+    let subject_cell = Cell::from(Line::from(subject_parts));
+
+    let date_cell = Cell::from(format_date(&e.date));
+
+    let mut cells = vec![unread_cell, from_cell, subject_cell, date_cell];
+    if show_folder {
+        cells.insert(
+            1,
+            Cell::from(Span::styled(
+                e.folder.as_str(),
+                Style::new().fg(Color::Magenta),
+            )),
+        );
     }
+
+    Some(Row::new(cells).style(row_style))
 }
 
 fn render_inbox_status_bar<I: ImapClient, S: SmtpClient>(
@@ -127,11 +209,48 @@ fn render_inbox_status_bar<I: ImapClient, S: SmtpClient>(
             String::new()
         } else {
             let selected = app.table_state.selected().map(|s| s + 1).unwrap_or(0);
-            format!(" {selected}/{count} conversations")
+            format!(
+                " {selected}/{count} conversations  sorted by {}",
+                app.sort_mode.label()
+            )
         }
     };
-    let bar = Paragraph::new(text);
+    let mut spans = vec![Span::raw(text)];
+    if app.queued > 0 {
+        spans.push(Span::styled(
+            format!("  {} in outbox", app.queued),
+            Style::new().fg(Color::Yellow).bold(),
+        ));
+    }
+    if app.scheduled > 0 {
+        spans.push(Span::styled(
+            format!("  {} scheduled", app.scheduled),
+            Style::new().fg(Color::Cyan),
+        ));
+    }
+    let bar = Paragraph::new(Line::from(spans));
     frame.render_widget(bar, area);
+    render_connection(frame, area, app.connection);
+}
+
+/// The IMAP connection state, right-aligned over a status bar.
+fn render_connection(frame: &mut Frame, area: ratatui::layout::Rect, state: ConnectionState) {
+    let span = match state {
+        ConnectionState::Connected => Span::styled("● online ", Style::new().fg(Color::Green)),
+        ConnectionState::Disconnected { retry_at, .. } => {
+            let wait = retry_at.saturating_duration_since(std::time::Instant::now());
+            let text = if wait.is_zero() {
+                "○ offline, reconnecting ".to_string()
+            } else {
+                format!("○ offline, retry in {}s ", wait.as_secs() + 1)
+            };
+            Span::styled(text, Style::new().fg(Color::Red).bold())
+        }
+    };
+    let width = (span.width() as u16).min(area.width);
+    let [_, right] =
+        Layout::horizontal([Constraint::Fill(1), Constraint::Length(width)]).areas(area);
+    frame.render_widget(Paragraph::new(Line::from(span)), right);
 }
 
 fn render_detail<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<I, S>) {
@@ -145,12 +264,13 @@ fn render_detail<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<
     render_detail_top_bar(frame, top);
     render_detail_main(frame, main, app);
     render_detail_status_bar(frame, status, app);
+    render_detail_pickers(frame, main, app);
 }
 
 fn render_detail_top_bar(frame: &mut Frame, area: ratatui::layout::Rect) {
     let bar = Paragraph::new(
         Line::from(
-            " Esc=Back  r=Reply  c=Compose  m-d=Delete  m-a=Archive  m-r=Read  m-l=Labels  j/k=Navigate",
+            " Esc=Back  r=Reply  c=Compose  l=Links  v=View  H=Source  m-d=Delete  m-a=Archive  m-r=Read  m-l=Labels  j/k=Navigate",
         )
         .style(Style::new().bold()),
     );
@@ -172,36 +292,55 @@ fn render_detail_main<I: ImapClient, S: SmtpClient>(
         let email = &app.emails[msg.email_index];
         let is_active = i == state.active_index;
 
+        let header_style = if is_active {
+            Style::new().bg(Color::DarkGray).fg(Color::White)
+        } else {
+            Style::new()
+        };
+
+        match (msg.display, msg.raw.as_deref()) {
+            (MessageDisplay::Source, Some(raw)) => {
+                lines.push(Line::from(Span::styled("▼ Source", header_style.bold())));
+                for raw_line in String::from_utf8_lossy(raw).lines() {
+                    lines.push(Line::from(format!("  {}", raw_line.replace('\t', "    "))));
+                }
+                lines.push(Line::from(""));
+                continue;
+            }
+            (MessageDisplay::Headers, Some(raw)) => {
+                lines.push(Line::from(Span::styled("▼ Headers", header_style.bold())));
+                for (name, value) in crate::mime::parse(raw).headers {
+                    lines.push(Line::from(vec![
+                        Span::styled(format!("  {name}: "), Style::new().bold()),
+                        Span::raw(value),
+                    ]));
+                }
+                lines.push(Line::from(""));
+                if let Some(ref body) = msg.body {
+                    lines.extend(body_lines(body, area.width));
+                    lines.push(Line::from(""));
+                }
+                continue;
+            }
+            _ => {}
+        }
+
         if let Some(ref body) = msg.body {
             // Expanded message
-            let header_style = if is_active {
-                Style::new().bg(Color::DarkGray).fg(Color::White)
-            } else {
-                Style::new()
-            };
-
+            lines.extend(address_lines(body, header_style));
             lines.push(Line::from(vec![
-                Span::styled("▼ From: ", header_style.bold()),
-                Span::styled(&body.from, header_style),
-            ]));
-            lines.push(Line::from(vec![
-                Span::styled("  To:   ", header_style),
-                Span::styled(body.to.join(", "), header_style),
-            ]));
-            lines.push(Line::from(vec![
-                Span::styled("  Date: ", header_style),
+                Span::styled("  Date:     ", header_style),
                 Span::styled(format_date(&body.date), header_style),
             ]));
             lines.push(Line::from(vec![
-                Span::styled("  Subj: ", header_style),
+                Span::styled("  Subj:     ", header_style),
                 Span::styled(&body.subject, header_style),
             ]));
-            lines.push(Line::from(""));
-
-            for text_line in body.body_text.lines() {
-                lines.push(Line::from(format!("  {text_line}")));
+            if let Some(ref security) = body.security {
+                lines.push(security_line(security, header_style));
             }
-
+            lines.push(Line::from(""));
+            lines.extend(body_lines(body, area.width));
             lines.push(Line::from(""));
         } else {
             // Collapsed message
@@ -213,7 +352,7 @@ fn render_detail_main<I: ImapClient, S: SmtpClient>(
 
             lines.push(Line::from(vec![
                 Span::styled("▶ ", style),
-                Span::styled(&email.from, style.bold()),
+                Span::styled(email.author(), style.bold()),
                 Span::styled(" — ", style),
                 Span::styled(format_date(&email.date), style),
                 Span::styled(" — ", style),
@@ -230,6 +369,160 @@ fn render_detail_main<I: ImapClient, S: SmtpClient>(
     frame.render_widget(paragraph, area);
 }
 
+/// Outcome of opening a PGP/MIME or S/MIME message: the signature check,
+/// coloured by how far it can be trusted, and whether it was encrypted.
+fn security_line(security: &Security, style: Style) -> Line<'_> {
+    let label = match security.scheme {
+        Scheme::Pgp => "  PGP:      ",
+        Scheme::Smime => "  S/MIME:   ",
+    };
+    let mut spans = vec![Span::styled(label, style)];
+    let signature = security.signature.as_ref().map(|status| match status {
+        SignatureStatus::Valid(uid) => (format!("Good signature from {uid}"), Color::Green),
+        SignatureStatus::UnknownKey(key_id) if security.scheme == Scheme::Pgp => {
+            (format!("Signed by unknown key {key_id}"), Color::Yellow)
+        }
+        SignatureStatus::UnknownKey(signer) => (
+            format!("Signed by {signer}, certificate not trusted"),
+            Color::Yellow,
+        ),
+        SignatureStatus::Bad(uid) => (format!("BAD signature from {uid}"), Color::Red),
+        SignatureStatus::Error(e) => (format!("Signature not checked: {e}"), Color::Yellow),
+    });
+    if let Some((text, color)) = signature {
+        spans.push(Span::styled(text, style.fg(color).bold()));
+        if security.encrypted {
+            spans.push(Span::styled(", encrypted", style));
+        }
+    } else if security.encrypted {
+        spans.push(Span::styled("Encrypted", style));
+    }
+    Line::from(spans)
+}
+
+/// From, To and Cc of an expanded message, plus Sender and Reply-To when
+/// they name someone other than the author.
+fn address_lines(body: &EmailBody, style: Style) -> Vec<Line<'_>> {
+    let mut fields = vec![("▼ From:     ", body.from.join(", "))];
+    if let Some(ref sender) = body.sender
+        && !body.from.contains(sender)
+    {
+        fields.push(("  Sender:   ", sender.clone()));
+    }
+    if !body.reply_to.is_empty() && body.reply_to != body.from {
+        fields.push(("  Reply-To: ", body.reply_to.join(", ")));
+    }
+    fields.push(("  To:       ", body.to.join(", ")));
+    if !body.cc.is_empty() {
+        fields.push(("  Cc:       ", body.cc.join(", ")));
+    }
+
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(i, (label, value))| {
+            let label_style = if i == 0 { style.bold() } else { style };
+            Line::from(vec![
+                Span::styled(label, label_style),
+                Span::styled(value, style),
+            ])
+        })
+        .collect()
+}
+
+/// The body of an expanded message, indented under its headers.
+fn body_lines(body: &EmailBody, pane_width: u16) -> Vec<Line<'_>> {
+    // HTML is laid out at the width left inside the border and indent
+    let rendered_html = body
+        .body_html
+        .as_deref()
+        .map(|html| crate::html::render(html, pane_width.saturating_sub(4) as usize).text);
+    let text = rendered_html.as_deref().unwrap_or(&body.body_text);
+    text.lines()
+        .map(|text_line| Line::from(format!("  {text_line}")))
+        .collect()
+}
+
+fn render_detail_pickers<I: ImapClient, S: SmtpClient>(
+    frame: &mut Frame,
+    area: ratatui::layout::Rect,
+    app: &App<I, S>,
+) {
+    let View::Detail(ref state) = app.view else {
+        return;
+    };
+
+    if let Some(ref picker) = state.link_picker {
+        render_picker(
+            frame,
+            area,
+            " Links  Enter=Open  y=Copy  Esc=Close ",
+            &picker.links,
+            picker.selected,
+        );
+    } else if let Some(ref picker) = state.attachment_picker {
+        let items: Vec<String> = picker
+            .attachments
+            .iter()
+            .map(|a| {
+                format!(
+                    "{}  ({}, {})",
+                    a.filename,
+                    a.content_type,
+                    format_size(a.data.len())
+                )
+            })
+            .collect();
+        render_picker(
+            frame,
+            area,
+            " Open  Enter=View  Esc=Close ",
+            &items,
+            picker.selected,
+        );
+    }
+}
+
+/// A numbered list popup centered over `area`.
+fn render_picker(
+    frame: &mut Frame,
+    area: ratatui::layout::Rect,
+    title: &str,
+    entries: &[String],
+    selected: usize,
+) {
+    let height = (entries.len() as u16 + 2).min(area.height);
+    let width = area.width.saturating_sub(8).max(20).min(area.width);
+    let popup = ratatui::layout::Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let items: Vec<ListItem> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| ListItem::new(format!("{:>2}. {entry}", i + 1)))
+        .collect();
+    let list = List::new(items)
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::new().bg(Color::DarkGray).fg(Color::White));
+    let mut list_state = ListState::default().with_selected(Some(selected));
+
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(list, popup, &mut list_state);
+}
+
+/// Human-readable size of an attachment.
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.0} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
 fn render_detail_status_bar<I: ImapClient, S: SmtpClient>(
     frame: &mut Frame,
     area: ratatui::layout::Rect,
@@ -246,6 +539,7 @@ fn render_detail_status_bar<I: ImapClient, S: SmtpClient>(
     };
     let bar = Paragraph::new(text);
     frame.render_widget(bar, area);
+    render_connection(frame, area, app.connection);
 }
 
 fn render_compose<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<I, S>) {
@@ -262,9 +556,25 @@ fn render_compose<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App
 
     // Top bar with keybind hints
     let hint = match state.step {
-        ComposeStep::Bcc => " Esc=Cancel  Alt+S=Send",
+        _ if state.confirm_send && state.send_at.is_some() => " y=Schedule  n=Back",
+        _ if state.confirm_send => " y=Send  n=Back",
+        _ if state.send_at.is_some() => " Enter=Schedule  Esc=Back",
+        _ if !state.suggestions.is_empty() => " Tab=Complete  ↑/↓=Choose  Esc=Dismiss",
+        ComposeStep::Body => " Esc=Cancel  Alt+S=Next  Alt+Q=Reflow",
+        ComposeStep::Bcc => " Esc=Cancel  Alt+S=Send  Alt+L=Send later",
         _ => " Esc=Cancel  Alt+S=Next",
     };
+    let mut hint = hint.to_string();
+    if !state.confirm_send && state.suggestions.is_empty() && state.send_at.is_none() {
+        if !app.identities.is_empty() {
+            hint.push_str("  Alt+F=From");
+        }
+        hint.push_str(if state.markdown {
+            "  Alt+M=Plain  Alt+P=Preview"
+        } else {
+            "  Alt+M=Markdown"
+        });
+    }
     let bar = Paragraph::new(Line::from(hint).style(Style::new().bold()));
     frame.render_widget(bar, top);
 
@@ -284,6 +594,12 @@ fn render_compose<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App
     let label_style = Style::new().bold();
     let active_style = Style::new().fg(Color::Yellow);
 
+    // Identity the message goes out as, cycled with Alt+F
+    lines.push(Line::from(vec![
+        Span::styled("  From: ", label_style),
+        Span::raw(&state.from),
+    ]));
+
     // Subject field (editable for new emails, static for replies)
     if !state.is_reply {
         let subj_style = if state.step == ComposeStep::Subject {
@@ -292,7 +608,7 @@ fn render_compose<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App
             Style::new()
         };
         lines.push(Line::from(vec![
-            Span::styled("  Sub: ", label_style),
+            Span::styled("  Sub:  ", label_style),
             Span::styled(&state.subject, subj_style),
             if state.step == ComposeStep::Subject {
                 Span::styled("_", active_style)
@@ -302,117 +618,458 @@ fn render_compose<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App
         ]));
     }
 
-    let to_style = if state.step == ComposeStep::To {
-        active_style
-    } else {
-        Style::new()
-    };
-    lines.push(Line::from(vec![
-        Span::styled("  To:  ", label_style),
-        Span::styled(&state.to, to_style),
-        if state.step == ComposeStep::To {
-            Span::styled("_", active_style)
-        } else {
-            Span::raw("")
-        },
-    ]));
-
-    let cc_style = if state.step == ComposeStep::Cc {
-        active_style
-    } else {
-        Style::new()
-    };
-    lines.push(Line::from(vec![
-        Span::styled("  CC:  ", label_style),
-        Span::styled(&state.cc, cc_style),
-        if state.step == ComposeStep::Cc {
-            Span::styled("_", active_style)
-        } else {
-            Span::raw("")
-        },
-    ]));
-
-    let bcc_style = if state.step == ComposeStep::Bcc {
-        active_style
-    } else {
-        Style::new()
-    };
-    lines.push(Line::from(vec![
-        Span::styled("  BCC: ", label_style),
-        Span::styled(&state.bcc, bcc_style),
-        if state.step == ComposeStep::Bcc {
-            Span::styled("_", active_style)
-        } else {
-            Span::raw("")
-        },
-    ]));
+    lines.push(address_line(
+        "  To:   ",
+        &state.to,
+        state.step == ComposeStep::To,
+    ));
+    lines.push(address_line(
+        "  CC:   ",
+        &state.cc,
+        state.step == ComposeStep::Cc,
+    ));
+    lines.push(address_line(
+        "  BCC:  ",
+        &state.bcc,
+        state.step == ComposeStep::Bcc,
+    ));
 
     // Separator
     lines.push(Line::from("  ─────────────────────────────────────────"));
 
-    // Body text
+    // Body text, soft-wrapped where it will be when sent
+    let mut body: Vec<Line> = Vec::new();
     for (i, line) in state.body_lines.iter().enumerate() {
-        if state.step == ComposeStep::Body && i == state.cursor_row {
-            // Show cursor in the body line
-            let (before, after) = if state.cursor_col <= line.len() {
-                (&line[..state.cursor_col], &line[state.cursor_col..])
+        let cursor =
+            (state.step == ComposeStep::Body && i == state.cursor_row).then_some(state.cursor_col);
+        let rows = flowed::split(line, flowed::WIDTH);
+        let last = rows.len() - 1;
+        for (r, range) in rows.into_iter().enumerate() {
+            let row = &line[range.clone()];
+            let col = cursor
+                .filter(|&col| col >= range.start && (col < range.end || r == last))
+                .map(|col| col - range.start);
+            if let Some(col) = col {
+                body.push(cursor_line(row, col));
+            } else if quote::depth(line).0 > 0 {
+                body.push(Line::from(Span::styled(
+                    format!("  {row}"),
+                    Style::new().fg(Color::DarkGray),
+                )));
             } else {
-                (line.as_str(), "")
-            };
-            lines.push(Line::from(vec![
-                Span::raw(format!("  {before}")),
-                Span::styled(
-                    if after.is_empty() {
-                        " ".to_string()
-                    } else {
-                        after.chars().next().unwrap().to_string()
-                    },
-                    Style::new().bg(Color::White).fg(Color::Black),
-                ),
-                Span::raw(if after.len() > 1 {
-                    after[after.chars().next().unwrap().len_utf8()..].to_string()
-                } else {
-                    String::new()
-                }),
-            ]));
-        } else {
-            lines.push(Line::from(format!("  {line}")));
+                body.push(Line::from(format!("  {row}")));
+            }
         }
     }
 
-    // Quoted text
-    if !state.quoted_text.is_empty() {
-        lines.push(Line::from(""));
-        for quoted_line in state.quoted_text.lines() {
-            lines.push(Line::from(Span::styled(
+    if state.preview {
+        // The HTML part as a mail client would lay it out
+        let html = markdown::to_html(&state.body(app.posting));
+        let rendered = crate::html::render(&html, inner.width.saturating_sub(2) as usize);
+        lines.extend(
+            rendered
+                .text
+                .lines()
+                .map(|preview_line| Line::from(format!("  {preview_line}"))),
+        );
+    } else {
+        // Quoted text, above or below the new text depending on posting style
+        let quote = state.quoted_text.lines().map(|quoted_line| {
+            Line::from(Span::styled(
                 format!("  {quoted_line}"),
                 Style::new().fg(Color::DarkGray),
-            )));
+            ))
+        });
+        if state.quoted_text.is_empty() {
+            lines.extend(body);
+        } else if app.posting == Posting::Bottom {
+            lines.extend(quote);
+            lines.push(Line::from(""));
+            lines.extend(body);
+        } else {
+            lines.extend(body);
+            lines.push(Line::from(""));
+            lines.extend(quote);
         }
     }
 
     let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
     frame.render_widget(paragraph, inner);
 
-    // Status bar
-    let status_text = state
-        .status_message
-        .as_deref()
-        .map(|s| format!(" {s}"))
-        .unwrap_or_else(|| {
-            format!(
-                " Step: {}",
-                match state.step {
-                    ComposeStep::Body => "Body",
-                    ComposeStep::Subject => "Subject",
-                    ComposeStep::To => "To",
-                    ComposeStep::Cc => "CC",
-                    ComposeStep::Bcc => "BCC",
-                }
-            )
-        });
-    let status_bar = Paragraph::new(status_text);
+    if !state.suggestions.is_empty() {
+        render_suggestions(frame, inner, state);
+    }
+
+    // Status bar, flagging a malformed address while it is being typed
+    let field = match state.step {
+        ComposeStep::To => Some(&state.to),
+        ComposeStep::Cc => Some(&state.cc),
+        ComposeStep::Bcc => Some(&state.bcc),
+        ComposeStep::Body | ComposeStep::Subject => None,
+    };
+    let field_error = field.and_then(|field| {
+        address::parse_list(field)
+            .into_iter()
+            .find_map(|entry| entry.parsed.err())
+    });
+    let status_bar = match (&state.send_at, &state.status_message, field_error) {
+        (Some(text), _, _) if !state.confirm_send => {
+            let mut line = cursor_line(text, state.send_at_cursor);
+            line.spans.insert(
+                0,
+                Span::styled(
+                    " Send at (HH:MM, tomorrow HH:MM, YYYY-MM-DD HH:MM, +2h):",
+                    Style::new().bold(),
+                ),
+            );
+            Paragraph::new(line)
+        }
+        (_, Some(message), _) => Paragraph::new(format!(" {message}")),
+        (_, None, Some(error)) => {
+            Paragraph::new(format!(" Invalid address: {error}")).style(Style::new().fg(Color::Red))
+        }
+        (_, None, None) => Paragraph::new(format!(
+            " Step: {}{}{}",
+            match state.step {
+                ComposeStep::Body => "Body",
+                ComposeStep::Subject => "Subject",
+                ComposeStep::To => "To",
+                ComposeStep::Cc => "CC",
+                ComposeStep::Bcc => "BCC",
+            },
+            match (state.markdown, state.preview) {
+                (_, true) => "  (Markdown preview)",
+                (true, false) => "  (Markdown)",
+                (false, false) => "",
+            },
+            security_status(state)
+        )),
+    };
     frame.render_widget(status_bar, status);
+    render_connection(frame, status, app.connection);
+}
+
+/// Sign and encrypt toggles for the compose status bar.
+#[cfg(feature = "pgp")]
+fn security_status(state: &ComposeState) -> String {
+    let on_off = |on| if on { "on" } else { "off" };
+    format!(
+        "  Sign: {} (Alt+G)  Encrypt: {} (Alt+E)",
+        on_off(state.sign),
+        on_off(state.encrypt)
+    )
+}
+
+#[cfg(not(feature = "pgp"))]
+fn security_status(_state: &ComposeState) -> &'static str {
+    ""
+}
+
+/// A body row with the cursor drawn at byte offset `col`.
+fn cursor_line(row: &str, col: usize) -> Line<'static> {
+    let (before, after) = if col <= row.len() {
+        (&row[..col], &row[col..])
+    } else {
+        (row, "")
+    };
+    let mut chars = after.chars();
+    let under = chars.next().unwrap_or(' ');
+    Line::from(vec![
+        Span::raw(format!("  {before}")),
+        Span::styled(
+            under.to_string(),
+            Style::new().bg(Color::White).fg(Color::Black),
+        ),
+        Span::raw(chars.as_str().to_string()),
+    ])
+}
+
+/// A recipient field. The field being edited shows its text with malformed
+/// entries highlighted; the others show one chip per recipient.
+fn address_line<'a>(label: &'a str, field: &'a str, active: bool) -> Line<'a> {
+    let active_style = Style::new().fg(Color::Yellow);
+    let error_style = Style::new()
+        .fg(Color::Red)
+        .add_modifier(Modifier::UNDERLINED);
+    let chip_style = Style::new().bg(Color::DarkGray).fg(Color::White);
+
+    let mut spans = vec![Span::styled(label, Style::new().bold())];
+    let entries = address::parse_list(field);
+    if active {
+        let mut pos = 0;
+        for entry in &entries {
+            spans.push(Span::styled(&field[pos..entry.range.start], active_style));
+            let style = if entry.parsed.is_ok() {
+                active_style
+            } else {
+                error_style
+            };
+            spans.push(Span::styled(&field[entry.range.clone()], style));
+            pos = entry.range.end;
+        }
+        spans.push(Span::styled(&field[pos..], active_style));
+        spans.push(Span::styled("_", active_style));
+        return Line::from(spans);
+    }
+
+    for entry in entries {
+        match entry.parsed {
+            Ok(Address::Mailbox(mailbox)) => {
+                let label = mailbox.name.unwrap_or(mailbox.email);
+                spans.push(Span::styled(format!(" {label} "), chip_style));
+            }
+            Ok(Address::Group { name, members }) => {
+                spans.push(Span::styled(
+                    format!(" {name} ({}) ", members.len()),
+                    chip_style,
+                ));
+            }
+            Err(_) => spans.push(Span::styled(&field[entry.range], error_style)),
+        }
+        spans.push(Span::raw(" "));
+    }
+    Line::from(spans)
+}
+
+/// Address completions in a dropdown just below the active recipient field.
+fn render_suggestions(frame: &mut Frame, area: ratatui::layout::Rect, state: &ComposeState) {
+    let field_row = match state.step {
+        ComposeStep::To => 0,
+        ComposeStep::Cc => 1,
+        ComposeStep::Bcc => 2,
+        ComposeStep::Body | ComposeStep::Subject => return,
+    } + 1
+        + u16::from(!state.is_reply);
+    let top = area.y + field_row + 1;
+    let left = area.x + 8;
+    if top >= area.bottom() || left >= area.right() {
+        return;
+    }
+
+    let longest = state
+        .suggestions
+        .iter()
+        .map(|s| s.chars().count())
+        .max()
+        .unwrap_or(0);
+    let popup = ratatui::layout::Rect {
+        x: left,
+        y: top,
+        width: (longest as u16 + 2).min(area.right() - left),
+        height: (state.suggestions.len() as u16 + 2).min(area.bottom() - top),
+    };
+
+    let items: Vec<ListItem> = state
+        .suggestions
+        .iter()
+        .map(|s| ListItem::new(s.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::bordered())
+        .highlight_style(Style::new().bg(Color::DarkGray).fg(Color::White));
+    let mut list_state = ListState::default().with_selected(Some(state.suggestion_selected));
+
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(list, popup, &mut list_state);
+}
+
+/// The outbox view: queued messages with their attempts and next retry.
+fn render_outbox<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<I, S>) {
+    let [top, main, status] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Fill(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let connection = app.connection;
+    let View::Outbox(ref mut state) = app.view else {
+        return;
+    };
+
+    let bar = Paragraph::new(
+        Line::from(" Esc=Back  j/k=Navigate  e=Edit  r=Retry now  d=Cancel")
+            .style(Style::new().bold()),
+    );
+    frame.render_widget(bar, top);
+
+    let block = Block::bordered().title(" Outbox ");
+    if state.entries.is_empty() {
+        frame.render_widget(Paragraph::new("Outbox is empty").block(block), main);
+    } else {
+        let now = chrono::Utc::now().timestamp();
+        let rows: Vec<Row> = state
+            .entries
+            .iter()
+            .map(|entry| {
+                let queued = DateTime::from_timestamp(entry.queued_at, 0)
+                    .map(|date| date.with_timezone(&Local).format("%b %d %H:%M").to_string())
+                    .unwrap_or_default();
+                let to = entry
+                    .draft
+                    .to
+                    .iter()
+                    .chain(&entry.draft.cc)
+                    .chain(&entry.draft.bcc)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let (state, color) = match entry.next_attempt {
+                    Some(at) if at > now => (format!("retry in {}", wait(at - now)), Color::Yellow),
+                    Some(_) => ("retrying".to_string(), Color::Yellow),
+                    None => ("failed".to_string(), Color::Red),
+                };
+                let plural = if entry.attempts == 1 { "" } else { "s" };
+                let status = Line::from(vec![
+                    Span::styled(state, Style::new().fg(color).bold()),
+                    Span::styled(
+                        format!(
+                            " ({} attempt{plural}): {}",
+                            entry.attempts, entry.last_error
+                        ),
+                        Style::new().fg(Color::DarkGray),
+                    ),
+                ]);
+                Row::new(vec![
+                    Cell::from(queued),
+                    Cell::from(to),
+                    Cell::from(entry.draft.subject.as_str()),
+                    Cell::from(status),
+                ])
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(13),
+                Constraint::Length(24),
+                Constraint::Fill(1),
+                Constraint::Fill(2),
+            ],
+        )
+        .block(block)
+        .row_highlight_style(Style::new().bg(Color::DarkGray).fg(Color::White));
+        frame.render_stateful_widget(table, main, &mut state.table_state);
+    }
+
+    let text = match state.status_message {
+        Some(ref msg) => format!(" {msg}"),
+        None => format!(" {} queued", state.entries.len()),
+    };
+    frame.render_widget(Paragraph::new(text), status);
+    render_connection(frame, status, connection);
+}
+
+fn render_scheduled<I: ImapClient, S: SmtpClient>(frame: &mut Frame, app: &mut App<I, S>) {
+    let [top, main, status] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Fill(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let connection = app.connection;
+    let View::Scheduled(ref mut state) = app.view else {
+        return;
+    };
+
+    let bar = Paragraph::new(
+        Line::from(" Esc=Back  j/k=Navigate  e=Edit  s=Send now  d=Cancel")
+            .style(Style::new().bold()),
+    );
+    frame.render_widget(bar, top);
+
+    let block = Block::bordered().title(" Scheduled ");
+    if state.entries.is_empty() {
+        frame.render_widget(Paragraph::new("Nothing scheduled").block(block), main);
+    } else {
+        let now = chrono::Utc::now().timestamp();
+        let rows: Vec<Row> = state
+            .entries
+            .iter()
+            .map(|entry| {
+                let send_at = DateTime::from_timestamp(entry.send_at, 0)
+                    .map(|date| {
+                        date.with_timezone(&Local)
+                            .format("%a %b %d %H:%M")
+                            .to_string()
+                    })
+                    .unwrap_or_default();
+                let to = entry
+                    .draft
+                    .to
+                    .iter()
+                    .chain(&entry.draft.cc)
+                    .chain(&entry.draft.bcc)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let status = match entry.error {
+                    Some(ref error) => Span::styled(
+                        format!("failed: {error}"),
+                        Style::new().fg(Color::Red).bold(),
+                    ),
+                    None if entry.send_at > now => Span::styled(
+                        format!("in {}", wait(entry.send_at - now)),
+                        Style::new().fg(Color::Cyan),
+                    ),
+                    None => Span::styled("sending", Style::new().fg(Color::Yellow)),
+                };
+                Row::new(vec![
+                    Cell::from(send_at),
+                    Cell::from(to),
+                    Cell::from(entry.draft.subject.as_str()),
+                    Cell::from(status),
+                ])
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(17),
+                Constraint::Length(24),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .block(block)
+        .row_highlight_style(Style::new().bg(Color::DarkGray).fg(Color::White));
+        frame.render_stateful_widget(table, main, &mut state.table_state);
+    }
+
+    let text = match state.status_message {
+        Some(ref msg) => format!(" {msg}"),
+        None => format!(" {} scheduled", state.entries.len()),
+    };
+    frame.render_widget(Paragraph::new(text), status);
+    render_connection(frame, status, connection);
+}
+
+/// A wait in seconds, in the largest whole unit: `45s`, `3m`, `2h`.
+fn wait(secs: i64) -> String {
+    match secs {
+        ..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        _ => format!("{}h", secs / 3600),
+    }
+}
+
+/// Bucket a message date into an inbox group heading relative to `today`.
+fn date_group(date: Option<DateTime<FixedOffset>>, today: NaiveDate) -> &'static str {
+    let Some(date) = date else {
+        return "Older";
+    };
+    let msg_date = date.with_timezone(&Local).date_naive();
+
+    if msg_date >= today {
+        "Today"
+    } else if Some(msg_date) == today.pred_opt() {
+        "Yesterday"
+    } else if msg_date.iso_week() == today.iso_week() {
+        "This week"
+    } else if msg_date.year() == today.year() && msg_date.month() == today.month() {
+        "This month"
+    } else {
+        "Older"
+    }
 }
 
 pub fn format_date(raw: &str) -> String {
@@ -10,16 +10,23 @@ fn mock_clients() -> (MockImapClient, MockSmtpClient) {
         Ok(crate::imap::EmailBody {
             uid,
             subject: "Test".to_string(),
-            from: "test@example.com".to_string(),
+            from: vec!["test@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
             to: vec!["me@example.com".to_string()],
+            cc: vec![],
             date: "2025-01-01".to_string(),
             body_text: "Test body".to_string(),
+            body_html: None,
+            security: None,
         })
     });
     imap.expect_mark_seen().returning(|_, _| Ok(()));
     imap.expect_delete_email().returning(|_, _| Ok(()));
     imap.expect_archive_email().returning(|_, _| Ok(()));
     imap.expect_append().returning(|_, _| Ok(()));
+    imap.expect_fetch_older().returning(|_, _, _| Ok(vec![]));
+    imap.expect_fetch_newer().returning(|_, _, _| Ok(vec![]));
     (imap, MockSmtpClient::new())
 }
 
@@ -43,27 +50,35 @@ fn render_with_emails() {
                 uid: 1,
                 folder: "INBOX".to_string(),
                 subject: "Hello".to_string(),
-                from: "alice@example.com".to_string(),
-                to: "me@example.com".to_string(),
+                from: vec!["alice@example.com".to_string()],
+                sender: None,
+                reply_to: vec![],
+                to: vec!["me@example.com".to_string()],
+                cc: vec![],
                 date: "2025-01-01".to_string(),
                 seen: false,
                 snippet: "Hey there".to_string(),
                 message_id: None,
                 in_reply_to: None,
                 references: vec![],
+                internal_date: None,
             },
             EmailSummary {
                 uid: 2,
                 folder: "INBOX".to_string(),
                 subject: "Meeting".to_string(),
-                from: "Bob Jones".to_string(),
-                to: "me@example.com".to_string(),
+                from: vec!["Bob Jones".to_string()],
+                sender: None,
+                reply_to: vec![],
+                to: vec!["me@example.com".to_string()],
+                cc: vec![],
                 date: "2025-01-02".to_string(),
                 seen: true,
                 snippet: "Let's meet".to_string(),
                 message_id: None,
                 in_reply_to: None,
                 references: vec![],
+                internal_date: None,
             },
         ],
         imap,
@@ -84,14 +99,18 @@ fn render_detail_view() {
             uid: 1,
             folder: "INBOX".to_string(),
             subject: "Hello".to_string(),
-            from: "alice@example.com".to_string(),
-            to: "me@example.com".to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
             date: "Mon, 01 Jan 2025 10:00:00 +0000".to_string(),
             seen: false,
             snippet: "Hey there".to_string(),
             message_id: None,
             in_reply_to: None,
             references: vec![],
+            internal_date: None,
         }],
         imap,
         smtp,
@@ -145,3 +164,729 @@ fn format_date_invalid() {
     let result = format_date("not a date");
     assert_eq!(result, "not a date");
 }
+
+#[test]
+fn date_group_buckets() {
+    // Wednesday
+    let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+    let at = |s: &str| DateTime::parse_from_rfc3339(s).ok();
+    assert_eq!(date_group(at("2025-01-15T12:00:00+00:00"), today), "Today");
+    assert_eq!(
+        date_group(at("2025-01-14T12:00:00+00:00"), today),
+        "Yesterday"
+    );
+    assert_eq!(
+        date_group(at("2025-01-13T12:00:00+00:00"), today),
+        "This week"
+    );
+    assert_eq!(
+        date_group(at("2025-01-02T12:00:00+00:00"), today),
+        "This month"
+    );
+    assert_eq!(date_group(at("2024-12-20T12:00:00+00:00"), today), "Older");
+    assert_eq!(date_group(None, today), "Older");
+}
+
+#[test]
+fn render_grouped_inbox_shows_headers() {
+    let backend = TestBackend::new(100, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(
+        vec![EmailSummary {
+            uid: 1,
+            folder: "INBOX".to_string(),
+            subject: "Hello".to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: Local::now().to_rfc2822(),
+            seen: false,
+            snippet: "Hey there".to_string(),
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            internal_date: None,
+        }],
+        imap,
+        smtp,
+        "me@example.com".to_string(),
+        None,
+    );
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Today"));
+    // The conversation row, not the header, is highlighted
+    assert_eq!(app.grouped_table_state.selected(), Some(1));
+}
+
+#[test]
+fn render_group_header_beside_folder_column() {
+    let backend = TestBackend::new(100, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(
+        vec![EmailSummary {
+            uid: 1,
+            folder: "INBOX".to_string(),
+            subject: "Hello".to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: (Local::now() - chrono::Duration::days(1)).to_rfc2822(),
+            seen: false,
+            snippet: "Hey there".to_string(),
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            internal_date: None,
+        }],
+        imap,
+        smtp,
+        "me@example.com".to_string(),
+        None,
+    );
+    app.show_folder_column = true;
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("Yesterday"));
+}
+
+fn buffer_text(terminal: &Terminal<TestBackend>) -> String {
+    terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|c| c.symbol())
+        .collect()
+}
+
+#[test]
+fn render_folder_column_and_scope() {
+    let backend = TestBackend::new(160, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(
+        vec![EmailSummary {
+            uid: 1,
+            folder: "Receipts".to_string(),
+            subject: "Order".to_string(),
+            from: vec!["shop@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: "2025-01-01".to_string(),
+            seen: true,
+            snippet: String::new(),
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            internal_date: None,
+        }],
+        imap,
+        smtp,
+        "me@example.com".to_string(),
+        None,
+    );
+    app.show_folder_column = true;
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("[all folders]"));
+    assert!(content.contains("Receipts"));
+
+    app.folder_filter = Some("Receipts".to_string());
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("[Receipts]"));
+}
+
+#[test]
+fn render_detail_view_lays_out_html_body() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let mut imap = MockImapClient::new();
+    imap.expect_fetch_email().returning(|uid, _folder| {
+        Ok(crate::imap::EmailBody {
+            uid,
+            subject: "Newsletter".to_string(),
+            from: vec!["news@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: "2025-01-01".to_string(),
+            body_text: "fallback".to_string(),
+            body_html: Some(
+                "<h1>Weekly</h1><ul><li>First item</li></ul>\
+                 <p>Read <a href=\"https://example.com/post\">more</a></p>"
+                    .to_string(),
+            ),
+            security: None,
+        })
+    });
+    imap.expect_mark_seen().returning(|_, _| Ok(()));
+    let mut app = App::new(
+        vec![EmailSummary {
+            uid: 1,
+            folder: "INBOX".to_string(),
+            subject: "Newsletter".to_string(),
+            from: vec!["news@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: "Mon, 01 Jan 2025 10:00:00 +0000".to_string(),
+            seen: false,
+            snippet: String::new(),
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            internal_date: None,
+        }],
+        imap,
+        MockSmtpClient::new(),
+        "me@example.com".to_string(),
+        None,
+    );
+    app.handle_key(
+        crossterm::event::KeyCode::Enter,
+        crossterm::event::KeyModifiers::NONE,
+    );
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Weekly"));
+    assert!(content.contains("• First item"));
+    assert!(content.contains("Read more[1]"));
+    assert!(content.contains("[1] https://example.com/post"));
+    assert!(!content.contains("fallback"));
+}
+
+#[test]
+fn render_link_picker_popup() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(
+        vec![EmailSummary {
+            uid: 1,
+            folder: "INBOX".to_string(),
+            subject: "Hello".to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: "2025-01-01".to_string(),
+            seen: true,
+            snippet: String::new(),
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            internal_date: None,
+        }],
+        imap,
+        smtp,
+        "me@example.com".to_string(),
+        None,
+    );
+    app.handle_key(
+        crossterm::event::KeyCode::Enter,
+        crossterm::event::KeyModifiers::NONE,
+    );
+    if let View::Detail(ref mut state) = app.view {
+        state.link_picker = Some(crate::app::LinkPicker {
+            links: vec!["https://example.com/first".to_string()],
+            selected: 0,
+        });
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Links"));
+    assert!(content.contains(" 1. https://example.com/first"));
+}
+
+#[test]
+fn format_size_units() {
+    assert_eq!(format_size(512), "512 B");
+    assert_eq!(format_size(2048), "2 KB");
+    assert_eq!(format_size(3 * 1_048_576 / 2), "1.5 MB");
+}
+
+#[test]
+fn render_full_headers_and_raw_source() {
+    let backend = TestBackend::new(100, 30);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(
+        vec![EmailSummary {
+            uid: 1,
+            folder: "INBOX".to_string(),
+            subject: "Hello".to_string(),
+            from: vec!["alice@example.com".to_string()],
+            sender: None,
+            reply_to: vec![],
+            to: vec!["me@example.com".to_string()],
+            cc: vec![],
+            date: "2025-01-01".to_string(),
+            seen: true,
+            snippet: String::new(),
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            internal_date: None,
+        }],
+        imap,
+        smtp,
+        "me@example.com".to_string(),
+        None,
+    );
+    app.handle_key(
+        crossterm::event::KeyCode::Enter,
+        crossterm::event::KeyModifiers::NONE,
+    );
+    let raw = b"Received: from mx.example.com\r\n\tby mail.example.org\r\n\
+                Authentication-Results: mx; dkim=pass\r\n\r\nBody\r\n"
+        .to_vec();
+    if let View::Detail(ref mut state) = app.view {
+        state.thread[0].raw = Some(raw);
+        state.thread[0].display = MessageDisplay::Headers;
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Received: from mx.example.com by mail.example.org"));
+    assert!(content.contains("Authentication-Results: mx; dkim=pass"));
+    assert!(content.contains("Test body"));
+
+    if let View::Detail(ref mut state) = app.view {
+        state.thread[0].display = MessageDisplay::Source;
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("  Received: from mx.example.com"));
+    // Folded continuation lines stay on their own line
+    assert!(content.contains("      by mail.example.org"));
+    assert!(!content.contains("Subj:"));
+}
+
+#[test]
+fn address_lines_show_cc_and_distinct_reply_to() {
+    let body = crate::imap::EmailBody {
+        uid: 1,
+        subject: "Hi".to_string(),
+        from: vec!["Alice <alice@example.com>".to_string()],
+        sender: Some("list@example.com".to_string()),
+        reply_to: vec!["team@example.com".to_string()],
+        to: vec!["me@example.com".to_string(), "bob@example.com".to_string()],
+        cc: vec!["carol@example.com".to_string()],
+        date: String::new(),
+        body_text: String::new(),
+        body_html: None,
+        security: None,
+    };
+    let text: Vec<String> = address_lines(&body, Style::new())
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    assert_eq!(
+        text,
+        vec![
+            "▼ From:     Alice <alice@example.com>",
+            "  Sender:   list@example.com",
+            "  Reply-To: team@example.com",
+            "  To:       me@example.com, bob@example.com",
+            "  Cc:       carol@example.com",
+        ]
+    );
+
+    // Sender and Reply-To that repeat the author are not shown
+    let plain = crate::imap::EmailBody {
+        sender: Some("Alice <alice@example.com>".to_string()),
+        reply_to: vec!["Alice <alice@example.com>".to_string()],
+        cc: vec![],
+        ..body
+    };
+    assert_eq!(address_lines(&plain, Style::new()).len(), 2);
+}
+
+#[test]
+fn security_line_reports_signature_and_encryption() {
+    let line = |signature: Option<SignatureStatus>, encrypted: bool| {
+        let security = Security {
+            scheme: Scheme::Pgp,
+            encrypted,
+            signature,
+        };
+        let line = security_line(&security, Style::new());
+        let color = line.spans.get(1).and_then(|span| span.style.fg);
+        (line.to_string(), color)
+    };
+
+    assert_eq!(
+        line(
+            Some(SignatureStatus::Valid(
+                "Alice <alice@example.com>".to_string()
+            )),
+            true
+        ),
+        (
+            "  PGP:      Good signature from Alice <alice@example.com>, encrypted".to_string(),
+            Some(Color::Green)
+        )
+    );
+    assert_eq!(
+        line(
+            Some(SignatureStatus::UnknownKey("0123ABCD".to_string())),
+            false
+        ),
+        (
+            "  PGP:      Signed by unknown key 0123ABCD".to_string(),
+            Some(Color::Yellow)
+        )
+    );
+    assert_eq!(
+        line(Some(SignatureStatus::Bad("Mallory".to_string())), false).1,
+        Some(Color::Red)
+    );
+    assert_eq!(line(None, true).0, "  PGP:      Encrypted");
+
+    let smime = Security {
+        scheme: Scheme::Smime,
+        encrypted: false,
+        signature: Some(SignatureStatus::UnknownKey(
+            "carol@corp.example".to_string(),
+        )),
+    };
+    assert_eq!(
+        security_line(&smime, Style::new()).to_string(),
+        "  S/MIME:   Signed by carol@corp.example, certificate not trusted"
+    );
+}
+
+#[test]
+fn render_address_suggestions_below_field() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    if let View::Compose(ref mut state) = app.view {
+        state.step = ComposeStep::To;
+        state.to = "al".to_string();
+        state.to_cursor = 2;
+        state.suggestions = vec![
+            "Alice Smith <alice@example.com>".to_string(),
+            "alan@example.com".to_string(),
+        ];
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let lines: Vec<String> = (0..buffer.area.height)
+        .map(|y| {
+            (0..buffer.area.width)
+                .map(|x| buffer[(x, y)].symbol())
+                .collect()
+        })
+        .collect();
+    assert!(lines[0].contains("Tab=Complete"));
+    assert!(lines[4].contains("To:   al_"));
+    assert!(lines[6].contains("│Alice Smith <alice@example.com>│"));
+    assert!(lines[7].contains("│alan@example.com"));
+}
+
+#[test]
+fn render_recipient_chips_and_errors() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    if let View::Compose(ref mut state) = app.view {
+        state.step = ComposeStep::Cc;
+        state.to =
+            "\"Smith, Alice\" <alice@example.com>, bob@example.com, Team: c@x.org;".to_string();
+        state.cc = "carol@example.com, dave".to_string();
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let row = |y: u16| -> String { (0..80).map(|x| buffer[(x, y)].symbol()).collect() };
+    assert!(row(4).contains("To:    Smith, Alice   bob@example.com   Team (1)"));
+    assert!(row(5).contains("CC:   carol@example.com, dave_"));
+    assert!(row(23).contains("Invalid address: missing @ in \"dave\""));
+
+    // The chip is highlighted and the malformed entry is red
+    let to_col = row(4).find("Smith").unwrap() as u16;
+    assert_eq!(buffer[(to_col, 4)].bg, Color::DarkGray);
+    let dave_col = row(5).find("dave").unwrap() as u16;
+    assert_eq!(buffer[(dave_col, 5)].fg, Color::Red);
+}
+
+#[test]
+fn render_compose_from_line() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(
+        Vec::new(),
+        imap,
+        smtp,
+        "Me <me@example.com>".to_string(),
+        None,
+    );
+    app.identities = vec!["support@example.com".to_string()];
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Alt+F=From"));
+    assert!(content.contains("From: Me <me@example.com>"));
+}
+
+#[test]
+fn render_compose_soft_wraps_body_at_72_columns() {
+    let backend = TestBackend::new(100, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    let line = format!("{}end", "word ".repeat(15));
+    if let View::Compose(ref mut state) = app.view {
+        state.cursor_col = line.len();
+        state.body_lines = vec![line];
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let rows: Vec<String> = (0..24)
+        .map(|y| (0..100).map(|x| buffer[(x, y)].symbol()).collect())
+        .collect();
+    let first = rows.iter().position(|r| r.contains("word word")).unwrap();
+    assert_eq!(rows[first].matches("word").count(), 14);
+    assert!(rows[first + 1].contains("  word end "));
+}
+
+#[test]
+fn render_compose_markdown_preview() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.markdown = true;
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    if let View::Compose(ref mut state) = app.view {
+        state.body_lines = vec!["Some **bold** words".to_string()];
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Some **bold** words"));
+    assert!(content.contains("Alt+P=Preview"));
+    assert!(content.contains("(Markdown)"));
+
+    app.handle_key(
+        crossterm::event::KeyCode::Char('p'),
+        crossterm::event::KeyModifiers::ALT,
+    );
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let content = buffer_text(&terminal);
+    assert!(content.contains("Some bold words"));
+    assert!(content.contains("(Markdown preview)"));
+}
+
+#[cfg(feature = "pgp")]
+#[test]
+fn render_compose_pgp_status() {
+    let backend = TestBackend::new(100, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.encrypt = true;
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("Sign: off (Alt+G)  Encrypt: on (Alt+E)"));
+}
+
+#[test]
+fn status_bar_shows_connection_state() {
+    let backend = TestBackend::new(80, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("● online"));
+
+    app.connection = ConnectionState::Disconnected {
+        attempts: 3,
+        retry_at: std::time::Instant::now() + std::time::Duration::from_millis(7500),
+    };
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("○ offline, retry in 8s"));
+}
+
+#[test]
+fn outbox_lists_queued_messages() {
+    let backend = TestBackend::new(120, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.queued = 2;
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("2 in outbox"));
+
+    let draft = crate::smtp::Email {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: "Stuck".to_string(),
+        body: String::new(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    };
+    let now = chrono::Utc::now().timestamp();
+    let entry = |id: &str, next_attempt| crate::outbox::Entry {
+        id: id.to_string(),
+        from: draft.from.clone(),
+        recipients: draft.to.clone(),
+        queued_at: now,
+        attempts: 3,
+        next_attempt,
+        last_error: "connection refused".to_string(),
+        draft: draft.clone(),
+    };
+    let mut table_state = ratatui::widgets::TableState::default();
+    table_state.select(Some(0));
+    app.view = View::Outbox(crate::app::OutboxState {
+        entries: vec![entry("1", Some(now + 150)), entry("2", None)],
+        table_state,
+        status_message: None,
+        confirm_cancel: false,
+    });
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let text = buffer_text(&terminal);
+    assert!(text.contains("you@example.com"));
+    assert!(text.contains("Stuck"));
+    assert!(text.contains("retry in 2m (3 attempts): connection refused"));
+    assert!(text.contains("failed (3 attempts)"));
+    assert!(text.contains("2 queued"));
+}
+
+#[test]
+fn waits_use_largest_unit() {
+    assert_eq!(wait(45), "45s");
+    assert_eq!(wait(150), "2m");
+    assert_eq!(wait(7200), "2h");
+}
+
+#[test]
+fn scheduled_view_lists_messages() {
+    let backend = TestBackend::new(120, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.scheduled = 2;
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("2 scheduled"));
+
+    let draft = crate::smtp::Email {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: vec![],
+        bcc: vec![],
+        subject: "Morning".to_string(),
+        body: String::new(),
+        in_reply_to: None,
+        references: vec![],
+        markdown: false,
+        sign: false,
+        encrypt: false,
+    };
+    let now = chrono::Utc::now().timestamp();
+    let entry = |id: &str, error: Option<&str>| crate::schedule::Scheduled {
+        id: id.to_string(),
+        send_at: now + 3 * 60 * 60 + 30,
+        from: String::new(),
+        recipients: vec![],
+        error: error.map(str::to_string),
+        draft_copy: None,
+        draft: draft.clone(),
+    };
+    app.view = View::Scheduled(crate::app::ScheduledState {
+        entries: vec![entry("1", None), entry("2", Some("connection refused"))],
+        table_state: ratatui::widgets::TableState::default(),
+        status_message: None,
+        confirm_cancel: false,
+    });
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let text = buffer_text(&terminal);
+    assert!(text.contains("you@example.com"));
+    assert!(text.contains("Morning"));
+    assert!(text.contains("in 3h"));
+    assert!(text.contains("failed: connection refused"));
+    assert!(text.contains("s=Send now"));
+}
+
+#[test]
+fn compose_shows_send_later_prompt() {
+    let backend = TestBackend::new(120, 24);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let (imap, smtp) = mock_clients();
+    let mut app = App::new(Vec::new(), imap, smtp, "me@example.com".to_string(), None);
+    app.handle_key(
+        crossterm::event::KeyCode::Char('c'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    if let View::Compose(ref mut state) = app.view {
+        state.step = ComposeStep::Bcc;
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    assert!(buffer_text(&terminal).contains("Alt+L=Send later"));
+
+    app.handle_key(
+        crossterm::event::KeyCode::Char('l'),
+        crossterm::event::KeyModifiers::ALT,
+    );
+    app.handle_key(
+        crossterm::event::KeyCode::Char('7'),
+        crossterm::event::KeyModifiers::NONE,
+    );
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let text = buffer_text(&terminal);
+    assert!(text.contains("Enter=Schedule  Esc=Back"));
+    assert!(text.contains("Send at (HH:MM, tomorrow HH:MM, YYYY-MM-DD HH:MM, +2h):  7"));
+
+    // Confirming a large recipient list replaces the prompt
+    if let View::Compose(ref mut state) = app.view {
+        state.confirm_send = true;
+        state.status_message = Some("Schedule to 12 recipients? (y/n)".to_string());
+    }
+    terminal.draw(|frame| render(frame, &mut app)).unwrap();
+    let text = buffer_text(&terminal);
+    assert!(text.contains("y=Schedule  n=Back"));
+    assert!(text.contains("Schedule to 12 recipients? (y/n)"));
+}